@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::{ErrorKind, Read};
+use serde::{Deserialize, Serialize};
+
+/// Smallest block size used for signatures, also used for small files
+const MIN_BLOCK_SIZE: u32 = 700;
+
+/// Largest block size used for signatures
+const MAX_BLOCK_SIZE: u32 = 128 * 1024;
+
+/// Checksums of a single block of a file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct BlockSignature {
+    pub weak: u32,
+    pub strong: u64
+}
+
+/// Checksums of the consecutive blocks of a file, which allow computing a delta against the file
+/// without having access to its content. All blocks but the last one are `block_size` bytes long.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Signature {
+    pub block_size: u32,
+    pub file_len: u64,
+    pub blocks: Vec<BlockSignature>
+}
+
+/// Instruction for reconstructing a file from a basis file and literal data
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeltaOp {
+    /// Copy `count` consecutive blocks starting at block `block` from the basis file
+    Copy { block: u32, count: u32 },
+    /// Take the next `len` bytes from the literal data
+    Literal { len: u32 }
+}
+
+/// Differences between a file and a basis file
+#[derive(Debug, Default)]
+pub struct Delta {
+    pub ops: Vec<DeltaOp>,
+    /// The data referenced by the [DeltaOp::Literal] ops, in the order of the ops
+    pub literal_data: Vec<u8>
+}
+
+/// Incrementally computed 64-bit FNV-1a hash, used for the strong block checksums and for verifying
+/// that a file was reconstructed correctly.
+#[derive(Debug, Clone, Copy)]
+pub struct Digest(u64);
+
+impl Digest {
+
+    pub fn new() -> Self {
+        Digest(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data.iter() {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Default for Digest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn strong_hash(data: &[u8]) -> u64 {
+    let mut digest = Digest::new();
+    digest.update(data);
+    digest.finish()
+}
+
+/// rsync's rolling checksum, which can be moved along the data one byte at a time
+struct RollingChecksum {
+    a: u32,
+    b: u32,
+    len: u32
+}
+
+impl RollingChecksum {
+
+    fn new(data: &[u8]) -> Self {
+        let mut checksum = RollingChecksum { a: 0, b: 0, len: data.len() as u32 };
+
+        for (idx, byte) in data.iter().enumerate() {
+            checksum.a = checksum.a.wrapping_add(*byte as u32);
+            checksum.b = checksum.b.wrapping_add(((data.len() - idx) as u32).wrapping_mul(*byte as u32));
+        }
+
+        checksum
+    }
+
+    fn value(&self) -> u32 {
+        (self.a & 0xffff) | ((self.b & 0xffff) << 16)
+    }
+
+    /// Move the window by one byte, i.e. remove the first byte and append the given one
+    fn roll(&mut self, removed: u8, added: u8) {
+        self.a = self.a.wrapping_sub(removed as u32).wrapping_add(added as u32);
+        self.b = self.b.wrapping_sub(self.len.wrapping_mul(removed as u32)).wrapping_add(self.a);
+    }
+
+    /// Shrink the window by removing its first byte, used once the end of the data is reached
+    fn shrink(&mut self, removed: u8) {
+        self.a = self.a.wrapping_sub(removed as u32);
+        self.b = self.b.wrapping_sub(self.len.wrapping_mul(removed as u32));
+        self.len -= 1;
+    }
+}
+
+/// Like rsync, choose a block size that grows with the square root of the file length, so that the
+/// size of the signature does not grow linearly with the file.
+pub fn block_size_for(file_len: u64) -> u32 {
+    let block_size = ((file_len as f64).sqrt() as u32) & !7;
+    block_size.clamp(MIN_BLOCK_SIZE, MAX_BLOCK_SIZE)
+}
+
+pub fn compute_signature<R: Read>(reader: &mut R, block_size: u32) -> io::Result<Signature> {
+    let mut signature = Signature { block_size, file_len: 0, blocks: vec![] };
+    let mut block = vec![0u8; block_size as usize];
+
+    loop {
+        let block_len = read_block(reader, &mut block)?;
+        if block_len == 0 {
+            return Ok(signature);
+        }
+
+        let data = &block[..block_len];
+        signature.blocks.push(BlockSignature { weak: RollingChecksum::new(data).value(), strong: strong_hash(data) });
+        signature.file_len += block_len as u64;
+    }
+}
+
+/// Fill the given buffer, unless the end of the data is reached. Returns the number of bytes read.
+fn read_block<R: Read>(reader: &mut R, block: &mut [u8]) -> io::Result<usize> {
+    let mut block_len = 0;
+
+    while block_len < block.len() {
+        match reader.read(&mut block[block_len..]) {
+            Ok(0) => break,
+            Ok(nr_of_bytes) => block_len += nr_of_bytes,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e)
+        }
+    }
+
+    Ok(block_len)
+}
+
+/// Compute the ops needed to turn the file with the given signature into the given data. Blocks of
+/// the data that are found anywhere in the basis file are copied from it, everything else is sent
+/// as literal data.
+pub fn compute_delta(signature: &Signature, data: &[u8]) -> Delta {
+    let mut delta = Delta::default();
+
+    let block_size = signature.block_size as usize;
+    if signature.blocks.is_empty() || block_size == 0 {
+        push_literal(&mut delta, data);
+        return delta;
+    }
+
+    let nr_of_blocks = signature.blocks.len();
+    let last_block_len = signature.file_len as usize - (nr_of_blocks - 1) * block_size;
+
+    let mut blocks_by_weak_checksum: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (idx, block) in signature.blocks.iter().enumerate() {
+        blocks_by_weak_checksum.entry(block.weak).or_default().push(idx);
+    }
+
+    let find_block = |weak: u32, window: &[u8]| -> Option<usize> {
+        let candidates = blocks_by_weak_checksum.get(&weak)?;
+        let mut strong: Option<u64> = None;
+
+        candidates.iter().copied().find(|idx| {
+            let block_len = if *idx == nr_of_blocks - 1 { last_block_len } else { block_size };
+            block_len == window.len()
+                && *strong.get_or_insert_with(|| strong_hash(window)) == signature.blocks[*idx].strong
+        })
+    };
+
+    let mut pos = 0;
+    let mut literal_start = 0;
+    let mut window_len = block_size.min(data.len());
+    let mut checksum = RollingChecksum::new(&data[..window_len]);
+
+    while pos < data.len() {
+        if let Some(block) = find_block(checksum.value(), &data[pos..pos + window_len]) {
+            push_literal(&mut delta, &data[literal_start..pos]);
+            push_copy(&mut delta, block as u32);
+
+            pos += window_len;
+            literal_start = pos;
+            window_len = block_size.min(data.len() - pos);
+            checksum = RollingChecksum::new(&data[pos..pos + window_len]);
+            continue;
+        }
+
+        if pos + window_len < data.len() {
+            checksum.roll(data[pos], data[pos + window_len]);
+        } else {
+            checksum.shrink(data[pos]);
+            window_len -= 1;
+        }
+
+        pos += 1;
+    }
+
+    push_literal(&mut delta, &data[literal_start..]);
+    delta
+}
+
+fn push_literal(delta: &mut Delta, data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+
+    delta.ops.push(DeltaOp::Literal { len: data.len() as u32 });
+    delta.literal_data.extend_from_slice(data);
+}
+
+fn push_copy(delta: &mut Delta, block: u32) {
+    if let Some(DeltaOp::Copy { block: first_block, count }) = delta.ops.last_mut()
+        && *first_block + *count == block {
+        *count += 1;
+        return;
+    }
+
+    delta.ops.push(DeltaOp::Copy { block, count: 1 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reconstruct the data from the basis file and the delta, as the agent does
+    fn apply(basis: &[u8], block_size: usize, delta: &Delta) -> Vec<u8> {
+        let mut data = vec![];
+        let mut literal_data = delta.literal_data.as_slice();
+
+        for op in delta.ops.iter() {
+            match *op {
+                DeltaOp::Copy { block, count } => {
+                    let start = block as usize * block_size;
+                    let end = (start + count as usize * block_size).min(basis.len());
+                    data.extend_from_slice(&basis[start..end]);
+                },
+                DeltaOp::Literal { len } => {
+                    let (literal, remaining) = literal_data.split_at(len as usize);
+                    data.extend_from_slice(literal);
+                    literal_data = remaining;
+                }
+            }
+        }
+
+        assert!(literal_data.is_empty());
+        data
+    }
+
+    fn round_trip(basis: &[u8], data: &[u8], block_size: u32) -> Delta {
+        let signature = compute_signature(&mut &basis[..], block_size).unwrap();
+        assert_eq!(signature.file_len, basis.len() as u64);
+
+        let delta = compute_delta(&signature, data);
+        assert_eq!(apply(basis, block_size as usize, &delta), data);
+        delta
+    }
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|idx| (idx * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn empty_files_round_trip() {
+        assert!(round_trip(b"", b"", 8).ops.is_empty());
+        assert!(round_trip(&sample(20), b"", 8).ops.is_empty());
+        assert_eq!(round_trip(b"", &sample(20), 8).ops, [DeltaOp::Literal { len: 20 }]);
+    }
+
+    #[test]
+    fn unchanged_file_is_copied() {
+        // Neither file is block-aligned, so the last block is shorter than the others
+        let data = sample(29);
+        assert_eq!(round_trip(&data, &data, 8).ops, [DeltaOp::Copy { block: 0, count: 4 }]);
+    }
+
+    #[test]
+    fn changed_files_round_trip() {
+        let basis = sample(100);
+
+        let mut modified = basis.clone();
+        modified[42] ^= 0xff;
+        round_trip(&basis, &modified, 8);
+
+        let mut prepended = b"prefix".to_vec();
+        prepended.extend_from_slice(&basis);
+        let delta = round_trip(&basis, &prepended, 8);
+        assert_eq!(delta.literal_data, b"prefix");
+
+        let mut appended = basis.clone();
+        appended.extend_from_slice(b"suffix");
+        round_trip(&basis, &appended, 8);
+
+        round_trip(&basis, &basis[..61], 8);
+        round_trip(&basis, &basis[13..], 8);
+        round_trip(&basis, &sample(3), 8);
+    }
+
+    #[test]
+    fn block_size_is_bounded() {
+        assert_eq!(block_size_for(0), MIN_BLOCK_SIZE);
+        assert_eq!(block_size_for(u64::MAX), MAX_BLOCK_SIZE);
+        assert_eq!(block_size_for(1 << 30) % 8, 0);
+    }
+}
@@ -0,0 +1,30 @@
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(std::io::Error),
+    Serde(serde_json::Error),
+    Protocol(String)
+}
+
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::Io(e) => write!(f, "I/O error: {e}"),
+            ProtocolError::Serde(e) => write!(f, "JSON error: {e}"),
+            ProtocolError::Protocol(e) => write!(f, "Protocol error: {e}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for ProtocolError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ProtocolError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Serde(e)
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::{Read, Write};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::errors::ProtocolError;
+
+/// Maximum size of a frame's JSON header, which e.g. holds the entries of a scanned directory tree
+pub const MAX_HEADER_LEN: usize = 64 * 1024 * 1024;
+
+/// Maximum size of a frame's binary payload
+pub const MAX_PAYLOAD_LEN: usize = 16 * 1024 * 1024;
+
+/// Write a frame, which consists of the lengths of the header and the payload (both as big-endian
+/// u32), followed by the JSON encoded header and the raw payload. The writer is flushed afterwards.
+pub fn write_frame<W: Write, T: Serialize>(writer: &mut W, header: &T, payload: &[u8]) -> Result<(), ProtocolError> {
+    let header = serde_json::to_vec(header)?;
+
+    if header.len() > MAX_HEADER_LEN || payload.len() > MAX_PAYLOAD_LEN {
+        return Err(ProtocolError::Protocol(format!(
+            "Frame exceeds the maximum size (header: {} bytes, payload: {} bytes)",
+            header.len(),
+            payload.len()
+        )));
+    }
+
+    writer.write_all(&(header.len() as u32).to_be_bytes())?;
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(&header)?;
+    writer.write_all(payload)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Read a frame written by [write_frame], returning its decoded header and its payload
+pub fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<(T, Vec<u8>), ProtocolError> {
+    let mut lengths = [0u8; 8];
+    reader.read_exact(&mut lengths)?;
+
+    let header_len = u32::from_be_bytes([lengths[0], lengths[1], lengths[2], lengths[3]]) as usize;
+    let payload_len = u32::from_be_bytes([lengths[4], lengths[5], lengths[6], lengths[7]]) as usize;
+
+    if header_len > MAX_HEADER_LEN || payload_len > MAX_PAYLOAD_LEN {
+        return Err(ProtocolError::Protocol(format!(
+            "Received frame exceeds the maximum size (header: {header_len} bytes, payload: {payload_len} bytes)"
+        )));
+    }
+
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header)?;
+
+    let mut payload = vec![0u8; payload_len];
+    reader.read_exact(&mut payload)?;
+
+    Ok((serde_json::from_slice(&header)?, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use crate::delta::DeltaOp;
+    use crate::Request;
+    use super::*;
+
+    #[test]
+    fn frames_round_trip() {
+        let mut buffer = vec![];
+        let ops = vec![DeltaOp::Copy { block: 1, count: 2 }, DeltaOp::Literal { len: 3 }];
+
+        write_frame(&mut buffer, &Request::WriteChunk { ops: ops.clone() }, b"abc").unwrap();
+        write_frame(&mut buffer, &Request::Scan { path: PathBuf::from("dir/file") }, &[]).unwrap();
+
+        let mut reader = buffer.as_slice();
+
+        let (request, payload): (Request, Vec<u8>) = read_frame(&mut reader).unwrap();
+        assert!(matches!(request, Request::WriteChunk { ops: read_ops } if read_ops == ops));
+        assert_eq!(payload, b"abc");
+
+        let (request, payload): (Request, Vec<u8>) = read_frame(&mut reader).unwrap();
+        assert!(matches!(request, Request::Scan { path } if path == Path::new("dir/file")));
+        assert!(payload.is_empty());
+
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn truncated_frame_is_rejected() {
+        let mut buffer = vec![];
+        write_frame(&mut buffer, &Request::Ping, b"payload").unwrap();
+
+        let result: Result<(Request, Vec<u8>), ProtocolError> = read_frame(&mut &buffer[..buffer.len() - 1]);
+        assert!(matches!(result, Err(ProtocolError::Io(_))));
+    }
+
+    #[test]
+    fn oversized_frame_is_rejected() {
+        let mut buffer = ((MAX_HEADER_LEN + 1) as u32).to_be_bytes().to_vec();
+        buffer.extend_from_slice(&0u32.to_be_bytes());
+
+        let result: Result<(Request, Vec<u8>), ProtocolError> = read_frame(&mut buffer.as_slice());
+        assert!(matches!(result, Err(ProtocolError::Protocol(_))));
+
+        let payload = vec![0u8; MAX_PAYLOAD_LEN + 1];
+        assert!(matches!(write_frame(&mut vec![], &Request::Ping, &payload), Err(ProtocolError::Protocol(_))));
+    }
+}
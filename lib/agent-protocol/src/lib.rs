@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::delta::{DeltaOp, Signature};
+
+pub mod delta;
+pub mod errors;
+pub mod frame;
+
+/// Version of the protocol spoken between the monitor and `wsync-agent`. Both sides must use the
+/// same version.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Point in time, as seconds and nanoseconds relative to the unix epoch
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub secs: i64,
+    pub nanos: u32
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(value: SystemTime) -> Self {
+        match value.duration_since(UNIX_EPOCH) {
+            Ok(duration) => Timestamp { secs: duration.as_secs() as i64, nanos: duration.subsec_nanos() },
+            Err(e) => {
+                let duration = e.duration();
+                if duration.subsec_nanos() == 0 {
+                    Timestamp { secs: -(duration.as_secs() as i64), nanos: 0 }
+                } else {
+                    Timestamp { secs: -(duration.as_secs() as i64) - 1, nanos: 1_000_000_000 - duration.subsec_nanos() }
+                }
+            }
+        }
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(value: Timestamp) -> Self {
+        if value.secs >= 0 {
+            UNIX_EPOCH + Duration::new(value.secs as u64, value.nanos)
+        } else {
+            UNIX_EPOCH - Duration::from_secs(value.secs.unsigned_abs()) + Duration::from_nanos(value.nanos as u64)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    /// Any other kind of resource, e.g. a socket or a device file
+    Other
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EntryInfo {
+    pub kind: EntryKind,
+    pub len: u64,
+    /// Permission bits of the resource
+    pub mode: u32,
+    pub mtime: Timestamp,
+    /// Target of a symlink
+    pub link_target: Option<PathBuf>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScannedEntry {
+    pub path: PathBuf,
+    pub info: EntryInfo
+}
+
+/// Requests sent by the monitor to the agent. Paths are relative to the remote workspace root, an
+/// empty path referring to the root itself. Unless noted otherwise, the agent answers each request
+/// with exactly one [Response].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Request {
+    /// First request of a session, answered with [Response::Ready]. Creates the remote workspace
+    /// root if it does not exist yet, but not its parent directories.
+    Hello {
+        version: u32,
+        root: PathBuf
+    },
+    Ping,
+    /// Answered with [Response::Stats], holding the information about each path in order
+    Stat {
+        paths: Vec<PathBuf>
+    },
+    /// Answered with [Response::Entries], holding the resource at the given path and, if it is a
+    /// directory, everything below it
+    Scan {
+        path: PathBuf
+    },
+    /// Answered with [Response::Signature] of the file at the given path, which is empty if the
+    /// file does not exist
+    Signature {
+        path: PathBuf,
+        block_size: u32
+    },
+    /// Start (re-)writing the file at the given path. It is followed by any number of
+    /// [Request::WriteChunk] requests and either a [Request::EndWrite] or [Request::AbortWrite]
+    /// request. Only the former is answered, once the file was replaced.
+    BeginWrite {
+        path: PathBuf,
+        /// Whether the ops refer to the blocks of the existing file, as opposed to only containing
+        /// literal data
+        with_basis: bool,
+        block_size: u32,
+        mode: u32,
+        mtime: Timestamp
+    },
+    /// Ops to be applied to the file being written, with the literal data being passed as payload.
+    /// Not answered.
+    WriteChunk {
+        ops: Vec<DeltaOp>
+    },
+    /// Atomically replace the file with the written content, unless it does not match the given
+    /// [crate::delta::Digest]
+    EndWrite {
+        digest: u64
+    },
+    /// Discard the written content. Not answered.
+    AbortWrite,
+    Mkdir {
+        path: PathBuf,
+        mode: u32
+    },
+    Symlink {
+        path: PathBuf,
+        target: PathBuf
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf
+    },
+    /// Delete the resource at the given path, recursively for directories. Succeeds if it does not
+    /// exist.
+    Delete {
+        path: PathBuf
+    },
+    Chmod {
+        path: PathBuf,
+        mode: u32
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Response {
+    Ready {
+        version: u32
+    },
+    Ok,
+    Error {
+        message: String
+    },
+    Stats {
+        entries: Vec<Option<EntryInfo>>
+    },
+    Entries {
+        entries: Vec<ScannedEntry>
+    },
+    Signature(Signature)
+}
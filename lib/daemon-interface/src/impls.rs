@@ -1,7 +1,19 @@
 use std::fmt::{Display, Formatter, Write};
 use serde::Serialize;
 use crate::response::{ErrorPayload, Response, ResponsePayload, ResponseStatus};
-use crate::{WorkspaceInfo, WorkspaceOverview};
+use crate::{
+    ConflictInfo,
+    ConflictResolution,
+    FailureCause,
+    HookOutcome,
+    RemoteFileVersions,
+    RemoteSyncPreview,
+    RemoteSyncStatus,
+    TransferStats,
+    TriggeredSyncOutcome,
+    WorkspaceInfo,
+    WorkspaceOverview
+};
 
 impl<T: Display + Serialize, E: Display + Serialize> Display for Response<T, E> {
 
@@ -69,6 +81,45 @@ impl Display for ResponsePayload {
             },
             ResponsePayload::DetachRemoteWorkspace(payload) => {
                 write!(f, "{}\n", payload)?;
+            },
+            ResponsePayload::PreviewSync(payload) => {
+                writeln!(f, "Sync preview of workspace '{}':", payload.workspace_name)?;
+
+                for entry in &payload.entries {
+                    write!(f, "\n{entry}")?;
+                }
+            },
+            ResponsePayload::ApproveDeletions(payload) => {
+                writeln!(f, "{payload}")?;
+            },
+            ResponsePayload::RejectDeletions(payload) => {
+                writeln!(f, "{payload}")?;
+            },
+            ResponsePayload::ListVersions(payload) => {
+                writeln!(f, "Versions of '{}' in workspace '{}':", payload.path.display(), payload.workspace_name)?;
+
+                for entry in &payload.entries {
+                    write!(f, "\n{entry}")?;
+                }
+            },
+            ResponsePayload::RestoreVersion(payload) => {
+                writeln!(f, "{payload}")?;
+            },
+            ResponsePayload::SetSchedule(payload) => {
+                writeln!(f, "{payload}")?;
+            },
+            ResponsePayload::Pause(payload) => {
+                writeln!(f, "{payload}")?;
+            },
+            ResponsePayload::Resume(payload) => {
+                writeln!(f, "{payload}")?;
+            },
+            ResponsePayload::TriggerSync(payload) => {
+                writeln!(f, "Triggered sync of workspace '{}':", payload.workspace_name)?;
+
+                for entry in &payload.entries {
+                    write!(f, "\n{entry}")?;
+                }
             }
         }
 
@@ -107,13 +158,219 @@ impl Display for WorkspaceInfo {
 
         write!(
             f,
-            "Workspace Name: {}, Path: {:?}, #Remote Workspaces: {}, Remote Workspaces: {:?}",
+            "Workspace Name: {}, Path: {:?}, #Remote Workspaces: {}, Remote Workspaces: {:?}, Debounce: {:?}, Max. parallel syncs: {}, Ignore patterns: {:?}, Deletion guard: {:?}, Initial sync: {:?}, Watcher: {:?}, Paused: {}",
             self.name,
             self.path,
             self.nr_of_remote_workspaces,
-            self.remote_workspaces
+            self.remote_workspaces,
+            self.debounce,
+            self.max_parallel_syncs,
+            self.ignore_patterns,
+            self.deletion_guard,
+            self.initial_sync,
+            self.watcher,
+            self.paused
         )?;
 
+        for sync_status in &self.sync_status {
+            write!(f, "\n\tSync status: {sync_status}")?;
+        }
+
+        if let Some(transferred) = &self.transferred {
+            write!(f, "\n\tTotal transferred: {transferred}")?;
+        }
+
+        for conflict in &self.conflicts {
+            write!(f, "\n\tConflict: {conflict}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for ConflictInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let resolution = match &self.resolution {
+            ConflictResolution::LocalWon => "kept local version".to_string(),
+            ConflictResolution::RemoteWon => "kept remote version".to_string(),
+            ConflictResolution::KeptBoth { conflict_copy } => {
+                format!("kept remote version, local version moved to '{}'", conflict_copy.display())
+            }
+        };
+
+        write!(
+            f,
+            "'{}' in '{}' at {} ({resolution})",
+            self.path.display(),
+            self.remote_workspace_name,
+            self.detected_at
+        )
+    }
+}
+
+impl Display for FailureCause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            FailureCause::Unreachable => "remote system unreachable",
+            FailureCause::ConnectionLost => "connection lost",
+            FailureCause::AuthenticationFailed => "authentication failed",
+            FailureCause::RemotePathMissing => "remote path missing",
+            FailureCause::PermissionDenied => "permission denied",
+            FailureCause::DiskFull => "disk full",
+            FailureCause::VanishedFiles => "files vanished",
+            FailureCause::PartialTransfer => "partial transfer",
+            FailureCause::Other => "unknown cause"
+        };
+
+        write!(f, "{description}")
+    }
+}
+
+impl Display for RemoteSyncStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = match (self.pending_deletions, self.syncing, self.consecutive_failures) {
+            (Some(nr_of_deletions), _, _) => format!(
+                "deletion pending approval (#{nr_of_deletions} deletion(s) held since {})",
+                self.deletions_held_since.unwrap_or_default()
+            ),
+            (None, true, _) => "syncing".to_string(),
+            (None, false, 0) => "in sync".to_string(),
+            (None, false, failures) => format!("failing (#{failures} consecutive failure(s))")
+        };
+
+        write!(f, "'{}' is {state}", self.remote_workspace_name)?;
+
+        if let Some(last_success_at) = self.last_success_at {
+            write!(f, ", last success at {last_success_at}")?;
+        }
+
+        if let Some(last_duration_ms) = self.last_duration_ms {
+            write!(f, ", last sync took {last_duration_ms}ms")?;
+        }
+
+        if let (Some(last_error), Some(last_failure_at)) = (&self.last_error, self.last_failure_at) {
+            match self.last_error_cause {
+                Some(cause) => write!(f, ", last error at {last_failure_at} ({cause}): {last_error}")?,
+                None => write!(f, ", last error at {last_failure_at}: {last_error}")?
+            }
+        }
+
+        if let Some(last_transfer) = &self.last_transfer {
+            write!(f, ", last sync {last_transfer}")?;
+        }
+
+        if let Some(total_transferred) = &self.total_transferred {
+            write!(f, ", in total {total_transferred}")?;
+        }
+
+        if let Some(last_hook) = &self.last_hook {
+            write!(f, ", last hook {last_hook}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for HookOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(error) = &self.error {
+            return write!(f, "could not be run at {}: {error}", self.finished_at);
+        }
+
+        match (self.timed_out, self.exit_code) {
+            (true, _) => write!(f, "timed out")?,
+            (false, Some(exit_code)) => write!(f, "exited with code {exit_code}")?,
+            (false, None) => write!(f, "was terminated by a signal")?
+        }
+
+        write!(f, " at {} after {}ms", self.finished_at, self.duration_ms)
+    }
+}
+
+impl Display for TransferStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "transferred #{} of #{} file(s), sent {} bytes, received {} bytes in {}ms (speedup {:.2})",
+            self.files_transferred,
+            self.files_considered,
+            self.bytes_sent,
+            self.bytes_received,
+            self.duration_ms,
+            self.speedup()
+        )
+    }
+}
+
+impl Display for RemoteSyncPreview {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Remote workspace '{}':", self.remote_workspace_name)?;
+
+        if let Some(error) = &self.error {
+            writeln!(f, "\tUnable to determine changes: {error}")?;
+            return Ok(());
+        }
+
+        if self.created.is_empty() && self.updated.is_empty() && self.deleted.is_empty() {
+            writeln!(f, "\tUp to date")?;
+            return Ok(());
+        }
+
+        for (marker, paths) in [("+", &self.created), ("~", &self.updated), ("-", &self.deleted)] {
+            for path in paths {
+                writeln!(f, "\t{marker} {}", path.display())?;
+            }
+        }
+
+        writeln!(
+            f,
+            "\t#created: {}, #updated: {}, #deleted: {}",
+            self.created.len(),
+            self.updated.len(),
+            self.deleted.len()
+        )
+    }
+}
+
+impl Display for TriggeredSyncOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' ", self.remote_workspace_name)?;
+
+        match (&self.skipped, &self.error) {
+            (Some(reason), _) => write!(f, "was skipped: {reason}")?,
+            (None, Some(error)) => match self.cause {
+                Some(cause) => write!(f, "failed ({cause}): {error}")?,
+                None => write!(f, "failed: {error}")?
+            },
+            (None, None) => write!(f, "was synced")?
+        }
+
+        if let Some(transferred) = &self.transferred {
+            write!(f, ", {transferred}")?;
+        }
+
+        writeln!(f)
+    }
+}
+
+impl Display for RemoteFileVersions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Remote workspace '{}':", self.remote_workspace_name)?;
+
+        if let Some(error) = &self.error {
+            writeln!(f, "\tUnable to determine versions: {error}")?;
+            return Ok(());
+        }
+
+        if self.versions.is_empty() {
+            writeln!(f, "\tNo versions kept")?;
+            return Ok(());
+        }
+
+        for version in &self.versions {
+            writeln!(f, "\t{} (replaced at {}, {} bytes)", version.version, version.created_at, version.size)?;
+        }
+
         Ok(())
     }
 }
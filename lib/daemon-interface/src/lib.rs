@@ -20,14 +20,304 @@ pub struct WorkspaceInfo {
     pub name: String,
     pub path: PathBuf,
     pub nr_of_remote_workspaces: usize,
-    pub remote_workspaces: Vec<RemoteWorkspace>
+    pub remote_workspaces: Vec<RemoteWorkspace>,
+    pub debounce: DebounceSettings,
+    pub max_parallel_syncs: usize,
+    pub ignore_patterns: Vec<String>,
+    pub deletion_guard: Option<DeletionGuardSettings>,
+    pub initial_sync: InitialSyncStrategy,
+    pub watcher: WatcherBackend,
+    /// Whether the syncs with all remote workspaces are paused
+    pub paused: bool,
+    /// Most recent conflicts detected while syncing with two-way remote workspaces
+    pub conflicts: Vec<ConflictInfo>,
+    /// Sync status of the remote workspaces the monitor already reported on
+    pub sync_status: Vec<RemoteSyncStatus>,
+    /// Statistics of all transfers to and from the remote workspaces since the monitor started
+    pub transferred: Option<TransferStats>
+}
+
+/// Outcome of the most recent syncs with a remote workspace. Points in time are given in seconds
+/// since the unix epoch.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemoteSyncStatus {
+    pub remote_workspace_name: String,
+    /// Whether a sync is currently in progress
+    pub syncing: bool,
+    pub last_started_at: Option<u64>,
+    pub last_success_at: Option<u64>,
+    /// Duration of the last finished sync, whether it succeeded or not
+    pub last_duration_ms: Option<u64>,
+    pub last_error: Option<String>,
+    /// Cause of the last error, if it could be determined
+    pub last_error_cause: Option<FailureCause>,
+    pub last_failure_at: Option<u64>,
+    /// Number of failed syncs since the last successful one
+    pub consecutive_failures: u32,
+    /// Number of deletions that are held until they are approved or rejected
+    pub pending_deletions: Option<usize>,
+    pub deletions_held_since: Option<u64>,
+    /// What the last sync transferred
+    pub last_transfer: Option<TransferStats>,
+    /// Statistics of all transfers since the monitor started
+    pub total_transferred: Option<TransferStats>,
+    /// Outcome of the last run of the remote workspace's post-sync hook
+    pub last_hook: Option<HookOutcome>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HookOutcome {
+    pub finished_at: u64,
+    pub duration_ms: u64,
+    /// Exit code of the hook, `None` if it was terminated by a signal or could not be run
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    /// Reason why the hook could not be run
+    pub error: Option<String>
+}
+
+/// Statistics of transfers to and from remote workspaces
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TransferStats {
+    /// Number of files, directories and other resources that were compared
+    pub files_considered: u64,
+    /// Number of regular files whose content was transferred
+    pub files_transferred: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Total size of the considered files, i.e. what would have been sent without the
+    /// delta-transfer algorithm
+    pub total_size: u64,
+    pub duration_ms: u64
+}
+
+impl TransferStats {
+
+    /// How much less was sent and received than the total size of the considered files
+    pub fn speedup(&self) -> f64 {
+        let transferred = self.bytes_sent + self.bytes_received;
+        if transferred == 0 {
+            return 0.0;
+        }
+
+        self.total_size as f64 / transferred as f64
+    }
+}
+
+/// Cause of a failed sync, as far as it could be determined from the exit code of the transfer and
+/// what it reported
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureCause {
+    /// The remote system could not be connected to, e.g. because it is down or can't be resolved
+    Unreachable,
+    /// The connection to the remote system broke down during the transfer, e.g. due to a timeout
+    ConnectionLost,
+    AuthenticationFailed,
+    /// The remote workspace, or a directory within it, does not exist
+    RemotePathMissing,
+    PermissionDenied,
+    DiskFull,
+    /// Local files vanished while they were being transferred
+    VanishedFiles,
+    /// Some resources could not be transferred for another reason
+    PartialTransfer,
+    Other
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DebounceSettings {
+    pub quiet_window_ms: u64,
+    pub max_latency_ms: u64
+}
+
+/// Thresholds above which syncs deleting resources are held until the deletions are approved
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeletionGuardSettings {
+    /// Maximum number of files that may be deleted by a single sync
+    pub max_deletions: Option<usize>,
+    /// Maximum percentage of the workspace's files that may be deleted by a single sync
+    pub max_deletion_percent: Option<u8>
+}
+
+/// How the monitor catches up the remote workspaces with changes made while it was not running
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum InitialSyncStrategy {
+    /// Sync the entire workspace
+    Full,
+    /// Compare the workspace with the remote workspaces by checksum and only sync the resources
+    /// that differ
+    Verify,
+    /// Only sync the resources that changed since the monitor last stopped
+    Modified,
+    /// Only sync the changes that were left unsynced when the monitor last stopped
+    Skip
+}
+
+/// How the monitor detects changes in the workspace on Linux
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum WatcherBackend {
+    /// Watch each directory of the workspace via inotify
+    Inotify,
+    /// Watch the workspace's filesystem as a whole via fanotify, which requires root privileges.
+    /// inotify is used instead if fanotify is not available.
+    Fanotify
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RemoteWorkspace {
     pub name: String,
     pub path: PathBuf,
-    pub connection_info: ConnectionInfo
+    pub connection_info: ConnectionInfo,
+    pub sync_options: SyncOptions,
+    pub two_way: Option<TwoWaySettings>,
+    pub versioning: Option<VersioningSettings>,
+    /// Windows of the day in which syncs are paused or transferred at a different bandwidth
+    pub schedule: Vec<ScheduleWindow>,
+    pub post_sync_hook: Option<PostSyncHook>,
+    /// Whether the syncs with the remote workspace are paused, regardless of the workspace
+    pub paused: bool
+}
+
+/// Command that is run on the remote system after changes were synced to a remote workspace
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PostSyncHook {
+    /// Shell command to run
+    pub command: String,
+    /// Directory the command is run in, relative to the remote workspace. Defaults to the remote
+    /// workspace.
+    pub working_dir: Option<PathBuf>,
+    /// The command is killed if it runs for longer than this
+    pub timeout_secs: u64,
+    /// The command is only run once no further sync succeeded for this many milliseconds
+    pub debounce_ms: u64
+}
+
+/// Window of the day, in local time of the system running the daemon, in which syncs with a
+/// remote workspace are treated differently. Times are given in minutes since midnight, windows
+/// ending before they start span midnight. If windows overlap, the first one applies.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduleWindow {
+    pub start_minute: u16,
+    pub end_minute: u16,
+    pub action: ScheduleAction
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum ScheduleAction {
+    /// Don't sync, changes are deferred until the window closes
+    Pause,
+    /// Sync without limiting the bandwidth
+    FullSpeed,
+    /// Limit the bandwidth to the given number of KiB per second
+    Bwlimit(u32)
+}
+
+/// Retention of the previous versions of files that syncs overwrite or delete in a remote
+/// workspace. Versions exceeding any of the limits are removed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersioningSettings {
+    /// Maximum number of versions kept per file
+    pub keep_versions: Option<usize>,
+    /// Maximum number of days a version is kept
+    pub keep_days: Option<u64>
+}
+
+/// Settings of remote workspaces whose changes are also synced back into the local workspace
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TwoWaySettings {
+    pub conflict_policy: ConflictPolicy,
+    /// Interval in which the remote workspace is checked for changes
+    pub poll_interval_secs: u64
+}
+
+/// Decides which version of a resource is kept if it was changed both locally and remotely
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum ConflictPolicy {
+    LocalWins,
+    RemoteWins,
+    /// Keep the remote version and move the local version to a copy with a conflict suffix
+    KeepBoth
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ConflictResolution {
+    LocalWon,
+    RemoteWon,
+    KeptBoth {
+        conflict_copy: PathBuf
+    }
+}
+
+/// Resource that was changed both locally and in a remote workspace since they were last synced
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConflictInfo {
+    pub remote_workspace_name: String,
+    pub path: PathBuf,
+    pub resolution: ConflictResolution,
+    /// Seconds since the unix epoch
+    pub detected_at: u64
+}
+
+/// Changes that syncing a workspace would make to one of its remote workspaces
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RemoteSyncPreview {
+    pub remote_workspace_name: String,
+    pub created: Vec<PathBuf>,
+    pub updated: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+    /// Reason why the changes could not be determined
+    pub error: Option<String>
+}
+
+/// Outcome of a sync with one of the remote workspaces that was triggered on demand
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TriggeredSyncOutcome {
+    pub remote_workspace_name: String,
+    /// Reason why the remote workspace was not synced, e.g. because its syncs are paused
+    pub skipped: Option<String>,
+    pub error: Option<String>,
+    /// Cause of the error, if it could be determined
+    pub cause: Option<FailureCause>,
+    /// What the sync transferred, if the remote workspace's backend keeps statistics
+    pub transferred: Option<TransferStats>
+}
+
+/// Previous versions of a file kept in one of the remote workspaces, newest first
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RemoteFileVersions {
+    pub remote_workspace_name: String,
+    pub versions: Vec<FileVersion>,
+    /// Reason why the versions could not be determined
+    pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FileVersion {
+    /// Identifies the version when restoring it
+    pub version: String,
+    /// Seconds since the unix epoch at which the version was replaced or deleted
+    pub created_at: u64,
+    pub size: u64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SyncOptions {
+    pub delete: bool,
+    pub compress: bool,
+    pub compress_choice: Option<String>,
+    pub checksum: bool,
+    pub bwlimit_kbps: Option<u32>,
+    pub chmod: Option<String>,
+    pub chown: Option<String>,
+    pub partial: bool,
+    pub inplace: bool,
+    pub hard_links: bool,
+    pub acls: bool,
+    pub xattrs: bool,
+    pub perms: bool,
+    pub owner: bool,
+    pub group: bool,
+    pub times: bool
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -45,5 +335,20 @@ pub enum ConnectionInfo {
         host: String,
         port: Option<u16>,
         username: Option<String>
+    },
+    /// Directory on a locally mounted filesystem, with the remote path being located below the
+    /// given mount point
+    Local {
+        path: PathBuf
+    },
+    /// Remote system running `wsync-agent`, which is started via SSH. If no host is given, the
+    /// agent is started as a local child process instead.
+    Agent {
+        host: Option<String>,
+        port: Option<u16>,
+        username: Option<String>,
+        identity_file: Option<PathBuf>,
+        /// Path to the agent executable, `wsync-agent` being looked up in the PATH by default
+        agent_path: Option<PathBuf>
     }
 }
@@ -1,7 +1,17 @@
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 use strum_macros::{AsRefStr, Display, EnumString};
-use crate::ConnectionInfo;
+use crate::{
+    ConnectionInfo,
+    DeletionGuardSettings,
+    InitialSyncStrategy,
+    PostSyncHook,
+    ScheduleWindow,
+    SyncOptions,
+    TwoWaySettings,
+    VersioningSettings,
+    WatcherBackend
+};
 
 /// Exhaustive enumeration of all commands understood and accepted by the wsync daemon.
 #[derive(Serialize, Deserialize, Debug, EnumString, AsRefStr, Display)]
@@ -19,7 +29,25 @@ pub enum Command {
     #[strum(serialize="attach_remote_workspace")]
     AttachRemoteWorkspace,
     #[strum(serialize="detach_remote_workspace")]
-    DetachRemoteWorkspace
+    DetachRemoteWorkspace,
+    #[strum(serialize="preview_sync")]
+    PreviewSync,
+    #[strum(serialize="approve_deletions")]
+    ApproveDeletions,
+    #[strum(serialize="reject_deletions")]
+    RejectDeletions,
+    #[strum(serialize="list_versions")]
+    ListVersions,
+    #[strum(serialize="restore_version")]
+    RestoreVersion,
+    #[strum(serialize="set_schedule")]
+    SetSchedule,
+    #[strum(serialize="pause")]
+    Pause,
+    #[strum(serialize="resume")]
+    Resume,
+    #[strum(serialize="trigger_sync")]
+    TriggerSync
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -35,7 +63,14 @@ pub struct WorkspaceInfoRequest {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AddWorkspaceRequest {
     pub name: String,
-    pub path: PathBuf
+    pub path: PathBuf,
+    pub quiet_window_ms: Option<u64>,
+    pub max_latency_ms: Option<u64>,
+    pub max_parallel_syncs: Option<usize>,
+    pub ignore_patterns: Vec<String>,
+    pub deletion_guard: Option<DeletionGuardSettings>,
+    pub initial_sync: Option<InitialSyncStrategy>,
+    pub watcher: Option<WatcherBackend>
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -48,7 +83,11 @@ pub struct AttachRemoteWorkspaceRequest {
     pub local_workspace_name: String,
     pub remote_workspace_name: String,
     pub remote_workspace_path: PathBuf,
-    pub connection_info: ConnectionInfo
+    pub connection_info: ConnectionInfo,
+    pub sync_options: SyncOptions,
+    pub two_way: Option<TwoWaySettings>,
+    pub versioning: Option<VersioningSettings>,
+    pub post_sync_hook: Option<PostSyncHook>
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -56,3 +95,59 @@ pub struct DetachRemoteWorkspaceRequest {
     pub local_workspace_name: String,
     pub remote_workspace_name: String
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PreviewSyncRequest {
+    pub name: String,
+    /// Only preview the sync with this remote workspace instead of all of them
+    pub remote_workspace_name: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResolveDeletionsRequest {
+    pub name: String,
+    /// Only resolve the deletions held for this remote workspace instead of all of them
+    pub remote_workspace_name: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListVersionsRequest {
+    pub name: String,
+    /// Only list the versions kept in this remote workspace instead of all of them
+    pub remote_workspace_name: Option<String>,
+    /// Workspace-relative path of the file
+    pub path: PathBuf
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RestoreVersionRequest {
+    pub name: String,
+    pub remote_workspace_name: String,
+    /// Workspace-relative path of the file
+    pub path: PathBuf,
+    pub version: String
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetScheduleRequest {
+    pub name: String,
+    pub remote_workspace_name: String,
+    /// Replaces the current schedule, an empty schedule syncs at all times
+    pub schedule: Vec<ScheduleWindow>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TriggerSyncRequest {
+    pub name: String,
+    /// Only sync with this remote workspace instead of all of them
+    pub remote_workspace_name: Option<String>,
+    /// Workspace-relative path of the resource to sync, the whole workspace being synced by default
+    pub path: Option<PathBuf>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PauseRequest {
+    pub name: String,
+    /// Only pause (or resume) the syncs with this remote workspace instead of the whole workspace
+    pub remote_workspace_name: Option<String>
+}
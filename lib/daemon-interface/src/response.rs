@@ -1,6 +1,7 @@
 use std::fmt::Display;
+use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
-use crate::{WorkspaceInfo, WorkspaceOverview};
+use crate::{RemoteFileVersions, RemoteSyncPreview, TriggeredSyncOutcome, WorkspaceInfo, WorkspaceOverview};
 use crate::response::ResponseStatus::{Error, NotFound, Success};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -21,6 +22,25 @@ pub struct ListWorkspaceInfoResponse {
     pub entries: Vec<WorkspaceInfo>
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PreviewSyncResponse {
+    pub workspace_name: String,
+    pub entries: Vec<RemoteSyncPreview>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TriggerSyncResponse {
+    pub workspace_name: String,
+    pub entries: Vec<TriggeredSyncOutcome>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ListVersionsResponse {
+    pub workspace_name: String,
+    pub path: PathBuf,
+    pub entries: Vec<RemoteFileVersions>
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ResponseStatus {
     Success,
@@ -37,7 +57,16 @@ pub enum ResponsePayload {
     AddWorkspace(String),
     RemoveWorkspace(String),
     AttachRemoteWorkspace(String),
-    DetachRemoteWorkspace(String)
+    DetachRemoteWorkspace(String),
+    PreviewSync(PreviewSyncResponse),
+    ApproveDeletions(String),
+    RejectDeletions(String),
+    ListVersions(ListVersionsResponse),
+    RestoreVersion(String),
+    SetSchedule(String),
+    Pause(String),
+    Resume(String),
+    TriggerSync(TriggerSyncResponse)
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::env;
+use std::fmt::Display;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
@@ -22,6 +23,14 @@ pub enum ConfigKey {
     MonitorExecutablePath,
     #[strum(serialize="LogDirectory")]
     LogDirectory,
+    #[strum(serialize="StateDirectory")]
+    StateDirectory,
+    #[strum(serialize="MaxConcurrentTransfers")]
+    MaxConcurrentTransfers,
+    #[strum(serialize="TotalBandwidthKbps")]
+    TotalBandwidthKbps,
+    #[strum(serialize="WorkspacePriorities")]
+    WorkspacePriorities,
 }
 
 #[derive(Debug)]
@@ -49,6 +58,21 @@ impl Config {
             .map(|val| PathBuf::from(val))
     }
 
+    /// Parse the value of the given key, which is `None` if the config doesn't specify it
+    pub fn get_parsed<T>(&self, key: ConfigKey) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: Display
+    {
+        let Some(val) = self.map.get(&key) else {
+            return Ok(None);
+        };
+
+        val.parse().map(Some).map_err(|e| {
+            Error::MalformedConfigFile(format!("Config entry '{key:?}' has an invalid value '{val}': {e}"))
+        })
+    }
+
 }
 
 static CONFIG: OnceLock<Config> = OnceLock::new();
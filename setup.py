@@ -11,6 +11,7 @@ BUILD_TARGET_DIR = "./build"
 
 HOME = os.environ["HOME"]
 WSYNC_LOG_DIRECTORY = os.path.join(HOME, ".wsync", "log")
+WSYNC_STATE_DIRECTORY = os.path.join(HOME, ".wsync", "state")
 WSYNC_DAEMON_CMD_SOCKET_DIRECTORY = "/tmp"
 WSYNC_DAEMON_CMD_SOCKET_NAME = "wsync-daemon-cmd.socket"
 WSYNC_WORKSPACE_CONFIGURATION_DIRECTORY = os.path.join(HOME, ".wsync")
@@ -67,6 +68,11 @@ def build_arg_parser():
         default=WSYNC_LOG_DIRECTORY,
         help="Directory in wich the wsync log files are stored")
 
+    parser.add_argument("--state-directory",
+        dest="WSYNC_STATE_DIRECTORY",
+        default=WSYNC_STATE_DIRECTORY,
+        help="Directory in which the monitors persist their state, e.g. the journals of not yet synced changes")
+
     parser.add_argument("--daemon-cmd-socket-directory",
         dest="WSYNC_DAEMON_CMD_SOCKET_DIRECTORY",
         default=WSYNC_DAEMON_CMD_SOCKET_DIRECTORY,
@@ -85,6 +91,8 @@ def prepare_wsync_exec_environment():
 
         os.makedirs(WSYNC_LOG_DIRECTORY, exist_ok=True)
 
+        os.makedirs(WSYNC_STATE_DIRECTORY, exist_ok=True)
+
         os.makedirs(WSYNC_DAEMON_CMD_SOCKET_DIRECTORY, exist_ok=True)
 
         os.makedirs(WSYNC_WORKSPACE_CONFIGURATION_DIRECTORY, exist_ok=True)
@@ -120,9 +128,11 @@ def build_crates():
         "./lib/daemon-client",
         "./lib/daemon-interface",
         "./lib/wsync-config",
+        "./lib/agent-protocol",
         "./src/client",
         "./src/monitor",
-        "./src/daemon"
+        "./src/daemon",
+        "./src/agent"
     ]
 
     if BUILD_CLEANUP:
@@ -164,6 +174,8 @@ def init_wsync_config():
 
         f.write("LogDirectory={}\n".format(WSYNC_LOG_DIRECTORY))
 
+        f.write("StateDirectory={}\n".format(WSYNC_STATE_DIRECTORY))
+
 def main():
     check_requirements()
 
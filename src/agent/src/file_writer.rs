@@ -0,0 +1,282 @@
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{File, FileTimes, OpenOptions, Permissions};
+use std::io::{BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use agent_protocol::delta::{DeltaOp, Digest};
+use agent_protocol::Timestamp;
+use crate::models::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Suffix of the temporary files that are renamed to their final name once completely written
+const TMP_FILE_SUFFIX: &str = ".wsync-tmp";
+
+/// A file that is being reconstructed from a basis file and literal data. The content is written to
+/// a temporary file next to the target file, which replaces the target file once it is complete.
+pub(crate) struct FileWriter {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    tmp_file: BufWriter<File>,
+    basis: Option<File>,
+    block_size: u64,
+    mode: u32,
+    mtime: Timestamp,
+    digest: Digest
+}
+
+impl FileWriter {
+
+    pub(crate) fn create(
+        path: PathBuf,
+        with_basis: bool,
+        block_size: u32,
+        mode: u32,
+        mtime: Timestamp
+    ) -> Result<Self> {
+        let basis = match with_basis {
+            true => Some(open_basis(&path)?),
+            false => None
+        };
+
+        let mut tmp_file_name = OsString::from(".");
+        tmp_file_name.push(path.file_name().unwrap_or_default());
+        tmp_file_name.push(TMP_FILE_SUFFIX);
+        let tmp_path = path.with_file_name(tmp_file_name);
+
+        // Left behind by an interrupted write. The temporary file is created exclusively, so that a
+        // symlink in its place is not followed.
+        match fs::remove_file(&tmp_path) {
+            Ok(_) => {},
+            Err(e) if e.kind() == ErrorKind::NotFound => {},
+            Err(e) => return Err(Error::new(format!("Unable to remove '{:?}': {e}", tmp_path)))
+        }
+
+        let tmp_file = OpenOptions::new().write(true).create_new(true).open(&tmp_path).map_err(|e| {
+            Error::new(format!("Unable to create '{:?}': {e}", tmp_path))
+        })?;
+
+        Ok(FileWriter {
+            path,
+            tmp_path,
+            tmp_file: BufWriter::new(tmp_file),
+            basis,
+            block_size: block_size as u64,
+            mode,
+            mtime,
+            digest: Digest::new()
+        })
+    }
+
+    /// Apply the given ops, taking the literal data from the given payload
+    pub(crate) fn apply(&mut self, ops: &[DeltaOp], payload: &[u8]) -> Result<()> {
+        let mut literal_data = payload;
+
+        for op in ops.iter() {
+            match *op {
+                DeltaOp::Copy { block, count } => self.copy_blocks(block as u64, count as u64)?,
+                DeltaOp::Literal { len } => {
+                    let len = len as usize;
+                    if len > literal_data.len() {
+                        return Err(Error::new("Literal op exceeds the received data"));
+                    }
+
+                    let (data, remaining) = literal_data.split_at(len);
+                    self.write(data)?;
+                    literal_data = remaining;
+                }
+            }
+        }
+
+        if !literal_data.is_empty() {
+            return Err(Error::new("Received more literal data than referenced by the ops"));
+        }
+
+        Ok(())
+    }
+
+    fn copy_blocks(&mut self, block: u64, count: u64) -> Result<()> {
+        let basis = self.basis.as_mut().ok_or(Error::new("Received copy op without a basis file"))?;
+
+        basis.seek(SeekFrom::Start(block * self.block_size)).map_err(|e| {
+            Error::new(format!("Unable to seek in '{:?}': {e}", self.path))
+        })?;
+
+        let mut data = vec![];
+        basis.take(count * self.block_size).read_to_end(&mut data).map_err(|e| {
+            Error::new(format!("Unable to read '{:?}': {e}", self.path))
+        })?;
+
+        self.write(&data)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.digest.update(data);
+        self.tmp_file.write_all(data).map_err(|e| Error::new(format!("Unable to write to '{:?}': {e}", self.tmp_path)))
+    }
+
+    /// Verify the written content and move it into place, replacing the previous file
+    pub(crate) fn finish(mut self, expected_digest: u64) -> Result<()> {
+        if self.digest.finish() != expected_digest {
+            self.abort();
+            return Err(Error::new("Digest of the reconstructed file does not match"));
+        }
+
+        if let Err(e) = self.tmp_file.flush() {
+            let error = Error::new(format!("Unable to write to '{:?}': {e}", self.tmp_path));
+            self.abort();
+            return Err(error);
+        }
+
+        let result = self.persist();
+        if result.is_err() {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+
+        result
+    }
+
+    fn persist(&self) -> Result<()> {
+        let tmp_file = self.tmp_file.get_ref();
+
+        tmp_file.set_permissions(Permissions::from_mode(self.mode)).map_err(|e| {
+            Error::new(format!("Unable to set permissions of '{:?}': {e}", self.tmp_path))
+        })?;
+
+        tmp_file.set_times(FileTimes::new().set_modified(self.mtime.into())).map_err(|e| {
+            Error::new(format!("Unable to set modification time of '{:?}': {e}", self.tmp_path))
+        })?;
+
+        if self.path.symlink_metadata().is_ok_and(|metadata| metadata.is_dir()) {
+            fs::remove_dir_all(&self.path).map_err(|e| Error::new(format!("Unable to remove '{:?}': {e}", self.path)))?;
+        }
+
+        fs::rename(&self.tmp_path, &self.path).map_err(|e| {
+            Error::new(format!("Unable to move '{:?}' to '{:?}': {e}", self.tmp_path, self.path))
+        })
+    }
+
+    /// Discard the written content, leaving the previous file untouched
+    pub(crate) fn abort(self) {
+        let _ = fs::remove_file(&self.tmp_path);
+    }
+}
+
+/// Open the file the content is reconstructed from, which must be a regular file rather than a
+/// symlink pointing anywhere
+fn open_basis(path: &Path) -> Result<File> {
+    let file = File::open(path).map_err(|e| Error::new(format!("Unable to open '{:?}': {e}", path)))?;
+
+    let metadata = fs::symlink_metadata(path).map_err(|e| Error::new(format!("Unable to access '{:?}': {e}", path)))?;
+    let file_metadata = file.metadata().map_err(|e| Error::new(format!("Unable to access '{:?}': {e}", path)))?;
+
+    // Comparing the opened file with the resource at the path detects a symlink, including one
+    // that replaced the file after it was opened
+    if !metadata.is_file() || (metadata.dev(), metadata.ino()) != (file_metadata.dev(), file_metadata.ino()) {
+        return Err(Error::new(format!("Basis file '{:?}' is not a regular file", path)));
+    }
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use agent_protocol::delta::{compute_delta, compute_signature, strong_hash};
+    use super::*;
+
+    const BLOCK_SIZE: u32 = 8;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wsync-file-writer-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn mtime() -> Timestamp {
+        Timestamp { secs: 1_000_000, nanos: 0 }
+    }
+
+    /// Rewrite the file at the given path with the given content, based on its current content
+    fn write(path: &Path, content: &[u8]) -> Result<()> {
+        let basis = fs::read(path).ok();
+        let signature = compute_signature(&mut basis.as_deref().unwrap_or_default(), BLOCK_SIZE).unwrap();
+        let delta = compute_delta(&signature, content);
+
+        let mut writer = FileWriter::create(path.to_path_buf(), basis.is_some(), BLOCK_SIZE, 0o640, mtime())?;
+        writer.apply(&delta.ops, &delta.literal_data)?;
+        writer.finish(strong_hash(content))
+    }
+
+    #[test]
+    fn file_is_reconstructed_from_basis() {
+        let dir = test_dir("reconstruct");
+        let path = dir.join("file");
+
+        assert!(write(&path, b"").is_ok());
+        assert_eq!(fs::read(&path).unwrap(), b"");
+
+        assert!(write(&path, b"a file that is not block-aligned").is_ok());
+        assert!(write(&path, b"a file that is still not block-aligned").is_ok());
+        assert_eq!(fs::read(&path).unwrap(), b"a file that is still not block-aligned");
+
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert_eq!(Timestamp::from(metadata.modified().unwrap()).secs, mtime().secs);
+        assert!(!dir.join(".file.wsync-tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn mismatching_digest_keeps_previous_file() {
+        let dir = test_dir("digest");
+        let path = dir.join("file");
+        fs::write(&path, b"previous").unwrap();
+
+        let mut writer = FileWriter::create(path.clone(), false, BLOCK_SIZE, 0o644, mtime()).ok().unwrap();
+        assert!(writer.apply(&[DeltaOp::Literal { len: 3 }], b"new").is_ok());
+        assert!(writer.finish(strong_hash(b"other")).is_err());
+
+        assert_eq!(fs::read(&path).unwrap(), b"previous");
+        assert!(!dir.join(".file.wsync-tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn malformed_ops_are_rejected() {
+        let dir = test_dir("ops");
+        let path = dir.join("file");
+
+        let mut writer = FileWriter::create(path.clone(), false, BLOCK_SIZE, 0o644, mtime()).ok().unwrap();
+        assert!(writer.apply(&[DeltaOp::Copy { block: 0, count: 1 }], b"").is_err());
+        assert!(writer.apply(&[DeltaOp::Literal { len: 4 }], b"abc").is_err());
+        assert!(writer.apply(&[DeltaOp::Literal { len: 1 }], b"abc").is_err());
+        writer.abort();
+
+        assert!(!dir.join(".file.wsync-tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn symlinks_are_not_followed() {
+        let dir = test_dir("symlinks");
+        fs::write(dir.join("outside"), b"outside").unwrap();
+
+        // The basis file
+        std::os::unix::fs::symlink(dir.join("outside"), dir.join("link")).unwrap();
+        assert!(FileWriter::create(dir.join("link"), true, BLOCK_SIZE, 0o644, mtime()).is_err());
+
+        // A temporary file left behind
+        std::os::unix::fs::symlink(dir.join("outside"), dir.join(".file.wsync-tmp")).unwrap();
+        assert!(write(&dir.join("file"), b"content").is_ok());
+
+        assert_eq!(fs::read(dir.join("outside")).unwrap(), b"outside");
+        assert_eq!(fs::read(dir.join("file")).unwrap(), b"content");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,296 @@
+use std::fs;
+use std::fs::{File, Metadata, Permissions};
+use std::io::ErrorKind;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use agent_protocol::delta::{compute_signature, Signature};
+use agent_protocol::{EntryInfo, EntryKind, ScannedEntry};
+use crate::models::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Resolve a path relative to the remote workspace root. Paths that could point outside of the root
+/// are rejected, i.e. paths containing `..` and paths within symlinked directories, whose target
+/// could be located anywhere. The resource itself may be a symlink, which is handled as such
+/// instead of being followed.
+pub(crate) fn resolve(root: &Path, relative_path: &Path) -> Result<PathBuf> {
+    let mut path = root.to_path_buf();
+    let mut components = relative_path.components().peekable();
+
+    while let Some(component) = components.next() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::CurDir => continue,
+            _ => return Err(Error::new(format!("'{:?}' is not a path within the remote workspace", relative_path)))
+        }
+
+        let is_parent = components.peek().is_some();
+        if is_parent && fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+            return Err(Error::new(format!("'{:?}' is located within a symlinked directory", relative_path)));
+        }
+    }
+
+    Ok(path)
+}
+
+/// Prepare the remote workspace root. Like rsync, the root itself is created if it does not exist,
+/// but its parent directories are not.
+pub(crate) fn handle_hello(root: &Path) -> Result<()> {
+    if !root.is_absolute() {
+        return Err(Error::new(format!("Remote workspace root '{:?}' is not absolute", root)));
+    }
+
+    match fs::create_dir(root) {
+        Ok(_) => {},
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {},
+        Err(e) => return Err(Error::new(format!("Unable to create remote workspace root '{:?}': {e}", root)))
+    }
+
+    if !root.is_dir() {
+        return Err(Error::new(format!("Remote workspace root '{:?}' is not a directory", root)));
+    }
+
+    Ok(())
+}
+
+fn to_entry_info(path: &Path, metadata: &Metadata) -> EntryInfo {
+    let file_type = metadata.file_type();
+
+    let kind = if file_type.is_dir() {
+        EntryKind::Directory
+    } else if file_type.is_symlink() {
+        EntryKind::Symlink
+    } else if file_type.is_file() {
+        EntryKind::File
+    } else {
+        EntryKind::Other
+    };
+
+    EntryInfo {
+        kind,
+        len: metadata.len(),
+        mode: metadata.permissions().mode() & 0o7777,
+        mtime: metadata.modified().unwrap_or(UNIX_EPOCH).into(),
+        link_target: if kind == EntryKind::Symlink { fs::read_link(path).ok() } else { None }
+    }
+}
+
+fn stat(path: &Path) -> Result<Option<Metadata>> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => Ok(Some(metadata)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::new(format!("Unable to access '{:?}': {e}", path)))
+    }
+}
+
+pub(crate) fn handle_stat(root: &Path, relative_paths: &[PathBuf]) -> Result<Vec<Option<EntryInfo>>> {
+    let mut entries = vec![];
+
+    for relative_path in relative_paths.iter() {
+        let path = resolve(root, relative_path)?;
+        let entry_info = stat(&path)?.map(|metadata| to_entry_info(&path, &metadata));
+        entries.push(entry_info);
+    }
+
+    Ok(entries)
+}
+
+pub(crate) fn handle_scan(root: &Path, relative_path: &Path) -> Result<Vec<ScannedEntry>> {
+    let mut entries = vec![];
+
+    if let Some(metadata) = stat(&resolve(root, relative_path)?)? {
+        scan_entry(root, relative_path.to_path_buf(), &metadata, &mut entries)?;
+    }
+
+    Ok(entries)
+}
+
+fn scan_entry(root: &Path, relative_path: PathBuf, metadata: &Metadata, entries: &mut Vec<ScannedEntry>) -> Result<()> {
+    let path = root.join(&relative_path);
+    entries.push(ScannedEntry { path: relative_path.clone(), info: to_entry_info(&path, metadata) });
+
+    if !metadata.is_dir() {
+        return Ok(());
+    }
+
+    let dir = path;
+    let dir_entries = match fs::read_dir(&dir) {
+        Ok(dir_entries) => dir_entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(Error::new(format!("Unable to read '{:?}': {e}", dir)))
+    };
+
+    for dir_entry in dir_entries {
+        let dir_entry = dir_entry.map_err(|e| Error::new(format!("Unable to read '{:?}': {e}", dir)))?;
+
+        let child_metadata = match dir_entry.metadata() {
+            Ok(child_metadata) => child_metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(Error::new(format!("Unable to access '{:?}': {e}", dir_entry.path())))
+        };
+
+        scan_entry(root, relative_path.join(dir_entry.file_name()), &child_metadata, entries)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn handle_signature(root: &Path, relative_path: &Path, block_size: u32) -> Result<Signature> {
+    let path = resolve(root, relative_path)?;
+    let empty_signature = Signature { block_size, file_len: 0, blocks: vec![] };
+
+    if !stat(&path)?.is_some_and(|metadata| metadata.is_file()) {
+        return Ok(empty_signature);
+    }
+
+    let mut file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(empty_signature),
+        Err(e) => return Err(Error::new(format!("Unable to open '{:?}': {e}", path)))
+    };
+
+    compute_signature(&mut file, block_size).map_err(|e| Error::new(format!("Unable to read '{:?}': {e}", path)))
+}
+
+pub(crate) fn handle_mkdir(root: &Path, relative_path: &Path, mode: u32) -> Result<()> {
+    let path = resolve(root, relative_path)?;
+
+    match stat(&path)? {
+        Some(metadata) if metadata.is_dir() => {},
+        Some(_) => {
+            remove(&path)?;
+            fs::create_dir(&path).map_err(|e| Error::new(format!("Unable to create '{:?}': {e}", path)))?;
+        },
+        None => {
+            fs::create_dir_all(&path).map_err(|e| Error::new(format!("Unable to create '{:?}': {e}", path)))?;
+        }
+    }
+
+    set_mode(&path, mode)
+}
+
+pub(crate) fn handle_symlink(root: &Path, relative_path: &Path, target: &Path) -> Result<()> {
+    let path = resolve(root, relative_path)?;
+
+    if stat(&path)?.is_some() {
+        remove(&path)?;
+    }
+
+    std::os::unix::fs::symlink(target, &path).map_err(|e| Error::new(format!("Unable to create '{:?}': {e}", path)))
+}
+
+pub(crate) fn handle_rename(root: &Path, from: &Path, to: &Path) -> Result<()> {
+    let from = resolve(root, from)?;
+    let to = resolve(root, to)?;
+
+    if from == root || to == root {
+        return Err(Error::new("The remote workspace root cannot be renamed"));
+    }
+
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).map_err(|e| Error::new(format!("Unable to create '{:?}': {e}", parent)))?;
+    }
+
+    fs::rename(&from, &to).map_err(|e| Error::new(format!("Unable to move '{:?}' to '{:?}': {e}", from, to)))
+}
+
+pub(crate) fn handle_delete(root: &Path, relative_path: &Path) -> Result<()> {
+    let path = resolve(root, relative_path)?;
+
+    if path == root || relative_path.as_os_str().is_empty() {
+        return Err(Error::new("The remote workspace root cannot be deleted"));
+    }
+
+    match stat(&path)? {
+        Some(_) => remove(&path),
+        None => Ok(())
+    }
+}
+
+pub(crate) fn handle_chmod(root: &Path, relative_path: &Path, mode: u32) -> Result<()> {
+    let path = resolve(root, relative_path)?;
+
+    // Changing the mode of a symlink would change the mode of its target instead
+    if stat(&path)?.is_some_and(|metadata| metadata.is_symlink()) {
+        return Ok(());
+    }
+
+    set_mode(&path, mode)
+}
+
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    fs::set_permissions(path, Permissions::from_mode(mode)).map_err(|e| {
+        Error::new(format!("Unable to set permissions of '{:?}': {e}", path))
+    })
+}
+
+fn remove(path: &Path) -> Result<()> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) => Err(e)
+    };
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(Error::new(format!("Unable to remove '{:?}': {e}", path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wsync-agent-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("root/dir")).unwrap();
+        fs::create_dir_all(dir.join("outside")).unwrap();
+        fs::write(dir.join("outside/file"), b"outside").unwrap();
+        std::os::unix::fs::symlink(dir.join("outside"), dir.join("root/link")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn paths_leaving_the_root_are_rejected() {
+        let dir = test_dir("resolve");
+        let root = dir.join("root");
+
+        assert_eq!(resolve(&root, Path::new("dir/file")).ok(), Some(root.join("dir/file")));
+        assert_eq!(resolve(&root, Path::new("./dir")).ok(), Some(root.join("dir")));
+        assert_eq!(resolve(&root, Path::new("")).ok(), Some(root.clone()));
+        // The symlink itself is handled as such
+        assert_eq!(resolve(&root, Path::new("link")).ok(), Some(root.join("link")));
+
+        for path in ["../outside/file", "dir/../../outside/file", "/etc/passwd", "link/file", "link/sub/file"] {
+            assert!(resolve(&root, Path::new(path)).is_err(), "{path}");
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn symlinked_directories_are_not_followed() {
+        let dir = test_dir("symlinks");
+        let root = dir.join("root");
+
+        assert!(handle_delete(&root, Path::new("link/file")).is_err());
+        assert!(handle_mkdir(&root, Path::new("link/new"), 0o755).is_err());
+        assert!(handle_symlink(&root, Path::new("link/new"), Path::new("/")).is_err());
+        assert!(handle_rename(&root, Path::new("dir"), Path::new("link/dir")).is_err());
+        assert!(dir.join("outside/file").exists());
+        assert!(!dir.join("outside/new").exists());
+        assert!(!dir.join("outside/dir").exists());
+
+        let mode = fs::metadata(dir.join("outside")).unwrap().permissions().mode();
+        assert!(handle_chmod(&root, Path::new("link"), 0o700).is_ok());
+        assert_eq!(fs::metadata(dir.join("outside")).unwrap().permissions().mode(), mode);
+
+        assert!(handle_delete(&root, Path::new("link")).is_ok());
+        assert!(dir.join("outside/file").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
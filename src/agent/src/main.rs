@@ -0,0 +1,180 @@
+use std::io;
+use std::io::{BufReader, BufWriter, ErrorKind, Stdin, Stdout};
+use std::path::PathBuf;
+use log::{debug, warn};
+use agent_protocol::errors::ProtocolError;
+use agent_protocol::frame::{read_frame, write_frame};
+use agent_protocol::{Request, Response, PROTOCOL_VERSION};
+use crate::file_writer::FileWriter;
+use crate::models::Error;
+use crate::util::error_exit;
+use crate::util::log::setup_logging;
+
+mod util;
+mod handlers;
+mod file_writer;
+mod models;
+
+/// Session with a single monitor, which sends its requests via stdin and receives the responses
+/// via stdout
+struct Session {
+    reader: BufReader<Stdin>,
+    writer: BufWriter<Stdout>,
+    root: PathBuf,
+    /// The file currently being written, along with the first error that occurred while writing it
+    pending_write: Option<(std::result::Result<FileWriter, Error>, PathBuf)>
+}
+
+impl Session {
+
+    fn read_request(&mut self) -> Result<Option<(Request, Vec<u8>)>, ProtocolError> {
+        match read_frame(&mut self.reader) {
+            Ok(frame) => Ok(Some(frame)),
+            Err(ProtocolError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+
+    fn respond(&mut self, response: Response) -> Result<(), ProtocolError> {
+        write_frame(&mut self.writer, &response, &[])
+    }
+
+    /// Handle a request, returning the response to be sent, if any
+    fn handle(&mut self, request: Request, payload: Vec<u8>) -> Option<Response> {
+        if !matches!(request, Request::WriteChunk { .. } | Request::EndWrite { .. } | Request::AbortWrite)
+            && let Some((writer, path)) = self.pending_write.take() {
+            warn!("Discarding incomplete write of '{:?}'", path);
+            if let Ok(writer) = writer {
+                writer.abort();
+            }
+        }
+
+        let result = match request {
+            Request::Hello { .. } => Err(Error::new("Session was already initialized")),
+            Request::Ping => Ok(Response::Ok),
+            Request::Stat { paths } => {
+                handlers::handle_stat(&self.root, &paths).map(|entries| Response::Stats { entries })
+            },
+            Request::Scan { path } => {
+                handlers::handle_scan(&self.root, &path).map(|entries| Response::Entries { entries })
+            },
+            Request::Signature { path, block_size } => {
+                handlers::handle_signature(&self.root, &path, block_size).map(Response::Signature)
+            },
+            Request::BeginWrite { path, with_basis, block_size, mode, mtime } => {
+                let writer = handlers::resolve(&self.root, &path).and_then(|resolved_path| {
+                    FileWriter::create(resolved_path, with_basis, block_size, mode, mtime)
+                });
+
+                self.pending_write = Some((writer, path));
+                return None;
+            },
+            Request::WriteChunk { ops } => {
+                // Errors are reported once the write is complete, since the monitor does not wait
+                // for a response before sending the next chunk
+                if let Some((Ok(mut writer), path)) = self.pending_write.take() {
+                    let pending_write = match writer.apply(&ops, &payload) {
+                        Ok(_) => Ok(writer),
+                        Err(e) => {
+                            writer.abort();
+                            Err(e)
+                        }
+                    };
+                    self.pending_write = Some((pending_write, path));
+                }
+                return None;
+            },
+            Request::AbortWrite => {
+                if let Some((Ok(writer), _)) = self.pending_write.take() {
+                    writer.abort();
+                }
+                return None;
+            },
+            Request::EndWrite { digest } => {
+                match self.pending_write.take() {
+                    Some((Ok(writer), _)) => writer.finish(digest).map(|_| Response::Ok),
+                    Some((Err(e), _)) => Err(e),
+                    None => Err(Error::new("No file is being written"))
+                }
+            },
+            Request::Mkdir { path, mode } => {
+                handlers::handle_mkdir(&self.root, &path, mode).map(|_| Response::Ok)
+            },
+            Request::Symlink { path, target } => {
+                handlers::handle_symlink(&self.root, &path, &target).map(|_| Response::Ok)
+            },
+            Request::Rename { from, to } => {
+                handlers::handle_rename(&self.root, &from, &to).map(|_| Response::Ok)
+            },
+            Request::Delete { path } => {
+                handlers::handle_delete(&self.root, &path).map(|_| Response::Ok)
+            },
+            Request::Chmod { path, mode } => {
+                handlers::handle_chmod(&self.root, &path, mode).map(|_| Response::Ok)
+            }
+        };
+
+        Some(result.unwrap_or_else(|e| {
+            debug!("Request failed: {e}");
+            Response::Error { message: e.msg }
+        }))
+    }
+}
+
+/// Wait for the monitor's hello request and prepare the remote workspace root it refers to
+fn start_session() -> Session {
+    let mut reader = BufReader::new(io::stdin());
+    let mut writer = BufWriter::new(io::stdout());
+
+    let hello = read_frame::<_, Request>(&mut reader).unwrap_or_else(|e| {
+        error_exit(Some(format!("Unable to read hello request: {e}")))
+    });
+
+    let (version, root) = match hello {
+        (Request::Hello { version, root }, _) => (version, root),
+        (request, _) => error_exit(Some(format!("Expected hello request, got: {:?}", request)))
+    };
+
+    let result = if version != PROTOCOL_VERSION {
+        Err(Error::new(format!("Unsupported protocol version {version}, expected {PROTOCOL_VERSION}")))
+    } else {
+        handlers::handle_hello(&root)
+    };
+
+    let response = match &result {
+        Ok(_) => Response::Ready { version: PROTOCOL_VERSION },
+        Err(e) => Response::Error { message: e.msg.clone() }
+    };
+
+    if let Err(e) = write_frame(&mut writer, &response, &[]) {
+        error_exit(Some(format!("Unable to respond to hello request: {e}")));
+    }
+
+    if let Err(e) = result {
+        error_exit(Some(format!("Unable to start session: {e}")));
+    }
+
+    debug!("Started session for remote workspace '{:?}'", root);
+    Session { reader, writer, root, pending_write: None }
+}
+
+fn main() {
+    setup_logging();
+
+    let mut session = start_session();
+
+    loop {
+        let (request, payload) = match session.read_request() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(e) => error_exit(Some(format!("Unable to read request: {e}")))
+        };
+
+        if let Some(response) = session.handle(request, payload)
+            && let Err(e) = session.respond(response) {
+            error_exit(Some(format!("Unable to send response: {e}")));
+        }
+    }
+
+    debug!("Monitor closed the session for '{:?}'", session.root);
+}
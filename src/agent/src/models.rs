@@ -0,0 +1,17 @@
+use std::fmt::{Display, Formatter};
+
+pub(crate) struct Error {
+    pub(crate) msg: String
+}
+
+impl Error {
+    pub fn new<T: AsRef<str>>(msg: T) -> Self {
+        Error { msg: msg.as_ref().to_string() }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
@@ -0,0 +1,30 @@
+use std::process;
+use log4rs::append::console::{ConsoleAppender, Target};
+use log4rs::Config;
+use log4rs::config::{Appender, Root};
+use log4rs::encode::pattern::PatternEncoder;
+use log::{debug, LevelFilter};
+
+pub(crate) fn setup_logging() {
+    let stderr = ConsoleAppender::builder()
+        .target(Target::Stderr)
+        .encoder(Box::new(PatternEncoder::new("{h({d(%Y-%m-%d %H:%M:%S)} - [{l}] wsync-agent: {m}{n})}")))
+        .build();
+
+    let appender = Appender::builder().build("stderr", Box::new(stderr));
+
+    let config = Config::builder()
+        .appender(appender)
+        .build(Root::builder().appender("stderr").build(LevelFilter::Info))
+        .unwrap_or_else(|e| {
+            eprintln!("An error occurred while initializing the logging infrastructure: {e:?}");
+            process::exit(1);
+        });
+
+    log4rs::init_config(config).unwrap_or_else(|e| {
+        eprintln!("An error occurred while initializing the logging infrastructure: {e:?}");
+        process::exit(1);
+    });
+
+    debug!("Initialized logging framework");
+}
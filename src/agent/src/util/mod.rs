@@ -0,0 +1,12 @@
+use std::process;
+use ::log::error;
+
+pub(crate) mod log;
+
+pub(crate) fn error_exit(msg: Option<String>) -> ! {
+    if let Some(msg) = msg {
+        error!("{msg}");
+    }
+    error!("Terminating wsync agent");
+    process::exit(1);
+}
@@ -1,7 +1,7 @@
 use std::net::IpAddr;
 use std::path::PathBuf;
 use std::process;
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 pub(self) type Result<T> = std::result::Result<T, Error>;
 
@@ -42,10 +42,37 @@ pub(crate) enum Command {
     RemoveWorkspace(RemoveWorkspaceArgs),
     /// Attach a remote workspace to a local workspace managed by wsync. Afterwards, all changes in
     /// the local workspace will be propagated to the remote workspace
-    AttachRemoteWorkspace(AttachRemoteWorkspaceCommand),
+    AttachRemoteWorkspace(Box<AttachRemoteWorkspaceCommand>),
     /// Detach a remote workspace from a local workspace managed by wsync. Afterwards, changes in
     /// the local workspace will no longer be propagated to the remote workspace
-    DetachRemoteWorkspace(DetachRemoteWorkspaceArgs)
+    DetachRemoteWorkspace(DetachRemoteWorkspaceArgs),
+    /// Show what syncing a workspace would change in its remote workspaces (i.e. which resources
+    /// would be created, updated or deleted), without changing anything
+    Preview(PreviewArgs),
+    /// Sync a workspace with its remote workspaces right away, regardless of what changed, and
+    /// wait for the outcome. Remote workspaces whose syncs are paused or hold deletions pending
+    /// approval are skipped
+    Sync(SyncArgs),
+    /// Approve deletions held back by the workspace's deletion guard, which are then synced to the
+    /// remote workspaces
+    ApproveDeletions(ResolveDeletionsArgs),
+    /// Reject deletions held back by the workspace's deletion guard. The deleted resources are
+    /// restored from the remote workspaces
+    RejectDeletions(ResolveDeletionsArgs),
+    /// List the previous versions of a file that are kept in the remote workspaces
+    ListVersions(ListVersionsArgs),
+    /// Restore a previous version of a file from a remote workspace into the local workspace,
+    /// replacing the current file. The restored file is then synced to all remote workspaces
+    RestoreVersion(RestoreVersionArgs),
+    /// Set the windows of the day in which syncs with a remote workspace are paused or transferred
+    /// at a different bandwidth, replacing its current schedule. Without any window, the remote
+    /// workspace is synced at all times at the bandwidth given when attaching it
+    SetSchedule(SetScheduleArgs),
+    /// Pause the syncs of a workspace, or only those with one of its remote workspaces. Changes are
+    /// still collected and synced once the syncs are resumed. Pauses persist across daemon restarts
+    Pause(PauseArgs),
+    /// Resume paused syncs, syncing the changes collected while they were paused
+    Resume(PauseArgs)
 }
 
 #[derive(Args)]
@@ -58,6 +85,125 @@ pub(crate) struct WorkspaceInfoArgs {
     pub(crate) name: String
 }
 
+#[derive(Args)]
+pub(crate) struct PreviewArgs {
+    /// Name of the local workspace whose sync should be previewed
+    #[arg(short, long)]
+    pub(crate) name: String,
+
+    /// Name of the remote workspace to preview the sync with. By default, the sync with all remote
+    /// workspaces is previewed
+    #[arg(short, long)]
+    pub(crate) remote: Option<String>
+}
+
+#[derive(Args)]
+pub(crate) struct SyncArgs {
+    /// Name of the local workspace that should be synced
+    #[arg(short, long)]
+    pub(crate) name: String,
+
+    /// Name of the remote workspace to sync with. By default, all remote workspaces are synced
+    #[arg(short, long)]
+    pub(crate) remote: Option<String>,
+
+    /// Path of the resource to sync, relative to the workspace root. By default, the whole
+    /// workspace is synced
+    #[arg(short, long)]
+    pub(crate) path: Option<PathBuf>
+}
+
+#[derive(Args)]
+pub(crate) struct ResolveDeletionsArgs {
+    /// Name of the local workspace whose held deletions should be resolved
+    #[arg(short, long)]
+    pub(crate) name: String,
+
+    /// Name of the remote workspace whose held deletions should be resolved. By default, the
+    /// deletions held for all remote workspaces are resolved
+    #[arg(short, long)]
+    pub(crate) remote: Option<String>
+}
+
+#[derive(Args)]
+pub(crate) struct ListVersionsArgs {
+    /// Name of the local workspace containing the file
+    #[arg(short, long)]
+    pub(crate) name: String,
+
+    /// Name of the remote workspace whose versions should be listed. By default, the versions kept
+    /// in all remote workspaces are listed
+    #[arg(short, long)]
+    pub(crate) remote: Option<String>,
+
+    /// Path of the file relative to the workspace root
+    #[arg(short, long)]
+    pub(crate) path: PathBuf
+}
+
+#[derive(Args)]
+pub(crate) struct RestoreVersionArgs {
+    /// Name of the local workspace containing the file
+    #[arg(short, long)]
+    pub(crate) name: String,
+
+    /// Name of the remote workspace keeping the version
+    #[arg(short, long)]
+    pub(crate) remote: String,
+
+    /// Path of the file relative to the workspace root
+    #[arg(short, long)]
+    pub(crate) path: PathBuf,
+
+    /// Version to be restored, as listed by 'list-versions'
+    #[arg(long, value_name = "VERSION")]
+    pub(crate) version_id: String
+}
+
+#[derive(Args)]
+pub(crate) struct PauseArgs {
+    /// Name of the local workspace whose syncs should be paused or resumed
+    #[arg(short, long)]
+    pub(crate) name: String,
+
+    /// Name of the remote workspace whose syncs should be paused or resumed. By default, the syncs
+    /// with all remote workspaces are paused or resumed
+    #[arg(short, long)]
+    pub(crate) remote: Option<String>
+}
+
+#[derive(Args)]
+pub(crate) struct SetScheduleArgs {
+    /// Name of the local workspace to which the remote workspace is attached
+    #[arg(short, long)]
+    pub(crate) name: String,
+
+    /// Name of the remote workspace whose schedule should be set
+    #[arg(short, long)]
+    pub(crate) remote: String,
+
+    /// Window of the day in local time and what happens within it: 'pause' defers syncs until the
+    /// window closes, 'full-speed' doesn't limit the bandwidth and a number limits it to that many
+    /// KiB per second (e.g. '09:00-10:00=pause' or '07:00-19:00=500'). Windows ending before they
+    /// start span midnight. Can be specified multiple times, if windows overlap the first one applies
+    #[arg(long = "window", value_name = "HH:MM-HH:MM=ACTION", value_parser = parse_schedule_window)]
+    pub(crate) windows: Vec<ScheduleWindowArg>
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ScheduleWindowArg {
+    pub(crate) start_minute: u16,
+    pub(crate) end_minute: u16,
+    pub(crate) action: ScheduleActionArg
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ScheduleActionArg {
+    Pause,
+    FullSpeed,
+    Bwlimit(u32)
+}
+
 #[derive(Args)]
 pub(crate) struct AddWorkspaceArgs {
     /// Name of the workspace to be added. This name must be unique among all managed local workspaces
@@ -68,7 +214,68 @@ pub(crate) struct AddWorkspaceArgs {
 
     /// Absolute path to the local workspace
     #[arg(short, long)]
-    pub(crate) path: PathBuf
+    pub(crate) path: PathBuf,
+
+    /// Number of milliseconds without further changes after which detected changes are synced
+    /// with the remote workspaces
+    #[arg(long)]
+    pub(crate) quiet_window_ms: Option<u64>,
+
+    /// Maximum number of milliseconds a detected change may be held back before it is synced,
+    /// even if further changes keep coming in
+    #[arg(long)]
+    pub(crate) max_latency_ms: Option<u64>,
+
+    /// Maximum number of remote workspaces that changes are synced to concurrently
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub(crate) max_parallel_syncs: Option<u16>,
+
+    /// Pattern (gitignore syntax) of resources in the workspace that should neither be watched nor
+    /// synced. Can be specified multiple times. Additional patterns can be placed in a
+    /// '.wsyncignore' file in the workspace root
+    #[arg(long = "ignore", value_name = "PATTERN")]
+    pub(crate) ignore_patterns: Vec<String>,
+
+    /// Hold syncs that would delete more than this many files in the remote workspaces until the
+    /// deletions are approved or rejected
+    #[arg(long)]
+    pub(crate) max_deletions: Option<usize>,
+
+    /// Hold syncs that would delete more than this percentage of the workspace's files in the
+    /// remote workspaces until the deletions are approved or rejected
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub(crate) max_deletion_percent: Option<u8>,
+
+    /// How the remote workspaces are caught up with changes made while the monitor was not
+    /// running, whenever the monitor starts
+    #[arg(long, value_enum)]
+    pub(crate) initial_sync: Option<InitialSyncArg>,
+
+    /// How the monitor detects changes in the workspace on Linux
+    #[arg(long, value_enum)]
+    pub(crate) watcher: Option<WatcherArg>
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum InitialSyncArg {
+    /// Sync the entire workspace (default)
+    Full,
+    /// Compare the workspace with the remote workspaces by checksum and only sync the resources
+    /// that differ. Only supported for remote workspaces synced via rsync
+    Verify,
+    /// Only sync the resources that changed since the monitor last stopped
+    Modified,
+    /// Only sync the changes that were left unsynced when the monitor last stopped
+    Skip
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum WatcherArg {
+    /// Watch each directory of the workspace via inotify (default)
+    Inotify,
+    /// Watch the workspace's filesystem as a whole via fanotify, which avoids walking the
+    /// workspace at startup. Requires the daemon to run as root, inotify being used otherwise
+    Fanotify
 }
 
 #[derive(Args)]
@@ -91,7 +298,15 @@ pub(crate) enum AttachRemoteWorkspaceSubcommands {
     Ssh(SshArgs),
     /// Attach a remote workspace to a local workspace managed by wsync.
     /// wsync will connect to the remote system via the rsync daemon
-    Rsync(RsyncArgs)
+    Rsync(RsyncArgs),
+    /// Attach a remote workspace to a local workspace managed by wsync.
+    /// wsync will mirror the workspace to a directory on a locally mounted filesystem (e.g. an
+    /// external drive or an NFS mount)
+    Local(LocalArgs),
+    /// Attach a remote workspace to a local workspace managed by wsync.
+    /// wsync will start 'wsync-agent' on the remote system via SSH and only transfer the changed
+    /// parts of files
+    Agent(AgentArgs)
 }
 
 #[derive(Args, Debug)]
@@ -111,6 +326,142 @@ pub(crate) struct AttachRemoteWorkspaceArgs {
     pub(crate) remote_path: PathBuf
 }
 
+#[derive(Args, Debug)]
+#[command(next_help_heading = "Sync options")]
+pub(crate) struct SyncOptionArgs {
+    /// Do not delete resources in the remote workspace that no longer exist in the local workspace
+    #[arg(long)]
+    pub(crate) no_delete: bool,
+
+    /// Do not compress data during the transfer
+    #[arg(long)]
+    pub(crate) no_compress: bool,
+
+    /// Compression algorithm to be used during the transfer (zstd, lz4, zlibx, zlib or none)
+    #[arg(long, conflicts_with = "no_compress")]
+    pub(crate) compress_choice: Option<String>,
+
+    /// Decide whether files need to be transferred based on their checksum instead of their size
+    /// and modification time
+    #[arg(long)]
+    pub(crate) checksum: bool,
+
+    /// Maximum bandwidth to be used for the transfer in KiB per second
+    #[arg(long, value_name = "KBPS", value_parser = clap::value_parser!(u32).range(1..))]
+    pub(crate) bwlimit: Option<u32>,
+
+    /// Permissions to be applied to the transferred files, in rsync's '--chmod' syntax
+    /// (e.g. 'D2775,F664')
+    #[arg(long)]
+    pub(crate) chmod: Option<String>,
+
+    /// Owner and group to be applied to the transferred files (USER:GROUP)
+    #[arg(long, value_name = "USER:GROUP")]
+    pub(crate) chown: Option<String>,
+
+    /// Keep partially transferred files, so that interrupted transfers can be resumed
+    #[arg(long)]
+    pub(crate) partial: bool,
+
+    /// Update files in the remote workspace in place instead of replacing them
+    #[arg(long)]
+    pub(crate) inplace: bool,
+
+    /// Preserve hard links
+    #[arg(long)]
+    pub(crate) hard_links: bool,
+
+    /// Preserve ACLs
+    #[arg(long)]
+    pub(crate) acls: bool,
+
+    /// Preserve extended attributes
+    #[arg(long)]
+    pub(crate) xattrs: bool,
+
+    /// Do not preserve permissions
+    #[arg(long)]
+    pub(crate) no_perms: bool,
+
+    /// Do not preserve the owner, which is otherwise preserved if permitted on the remote system
+    #[arg(long)]
+    pub(crate) no_owner: bool,
+
+    /// Do not preserve the group, which is otherwise preserved if permitted on the remote system
+    #[arg(long)]
+    pub(crate) no_group: bool,
+
+    /// Do not preserve modification times
+    #[arg(long)]
+    pub(crate) no_times: bool
+}
+
+#[derive(Args, Debug)]
+#[command(next_help_heading = "Two-way sync")]
+pub(crate) struct TwoWayArgs {
+    /// Also sync changes made in the remote workspace back into the local workspace
+    #[arg(long, conflicts_with_all = ["no_delete", "no_times"])]
+    pub(crate) two_way: bool,
+
+    /// Decide which version of a resource is kept if it was changed both locally and remotely
+    #[arg(long, value_enum, default_value_t = ConflictPolicyArg::KeepBoth, requires = "two_way")]
+    pub(crate) conflict_policy: ConflictPolicyArg,
+
+    /// Interval in seconds in which the remote workspace is checked for changes
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..), requires = "two_way")]
+    pub(crate) poll_interval_secs: u64
+}
+
+#[derive(Args, Debug)]
+#[command(next_help_heading = "Versioning")]
+pub(crate) struct VersioningArgs {
+    /// Keep the previous versions of files that syncs overwrite or delete in the remote workspace,
+    /// in a directory per sync below '.wsync-versions'
+    #[arg(long)]
+    pub(crate) versioning: bool,
+
+    /// Maximum number of versions kept per file
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), requires = "versioning")]
+    pub(crate) keep_versions: Option<u32>,
+
+    /// Maximum number of days a version is kept
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), requires = "versioning")]
+    pub(crate) keep_days: Option<u32>
+}
+
+#[derive(Args, Debug)]
+#[command(next_help_heading = "Post-sync hook")]
+pub(crate) struct HookArgs {
+    /// Shell command that is run on the remote system after changes were synced to the remote
+    /// workspace, e.g. to restart a server
+    #[arg(long = "hook", value_name = "COMMAND")]
+    pub(crate) hook_command: Option<String>,
+
+    /// Directory the hook is run in, relative to the remote workspace.
+    /// Defaults to the remote workspace
+    #[arg(long, requires = "hook_command")]
+    pub(crate) hook_working_dir: Option<PathBuf>,
+
+    /// Number of seconds after which the hook is killed
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..), requires = "hook_command")]
+    pub(crate) hook_timeout_secs: u64,
+
+    /// Number of milliseconds without a further sync after which the hook is run, so that it runs
+    /// once after a burst of syncs
+    #[arg(long, default_value_t = 1000, requires = "hook_command")]
+    pub(crate) hook_debounce_ms: u64
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum ConflictPolicyArg {
+    /// Keep the local version
+    LocalWins,
+    /// Keep the remote version
+    RemoteWins,
+    /// Keep the remote version and move the local version to a copy with a conflict suffix
+    KeepBoth
+}
+
 #[derive(Args)]
 #[group(required = false, multiple = false)]
 pub(crate) struct HostInfo {
@@ -148,7 +499,19 @@ pub(crate) struct SshArgs {
     /// Alias specified in the SSH config file that defines all the information required to establish
     /// an SSH connection to the remote system.
     #[arg(long, conflicts_with_all = vec!["hostname", "ip_addr", "port", "user", "identity_file"])]
-    pub(crate) host_alias: Option<String>
+    pub(crate) host_alias: Option<String>,
+
+    #[command(flatten)]
+    pub(crate) two_way: TwoWayArgs,
+
+    #[command(flatten)]
+    pub(crate) versioning: VersioningArgs,
+
+    #[command(flatten)]
+    pub(crate) hook: HookArgs,
+
+    #[command(flatten)]
+    pub(crate) sync_options: SyncOptionArgs
 }
 
 #[derive(Args)]
@@ -165,7 +528,76 @@ pub(crate) struct RsyncArgs {
 
     /// Username to be used when establishing a connection
     #[arg(long)]
-    pub(crate) user: Option<String>
+    pub(crate) user: Option<String>,
+
+    #[command(flatten)]
+    pub(crate) two_way: TwoWayArgs,
+
+    #[command(flatten)]
+    pub(crate) versioning: VersioningArgs,
+
+    #[command(flatten)]
+    pub(crate) sync_options: SyncOptionArgs
+}
+
+#[derive(Args)]
+pub(crate) struct LocalArgs {
+    #[command(flatten)]
+    pub(crate) args: AttachRemoteWorkspaceArgs,
+
+    /// Absolute path to the mount point of the filesystem holding the remote workspace. The remote
+    /// workspace directory must already exist, so that syncs fail while the filesystem is unmounted
+    #[arg(long)]
+    pub(crate) mount_point: PathBuf,
+
+    #[command(flatten)]
+    pub(crate) two_way: TwoWayArgs,
+
+    #[command(flatten)]
+    pub(crate) hook: HookArgs,
+
+    #[command(flatten)]
+    pub(crate) sync_options: SyncOptionArgs
+}
+
+#[derive(Args)]
+pub(crate) struct AgentArgs {
+    #[command(flatten)]
+    pub(crate) args: AttachRemoteWorkspaceArgs,
+
+    #[command(flatten)]
+    pub(crate) host_info: HostInfo,
+
+    /// Alias specified in the SSH config file of the remote system to start the agent on
+    #[arg(long, conflicts_with_all = vec!["hostname", "ip_addr"])]
+    pub(crate) host_alias: Option<String>,
+
+    /// Port to be used when establishing an SSH connection
+    #[arg(long)]
+    pub(crate) port: Option<u16>,
+
+    /// Username to be used when establishing an SSH connection
+    #[arg(long)]
+    pub(crate) user: Option<String>,
+
+    /// Path to an SSH identity file holding the key needed to authenticate with the remote system.
+    #[arg(long)]
+    pub(crate) identity_file: Option<PathBuf>,
+
+    /// Path to the agent executable on the remote system, 'wsync-agent' being looked up in the
+    /// PATH by default
+    #[arg(long)]
+    pub(crate) agent_path: Option<PathBuf>,
+
+    /// Start the agent as a local child process instead of connecting to a remote system
+    #[arg(long, conflicts_with_all = vec!["hostname", "ip_addr", "host_alias", "port", "user", "identity_file"])]
+    pub(crate) local: bool,
+
+    #[command(flatten)]
+    pub(crate) hook: HookArgs,
+
+    #[command(flatten)]
+    pub(crate) sync_options: SyncOptionArgs
 }
 
 #[derive(Args)]
@@ -180,6 +612,37 @@ pub(crate) struct DetachRemoteWorkspaceArgs {
     pub(crate) remote_workspace_name: String,
 }
 
+fn parse_schedule_window(window: &str) -> std::result::Result<ScheduleWindowArg, String> {
+    let (times, action) = window.split_once('=').ok_or("expected HH:MM-HH:MM=ACTION")?;
+    let (start, end) = times.split_once('-').ok_or("expected the window as HH:MM-HH:MM")?;
+
+    let action = match action {
+        "pause" => ScheduleActionArg::Pause,
+        "full-speed" => ScheduleActionArg::FullSpeed,
+        bwlimit => match bwlimit.parse::<u32>() {
+            Ok(bwlimit) if bwlimit > 0 => ScheduleActionArg::Bwlimit(bwlimit),
+            _ => return Err(format!("'{action}' is neither 'pause', 'full-speed' nor a bandwidth limit in KiB per second"))
+        }
+    };
+
+    Ok(ScheduleWindowArg { start_minute: parse_time_of_day(start)?, end_minute: parse_time_of_day(end)?, action })
+}
+
+/// Minutes since midnight of a time given as HH:MM
+fn parse_time_of_day(time: &str) -> std::result::Result<u16, String> {
+    let invalid = || format!("'{time}' is not a time of day (HH:MM)");
+
+    let (hours, minutes) = time.split_once(':').ok_or_else(invalid)?;
+    let hours: u16 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u16 = minutes.parse().map_err(|_| invalid())?;
+
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+
+    Ok(hours * 60 + minutes)
+}
+
 pub(self) fn validate_ssh_connection_args(args: &SshArgs) -> Result<()> {
 
     if args.host_alias.is_some() {
@@ -207,6 +670,41 @@ pub(self) fn validate_rsync_connection_args(args: &RsyncArgs) -> Result<()> {
     Ok(())
 }
 
+fn validate_local_args(args: &LocalArgs) -> Result<()> {
+
+    if !args.args.remote_path.starts_with(&args.mount_point) || args.args.remote_path == args.mount_point {
+        return Err(Error::new(
+            "The remote path must be a directory below the mount point!".to_string()
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_agent_args(args: &AgentArgs) -> Result<()> {
+
+    if !args.local && args.host_alias.is_none() && args.host_info.hostname.is_none() && args.host_info.ip_addr.is_none() {
+        return Err(Error::new(
+            "Either the remote system's domain name, its IP address or a host alias must be provided, \
+            or the agent must be started locally!".to_string()
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_add_workspace_args(args: &AddWorkspaceArgs) -> Result<()> {
+
+    if let (Some(quiet_window_ms), Some(max_latency_ms)) = (args.quiet_window_ms, args.max_latency_ms)
+        && max_latency_ms < quiet_window_ms {
+        return Err(Error::new(
+            "The max latency must not be shorter than the quiet window!".to_string()
+        ));
+    }
+
+    Ok(())
+}
+
 pub(self) fn validate_attach_remote_ws_subcommand(sub_cmd: &AttachRemoteWorkspaceCommand) -> Result<()> {
     match &sub_cmd.command {
         AttachRemoteWorkspaceSubcommands::Ssh(args) => {
@@ -214,6 +712,12 @@ pub(self) fn validate_attach_remote_ws_subcommand(sub_cmd: &AttachRemoteWorkspac
         },
         AttachRemoteWorkspaceSubcommands::Rsync(args) => {
             validate_rsync_connection_args(args)?;
+        },
+        AttachRemoteWorkspaceSubcommands::Local(args) => {
+            validate_local_args(args)?;
+        },
+        AttachRemoteWorkspaceSubcommands::Agent(args) => {
+            validate_agent_args(args)?;
         }
     }
     Ok(())
@@ -223,14 +727,15 @@ pub(crate) fn parse_cli_arguments() -> Cli {
     let cli: Cli = Cli::parse();
 
     // Perform additional validation that cannot be expressed with clap
-    match &cli.command {
-        Command::AttachRemoteWorkspace(sub_cmd) => {
-            if let Err(e) = validate_attach_remote_ws_subcommand(sub_cmd) {
-                eprintln!("[INPUT VALIDATION ERROR] {}", e.msg);
-                process::exit(1);
-            }
-        },
-        _ => {}
+    let validation_result = match &cli.command {
+        Command::AddWorkspace(args) => validate_add_workspace_args(args),
+        Command::AttachRemoteWorkspace(sub_cmd) => validate_attach_remote_ws_subcommand(sub_cmd),
+        _ => Ok(())
+    };
+
+    if let Err(e) = validation_result {
+        eprintln!("[INPUT VALIDATION ERROR] {}", e.msg);
+        process::exit(1);
     }
 
     cli
@@ -4,22 +4,60 @@ use serde_json::Value;
 use crate::cli::{
     WorkspaceInfoArgs,
     AddWorkspaceArgs,
+    InitialSyncArg,
+    WatcherArg,
     RemoveWorkspaceArgs,
     AttachRemoteWorkspaceSubcommands,
     SshArgs,
     RsyncArgs,
+    LocalArgs,
+    AgentArgs,
     DetachRemoteWorkspaceArgs,
+    PreviewArgs,
+    ResolveDeletionsArgs,
+    ListVersionsArgs,
+    RestoreVersionArgs,
+    SetScheduleArgs,
+    PauseArgs,
+    SyncArgs,
+    ScheduleWindowArg,
+    ScheduleActionArg,
     Cli,
     Command,
     HostInfo,
+    SyncOptionArgs,
+    TwoWayArgs,
+    VersioningArgs,
+    HookArgs,
+    ConflictPolicyArg,
+};
+use daemon_interface::{
+    request,
+    ConflictPolicy,
+    ConnectionInfo,
+    DeletionGuardSettings,
+    InitialSyncStrategy,
+    PostSyncHook,
+    ScheduleAction,
+    ScheduleWindow,
+    SyncOptions,
+    TwoWaySettings,
+    VersioningSettings,
+    WatcherBackend
 };
-use daemon_interface::{request, ConnectionInfo};
 use daemon_interface::request::{
     AddWorkspaceRequest,
     AttachRemoteWorkspaceRequest,
     CommandRequest,
     DetachRemoteWorkspaceRequest,
+    ListVersionsRequest,
+    PauseRequest,
+    PreviewSyncRequest,
     RemoveWorkspaceRequest,
+    ResolveDeletionsRequest,
+    RestoreVersionRequest,
+    SetScheduleRequest,
+    TriggerSyncRequest,
     WorkspaceInfoRequest,
 };
 
@@ -69,11 +107,44 @@ impl ClientRequest {
                     AttachRemoteWorkspaceSubcommands::Rsync(args) => {
                         Ok(Self::get_rsync_attach_remote_workspace_request(args)?)
                     }
+                    AttachRemoteWorkspaceSubcommands::Local(args) => {
+                        Ok(Self::get_local_attach_remote_workspace_request(args)?)
+                    }
+                    AttachRemoteWorkspaceSubcommands::Agent(args) => {
+                        Ok(Self::get_agent_attach_remote_workspace_request(args)?)
+                    }
                 }
             }
             Command::DetachRemoteWorkspace(args) => {
                 Ok(Self::get_detach_remote_workspace_request(args)?)
             }
+            Command::Preview(args) => {
+                Ok(Self::get_preview_sync_request(args)?)
+            }
+            Command::ApproveDeletions(args) => {
+                Ok(Self::get_resolve_deletions_request(request::Command::ApproveDeletions, args)?)
+            }
+            Command::RejectDeletions(args) => {
+                Ok(Self::get_resolve_deletions_request(request::Command::RejectDeletions, args)?)
+            }
+            Command::ListVersions(args) => {
+                Self::get_list_versions_request(args)
+            }
+            Command::RestoreVersion(args) => {
+                Self::get_restore_version_request(args)
+            }
+            Command::SetSchedule(args) => {
+                Self::get_set_schedule_request(args)
+            }
+            Command::Pause(args) => {
+                Self::get_pause_request(request::Command::Pause, args)
+            }
+            Command::Resume(args) => {
+                Self::get_pause_request(request::Command::Resume, args)
+            }
+            Command::Sync(args) => {
+                Self::get_trigger_sync_request(args)
+            }
         }
     }
 
@@ -112,9 +183,33 @@ impl ClientRequest {
     }
 
     fn add_workspace_args_to_json(args: AddWorkspaceArgs) -> Result<Value> {
+        let deletion_guard = match (args.max_deletions, args.max_deletion_percent) {
+            (None, None) => None,
+            (max_deletions, max_deletion_percent) => Some(DeletionGuardSettings { max_deletions, max_deletion_percent })
+        };
+
+        let initial_sync = args.initial_sync.map(|initial_sync| match initial_sync {
+            InitialSyncArg::Full => InitialSyncStrategy::Full,
+            InitialSyncArg::Verify => InitialSyncStrategy::Verify,
+            InitialSyncArg::Modified => InitialSyncStrategy::Modified,
+            InitialSyncArg::Skip => InitialSyncStrategy::Skip
+        });
+
+        let watcher = args.watcher.map(|watcher| match watcher {
+            WatcherArg::Inotify => WatcherBackend::Inotify,
+            WatcherArg::Fanotify => WatcherBackend::Fanotify
+        });
+
         let data = AddWorkspaceRequest {
             name: args.name,
             path: args.path,
+            quiet_window_ms: args.quiet_window_ms,
+            max_latency_ms: args.max_latency_ms,
+            max_parallel_syncs: args.max_parallel_syncs.map(usize::from),
+            ignore_patterns: args.ignore_patterns,
+            deletion_guard,
+            initial_sync,
+            watcher
         };
 
         Ok(Self::get_command_data(data)?)
@@ -159,6 +254,10 @@ impl ClientRequest {
             remote_workspace_name: args.args.remote_workspace_name,
             remote_workspace_path: args.args.remote_path,
             connection_info,
+            sync_options: Self::to_sync_options(args.sync_options),
+            two_way: Self::to_two_way_settings(args.two_way),
+            versioning: Self::to_versioning_settings(args.versioning),
+            post_sync_hook: Self::to_post_sync_hook(args.hook),
         };
 
         Ok(Self::get_command_data(data)?)
@@ -183,11 +282,130 @@ impl ClientRequest {
             remote_workspace_name: args.args.remote_workspace_name,
             remote_workspace_path: args.args.remote_path,
             connection_info,
+            sync_options: Self::to_sync_options(args.sync_options),
+            two_way: Self::to_two_way_settings(args.two_way),
+            versioning: Self::to_versioning_settings(args.versioning),
+            post_sync_hook: None,
         };
 
         Ok(Self::get_command_data(data)?)
     }
 
+    fn get_local_attach_remote_workspace_request(args: LocalArgs) -> Result<Self> {
+        let command_request = Self::get_command_request(request::Command::AttachRemoteWorkspace)?;
+        let command_data = Self::local_attach_remote_workspace_args_to_json(args)?;
+
+        Ok(Self { command_request, command_data: Some(command_data) })
+    }
+
+    fn local_attach_remote_workspace_args_to_json(args: LocalArgs) -> Result<Value> {
+        let connection_info = ConnectionInfo::Local { path: args.mount_point };
+
+        let data = AttachRemoteWorkspaceRequest {
+            local_workspace_name: args.args.workspace_name,
+            remote_workspace_name: args.args.remote_workspace_name,
+            remote_workspace_path: args.args.remote_path,
+            connection_info,
+            sync_options: Self::to_sync_options(args.sync_options),
+            two_way: Self::to_two_way_settings(args.two_way),
+            versioning: None,
+            post_sync_hook: Self::to_post_sync_hook(args.hook),
+        };
+
+        Self::get_command_data(data)
+    }
+
+    fn get_agent_attach_remote_workspace_request(args: AgentArgs) -> Result<Self> {
+        let command_request = Self::get_command_request(request::Command::AttachRemoteWorkspace)?;
+        let command_data = Self::agent_attach_remote_workspace_args_to_json(args)?;
+
+        Ok(Self { command_request, command_data: Some(command_data) })
+    }
+
+    fn agent_attach_remote_workspace_args_to_json(args: AgentArgs) -> Result<Value> {
+        let host = match (args.local, args.host_alias) {
+            (true, _) => None,
+            (false, Some(host_alias)) => Some(host_alias),
+            (false, None) => Some(Self::unwrap_host_info(args.host_info))
+        };
+
+        let connection_info = ConnectionInfo::Agent {
+            host,
+            port: args.port,
+            username: args.user,
+            identity_file: args.identity_file,
+            agent_path: args.agent_path,
+        };
+
+        let data = AttachRemoteWorkspaceRequest {
+            local_workspace_name: args.args.workspace_name,
+            remote_workspace_name: args.args.remote_workspace_name,
+            remote_workspace_path: args.args.remote_path,
+            connection_info,
+            sync_options: Self::to_sync_options(args.sync_options),
+            two_way: None,
+            versioning: None,
+            post_sync_hook: Self::to_post_sync_hook(args.hook),
+        };
+
+        Self::get_command_data(data)
+    }
+
+    fn to_sync_options(args: SyncOptionArgs) -> SyncOptions {
+        SyncOptions {
+            delete: !args.no_delete,
+            compress: !args.no_compress,
+            compress_choice: args.compress_choice,
+            checksum: args.checksum,
+            bwlimit_kbps: args.bwlimit,
+            chmod: args.chmod,
+            chown: args.chown,
+            partial: args.partial,
+            inplace: args.inplace,
+            hard_links: args.hard_links,
+            acls: args.acls,
+            xattrs: args.xattrs,
+            perms: !args.no_perms,
+            owner: !args.no_owner,
+            group: !args.no_group,
+            times: !args.no_times,
+        }
+    }
+
+    fn to_two_way_settings(args: TwoWayArgs) -> Option<TwoWaySettings> {
+        if !args.two_way {
+            return None;
+        }
+
+        let conflict_policy = match args.conflict_policy {
+            ConflictPolicyArg::LocalWins => ConflictPolicy::LocalWins,
+            ConflictPolicyArg::RemoteWins => ConflictPolicy::RemoteWins,
+            ConflictPolicyArg::KeepBoth => ConflictPolicy::KeepBoth
+        };
+
+        Some(TwoWaySettings { conflict_policy, poll_interval_secs: args.poll_interval_secs })
+    }
+
+    fn to_post_sync_hook(args: HookArgs) -> Option<PostSyncHook> {
+        Some(PostSyncHook {
+            command: args.hook_command?,
+            working_dir: args.hook_working_dir,
+            timeout_secs: args.hook_timeout_secs,
+            debounce_ms: args.hook_debounce_ms
+        })
+    }
+
+    fn to_versioning_settings(args: VersioningArgs) -> Option<VersioningSettings> {
+        if !args.versioning {
+            return None;
+        }
+
+        Some(VersioningSettings {
+            keep_versions: args.keep_versions.map(|keep_versions| keep_versions as usize),
+            keep_days: args.keep_days.map(u64::from)
+        })
+    }
+
     fn unwrap_host_info(host_info: HostInfo) -> String {
         if host_info.hostname.is_some() {
             host_info.hostname.unwrap()
@@ -212,6 +430,137 @@ impl ClientRequest {
         Ok(Self::get_command_data(data)?)
     }
 
+    fn get_preview_sync_request(args: PreviewArgs) -> Result<Self> {
+        let command_request = Self::get_command_request(request::Command::PreviewSync)?;
+        let command_data = Self::preview_sync_args_to_json(args)?;
+
+        Ok(Self { command_request, command_data: Some(command_data) })
+    }
+
+    fn preview_sync_args_to_json(args: PreviewArgs) -> Result<Value> {
+        let data = PreviewSyncRequest {
+            name: args.name,
+            remote_workspace_name: args.remote,
+        };
+
+        Self::get_command_data(data)
+    }
+
+    fn get_resolve_deletions_request(command: request::Command, args: ResolveDeletionsArgs) -> Result<Self> {
+        let command_request = Self::get_command_request(command)?;
+        let command_data = Self::resolve_deletions_args_to_json(args)?;
+
+        Ok(Self { command_request, command_data: Some(command_data) })
+    }
+
+    fn resolve_deletions_args_to_json(args: ResolveDeletionsArgs) -> Result<Value> {
+        let data = ResolveDeletionsRequest {
+            name: args.name,
+            remote_workspace_name: args.remote,
+        };
+
+        Self::get_command_data(data)
+    }
+
+    fn get_list_versions_request(args: ListVersionsArgs) -> Result<Self> {
+        let command_request = Self::get_command_request(request::Command::ListVersions)?;
+        let command_data = Self::list_versions_args_to_json(args)?;
+
+        Ok(Self { command_request, command_data: Some(command_data) })
+    }
+
+    fn list_versions_args_to_json(args: ListVersionsArgs) -> Result<Value> {
+        let data = ListVersionsRequest {
+            name: args.name,
+            remote_workspace_name: args.remote,
+            path: args.path,
+        };
+
+        Self::get_command_data(data)
+    }
+
+    fn get_restore_version_request(args: RestoreVersionArgs) -> Result<Self> {
+        let command_request = Self::get_command_request(request::Command::RestoreVersion)?;
+        let command_data = Self::restore_version_args_to_json(args)?;
+
+        Ok(Self { command_request, command_data: Some(command_data) })
+    }
+
+    fn restore_version_args_to_json(args: RestoreVersionArgs) -> Result<Value> {
+        let data = RestoreVersionRequest {
+            name: args.name,
+            remote_workspace_name: args.remote,
+            path: args.path,
+            version: args.version_id,
+        };
+
+        Self::get_command_data(data)
+    }
+
+    fn get_set_schedule_request(args: SetScheduleArgs) -> Result<Self> {
+        let command_request = Self::get_command_request(request::Command::SetSchedule)?;
+        let command_data = Self::set_schedule_args_to_json(args)?;
+
+        Ok(Self { command_request, command_data: Some(command_data) })
+    }
+
+    fn set_schedule_args_to_json(args: SetScheduleArgs) -> Result<Value> {
+        let data = SetScheduleRequest {
+            name: args.name,
+            remote_workspace_name: args.remote,
+            schedule: args.windows.into_iter().map(Self::to_schedule_window).collect(),
+        };
+
+        Self::get_command_data(data)
+    }
+
+    fn get_pause_request(command: request::Command, args: PauseArgs) -> Result<Self> {
+        let command_request = Self::get_command_request(command)?;
+        let command_data = Self::pause_args_to_json(args)?;
+
+        Ok(Self { command_request, command_data: Some(command_data) })
+    }
+
+    fn pause_args_to_json(args: PauseArgs) -> Result<Value> {
+        let data = PauseRequest {
+            name: args.name,
+            remote_workspace_name: args.remote,
+        };
+
+        Self::get_command_data(data)
+    }
+
+    fn get_trigger_sync_request(args: SyncArgs) -> Result<Self> {
+        let command_request = Self::get_command_request(request::Command::TriggerSync)?;
+        let command_data = Self::trigger_sync_args_to_json(args)?;
+
+        Ok(Self { command_request, command_data: Some(command_data) })
+    }
+
+    fn trigger_sync_args_to_json(args: SyncArgs) -> Result<Value> {
+        let data = TriggerSyncRequest {
+            name: args.name,
+            remote_workspace_name: args.remote,
+            path: args.path,
+        };
+
+        Self::get_command_data(data)
+    }
+
+    fn to_schedule_window(window: ScheduleWindowArg) -> ScheduleWindow {
+        let action = match window.action {
+            ScheduleActionArg::Pause => ScheduleAction::Pause,
+            ScheduleActionArg::FullSpeed => ScheduleAction::FullSpeed,
+            ScheduleActionArg::Bwlimit(bwlimit) => ScheduleAction::Bwlimit(bwlimit)
+        };
+
+        ScheduleWindow {
+            start_minute: window.start_minute,
+            end_minute: window.end_minute,
+            action
+        }
+    }
+
     fn get_command_request(command: request::Command) -> Result<Value> {
         let command_request = CommandRequest {
             command: command.to_string()
@@ -1,5 +1,6 @@
 pub(crate) mod socket;
 pub(crate) mod models;
+pub(crate) mod validation;
 
 #[derive(Debug)]
 pub(crate) struct Error {
@@ -9,13 +9,253 @@ pub(crate) struct WorkspaceInformation {
     pub name: String,
     pub local_path: PathBuf,
     pub remote_workspaces: Vec<RemoteWorkspace>,
+    #[serde(default)]
+    pub debounce: DebounceSettings,
+    #[serde(default = "default_max_parallel_syncs")]
+    pub max_parallel_syncs: usize,
+    #[serde(default)]
+    pub ignore_patterns: Vec<String>,
+    #[serde(default)]
+    pub deletion_guard: Option<DeletionGuardSettings>,
+    #[serde(default)]
+    pub initial_sync: InitialSync,
+    #[serde(default)]
+    pub watcher: Watcher,
+    /// Set if the syncs with all remote workspaces are paused until they are resumed
+    #[serde(default)]
+    pub paused: bool
+}
+
+pub(crate) fn default_max_parallel_syncs() -> usize {
+    4
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct DebounceSettings {
+    pub quiet_window_ms: u64,
+    pub max_latency_ms: u64
+}
+
+/// Thresholds above which the monitor holds syncs that delete resources until they are approved
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct DeletionGuardSettings {
+    pub max_deletions: Option<usize>,
+    pub max_deletion_percent: Option<u8>
+}
+
+/// Strategy of the sync the monitor performs when it starts
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub(crate) enum InitialSync {
+    #[default]
+    Full,
+    Verify,
+    Modified,
+    Skip
+}
+
+/// How the monitor detects changes in the workspace on Linux
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub(crate) enum Watcher {
+    #[default]
+    Inotify,
+    Fanotify
+}
+
+impl Default for DebounceSettings {
+    fn default() -> Self {
+        DebounceSettings { quiet_window_ms: 250, max_latency_ms: 2000 }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub(crate) struct RemoteWorkspace {
     pub name: String,
     pub remote_path: PathBuf,
-    pub connection_info: ConnectionInfo
+    pub connection_info: ConnectionInfo,
+    #[serde(default)]
+    pub sync_options: SyncOptions,
+    /// Set if changes made in the remote workspace are synced back into the local workspace
+    #[serde(default)]
+    pub two_way: Option<TwoWaySettings>,
+    /// Set if the previous versions of files that syncs overwrite or delete are kept in the remote
+    /// workspace
+    #[serde(default)]
+    pub versioning: Option<VersioningSettings>,
+    /// Windows of the day in which syncs are paused or transferred at a different bandwidth
+    #[serde(default)]
+    pub schedule: Vec<ScheduleWindow>,
+    /// Set if a command is run in the remote workspace after changes were synced to it
+    #[serde(default)]
+    pub post_sync_hook: Option<PostSyncHook>,
+    /// Set if the syncs with the remote workspace are paused until they are resumed
+    #[serde(default)]
+    pub paused: bool
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct PostSyncHook {
+    pub command: String,
+    pub working_dir: Option<PathBuf>,
+    pub timeout_secs: u64,
+    pub debounce_ms: u64
+}
+
+/// Window of the day, in local time, in which syncs with a remote workspace are treated
+/// differently. Times are given in minutes since midnight, windows ending before they start span
+/// midnight.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct ScheduleWindow {
+    pub start_minute: u16,
+    pub end_minute: u16,
+    pub action: ScheduleAction
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub(crate) enum ScheduleAction {
+    Pause,
+    FullSpeed,
+    Bwlimit(u32)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct VersioningSettings {
+    pub keep_versions: Option<usize>,
+    pub keep_days: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct TwoWaySettings {
+    pub conflict_policy: ConflictPolicy,
+    pub poll_interval_secs: u64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub(crate) enum ConflictPolicy {
+    LocalWins,
+    RemoteWins,
+    KeepBoth
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) enum ConflictResolution {
+    LocalWon,
+    RemoteWon,
+    KeptBoth {
+        conflict_copy: PathBuf
+    }
+}
+
+/// Conflict detected by a monitor while syncing with a two-way remote workspace
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct ConflictReport {
+    pub remote_workspace: String,
+    pub path: PathBuf,
+    pub resolution: ConflictResolution,
+    pub detected_at: u64
+}
+
+/// Cause of a failed sync, as determined by the monitor
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub(crate) enum FailureCause {
+    Unreachable,
+    ConnectionLost,
+    AuthenticationFailed,
+    RemotePathMissing,
+    PermissionDenied,
+    DiskFull,
+    VanishedFiles,
+    PartialTransfer,
+    Other
+}
+
+/// Outcome of the most recent syncs with a remote workspace, as reported by its monitor
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RemoteSyncStatus {
+    pub syncing: bool,
+    pub last_started_at: Option<u64>,
+    pub last_success_at: Option<u64>,
+    pub last_duration_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub last_error_cause: Option<FailureCause>,
+    pub last_failure_at: Option<u64>,
+    pub consecutive_failures: u32,
+    /// Number of deletions held by the monitor until they are approved or rejected
+    pub pending_deletions: Option<usize>,
+    pub deletions_held_since: Option<u64>,
+    /// What the last sync transferred and the totals since the monitor started
+    pub last_transfer: Option<TransferStats>,
+    pub total_transferred: Option<TransferStats>,
+    pub last_hook: Option<HookOutcome>
+}
+
+/// Outcome of a run of a remote workspace's post-sync hook, as reported by the monitor
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct HookOutcome {
+    pub finished_at: u64,
+    pub duration_ms: u64,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub error: Option<String>
+}
+
+/// Statistics of the transfers to and from remote workspaces, as reported by the monitor
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct TransferStats {
+    pub files_considered: u64,
+    pub files_transferred: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub total_size: u64,
+    pub duration_ms: u64
+}
+
+/// Options controlling how rsync transfers changes to a remote workspace
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub(crate) struct SyncOptions {
+    pub delete: bool,
+    pub compress: bool,
+    pub compress_choice: Option<String>,
+    pub checksum: bool,
+    pub bwlimit_kbps: Option<u32>,
+    pub chmod: Option<String>,
+    pub chown: Option<String>,
+    pub partial: bool,
+    pub inplace: bool,
+    pub hard_links: bool,
+    pub acls: bool,
+    pub xattrs: bool,
+    /// Preserve permissions
+    pub perms: bool,
+    /// Preserve the owner, if permitted on the remote system
+    pub owner: bool,
+    /// Preserve the group, if permitted on the remote system
+    pub group: bool,
+    /// Preserve modification times
+    pub times: bool
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            delete: true,
+            compress: true,
+            compress_choice: None,
+            checksum: false,
+            bwlimit_kbps: None,
+            chmod: None,
+            chown: None,
+            partial: false,
+            inplace: false,
+            hard_links: false,
+            acls: false,
+            xattrs: false,
+            perms: true,
+            owner: true,
+            group: true,
+            times: true
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -33,5 +273,64 @@ pub(crate) enum ConnectionInfo {
         host: String,
         port: Option<u16>,
         username: Option<String>
+    },
+    /// Directory on a locally mounted filesystem, with the remote path being located below the
+    /// given mount point
+    Local {
+        path: PathBuf
+    },
+    /// Remote system running `wsync-agent`, which is started via SSH. If no host is given, the
+    /// agent is started as a local child process instead.
+    Agent {
+        host: Option<String>,
+        port: Option<u16>,
+        username: Option<String>,
+        identity_file: Option<PathBuf>,
+        /// Path to the agent executable, `wsync-agent` being looked up in the PATH by default
+        agent_path: Option<PathBuf>
     }
 }
+
+/// Changes that syncing a workspace would make to one of its remote workspaces, as reported by the
+/// monitor
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct SyncPreview {
+    pub remote_workspace: String,
+    pub created: Vec<PathBuf>,
+    pub updated: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+    pub error: Option<String>
+}
+
+/// Outcome of a sync with one of the remote workspaces that was triggered on demand, as reported by
+/// the monitor
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct TriggeredSyncOutcome {
+    pub remote_workspace: String,
+    pub skipped: Option<String>,
+    pub error: Option<String>,
+    pub cause: Option<FailureCause>,
+    pub transferred: Option<TransferStats>
+}
+
+/// Versions of a file kept in one of the remote workspaces, as reported by the monitor
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct FileVersions {
+    pub remote_workspace: String,
+    pub versions: Vec<FileVersion>,
+    pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct FileVersion {
+    pub version: String,
+    pub created_at: u64,
+    pub size: u64
+}
+
+/// Outcome of restoring a version of a file, as reported by the monitor
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct VersionRestore {
+    pub remote_workspace: String,
+    pub error: Option<String>
+}
@@ -0,0 +1,342 @@
+use std::path::{Component, Path};
+use crate::domain::Error;
+use crate::domain::models::{
+    ConnectionInfo,
+    DebounceSettings,
+    DeletionGuardSettings,
+    PostSyncHook,
+    RemoteWorkspace,
+    ScheduleAction,
+    ScheduleWindow,
+    SyncOptions,
+    TwoWaySettings,
+    VersioningSettings
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+const SUPPORTED_COMPRESS_CHOICES: [&str; 5] = ["zstd", "lz4", "zlibx", "zlib", "none"];
+
+/// Checks that a remote workspace can be synced with, so that misconfigurations are reported to the
+/// user instead of making every sync fail.
+pub(crate) fn validate_remote_workspace(remote_workspace: &RemoteWorkspace) -> Result<()> {
+    validate_sync_options(&remote_workspace.sync_options)?;
+
+    match &remote_workspace.connection_info {
+        ConnectionInfo::Local { path } => validate_local_target(path, remote_workspace)?,
+        ConnectionInfo::Agent { .. } => validate_agent_target(remote_workspace)?,
+        _ => {}
+    }
+
+    if let Some(two_way) = &remote_workspace.two_way {
+        validate_two_way(two_way, remote_workspace)?;
+    }
+
+    if let Some(versioning) = &remote_workspace.versioning {
+        validate_versioning(versioning, remote_workspace)?;
+    }
+
+    validate_schedule(&remote_workspace.schedule, remote_workspace)?;
+
+    if let Some(post_sync_hook) = &remote_workspace.post_sync_hook {
+        validate_post_sync_hook(post_sync_hook, remote_workspace)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that changes are not held back for less than the quiet window, which would defeat it
+pub(crate) fn validate_debounce(debounce: &DebounceSettings) -> Result<()> {
+    if debounce.max_latency_ms < debounce.quiet_window_ms {
+        return Err(Error::new(format!(
+            "The max latency ({} ms) must not be shorter than the quiet window ({} ms)",
+            debounce.max_latency_ms, debounce.quiet_window_ms
+        )));
+    }
+
+    Ok(())
+}
+
+/// Checks that a deletion guard has at least one meaningful threshold
+pub(crate) fn validate_deletion_guard(deletion_guard: &DeletionGuardSettings) -> Result<()> {
+    if deletion_guard.max_deletions.is_none() && deletion_guard.max_deletion_percent.is_none() {
+        return Err(Error::new(
+            "The deletion guard requires a maximum number or percentage of deletions".to_string()
+        ));
+    }
+
+    if deletion_guard.max_deletion_percent.is_some_and(|percent| percent > 100) {
+        return Err(Error::new("The maximum percentage of deletions must not exceed 100".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Checks that a path refers to a resource within a workspace, relative to the workspace root
+pub(crate) fn validate_workspace_relative_path(path: &Path) -> Result<()> {
+    let is_workspace_relative = path.components().next().is_some()
+        && path.components().all(|component| matches!(component, Component::Normal(_)));
+
+    if !is_workspace_relative {
+        return Err(Error::new(format!("'{}' is not a path relative to the workspace root", path.display())));
+    }
+
+    Ok(())
+}
+
+/// Versions are named after the time of the sync that replaced them, in milliseconds since the unix
+/// epoch
+pub(crate) fn validate_version(version: &str) -> Result<()> {
+    if version.is_empty() || !version.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(Error::new(format!("'{version}' is not a valid version")));
+    }
+
+    Ok(())
+}
+
+/// Checks that the sync options of a remote workspace translate into a valid rsync invocation
+fn validate_sync_options(sync_options: &SyncOptions) -> Result<()> {
+    if let Some(compress_choice) = &sync_options.compress_choice {
+        if !sync_options.compress {
+            return Err(Error::new(
+                "A compression algorithm can only be chosen if compression is enabled".to_string()
+            ));
+        }
+
+        if !SUPPORTED_COMPRESS_CHOICES.contains(&compress_choice.as_str()) {
+            return Err(Error::new(format!(
+                "Unsupported compression algorithm '{compress_choice}', expected one of: {}",
+                SUPPORTED_COMPRESS_CHOICES.join(", ")
+            )));
+        }
+    }
+
+    if sync_options.bwlimit_kbps == Some(0) {
+        return Err(Error::new(
+            "The bandwidth limit must be greater than 0, omit it to not limit the bandwidth".to_string()
+        ));
+    }
+
+    if let Some(chmod) = &sync_options.chmod {
+        validate_chmod(chmod)?;
+    }
+
+    if let Some(chown) = &sync_options.chown {
+        validate_chown(chown)?;
+    }
+
+    Ok(())
+}
+
+/// Local remote workspaces must be located below their mount point and are synced natively
+fn validate_local_target(mount_point: &Path, remote_workspace: &RemoteWorkspace) -> Result<()> {
+    let remote_path = &remote_workspace.remote_path;
+
+    if !mount_point.is_absolute() || !remote_path.is_absolute() {
+        return Err(Error::new("Mount point and remote path must be absolute paths".to_string()));
+    }
+
+    if !remote_path.starts_with(mount_point) || remote_path == mount_point {
+        return Err(Error::new(format!(
+            "Remote path '{}' must be a directory below the mount point '{}'",
+            remote_path.display(),
+            mount_point.display()
+        )));
+    }
+
+    validate_native_sync_options(&remote_workspace.sync_options, "local")
+}
+
+/// The agent resolves the remote path on the remote system, so it can't be relative to the
+/// directory the agent happens to be started in
+fn validate_agent_target(remote_workspace: &RemoteWorkspace) -> Result<()> {
+    if !remote_workspace.remote_path.is_absolute() {
+        return Err(Error::new("Remote path must be an absolute path".to_string()));
+    }
+
+    validate_native_sync_options(&remote_workspace.sync_options, "agent")?;
+
+    // The agent always applies the permissions and modification times of the local files, while
+    // it never changes the ownership
+    let sync_options = &remote_workspace.sync_options;
+    for (option, is_set) in [("--no-perms", !sync_options.perms), ("--no-times", !sync_options.times)] {
+        if is_set {
+            return Err(Error::new(format!("'{option}' is not supported for agent remote workspaces")));
+        }
+    }
+
+    Ok(())
+}
+
+/// Remote workspaces that are not synced via rsync don't support the sync options that affect
+/// ownership, extended attributes or the transfer itself
+fn validate_native_sync_options(sync_options: &SyncOptions, transport: &str) -> Result<()> {
+    let unsupported_options = [
+        ("--compress-choice", sync_options.compress_choice.is_some()),
+        ("--bwlimit", sync_options.bwlimit_kbps.is_some()),
+        ("--chmod", sync_options.chmod.is_some()),
+        ("--chown", sync_options.chown.is_some()),
+        ("--hard-links", sync_options.hard_links),
+        ("--acls", sync_options.acls),
+        ("--xattrs", sync_options.xattrs)
+    ];
+
+    for (option, is_set) in unsupported_options {
+        if is_set {
+            return Err(Error::new(format!("'{option}' is not supported for {transport} remote workspaces")));
+        }
+    }
+
+    Ok(())
+}
+
+/// Two-way sync mirrors deletions in both directions and relies on listing the remote workspace,
+/// which the agent does not support yet
+fn validate_two_way(two_way: &TwoWaySettings, remote_workspace: &RemoteWorkspace) -> Result<()> {
+    if two_way.poll_interval_secs == 0 {
+        return Err(Error::new("The poll interval of a two-way sync must be greater than 0".to_string()));
+    }
+
+    if matches!(remote_workspace.connection_info, ConnectionInfo::Agent { .. }) {
+        return Err(Error::new("Two-way sync is not supported for agent remote workspaces".to_string()));
+    }
+
+    if !remote_workspace.sync_options.delete {
+        return Err(Error::new("Two-way sync requires deletions to be synced".to_string()));
+    }
+
+    // Changes are detected by comparing the modification times of both sides
+    if !remote_workspace.sync_options.times {
+        return Err(Error::new("Two-way sync requires modification times to be preserved".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Versions are kept by rsync's `--backup-dir`, so only remote workspaces synced via rsync keep them
+fn validate_versioning(versioning: &VersioningSettings, remote_workspace: &RemoteWorkspace) -> Result<()> {
+    if versioning.keep_versions == Some(0) || versioning.keep_days == Some(0) {
+        return Err(Error::new(
+            "The number of versions and days to keep must be greater than 0, omit them to keep all versions".to_string()
+        ));
+    }
+
+    if matches!(remote_workspace.connection_info, ConnectionInfo::Local { .. } | ConnectionInfo::Agent { .. }) {
+        return Err(Error::new(
+            "Keeping versions is only supported for remote workspaces that are synced via rsync".to_string()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Windows must cover part of the day, and only limit the bandwidth of remote workspaces that are
+/// synced via rsync
+fn validate_schedule(schedule: &[ScheduleWindow], remote_workspace: &RemoteWorkspace) -> Result<()> {
+    const MINUTES_PER_DAY: u16 = 24 * 60;
+
+    for window in schedule {
+        if window.start_minute >= MINUTES_PER_DAY || window.end_minute >= MINUTES_PER_DAY {
+            return Err(Error::new("The start and end of a schedule window must be a time of day".to_string()));
+        }
+
+        if window.start_minute == window.end_minute {
+            return Err(Error::new("The start and end of a schedule window must differ".to_string()));
+        }
+
+        match window.action {
+            ScheduleAction::Bwlimit(0) => return Err(Error::new(
+                "The bandwidth limit of a schedule window must be greater than 0".to_string()
+            )),
+            ScheduleAction::Bwlimit(_) | ScheduleAction::FullSpeed
+                if matches!(remote_workspace.connection_info, ConnectionInfo::Local { .. } | ConnectionInfo::Agent { .. }) => {
+                return Err(Error::new(
+                    "Schedule windows can only change the bandwidth of remote workspaces that are synced via rsync".to_string()
+                ));
+            },
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Hooks are run by a shell on the remote system, which the rsync daemon doesn't provide
+fn validate_post_sync_hook(post_sync_hook: &PostSyncHook, remote_workspace: &RemoteWorkspace) -> Result<()> {
+    if post_sync_hook.command.trim().is_empty() {
+        return Err(Error::new("The command of a post-sync hook must not be empty".to_string()));
+    }
+
+    if post_sync_hook.timeout_secs == 0 {
+        return Err(Error::new("The timeout of a post-sync hook must be greater than 0".to_string()));
+    }
+
+    if let Some(working_dir) = &post_sync_hook.working_dir {
+        validate_workspace_relative_path(working_dir).map_err(|_| Error::new(format!(
+            "The working directory '{}' of a post-sync hook must be relative to the remote workspace",
+            working_dir.display()
+        )))?;
+    }
+
+    if matches!(remote_workspace.connection_info, ConnectionInfo::RsyncDaemon { .. }) {
+        return Err(Error::new(
+            "Post-sync hooks are not supported for remote workspaces that are synced via the rsync daemon".to_string()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Accepts comma-separated rules in rsync's `--chmod` syntax, i.e. symbolic (e.g. `Dg+s,Fu=rw`)
+/// or octal (e.g. `D2775,F664`) modes, optionally prefixed with `D` or `F`.
+fn validate_chmod(chmod: &str) -> Result<()> {
+    let invalid = |rule: &str| Error::new(format!("Invalid chmod rule '{rule}' in '{chmod}'"));
+
+    for rule in chmod.split(',') {
+        let mode = rule.strip_prefix(['D', 'F']).unwrap_or(rule);
+
+        if mode.is_empty() {
+            return Err(invalid(rule));
+        }
+
+        let is_octal = mode.len() <= 4 && mode.chars().all(|c| ('0'..='7').contains(&c));
+        let is_symbolic = mode.contains(['+', '-', '='])
+            && mode.chars().all(|c| "ugoa+-=rwxXst".contains(c));
+
+        if !is_octal && !is_symbolic {
+            return Err(invalid(rule));
+        }
+    }
+
+    Ok(())
+}
+
+/// Accepts `USER`, `USER:GROUP` or `:GROUP`, where user and group are names or numeric ids
+fn validate_chown(chown: &str) -> Result<()> {
+    let (user, group) = match chown.split_once(':') {
+        Some((user, group)) => (user, group),
+        None => (chown, "")
+    };
+
+    let is_valid_name = |name: &str| {
+        name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    };
+
+    if (user.is_empty() && group.is_empty()) || !is_valid_name(user) || !is_valid_name(group) {
+        return Err(Error::new(format!("Invalid chown value '{chown}', expected 'USER:GROUP'")));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debounce_latency_must_cover_the_quiet_window() {
+        assert!(validate_debounce(&DebounceSettings::default()).is_ok());
+        assert!(validate_debounce(&DebounceSettings { quiet_window_ms: 500, max_latency_ms: 500 }).is_ok());
+        assert!(validate_debounce(&DebounceSettings { quiet_window_ms: 5000, max_latency_ms: 2000 }).is_err());
+    }
+}
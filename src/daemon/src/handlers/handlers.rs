@@ -1,8 +1,10 @@
+use std::ffi::OsStr;
 use std::fmt::Display;
 use std::os::unix::net::UnixStream;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::thread;
+use std::time::{Duration, Instant};
 use log::{debug, info, warn};
 use serde::Serialize;
 use uuid::Uuid;
@@ -13,21 +15,53 @@ use daemon_interface::request::{
     Command,
     CommandRequest,
     DetachRemoteWorkspaceRequest,
+    ListVersionsRequest,
+    PauseRequest,
+    PreviewSyncRequest,
     RemoveWorkspaceRequest,
+    ResolveDeletionsRequest,
+    RestoreVersionRequest,
+    SetScheduleRequest,
+    TriggerSyncRequest,
     WorkspaceInfoRequest
 };
 use daemon_interface::response::{DefaultResponse, Response, ResponsePayload};
 use daemon_interface::response::ErrorPayload::Message;
 use crate::daemon_state::DaemonState;
-use crate::domain::models::{RemoteWorkspace, WorkspaceInformation};
+use crate::domain::models::{
+    ConnectionInfo,
+    FileVersions,
+    RemoteWorkspace,
+    ScheduleWindow,
+    SyncPreview,
+    VersionRestore,
+    WorkspaceInformation
+};
+use crate::domain::validation::{
+    validate_debounce,
+    validate_deletion_guard,
+    validate_remote_workspace,
+    validate_version,
+    validate_workspace_relative_path
+};
 use crate::handlers::errors::Error;
 use crate::handlers::mappers::domain_to_interface::{
+    to_list_versions_response,
     to_list_workspace_info_response,
     to_list_workspaces_response,
+    to_preview_sync_response,
+    to_trigger_sync_response,
     to_workspace_info_response
 };
+use crate::monitor_manager::{MonitorCommand, MonitorManager};
 use crate::workspace_config;
 
+/// Interval in which the outcome of a triggered sync is checked for
+const TRIGGERED_SYNC_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Clients stop waiting for a triggered sync after this, while the sync itself keeps going
+const TRIGGERED_SYNC_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
 type Result<T> = std::result::Result<T, Error>;
 
 pub(crate) fn handle_request(req_id: Uuid, stream: UnixStream, state: Arc<Mutex<DaemonState>>) {
@@ -64,7 +98,16 @@ pub(crate) fn handle_request(req_id: Uuid, stream: UnixStream, state: Arc<Mutex<
         Command::AddWorkspace => handle_add_workspace_cmd(req_id, &mut client, state),
         Command::RemoveWorkspace => handle_remove_workspace_cmd(req_id, &mut client, state),
         Command::AttachRemoteWorkspace => handle_attach_remote_workspace_cmd(req_id, &mut client, state),
-        Command::DetachRemoteWorkspace => handle_detach_remote_workspace_cmd(req_id, &mut client, state)
+        Command::DetachRemoteWorkspace => handle_detach_remote_workspace_cmd(req_id, &mut client, state),
+        Command::PreviewSync => handle_preview_sync_cmd(req_id, &mut client, state),
+        Command::ApproveDeletions => handle_resolve_deletions_cmd(req_id, &mut client, state, true),
+        Command::RejectDeletions => handle_resolve_deletions_cmd(req_id, &mut client, state, false),
+        Command::ListVersions => handle_list_versions_cmd(req_id, &mut client, state),
+        Command::RestoreVersion => handle_restore_version_cmd(req_id, &mut client, state),
+        Command::SetSchedule => handle_set_schedule_cmd(req_id, &mut client, state),
+        Command::Pause => handle_pause_cmd(req_id, &mut client, state, true),
+        Command::Resume => handle_pause_cmd(req_id, &mut client, state, false),
+        Command::TriggerSync => handle_trigger_sync_cmd(req_id, &mut client, state)
     };
 
     if let Err(err) = command_handler_result {
@@ -112,13 +155,14 @@ fn handle_workspace_info_cmd(
 
     let guard = state.lock().unwrap();
     let search_result = guard.ws_config.find_by_name(&data.name);
+    let reports = guard.monitor_manager.reports.clone();
     drop(guard);
 
     let response = match search_result {
         Some(ws_info) => {
             debug!("[{req_id}] Found a workspace with the name '{}'", data.name);
 
-            let response_data = to_workspace_info_response(ws_info);
+            let response_data = to_workspace_info_response(ws_info, &reports.lock().unwrap());
             Response::success(Some(ResponsePayload::WorkspaceInfo(response_data)))
         },
         None => {
@@ -164,11 +208,12 @@ fn handle_list_workspace_info_cmd(
 
     let guard = state.lock().unwrap();
     let ws_entries = guard.ws_config.all();
+    let reports = guard.monitor_manager.reports.clone();
     drop(guard);
 
     debug!("[{req_id}] Found #{} workspaces: {:?}", ws_entries.len(), ws_entries);
 
-    let response_data = to_list_workspace_info_response(ws_entries);
+    let response_data = to_list_workspace_info_response(ws_entries, &reports.lock().unwrap());
     let response: DefaultResponse = Response::success(Some(ResponsePayload::ListWorkspaceInfo(response_data)));
     generic_write_json(&mut client, &response)?;
 
@@ -189,8 +234,25 @@ fn handle_add_workspace_cmd(
         )
     })?;
 
+    let workspace = WorkspaceInformation::from(data.clone());
+
+    if let Err(e) = validate_debounce(&workspace.debounce) {
+        debug!("[{req_id}] Rejecting debounce settings {:?}: {}", workspace.debounce, e.msg);
+        let response: DefaultResponse = Response::error(Some(Message(e.msg)));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    if let Some(deletion_guard) = &workspace.deletion_guard
+        && let Err(e) = validate_deletion_guard(deletion_guard) {
+        debug!("[{req_id}] Rejecting deletion guard {deletion_guard:?}: {}", e.msg);
+        let response: DefaultResponse = Response::error(Some(Message(e.msg)));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
     let mut guard = state.lock().unwrap();
-    let res = guard.ws_config.add_workspace(WorkspaceInformation::from(data.clone()));
+    let res = guard.ws_config.add_workspace(workspace);
     drop(guard);
 
     let response = match res {
@@ -268,6 +330,7 @@ fn handle_remove_workspace_cmd(
     match monitor_manager_res {
         Ok(()) => {
             debug!("[{req_id}] Successfully terminated monitor process for workspace '{}'.", &data.name);
+            guard.monitor_manager.reports.lock().unwrap().remove_workspace(&data.name);
         },
         Err(err) => {
             debug!("[{req_id}] Failed to terminate monitor process for workspace '{}'.", &data.name);
@@ -302,11 +365,20 @@ fn handle_attach_remote_workspace_cmd(
         )
     })?;
 
+    let remote_workspace = RemoteWorkspace::from(data.clone());
+
+    if let Err(e) = validate_remote_workspace(&remote_workspace) {
+        debug!("[{req_id}] Rejecting remote workspace '{}': {}", data.remote_workspace_name, e.msg);
+        let response: DefaultResponse = Response::error(Some(Message(e.msg)));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
     let mut guard = state.lock().unwrap();
 
     let config_result = guard.ws_config.attach_remote_workspace(
         data.local_workspace_name.clone(),
-        RemoteWorkspace::from(data.clone())
+        remote_workspace
     );
 
     match config_result {
@@ -473,6 +545,668 @@ fn handle_detach_remote_workspace_cmd(
     Ok(())
 }
 
+fn handle_preview_sync_cmd(
+    req_id: Uuid,
+    client: &mut Client,
+    state: Arc<Mutex<DaemonState>>
+) -> Result<()> {
+    debug!("[{req_id}] Handling 'preview_sync' command...");
+
+    let data: PreviewSyncRequest = client.read_json().map_err(|e| {
+        Error::both(
+            format!("Unable to read data required to processes the 'preview_sync' command: {e}"),
+            "Unable to read data required to process the 'preview_sync' command"
+        )
+    })?;
+
+    let guard = state.lock().unwrap();
+
+    let mut workspace = match guard.ws_config.find_by_name(&data.name) {
+        Some(workspace) => workspace,
+        None => {
+            drop(guard);
+            debug!("[{req_id}] No workspace with the name '{}' found.", data.name);
+
+            let response: DefaultResponse = Response::not_found(Some(Message(
+                format!("No local workspace with the name '{}' found.", data.name))
+            ));
+            generic_write_json(client, &response)?;
+            return Ok(());
+        }
+    };
+
+    if let Some(remote_workspace_name) = &data.remote_workspace_name {
+        workspace.remote_workspaces.retain(|rw| &rw.name == remote_workspace_name);
+
+        if workspace.remote_workspaces.is_empty() {
+            drop(guard);
+            debug!("[{req_id}] No remote workspace with the name '{remote_workspace_name}' found.");
+
+            let response: DefaultResponse = Response::not_found(Some(Message(format!(
+                "No remote workspace with the name '{}' is attached to '{}'.", remote_workspace_name, data.name
+            ))));
+            generic_write_json(client, &response)?;
+            return Ok(());
+        }
+    }
+
+    let spawn_result = guard.monitor_manager.spawn_monitor_task(&workspace, ["--preview"], "preview sync");
+
+    // Previewing requires contacting the remote systems, so don't block other requests meanwhile
+    drop(guard);
+
+    let previews: Vec<SyncPreview> = spawn_result
+        .and_then(|child| MonitorManager::collect_task_result(child, "preview sync"))
+        .map_err(|e| {
+            Error::both(
+                format!("{e}"),
+                format!("Unable to preview the sync of workspace '{}'", data.name)
+            )
+        })?;
+
+    debug!("[{req_id}] Previewed sync of '{}' with #{} remote workspace(s)", data.name, previews.len());
+
+    let response: DefaultResponse = Response::success(Some(
+        ResponsePayload::PreviewSync(to_preview_sync_response(data.name, previews))
+    ));
+    generic_write_json(client, &response)?;
+
+    Ok(())
+}
+
+fn handle_resolve_deletions_cmd(
+    req_id: Uuid,
+    client: &mut Client,
+    state: Arc<Mutex<DaemonState>>,
+    approve: bool
+) -> Result<()> {
+    let command_name = if approve { "approve_deletions" } else { "reject_deletions" };
+    debug!("[{req_id}] Handling '{command_name}' command...");
+
+    let data: ResolveDeletionsRequest = client.read_json().map_err(|e| {
+        Error::both(
+            format!("Unable to read data required to processes the '{command_name}' command: {e}"),
+            format!("Unable to read data required to process the '{command_name}' command")
+        )
+    })?;
+
+    let mut guard = state.lock().unwrap();
+
+    let workspace = match guard.ws_config.find_by_name(&data.name) {
+        Some(workspace) => workspace,
+        None => {
+            drop(guard);
+            debug!("[{req_id}] No workspace with the name '{}' found.", data.name);
+
+            let response: DefaultResponse = Response::not_found(Some(Message(
+                format!("No local workspace with the name '{}' found.", data.name))
+            ));
+            generic_write_json(client, &response)?;
+            return Ok(());
+        }
+    };
+
+    let remote_workspace_name = data.remote_workspace_name.as_deref();
+    if let Some(remote_workspace_name) = remote_workspace_name
+        && !workspace.remote_workspaces.iter().any(|rw| rw.name == remote_workspace_name) {
+        drop(guard);
+        debug!("[{req_id}] No remote workspace with the name '{remote_workspace_name}' found.");
+
+        let response: DefaultResponse = Response::not_found(Some(Message(format!(
+            "No remote workspace with the name '{}' is attached to '{}'.", remote_workspace_name, data.name
+        ))));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    let reports = guard.monitor_manager.reports.clone();
+    let reports = reports.lock().unwrap();
+
+    let held_remote_workspaces: Vec<&RemoteWorkspace> = workspace.remote_workspaces
+        .iter()
+        .filter(|rw| remote_workspace_name.is_none_or(|name| rw.name == name))
+        .filter(|rw| reports.has_pending_deletions(&data.name, Some(&rw.name)))
+        .collect();
+
+    drop(reports);
+
+    // Agents can't list the remote workspace, which is required to restore the deleted resources
+    let unrestorable = held_remote_workspaces
+        .iter()
+        .find(|rw| matches!(rw.connection_info, ConnectionInfo::Agent { .. }));
+
+    let error_msg = match (held_remote_workspaces.is_empty(), unrestorable) {
+        (true, _) => Some(format!("No deletions of workspace '{}' are pending approval.", data.name)),
+        (false, Some(rw)) if !approve => Some(format!(
+            "Deleted resources can't be restored from agent remote workspace '{}'. Restore them manually \
+            and approve the deletions instead.",
+            rw.name
+        )),
+        _ => None
+    };
+
+    if let Some(error_msg) = error_msg {
+        drop(guard);
+        debug!("[{req_id}] {error_msg}");
+
+        let response: DefaultResponse = Response::error(Some(Message(error_msg)));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    let command = MonitorCommand::ResolveDeletions { remote_workspace: data.remote_workspace_name.clone(), approve };
+    let send_result = guard.monitor_manager.send_command(&data.name, &command);
+    drop(guard);
+
+    send_result.map_err(|e| {
+        Error::both(
+            format!("{e}"),
+            format!("Unable to pass the decision to the monitor of workspace '{}'", data.name)
+        )
+    })?;
+
+    info!("[{req_id}] Deletions of workspace '{}' were {}", data.name, if approve { "approved" } else { "rejected" });
+
+    let response: DefaultResponse = Response::success(Some(if approve {
+        ResponsePayload::ApproveDeletions(
+            "Approved deletions, which will now be synced to the remote workspace(s).".to_string()
+        )
+    } else {
+        ResponsePayload::RejectDeletions(
+            "Rejected deletions, the deleted resources will be restored from the remote workspace(s).".to_string()
+        )
+    }));
+    generic_write_json(client, &response)?;
+
+    Ok(())
+}
+
+fn handle_list_versions_cmd(
+    req_id: Uuid,
+    client: &mut Client,
+    state: Arc<Mutex<DaemonState>>
+) -> Result<()> {
+    debug!("[{req_id}] Handling 'list_versions' command...");
+
+    let data: ListVersionsRequest = client.read_json().map_err(|e| {
+        Error::both(
+            format!("Unable to read data required to processes the 'list_versions' command: {e}"),
+            "Unable to read data required to process the 'list_versions' command"
+        )
+    })?;
+
+    if let Err(e) = validate_workspace_relative_path(&data.path) {
+        debug!("[{req_id}] Rejecting path {:?}: {}", data.path, e.msg);
+        let response: DefaultResponse = Response::error(Some(Message(e.msg)));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    let guard = state.lock().unwrap();
+
+    let mut workspace = match guard.ws_config.find_by_name(&data.name) {
+        Some(workspace) => workspace,
+        None => {
+            drop(guard);
+            debug!("[{req_id}] No workspace with the name '{}' found.", data.name);
+
+            let response: DefaultResponse = Response::not_found(Some(Message(
+                format!("No local workspace with the name '{}' found.", data.name))
+            ));
+            generic_write_json(client, &response)?;
+            return Ok(());
+        }
+    };
+
+    if let Some(remote_workspace_name) = &data.remote_workspace_name {
+        workspace.remote_workspaces.retain(|rw| &rw.name == remote_workspace_name);
+
+        if workspace.remote_workspaces.is_empty() {
+            drop(guard);
+            debug!("[{req_id}] No remote workspace with the name '{remote_workspace_name}' found.");
+
+            let response: DefaultResponse = Response::not_found(Some(Message(format!(
+                "No remote workspace with the name '{}' is attached to '{}'.", remote_workspace_name, data.name
+            ))));
+            generic_write_json(client, &response)?;
+            return Ok(());
+        }
+    }
+
+    workspace.remote_workspaces.retain(|rw| rw.versioning.is_some());
+
+    if workspace.remote_workspaces.is_empty() {
+        drop(guard);
+        debug!("[{req_id}] No remote workspace of '{}' keeps versions.", data.name);
+
+        let response: DefaultResponse = Response::error(Some(Message(format!(
+            "No remote workspace of '{}' keeps versions.", data.name
+        ))));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    let args = [OsStr::new("--list-versions"), data.path.as_os_str()];
+    let spawn_result = guard.monitor_manager.spawn_monitor_task(&workspace, args, "list versions");
+
+    // Listing versions requires contacting the remote systems, so don't block other requests meanwhile
+    drop(guard);
+
+    let versions: Vec<FileVersions> = spawn_result
+        .and_then(|child| MonitorManager::collect_task_result(child, "list versions"))
+        .map_err(|e| {
+            Error::both(
+                format!("{e}"),
+                format!("Unable to list the versions of '{}' in workspace '{}'", data.path.display(), data.name)
+            )
+        })?;
+
+    debug!("[{req_id}] Listed versions of '{:?}' in #{} remote workspace(s)", data.path, versions.len());
+
+    let response: DefaultResponse = Response::success(Some(
+        ResponsePayload::ListVersions(to_list_versions_response(data.name, data.path, versions))
+    ));
+    generic_write_json(client, &response)?;
+
+    Ok(())
+}
+
+fn handle_restore_version_cmd(
+    req_id: Uuid,
+    client: &mut Client,
+    state: Arc<Mutex<DaemonState>>
+) -> Result<()> {
+    debug!("[{req_id}] Handling 'restore_version' command...");
+
+    let data: RestoreVersionRequest = client.read_json().map_err(|e| {
+        Error::both(
+            format!("Unable to read data required to processes the 'restore_version' command: {e}"),
+            "Unable to read data required to process the 'restore_version' command"
+        )
+    })?;
+
+    if let Err(e) = validate_workspace_relative_path(&data.path).and_then(|_| validate_version(&data.version)) {
+        debug!("[{req_id}] Rejecting version '{}' of {:?}: {}", data.version, data.path, e.msg);
+        let response: DefaultResponse = Response::error(Some(Message(e.msg)));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    let guard = state.lock().unwrap();
+
+    let mut workspace = match guard.ws_config.find_by_name(&data.name) {
+        Some(workspace) => workspace,
+        None => {
+            drop(guard);
+            debug!("[{req_id}] No workspace with the name '{}' found.", data.name);
+
+            let response: DefaultResponse = Response::not_found(Some(Message(
+                format!("No local workspace with the name '{}' found.", data.name))
+            ));
+            generic_write_json(client, &response)?;
+            return Ok(());
+        }
+    };
+
+    workspace.remote_workspaces.retain(|rw| rw.name == data.remote_workspace_name);
+
+    let error_response: Option<DefaultResponse> = match workspace.remote_workspaces.first() {
+        None => Some(Response::not_found(Some(Message(format!(
+            "No remote workspace with the name '{}' is attached to '{}'.", data.remote_workspace_name, data.name
+        ))))),
+        Some(rw) if rw.versioning.is_none() => Some(Response::error(Some(Message(format!(
+            "Remote workspace '{}' does not keep versions.", rw.name
+        ))))),
+        Some(_) => None
+    };
+
+    if let Some(response) = error_response {
+        drop(guard);
+        debug!("[{req_id}] Unable to restore from '{}': {response}", data.remote_workspace_name);
+
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    let args = [OsStr::new("--restore-version"), data.path.as_os_str(), OsStr::new(&data.version)];
+    let spawn_result = guard.monitor_manager.spawn_monitor_task(&workspace, args, "restore version");
+
+    // Restoring requires contacting the remote system, so don't block other requests meanwhile
+    drop(guard);
+
+    let restore: VersionRestore = spawn_result
+        .and_then(|child| MonitorManager::collect_task_result(child, "restore version"))
+        .map_err(|e| {
+            Error::both(
+                format!("{e}"),
+                format!("Unable to restore version '{}' of '{}'", data.version, data.path.display())
+            )
+        })?;
+
+    let response: DefaultResponse = match restore.error {
+        Some(error) => {
+            debug!("[{req_id}] Restoring version '{}' of '{:?}' failed: {error}", data.version, data.path);
+
+            Response::error(Some(Message(format!(
+                "Unable to restore version '{}' of '{}' from '{}': {error}",
+                data.version,
+                data.path.display(),
+                restore.remote_workspace
+            ))))
+        },
+        None => {
+            info!("[{req_id}] Restored version '{}' of '{:?}' in workspace '{}'", data.version, data.path, data.name);
+
+            Response::success(Some(ResponsePayload::RestoreVersion(format!(
+                "Restored version '{}' of '{}' from '{}', which will now be synced to the remote workspace(s).",
+                data.version,
+                data.path.display(),
+                restore.remote_workspace
+            ))))
+        }
+    };
+    generic_write_json(client, &response)?;
+
+    Ok(())
+}
+
+fn handle_set_schedule_cmd(
+    req_id: Uuid,
+    client: &mut Client,
+    state: Arc<Mutex<DaemonState>>
+) -> Result<()> {
+    debug!("[{req_id}] Handling 'set_schedule' command...");
+
+    let data: SetScheduleRequest = client.read_json().map_err(|e| {
+        Error::both(
+            format!("Unable to read data required to processes the 'set_schedule' command: {e}"),
+            "Unable to read data required to process the 'set_schedule' command"
+        )
+    })?;
+
+    let mut guard = state.lock().unwrap();
+
+    let remote_workspace = guard.ws_config
+        .find_by_name(&data.name)
+        .and_then(|workspace| {
+            workspace.remote_workspaces.into_iter().find(|rw| rw.name == data.remote_workspace_name)
+        });
+
+    let Some(mut remote_workspace) = remote_workspace else {
+        drop(guard);
+        debug!("[{req_id}] No remote workspace '{}' of '{}' found.", data.remote_workspace_name, data.name);
+
+        let response: DefaultResponse = Response::not_found(Some(Message(format!(
+            "No remote workspace with the name '{}' is attached to '{}'.", data.remote_workspace_name, data.name
+        ))));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    };
+
+    remote_workspace.schedule = data.schedule.into_iter().map(ScheduleWindow::from).collect();
+
+    if let Err(e) = validate_remote_workspace(&remote_workspace) {
+        drop(guard);
+        debug!("[{req_id}] Rejecting schedule of '{}': {}", data.remote_workspace_name, e.msg);
+
+        let response: DefaultResponse = Response::error(Some(Message(e.msg)));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    if let Err(e) = guard.ws_config.update_remote_workspace(data.name.clone(), remote_workspace) {
+        debug!("[{req_id}] Failed to update the schedule of '{}' in the workspaces config file", data.remote_workspace_name);
+
+        return Err(Error::both(
+            format!("{e}"),
+            format!(
+                "Failed to set the schedule of remote workspace '{}' because there was an error while \
+                trying to modify the workspaces configuration file",
+                data.remote_workspace_name
+            )
+        ));
+    }
+
+    let updated_workspace: WorkspaceInformation = guard.ws_config.find_by_name(&data.name).unwrap();
+
+    let mm_res = guard.monitor_manager.restart_monitor(&updated_workspace);
+
+    drop(guard);
+
+    if let Err(e) = mm_res {
+        debug!("[{req_id}] Failed to (re)start the monitor process for workspace '{}'", data.name);
+
+        return Err(Error::both(
+            format!("{e}"),
+            format!(
+                "(Re)starting the monitor process for workspace '{}' failed, so the schedule is not \
+                applied yet.",
+                data.name
+            )
+        ));
+    }
+
+    info!("[{req_id}] Set the schedule of remote workspace '{}' of '{}'", data.remote_workspace_name, data.name);
+
+    let response: DefaultResponse = Response::success(Some(
+        ResponsePayload::SetSchedule("Successfully set schedule!".to_string())
+    ));
+    generic_write_json(client, &response)?;
+
+    Ok(())
+}
+
+fn handle_pause_cmd(
+    req_id: Uuid,
+    client: &mut Client,
+    state: Arc<Mutex<DaemonState>>,
+    paused: bool
+) -> Result<()> {
+    let command_name = if paused { "pause" } else { "resume" };
+    debug!("[{req_id}] Handling '{command_name}' command...");
+
+    let data: PauseRequest = client.read_json().map_err(|e| {
+        Error::both(
+            format!("Unable to read data required to processes the '{command_name}' command: {e}"),
+            format!("Unable to read data required to process the '{command_name}' command")
+        )
+    })?;
+
+    let subject = match &data.remote_workspace_name {
+        Some(remote_workspace_name) => format!("remote workspace '{remote_workspace_name}' of '{}'", data.name),
+        None => format!("workspace '{}'", data.name)
+    };
+
+    let mut guard = state.lock().unwrap();
+
+    let found = guard.ws_config.find_by_name(&data.name).is_some_and(|workspace| {
+        data.remote_workspace_name
+            .as_ref()
+            .is_none_or(|name| workspace.remote_workspaces.iter().any(|rw| rw.name == *name))
+    });
+
+    if !found {
+        drop(guard);
+        debug!("[{req_id}] No {subject} found.");
+
+        let response: DefaultResponse = Response::not_found(Some(Message(format!("No {subject} found."))));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    // Persisted first, so that the monitor picks the state up if it is (re)started meanwhile
+    if let Err(e) = guard.ws_config.set_paused(data.name.clone(), data.remote_workspace_name.clone(), paused) {
+        debug!("[{req_id}] Failed to update the pause state of {subject} in the workspaces config file");
+
+        return Err(Error::both(
+            format!("{e}"),
+            format!(
+                "Failed to {command_name} the syncs of {subject} because there was an error while trying \
+                to modify the workspaces configuration file"
+            )
+        ));
+    }
+
+    // The running monitor is told instead of being restarted, so that the changes it collected
+    // while the syncs were paused are not lost
+    let mm_res = if guard.monitor_manager.is_running(&data.name) {
+        let command = MonitorCommand::SetPaused { remote_workspace: data.remote_workspace_name.clone(), paused };
+
+        guard.monitor_manager.send_command(&data.name, &command).or_else(|e| {
+            warn!("[{req_id}] {e}, restarting the monitor of workspace '{}' instead", data.name);
+            let updated_workspace: WorkspaceInformation = guard.ws_config.find_by_name(&data.name).unwrap();
+            guard.monitor_manager.restart_monitor(&updated_workspace)
+        })
+    } else {
+        Ok(())
+    };
+
+    drop(guard);
+
+    if let Err(e) = mm_res {
+        debug!("[{req_id}] Failed to (re)start the monitor process for workspace '{}'", data.name);
+
+        return Err(Error::both(
+            format!("{e}"),
+            format!(
+                "(Re)starting the monitor process for workspace '{}' failed, so the syncs are not {} yet.",
+                data.name,
+                if paused { "paused" } else { "resumed" }
+            )
+        ));
+    }
+
+    info!("[{req_id}] {} the syncs of {subject}", if paused { "Paused" } else { "Resumed" });
+
+    let response: DefaultResponse = Response::success(Some(if paused {
+        ResponsePayload::Pause(format!(
+            "Paused the syncs of {subject}, changes are collected until they are resumed."
+        ))
+    } else {
+        ResponsePayload::Resume(format!(
+            "Resumed the syncs of {subject}, changes collected meanwhile will now be synced."
+        ))
+    }));
+    generic_write_json(client, &response)?;
+
+    Ok(())
+}
+
+fn handle_trigger_sync_cmd(
+    req_id: Uuid,
+    client: &mut Client,
+    state: Arc<Mutex<DaemonState>>
+) -> Result<()> {
+    debug!("[{req_id}] Handling 'trigger_sync' command...");
+
+    let data: TriggerSyncRequest = client.read_json().map_err(|e| {
+        Error::both(
+            format!("Unable to read data required to processes the 'trigger_sync' command: {e}"),
+            "Unable to read data required to process the 'trigger_sync' command"
+        )
+    })?;
+
+    if let Some(path) = &data.path
+        && let Err(e) = validate_workspace_relative_path(path) {
+        debug!("[{req_id}] Rejecting path {:?}: {}", path, e.msg);
+        let response: DefaultResponse = Response::error(Some(Message(e.msg)));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    let mut guard = state.lock().unwrap();
+
+    let workspace = match guard.ws_config.find_by_name(&data.name) {
+        Some(workspace) => workspace,
+        None => {
+            drop(guard);
+            debug!("[{req_id}] No workspace with the name '{}' found.", data.name);
+
+            let response: DefaultResponse = Response::not_found(Some(Message(
+                format!("No local workspace with the name '{}' found.", data.name))
+            ));
+            generic_write_json(client, &response)?;
+            return Ok(());
+        }
+    };
+
+    if let Some(remote_workspace_name) = &data.remote_workspace_name
+        && !workspace.remote_workspaces.iter().any(|rw| rw.name == *remote_workspace_name) {
+        drop(guard);
+        debug!("[{req_id}] No remote workspace with the name '{remote_workspace_name}' found.");
+
+        let response: DefaultResponse = Response::not_found(Some(Message(format!(
+            "No remote workspace with the name '{}' is attached to '{}'.", remote_workspace_name, data.name
+        ))));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    if !guard.monitor_manager.is_running(&data.name) {
+        drop(guard);
+        debug!("[{req_id}] No monitor is running for workspace '{}'", data.name);
+
+        let response: DefaultResponse = Response::error(Some(Message(format!(
+            "No monitor is running for workspace '{}', e.g. because no remote workspace is attached to it.", data.name
+        ))));
+        generic_write_json(client, &response)?;
+        return Ok(());
+    }
+
+    let id = req_id.to_string();
+    let reports = guard.monitor_manager.reports.clone();
+    reports.lock().unwrap().expect_triggered_sync(&data.name, &id);
+
+    let command = MonitorCommand::TriggerSync {
+        id: id.clone(),
+        remote_workspace: data.remote_workspace_name.clone(),
+        path: data.path.clone()
+    };
+    let send_result = guard.monitor_manager.send_command(&data.name, &command);
+
+    // The sync might take a while, so don't block other requests meanwhile
+    drop(guard);
+
+    if let Err(e) = send_result {
+        reports.lock().unwrap().forget_triggered_sync(&id);
+
+        return Err(Error::both(
+            format!("{e}"),
+            format!("Unable to trigger a sync of workspace '{}'", data.name)
+        ));
+    }
+
+    let started = Instant::now();
+    let outcome = loop {
+        if let Some(outcome) = reports.lock().unwrap().take_triggered_sync(&id) {
+            break outcome;
+        }
+
+        if started.elapsed() >= TRIGGERED_SYNC_TIMEOUT {
+            reports.lock().unwrap().forget_triggered_sync(&id);
+            break Err(format!("The sync didn't finish within {:?}, it is still in progress", TRIGGERED_SYNC_TIMEOUT));
+        }
+
+        thread::sleep(TRIGGERED_SYNC_POLL_INTERVAL);
+    };
+
+    let outcomes = outcome.map_err(|e| {
+        Error::both(
+            format!("Triggered sync of workspace '{}' did not finish: {e}", data.name),
+            format!("The triggered sync of workspace '{}' did not finish: {e}", data.name)
+        )
+    })?;
+
+    info!("[{req_id}] Triggered sync of '{}' with #{} remote workspace(s)", data.name, outcomes.len());
+
+    let response: DefaultResponse = Response::success(Some(
+        ResponsePayload::TriggerSync(to_trigger_sync_response(data.name, outcomes))
+    ));
+    generic_write_json(client, &response)?;
+
+    Ok(())
+}
+
 fn generic_write_json<T: Serialize + Display, E: Serialize + Display>(
     client: &mut Client,
     response: &Response<T, E>
@@ -1,6 +1,40 @@
-use daemon_interface::response::{ListWorkspaceInfoResponse, ListWorkspacesResponse, WorkspaceInfoResponse};
+use std::path::PathBuf;
+use daemon_interface::response::{
+    ListVersionsResponse,
+    ListWorkspaceInfoResponse,
+    ListWorkspacesResponse,
+    PreviewSyncResponse,
+    TriggerSyncResponse,
+    WorkspaceInfoResponse
+};
 use daemon_interface::WorkspaceInfo;
-use crate::domain::models::{ConnectionInfo, RemoteWorkspace, WorkspaceInformation};
+use crate::domain::models::{
+    ConflictPolicy,
+    ConflictReport,
+    ConflictResolution,
+    ConnectionInfo,
+    DebounceSettings,
+    DeletionGuardSettings,
+    FailureCause,
+    FileVersion,
+    FileVersions,
+    HookOutcome,
+    InitialSync,
+    PostSyncHook,
+    RemoteSyncStatus,
+    RemoteWorkspace,
+    ScheduleAction,
+    ScheduleWindow,
+    SyncOptions,
+    SyncPreview,
+    TransferStats,
+    TriggeredSyncOutcome,
+    TwoWaySettings,
+    VersioningSettings,
+    Watcher,
+    WorkspaceInformation
+};
+use crate::monitor_events::MonitorReports;
 
 impl Into<daemon_interface::WorkspaceInfo> for WorkspaceInformation {
     fn into(self) -> WorkspaceInfo {
@@ -13,7 +47,59 @@ impl Into<daemon_interface::WorkspaceInfo> for WorkspaceInformation {
             name: self.name,
             path: self.local_path,
             nr_of_remote_workspaces: remote_workspaces.len(),
-            remote_workspaces
+            remote_workspaces,
+            debounce: self.debounce.into(),
+            max_parallel_syncs: self.max_parallel_syncs,
+            ignore_patterns: self.ignore_patterns,
+            deletion_guard: self.deletion_guard.map(|deletion_guard| deletion_guard.into()),
+            initial_sync: self.initial_sync.into(),
+            watcher: self.watcher.into(),
+            paused: self.paused,
+            conflicts: vec![],
+            sync_status: vec![],
+            transferred: None
+        }
+    }
+}
+
+impl From<DebounceSettings> for daemon_interface::DebounceSettings {
+    fn from(value: DebounceSettings) -> Self {
+        daemon_interface::DebounceSettings {
+            quiet_window_ms: value.quiet_window_ms,
+            max_latency_ms: value.max_latency_ms
+        }
+    }
+}
+
+impl From<DeletionGuardSettings> for daemon_interface::DeletionGuardSettings {
+    fn from(value: DeletionGuardSettings) -> Self {
+        daemon_interface::DeletionGuardSettings {
+            max_deletions: value.max_deletions,
+            max_deletion_percent: value.max_deletion_percent
+        }
+    }
+}
+
+impl From<SyncPreview> for daemon_interface::RemoteSyncPreview {
+    fn from(value: SyncPreview) -> Self {
+        daemon_interface::RemoteSyncPreview {
+            remote_workspace_name: value.remote_workspace,
+            created: value.created,
+            updated: value.updated,
+            deleted: value.deleted,
+            error: value.error
+        }
+    }
+}
+
+impl From<TriggeredSyncOutcome> for daemon_interface::TriggeredSyncOutcome {
+    fn from(value: TriggeredSyncOutcome) -> Self {
+        daemon_interface::TriggeredSyncOutcome {
+            remote_workspace_name: value.remote_workspace,
+            skipped: value.skipped,
+            error: value.error,
+            cause: value.cause.map(|cause| cause.into()),
+            transferred: value.transferred.map(|stats| stats.into())
         }
     }
 }
@@ -23,7 +109,191 @@ impl Into<daemon_interface::RemoteWorkspace> for RemoteWorkspace {
         daemon_interface::RemoteWorkspace {
             name: self.name,
             path: self.remote_path,
-            connection_info: self.connection_info.into()
+            connection_info: self.connection_info.into(),
+            sync_options: self.sync_options.into(),
+            two_way: self.two_way.map(|two_way| two_way.into()),
+            versioning: self.versioning.map(|versioning| versioning.into()),
+            schedule: self.schedule.into_iter().map(|window| window.into()).collect(),
+            post_sync_hook: self.post_sync_hook.map(|post_sync_hook| post_sync_hook.into()),
+            paused: self.paused
+        }
+    }
+}
+
+impl From<TwoWaySettings> for daemon_interface::TwoWaySettings {
+    fn from(value: TwoWaySettings) -> Self {
+        daemon_interface::TwoWaySettings {
+            conflict_policy: value.conflict_policy.into(),
+            poll_interval_secs: value.poll_interval_secs
+        }
+    }
+}
+
+impl From<VersioningSettings> for daemon_interface::VersioningSettings {
+    fn from(value: VersioningSettings) -> Self {
+        daemon_interface::VersioningSettings {
+            keep_versions: value.keep_versions,
+            keep_days: value.keep_days
+        }
+    }
+}
+
+impl From<PostSyncHook> for daemon_interface::PostSyncHook {
+    fn from(value: PostSyncHook) -> Self {
+        daemon_interface::PostSyncHook {
+            command: value.command,
+            working_dir: value.working_dir,
+            timeout_secs: value.timeout_secs,
+            debounce_ms: value.debounce_ms
+        }
+    }
+}
+
+impl From<HookOutcome> for daemon_interface::HookOutcome {
+    fn from(value: HookOutcome) -> Self {
+        daemon_interface::HookOutcome {
+            finished_at: value.finished_at,
+            duration_ms: value.duration_ms,
+            exit_code: value.exit_code,
+            timed_out: value.timed_out,
+            error: value.error
+        }
+    }
+}
+
+impl From<ScheduleWindow> for daemon_interface::ScheduleWindow {
+    fn from(value: ScheduleWindow) -> Self {
+        let action = match value.action {
+            ScheduleAction::Pause => daemon_interface::ScheduleAction::Pause,
+            ScheduleAction::FullSpeed => daemon_interface::ScheduleAction::FullSpeed,
+            ScheduleAction::Bwlimit(bwlimit) => daemon_interface::ScheduleAction::Bwlimit(bwlimit)
+        };
+
+        daemon_interface::ScheduleWindow {
+            start_minute: value.start_minute,
+            end_minute: value.end_minute,
+            action
+        }
+    }
+}
+
+impl From<FileVersions> for daemon_interface::RemoteFileVersions {
+    fn from(value: FileVersions) -> Self {
+        daemon_interface::RemoteFileVersions {
+            remote_workspace_name: value.remote_workspace,
+            versions: value.versions.into_iter().map(|version| version.into()).collect(),
+            error: value.error
+        }
+    }
+}
+
+impl From<FileVersion> for daemon_interface::FileVersion {
+    fn from(value: FileVersion) -> Self {
+        daemon_interface::FileVersion {
+            version: value.version,
+            created_at: value.created_at,
+            size: value.size
+        }
+    }
+}
+
+impl From<ConflictPolicy> for daemon_interface::ConflictPolicy {
+    fn from(value: ConflictPolicy) -> Self {
+        match value {
+            ConflictPolicy::LocalWins => daemon_interface::ConflictPolicy::LocalWins,
+            ConflictPolicy::RemoteWins => daemon_interface::ConflictPolicy::RemoteWins,
+            ConflictPolicy::KeepBoth => daemon_interface::ConflictPolicy::KeepBoth
+        }
+    }
+}
+
+impl From<InitialSync> for daemon_interface::InitialSyncStrategy {
+    fn from(value: InitialSync) -> Self {
+        match value {
+            InitialSync::Full => daemon_interface::InitialSyncStrategy::Full,
+            InitialSync::Verify => daemon_interface::InitialSyncStrategy::Verify,
+            InitialSync::Modified => daemon_interface::InitialSyncStrategy::Modified,
+            InitialSync::Skip => daemon_interface::InitialSyncStrategy::Skip
+        }
+    }
+}
+
+impl From<Watcher> for daemon_interface::WatcherBackend {
+    fn from(value: Watcher) -> Self {
+        match value {
+            Watcher::Inotify => daemon_interface::WatcherBackend::Inotify,
+            Watcher::Fanotify => daemon_interface::WatcherBackend::Fanotify
+        }
+    }
+}
+
+impl From<FailureCause> for daemon_interface::FailureCause {
+    fn from(value: FailureCause) -> Self {
+        match value {
+            FailureCause::Unreachable => daemon_interface::FailureCause::Unreachable,
+            FailureCause::ConnectionLost => daemon_interface::FailureCause::ConnectionLost,
+            FailureCause::AuthenticationFailed => daemon_interface::FailureCause::AuthenticationFailed,
+            FailureCause::RemotePathMissing => daemon_interface::FailureCause::RemotePathMissing,
+            FailureCause::PermissionDenied => daemon_interface::FailureCause::PermissionDenied,
+            FailureCause::DiskFull => daemon_interface::FailureCause::DiskFull,
+            FailureCause::VanishedFiles => daemon_interface::FailureCause::VanishedFiles,
+            FailureCause::PartialTransfer => daemon_interface::FailureCause::PartialTransfer,
+            FailureCause::Other => daemon_interface::FailureCause::Other
+        }
+    }
+}
+
+impl From<TransferStats> for daemon_interface::TransferStats {
+    fn from(value: TransferStats) -> Self {
+        daemon_interface::TransferStats {
+            files_considered: value.files_considered,
+            files_transferred: value.files_transferred,
+            bytes_sent: value.bytes_sent,
+            bytes_received: value.bytes_received,
+            total_size: value.total_size,
+            duration_ms: value.duration_ms
+        }
+    }
+}
+
+impl From<ConflictReport> for daemon_interface::ConflictInfo {
+    fn from(value: ConflictReport) -> Self {
+        let resolution = match value.resolution {
+            ConflictResolution::LocalWon => daemon_interface::ConflictResolution::LocalWon,
+            ConflictResolution::RemoteWon => daemon_interface::ConflictResolution::RemoteWon,
+            ConflictResolution::KeptBoth { conflict_copy } => {
+                daemon_interface::ConflictResolution::KeptBoth { conflict_copy }
+            }
+        };
+
+        daemon_interface::ConflictInfo {
+            remote_workspace_name: value.remote_workspace,
+            path: value.path,
+            resolution,
+            detected_at: value.detected_at
+        }
+    }
+}
+
+impl From<SyncOptions> for daemon_interface::SyncOptions {
+    fn from(value: SyncOptions) -> Self {
+        daemon_interface::SyncOptions {
+            delete: value.delete,
+            compress: value.compress,
+            compress_choice: value.compress_choice,
+            checksum: value.checksum,
+            bwlimit_kbps: value.bwlimit_kbps,
+            chmod: value.chmod,
+            chown: value.chown,
+            partial: value.partial,
+            inplace: value.inplace,
+            hard_links: value.hard_links,
+            acls: value.acls,
+            xattrs: value.xattrs,
+            perms: value.perms,
+            owner: value.owner,
+            group: value.group,
+            times: value.times
         }
     }
 }
@@ -57,19 +327,74 @@ impl Into<daemon_interface::ConnectionInfo> for ConnectionInfo {
                     port,
                     username
                 }
+            },
+            ConnectionInfo::Local { path } => {
+                daemon_interface::ConnectionInfo::Local { path }
+            },
+            ConnectionInfo::Agent {
+                host,
+                port,
+                username,
+                identity_file,
+                agent_path
+            } => {
+                daemon_interface::ConnectionInfo::Agent {
+                    host,
+                    port,
+                    username,
+                    identity_file,
+                    agent_path
+                }
             }
         }
     }
 }
 
-pub(in crate::handlers) fn to_workspace_info_response(data: WorkspaceInformation) -> WorkspaceInfoResponse {
+fn to_remote_sync_status(remote_workspace_name: String, status: RemoteSyncStatus) -> daemon_interface::RemoteSyncStatus {
+    daemon_interface::RemoteSyncStatus {
+        remote_workspace_name,
+        syncing: status.syncing,
+        last_started_at: status.last_started_at,
+        last_success_at: status.last_success_at,
+        last_duration_ms: status.last_duration_ms,
+        last_error: status.last_error,
+        last_error_cause: status.last_error_cause.map(|cause| cause.into()),
+        last_failure_at: status.last_failure_at,
+        consecutive_failures: status.consecutive_failures,
+        pending_deletions: status.pending_deletions,
+        deletions_held_since: status.deletions_held_since,
+        last_transfer: status.last_transfer.map(|stats| stats.into()),
+        total_transferred: status.total_transferred.map(|stats| stats.into()),
+        last_hook: status.last_hook.map(|last_hook| last_hook.into())
+    }
+}
+
+pub(in crate::handlers) fn to_workspace_info_response(
+    data: WorkspaceInformation,
+    reports: &MonitorReports
+) -> WorkspaceInfoResponse {
+    let conflicts = reports.conflicts(&data.name);
+    let transferred = reports.transferred(&data.name);
+    let sync_status: Vec<daemon_interface::RemoteSyncStatus> = data.remote_workspaces
+        .iter()
+        .filter_map(|rw| {
+            reports.sync_status(&data.name, &rw.name).map(|status| to_remote_sync_status(rw.name.clone(), status))
+        })
+        .collect();
+
     let remote_workspaces: Vec<daemon_interface::RemoteWorkspace> = data.remote_workspaces
         .into_iter()
         .map(|rw| {
             daemon_interface::RemoteWorkspace {
                 name: rw.name,
                 path: rw.remote_path,
-                connection_info: rw.connection_info.into()
+                connection_info: rw.connection_info.into(),
+                sync_options: rw.sync_options.into(),
+                two_way: rw.two_way.map(|two_way| two_way.into()),
+                versioning: rw.versioning.map(|versioning| versioning.into()),
+                schedule: rw.schedule.into_iter().map(|window| window.into()).collect(),
+                post_sync_hook: rw.post_sync_hook.map(|post_sync_hook| post_sync_hook.into()),
+                paused: rw.paused
             }
         })
         .collect();
@@ -78,7 +403,17 @@ pub(in crate::handlers) fn to_workspace_info_response(data: WorkspaceInformation
         name: data.name,
         path: data.local_path,
         nr_of_remote_workspaces: remote_workspaces.len(),
-        remote_workspaces
+        remote_workspaces,
+        debounce: data.debounce.into(),
+        max_parallel_syncs: data.max_parallel_syncs,
+        ignore_patterns: data.ignore_patterns,
+        deletion_guard: data.deletion_guard.map(|deletion_guard| deletion_guard.into()),
+        initial_sync: data.initial_sync.into(),
+        watcher: data.watcher.into(),
+        paused: data.paused,
+        conflicts: conflicts.into_iter().map(|conflict| conflict.into()).collect(),
+        sync_status,
+        transferred: transferred.map(|stats| stats.into())
     };
 
     WorkspaceInfoResponse { info }
@@ -102,10 +437,13 @@ pub(in crate::handlers) fn to_list_workspaces_response(data: Vec<WorkspaceInform
     }
 }
 
-pub(in crate::handlers) fn to_list_workspace_info_response(data: Vec<WorkspaceInformation>) -> ListWorkspaceInfoResponse {
+pub(in crate::handlers) fn to_list_workspace_info_response(
+    data: Vec<WorkspaceInformation>,
+    reports: &MonitorReports
+) -> ListWorkspaceInfoResponse {
     let workspaces_info: Vec<daemon_interface::WorkspaceInfo> = data
         .into_iter()
-        .map(|workspace| workspace.into())
+        .map(|workspace| to_workspace_info_response(workspace, reports).info)
         .collect();
 
     ListWorkspaceInfoResponse {
@@ -113,3 +451,32 @@ pub(in crate::handlers) fn to_list_workspace_info_response(data: Vec<WorkspaceIn
         entries: workspaces_info
     }
 }
+
+pub(in crate::handlers) fn to_preview_sync_response(workspace_name: String, data: Vec<SyncPreview>) -> PreviewSyncResponse {
+    PreviewSyncResponse {
+        workspace_name,
+        entries: data.into_iter().map(|preview| preview.into()).collect()
+    }
+}
+
+pub(in crate::handlers) fn to_trigger_sync_response(
+    workspace_name: String,
+    data: Vec<TriggeredSyncOutcome>
+) -> TriggerSyncResponse {
+    TriggerSyncResponse {
+        workspace_name,
+        entries: data.into_iter().map(|outcome| outcome.into()).collect()
+    }
+}
+
+pub(in crate::handlers) fn to_list_versions_response(
+    workspace_name: String,
+    path: PathBuf,
+    data: Vec<FileVersions>
+) -> ListVersionsResponse {
+    ListVersionsResponse {
+        workspace_name,
+        path,
+        entries: data.into_iter().map(|versions| versions.into()).collect()
+    }
+}
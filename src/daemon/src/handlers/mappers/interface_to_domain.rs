@@ -1,12 +1,69 @@
 use daemon_interface::request::{AddWorkspaceRequest, AttachRemoteWorkspaceRequest};
-use crate::domain::models::{ConnectionInfo, RemoteWorkspace, WorkspaceInformation};
+use crate::domain::models::{
+    default_max_parallel_syncs,
+    ConflictPolicy,
+    ConnectionInfo,
+    DebounceSettings,
+    DeletionGuardSettings,
+    InitialSync,
+    PostSyncHook,
+    RemoteWorkspace,
+    ScheduleAction,
+    ScheduleWindow,
+    SyncOptions,
+    TwoWaySettings,
+    VersioningSettings,
+    Watcher,
+    WorkspaceInformation
+};
 
 impl From<AddWorkspaceRequest> for WorkspaceInformation {
     fn from(value: AddWorkspaceRequest) -> Self {
+        let default_debounce = DebounceSettings::default();
+
         Self {
             name: value.name,
             local_path: value.path,
-            remote_workspaces: vec![]
+            remote_workspaces: vec![],
+            debounce: DebounceSettings {
+                quiet_window_ms: value.quiet_window_ms.unwrap_or(default_debounce.quiet_window_ms),
+                max_latency_ms: value.max_latency_ms.unwrap_or(default_debounce.max_latency_ms)
+            },
+            max_parallel_syncs: value.max_parallel_syncs.unwrap_or(default_max_parallel_syncs()).max(1),
+            ignore_patterns: value.ignore_patterns,
+            deletion_guard: value.deletion_guard.map(DeletionGuardSettings::from),
+            initial_sync: value.initial_sync.map(InitialSync::from).unwrap_or_default(),
+            watcher: value.watcher.map(Watcher::from).unwrap_or_default(),
+            paused: false
+        }
+    }
+}
+
+impl From<daemon_interface::InitialSyncStrategy> for InitialSync {
+    fn from(value: daemon_interface::InitialSyncStrategy) -> Self {
+        match value {
+            daemon_interface::InitialSyncStrategy::Full => InitialSync::Full,
+            daemon_interface::InitialSyncStrategy::Verify => InitialSync::Verify,
+            daemon_interface::InitialSyncStrategy::Modified => InitialSync::Modified,
+            daemon_interface::InitialSyncStrategy::Skip => InitialSync::Skip
+        }
+    }
+}
+
+impl From<daemon_interface::WatcherBackend> for Watcher {
+    fn from(value: daemon_interface::WatcherBackend) -> Self {
+        match value {
+            daemon_interface::WatcherBackend::Inotify => Watcher::Inotify,
+            daemon_interface::WatcherBackend::Fanotify => Watcher::Fanotify
+        }
+    }
+}
+
+impl From<daemon_interface::DeletionGuardSettings> for DeletionGuardSettings {
+    fn from(value: daemon_interface::DeletionGuardSettings) -> Self {
+        Self {
+            max_deletions: value.max_deletions,
+            max_deletion_percent: value.max_deletion_percent
         }
     }
 }
@@ -16,7 +73,91 @@ impl From<AttachRemoteWorkspaceRequest> for RemoteWorkspace {
         Self {
             name: value.remote_workspace_name,
             remote_path: value.remote_workspace_path,
-            connection_info: ConnectionInfo::from(value.connection_info)
+            connection_info: ConnectionInfo::from(value.connection_info),
+            sync_options: SyncOptions::from(value.sync_options),
+            two_way: value.two_way.map(TwoWaySettings::from),
+            versioning: value.versioning.map(VersioningSettings::from),
+            schedule: vec![],
+            post_sync_hook: value.post_sync_hook.map(PostSyncHook::from),
+            paused: false
+        }
+    }
+}
+
+impl From<daemon_interface::PostSyncHook> for PostSyncHook {
+    fn from(value: daemon_interface::PostSyncHook) -> Self {
+        Self {
+            command: value.command,
+            working_dir: value.working_dir,
+            timeout_secs: value.timeout_secs,
+            debounce_ms: value.debounce_ms
+        }
+    }
+}
+
+impl From<daemon_interface::ScheduleWindow> for ScheduleWindow {
+    fn from(value: daemon_interface::ScheduleWindow) -> Self {
+        let action = match value.action {
+            daemon_interface::ScheduleAction::Pause => ScheduleAction::Pause,
+            daemon_interface::ScheduleAction::FullSpeed => ScheduleAction::FullSpeed,
+            daemon_interface::ScheduleAction::Bwlimit(bwlimit) => ScheduleAction::Bwlimit(bwlimit)
+        };
+
+        Self {
+            start_minute: value.start_minute,
+            end_minute: value.end_minute,
+            action
+        }
+    }
+}
+
+impl From<daemon_interface::TwoWaySettings> for TwoWaySettings {
+    fn from(value: daemon_interface::TwoWaySettings) -> Self {
+        Self {
+            conflict_policy: ConflictPolicy::from(value.conflict_policy),
+            poll_interval_secs: value.poll_interval_secs
+        }
+    }
+}
+
+impl From<daemon_interface::VersioningSettings> for VersioningSettings {
+    fn from(value: daemon_interface::VersioningSettings) -> Self {
+        Self {
+            keep_versions: value.keep_versions,
+            keep_days: value.keep_days
+        }
+    }
+}
+
+impl From<daemon_interface::ConflictPolicy> for ConflictPolicy {
+    fn from(value: daemon_interface::ConflictPolicy) -> Self {
+        match value {
+            daemon_interface::ConflictPolicy::LocalWins => ConflictPolicy::LocalWins,
+            daemon_interface::ConflictPolicy::RemoteWins => ConflictPolicy::RemoteWins,
+            daemon_interface::ConflictPolicy::KeepBoth => ConflictPolicy::KeepBoth
+        }
+    }
+}
+
+impl From<daemon_interface::SyncOptions> for SyncOptions {
+    fn from(value: daemon_interface::SyncOptions) -> Self {
+        Self {
+            delete: value.delete,
+            compress: value.compress,
+            compress_choice: value.compress_choice,
+            checksum: value.checksum,
+            bwlimit_kbps: value.bwlimit_kbps,
+            chmod: value.chmod,
+            chown: value.chown,
+            partial: value.partial,
+            inplace: value.inplace,
+            hard_links: value.hard_links,
+            acls: value.acls,
+            xattrs: value.xattrs,
+            perms: value.perms,
+            owner: value.owner,
+            group: value.group,
+            times: value.times
         }
     }
 }
@@ -50,6 +191,24 @@ impl From<daemon_interface::ConnectionInfo> for ConnectionInfo {
                     port,
                     username
                 }
+            },
+            daemon_interface::ConnectionInfo::Local { path } => {
+                ConnectionInfo::Local { path }
+            },
+            daemon_interface::ConnectionInfo::Agent {
+                host,
+                port,
+                username,
+                identity_file,
+                agent_path
+            } => {
+                ConnectionInfo::Agent {
+                    host,
+                    port,
+                    username,
+                    identity_file,
+                    agent_path
+                }
             }
         }
     }
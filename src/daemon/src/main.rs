@@ -19,6 +19,8 @@ mod workspace_config;
 mod util;
 mod domain;
 mod monitor_manager;
+mod monitor_events;
+mod permits;
 mod daemon_state;
 mod handlers;
 mod watchdog;
@@ -0,0 +1,321 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader};
+use std::process::ChildStdout;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use crate::domain::models::{
+    ConflictReport,
+    FailureCause,
+    HookOutcome,
+    RemoteSyncStatus,
+    TransferStats,
+    TriggeredSyncOutcome
+};
+use crate::monitor_manager::{write_command, MonitorCommand, MonitorStdin};
+use crate::permits::SharedSyncPermits;
+
+/// Number of conflicts that are kept per workspace
+const MAX_CONFLICTS_PER_WORKSPACE: usize = 50;
+
+/// Events reported by a monitor to the daemon, each being written to the monitor's stdout as a
+/// single line of JSON
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) enum MonitorEvent {
+    Conflict(ConflictReport),
+    SyncStarted {
+        remote_workspace: String,
+        started_at: u64
+    },
+    SyncSucceeded {
+        remote_workspace: String,
+        finished_at: u64,
+        duration_ms: u64
+    },
+    SyncFailed {
+        remote_workspace: String,
+        finished_at: u64,
+        duration_ms: u64,
+        error: String,
+        cause: Option<FailureCause>
+    },
+    Transferred {
+        remote_workspace: String,
+        last_sync: TransferStats,
+        remote_total: TransferStats,
+        workspace_total: TransferStats
+    },
+    DeletionsHeld {
+        remote_workspace: String,
+        nr_of_deletions: usize,
+        held_since: u64
+    },
+    DeletionsReleased {
+        remote_workspace: String,
+        approved: bool
+    },
+    HookFinished {
+        remote_workspace: String,
+        finished_at: u64,
+        duration_ms: u64,
+        exit_code: Option<i32>,
+        timed_out: bool,
+        error: Option<String>
+    },
+    TriggeredSyncFinished {
+        id: String,
+        outcomes: Vec<TriggeredSyncOutcome>
+    },
+    PermitRequested {
+        id: String,
+        remote_workspace: String
+    },
+    PermitReleased {
+        id: String
+    }
+}
+
+/// Sync triggered on demand, whose outcome is awaited by the request that triggered it
+struct TriggeredSync {
+    workspace_id: String,
+    /// Set once the monitor reported the outcome, or stopped before doing so
+    outcome: Option<Result<Vec<TriggeredSyncOutcome>, String>>
+}
+
+/// What the monitors reported about their workspaces, i.e. detected conflicts, the outcomes of
+/// their syncs and what they transferred. Reports are kept in memory only and are dropped once the workspace is removed.
+#[derive(Default)]
+pub(crate) struct MonitorReports {
+    conflicts: HashMap<String, VecDeque<ConflictReport>>,
+    /// Sync status per workspace and remote workspace
+    sync_status: HashMap<String, HashMap<String, RemoteSyncStatus>>,
+    /// Statistics of all transfers of a workspace since its monitor started
+    transferred: HashMap<String, TransferStats>,
+    /// Syncs triggered on demand by their id, until their outcome is collected
+    triggered_syncs: HashMap<String, TriggeredSync>
+}
+
+pub(crate) type SharedMonitorReports = Arc<Mutex<MonitorReports>>;
+
+impl MonitorReports {
+
+    /// Conflicts of the given workspace, most recent first
+    pub(crate) fn conflicts(&self, workspace_id: &str) -> Vec<ConflictReport> {
+        self.conflicts
+            .get(workspace_id)
+            .map(|conflicts| conflicts.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Sync status of the given remote workspace, if its monitor reported any sync yet
+    pub(crate) fn sync_status(&self, workspace_id: &str, remote_workspace: &str) -> Option<RemoteSyncStatus> {
+        self.sync_status.get(workspace_id)?.get(remote_workspace).cloned()
+    }
+
+    /// Statistics of all transfers of the given workspace, if its monitor reported any yet
+    pub(crate) fn transferred(&self, workspace_id: &str) -> Option<TransferStats> {
+        self.transferred.get(workspace_id).cloned()
+    }
+
+    /// Whether the monitor holds deletions for the given remote workspace, or for any remote
+    /// workspace of the workspace if none is given
+    pub(crate) fn has_pending_deletions(&self, workspace_id: &str, remote_workspace: Option<&str>) -> bool {
+        self.sync_status
+            .get(workspace_id)
+            .into_iter()
+            .flat_map(|statuses| statuses.iter())
+            .filter(|(name, _)| remote_workspace.is_none_or(|remote_workspace| *name == remote_workspace))
+            .any(|(_, status)| status.pending_deletions.is_some())
+    }
+
+    pub(crate) fn remove_workspace(&mut self, workspace_id: &str) {
+        self.conflicts.remove(workspace_id);
+        self.sync_status.remove(workspace_id);
+        self.transferred.remove(workspace_id);
+    }
+
+    /// Keep the outcome of the sync with the given id once the monitor of the given workspace
+    /// reports it, which must be called before the sync is triggered
+    pub(crate) fn expect_triggered_sync(&mut self, workspace_id: &str, id: &str) {
+        self.triggered_syncs.insert(id.to_string(), TriggeredSync { workspace_id: workspace_id.to_string(), outcome: None });
+    }
+
+    /// Remove and return the outcome of the triggered sync with the given id, unless it is still
+    /// in progress. The error describes why the sync didn't finish.
+    pub(crate) fn take_triggered_sync(&mut self, id: &str) -> Option<Result<Vec<TriggeredSyncOutcome>, String>> {
+        match self.triggered_syncs.get(id) {
+            Some(triggered_sync) if triggered_sync.outcome.is_some() => {
+                self.triggered_syncs.remove(id).and_then(|triggered_sync| triggered_sync.outcome)
+            },
+            _ => None
+        }
+    }
+
+    /// Stop keeping the outcome of a triggered sync that is no longer awaited
+    pub(crate) fn forget_triggered_sync(&mut self, id: &str) {
+        self.triggered_syncs.remove(id);
+    }
+
+    /// Syncs that were in progress when the monitor stopped will never be reported as finished
+    fn monitor_stopped(&mut self, workspace_id: &str) {
+        for status in self.sync_status.get_mut(workspace_id).into_iter().flat_map(|statuses| statuses.values_mut()) {
+            status.syncing = false;
+        }
+
+        for triggered_sync in self.triggered_syncs.values_mut().filter(|sync| sync.workspace_id == workspace_id) {
+            triggered_sync.outcome.get_or_insert_with(|| Err(String::from("The monitor stopped before the sync finished")));
+        }
+    }
+
+    fn status_mut(&mut self, workspace_id: &str, remote_workspace: String) -> &mut RemoteSyncStatus {
+        self.sync_status.entry(workspace_id.to_string()).or_default().entry(remote_workspace).or_default()
+    }
+
+    fn record(&mut self, workspace_id: &str, event: MonitorEvent) {
+        match event {
+            MonitorEvent::Conflict(conflict) => {
+                warn!(
+                    "Conflict in '{}' of remote workspace '{}' of '{workspace_id}': {:?}",
+                    conflict.path.display(),
+                    conflict.remote_workspace,
+                    conflict.resolution
+                );
+
+                let conflicts = self.conflicts.entry(workspace_id.to_string()).or_default();
+                if conflicts.len() == MAX_CONFLICTS_PER_WORKSPACE {
+                    conflicts.pop_front();
+                }
+                conflicts.push_back(conflict);
+            },
+            MonitorEvent::SyncStarted { remote_workspace, started_at } => {
+                let status = self.status_mut(workspace_id, remote_workspace);
+                status.syncing = true;
+                status.last_started_at = Some(started_at);
+            },
+            MonitorEvent::SyncSucceeded { remote_workspace, finished_at, duration_ms } => {
+                let status = self.status_mut(workspace_id, remote_workspace);
+                status.syncing = false;
+                status.last_success_at = Some(finished_at);
+                status.last_duration_ms = Some(duration_ms);
+                status.consecutive_failures = 0;
+            },
+            MonitorEvent::SyncFailed { remote_workspace, finished_at, duration_ms, error, cause } => {
+                debug!("Sync of '{workspace_id}' with '{remote_workspace}' failed: {error}");
+
+                let status = self.status_mut(workspace_id, remote_workspace);
+                status.syncing = false;
+                status.last_duration_ms = Some(duration_ms);
+                status.last_error = Some(error);
+                status.last_error_cause = cause;
+                status.last_failure_at = Some(finished_at);
+                status.consecutive_failures += 1;
+            },
+            MonitorEvent::Transferred { remote_workspace, last_sync, remote_total, workspace_total } => {
+                let status = self.status_mut(workspace_id, remote_workspace);
+                status.last_transfer = Some(last_sync);
+                status.total_transferred = Some(remote_total);
+
+                self.transferred.insert(workspace_id.to_string(), workspace_total);
+            },
+            MonitorEvent::DeletionsHeld { remote_workspace, nr_of_deletions, held_since } => {
+                warn!(
+                    "Syncs of '{workspace_id}' with '{remote_workspace}' are held, as they would delete \
+                    #{nr_of_deletions} resource(s)"
+                );
+
+                let status = self.status_mut(workspace_id, remote_workspace);
+                status.pending_deletions = Some(nr_of_deletions);
+                status.deletions_held_since = Some(held_since);
+            },
+            MonitorEvent::DeletionsReleased { remote_workspace, approved } => {
+                debug!(
+                    "Deletions of '{workspace_id}' in '{remote_workspace}' were {}",
+                    if approved { "approved" } else { "rejected" }
+                );
+
+                let status = self.status_mut(workspace_id, remote_workspace);
+                status.pending_deletions = None;
+                status.deletions_held_since = None;
+            },
+            MonitorEvent::HookFinished { remote_workspace, finished_at, duration_ms, exit_code, timed_out, error } => {
+                debug!(
+                    "Post-sync hook of '{workspace_id}' in '{remote_workspace}' finished with exit code {:?}",
+                    exit_code
+                );
+
+                let status = self.status_mut(workspace_id, remote_workspace);
+                status.last_hook = Some(HookOutcome { finished_at, duration_ms, exit_code, timed_out, error });
+            },
+            MonitorEvent::TriggeredSyncFinished { id, outcomes } => {
+                debug!("Triggered sync '{id}' of '{workspace_id}' finished");
+
+                if let Some(triggered_sync) = self.triggered_syncs.get_mut(&id) {
+                    triggered_sync.outcome = Some(Ok(outcomes));
+                }
+            },
+            // Handled by the event reader, as they concern the permits rather than the reports
+            MonitorEvent::PermitRequested { .. } | MonitorEvent::PermitReleased { .. } => {}
+        }
+    }
+}
+
+/// Pass a permit requested by the monitor to the coordinator. Permits are granted right away if the
+/// transfers are not coordinated, which only happens if the monitor was started differently.
+fn request_permit(
+    workspace_id: &str,
+    id: String,
+    remote_workspace: String,
+    permits: Option<&SharedSyncPermits>,
+    stdin: &MonitorStdin
+) {
+    match permits {
+        Some(permits) => permits.lock().unwrap().request(workspace_id, id, remote_workspace, stdin.clone()),
+        None => {
+            if let Err(e) = write_command(stdin, &MonitorCommand::PermitGranted { id, bwlimit_kbps: None }) {
+                warn!("Unable to grant permit to the monitor of '{workspace_id}': {e}");
+            }
+        }
+    }
+}
+
+/// Read the events reported by the monitor of the given workspace until it terminates. Permits
+/// are granted to the monitor via the given stdin.
+pub(crate) fn spawn_event_reader(
+    workspace_id: String,
+    stdout: ChildStdout,
+    reports: SharedMonitorReports,
+    permits: Option<SharedSyncPermits>,
+    stdin: MonitorStdin
+) {
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    warn!("Unable to read events of the monitor of '{workspace_id}': {e}");
+                    break;
+                }
+            };
+
+            match serde_json::from_str::<MonitorEvent>(&line) {
+                Ok(MonitorEvent::PermitRequested { id, remote_workspace }) => {
+                    request_permit(&workspace_id, id, remote_workspace, permits.as_ref(), &stdin);
+                },
+                Ok(MonitorEvent::PermitReleased { id }) => {
+                    if let Some(permits) = &permits {
+                        permits.lock().unwrap().release(&stdin, &id);
+                    }
+                },
+                Ok(event) => reports.lock().unwrap().record(&workspace_id, event),
+                Err(e) => warn!("Received malformed event '{line}' from the monitor of '{workspace_id}': {e}")
+            }
+        }
+
+        reports.lock().unwrap().monitor_stopped(&workspace_id);
+        if let Some(permits) = &permits {
+            permits.lock().unwrap().monitor_stopped(&stdin);
+        }
+        debug!("Stopped reading events of the monitor of '{workspace_id}'");
+    });
+}
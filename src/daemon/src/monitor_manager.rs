@@ -1,12 +1,18 @@
 use std::collections::HashMap;
-use std::io::Write;
-use std::path::Path;
-use std::process::{Child, Command, Stdio};
+use std::ffi::OsStr;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
 use std::collections::hash_map::Entry;
 use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
 use log::debug;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use wsync_config::{config, ConfigKey};
 use crate::domain::models::WorkspaceInformation;
+use crate::monitor_events::{spawn_event_reader, SharedMonitorReports};
+use crate::permits::{SharedSyncPermits, SyncPermits};
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -26,9 +32,55 @@ impl Error {
     }
 }
 
+/// Commands sent to a running monitor, each as a single line of JSON on its stdin
+#[derive(Serialize, Debug)]
+pub(crate) enum MonitorCommand {
+    ResolveDeletions {
+        remote_workspace: Option<String>,
+        approve: bool
+    },
+    SetPaused {
+        remote_workspace: Option<String>,
+        paused: bool
+    },
+    TriggerSync {
+        id: String,
+        remote_workspace: Option<String>,
+        path: Option<PathBuf>
+    },
+    /// Permit to run the transfer that requested it, limited to the given bandwidth
+    PermitGranted {
+        id: String,
+        bwlimit_kbps: Option<u32>
+    }
+}
+
+/// Passed to monitors if they must ask the daemon for a permit before each transfer
+const SYNC_PERMITS_ARG: &str = "--sync-permits";
+
+/// Stdin of a running monitor, which is shared with its event reader to grant it permits
+pub(crate) type MonitorStdin = Arc<Mutex<ChildStdin>>;
+
+/// Send a command to a running monitor, as a single line of JSON
+pub(crate) fn write_command(stdin: &MonitorStdin, command: &MonitorCommand) -> Result<()> {
+    let mut line = serde_json::to_string(command).map_err(|e| {
+        Error::new(format!("Unable to serialize command {command:?}: {e}"))
+    })?;
+    line.push('\n');
+
+    let mut stdin = stdin.lock().unwrap();
+    stdin.write_all(line.as_bytes()).and_then(|_| stdin.flush()).map_err(|e| {
+        Error::new(format!("Unable to send command: {e}"))
+    })
+}
+
 pub(crate) struct MonitorManager {
     // Only to be directly accessed by the watchdog
     pub(crate) ws_id_to_monitor: HashMap<String, Child>,
+    pub(self) ws_id_to_stdin: HashMap<String, MonitorStdin>,
+    pub(crate) reports: SharedMonitorReports,
+    /// Coordinates the transfers of all monitors, if the config limits them
+    pub(self) permits: Option<SharedSyncPermits>,
     pub(self) monitor_executable: String
 }
 
@@ -47,7 +99,17 @@ impl MonitorManager {
             ));
         }
 
-        Ok( MonitorManager { ws_id_to_monitor: HashMap::new(), monitor_executable } )
+        let permits = SyncPermits::init().map_err(|e| {
+            Error::new(format!("Invalid transfer limits: {e}"))
+        })?;
+
+        Ok( MonitorManager {
+            ws_id_to_monitor: HashMap::new(),
+            ws_id_to_stdin: HashMap::new(),
+            reports: SharedMonitorReports::default(),
+            permits,
+            monitor_executable
+        } )
     }
 
     pub(crate) fn start_monitor(&mut self, workspace: &WorkspaceInformation) -> Result<()> {
@@ -72,9 +134,13 @@ impl MonitorManager {
                     ))
                 })?;
 
+                // Monitors log to stderr and report events to the daemon via stdout
+                let args: &[&str] = if self.permits.is_some() { &[SYNC_PERMITS_ARG] } else { &[] };
+
                 let mut child = Command::new(&self.monitor_executable)
+                    .args(args)
                     .stdin(Stdio::piped())
-                    .stdout(Stdio::inherit())
+                    .stdout(Stdio::piped())
                     .stderr(Stdio::inherit())
                     .spawn()
                     .map_err(|e| {
@@ -83,7 +149,7 @@ impl MonitorManager {
                         ))
                     })?;
 
-                match child.stdin.take() {
+                let stdin = match child.stdin.take() {
                     Some(mut stdin) => {
                         let res = stdin.write_all(serialized_ws.as_bytes()).map_err(|e| {
                             Error::new(format!(
@@ -95,6 +161,9 @@ impl MonitorManager {
                             let _ = Self::kill_monitor(child);
                             return Err(e);
                         }
+
+                        // Kept open to send commands to the monitor
+                        Arc::new(Mutex::new(stdin))
                     },
                     None => {
                         let _ = Self::kill_monitor(child);
@@ -102,14 +171,107 @@ impl MonitorManager {
                             "Failed to open stdin of spawned monitor to pass it the workspace information".to_string()
                         ));
                     }
+                };
+
+                if let Some(stdout) = child.stdout.take() {
+                    spawn_event_reader(
+                        workspace.name.clone(),
+                        stdout,
+                        self.reports.clone(),
+                        self.permits.clone(),
+                        stdin.clone()
+                    );
                 }
 
+                self.ws_id_to_stdin.insert(workspace.name.clone(), stdin);
                 entry.insert(child);
                 Ok(())
             }
         }
     }
 
+    pub(crate) fn is_running(&self, workspace_id: &str) -> bool {
+        self.ws_id_to_monitor.contains_key(workspace_id)
+    }
+
+    pub(crate) fn send_command(&mut self, workspace_id: &str, command: &MonitorCommand) -> Result<()> {
+        let stdin = self.ws_id_to_stdin
+            .get(workspace_id)
+            .ok_or_else(|| Error::new(format!("No monitor is running for workspace '{workspace_id}'")))?;
+
+        write_command(stdin, command).map_err(|e| {
+            Error::new(format!("Unable to reach the monitor of '{workspace_id}': {e}"))
+        })
+    }
+
+    /// Spawn a monitor that performs a one-shot task for the given workspace (e.g. previewing a
+    /// sync) selected by the given arguments, instead of watching it. The `task` describes it in
+    /// error messages. The result is collected with [MonitorManager::collect_task_result], which
+    /// doesn't require access to the monitor manager, as tasks contact the remote systems and might
+    /// take a while.
+    pub(crate) fn spawn_monitor_task<I, S>(&self, workspace: &WorkspaceInformation, args: I, task: &str) -> Result<Child>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>
+    {
+        let serialized_ws = serde_json::to_string(workspace).map_err(|e| {
+            Error::new(format!(
+                "Unable to {task} of '{}' because serializing the workspace information failed: {e}",
+                workspace.name
+            ))
+        })?;
+
+        let mut child = Command::new(&self.monitor_executable)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| {
+                Error::new(format!("Spawning monitor to {task} of '{}' failed: {e}", workspace.name))
+            })?;
+
+        let res = match child.stdin.take() {
+            Some(mut stdin) => stdin.write_all(serialized_ws.as_bytes()).map_err(|e| {
+                Error::new(format!(
+                    "Unable to pass serialized workspace information to spawned monitor: {e}"
+                ))
+            }),
+            None => Err(Error::new(
+                "Failed to open stdin of spawned monitor to pass it the workspace information".to_string()
+            ))
+        };
+
+        if let Err(e) = res {
+            let _ = Self::kill_monitor(child);
+            return Err(e);
+        }
+
+        Ok(child)
+    }
+
+    pub(crate) fn collect_task_result<T: DeserializeOwned>(mut child: Child, task: &str) -> Result<T> {
+        let mut output = String::new();
+
+        if let Some(mut stdout) = child.stdout.take()
+            && let Err(e) = stdout.read_to_string(&mut output) {
+            let _ = Self::kill_monitor(child);
+            return Err(Error::new(format!("Unable to read result of '{task}' from monitor: {e}")));
+        }
+
+        let status = child.wait().map_err(|e| {
+            Error::new(format!("Unable to wait for monitor performing '{task}': {e}"))
+        })?;
+
+        if !status.success() {
+            return Err(Error::new(format!("Monitor performing '{task}' exited with status '{status}'")));
+        }
+
+        serde_json::from_str(&output).map_err(|e| {
+            Error::new(format!("Unable to parse result of '{task}' returned by monitor: {e}"))
+        })
+    }
+
     pub(crate) fn restart_monitor(&mut self, workspace: &WorkspaceInformation) -> Result<()> {
 
         if !self.ws_id_to_monitor.contains_key(&workspace.name) {
@@ -124,6 +286,8 @@ impl MonitorManager {
 
     pub(crate) fn terminate_monitor(&mut self, workspace_id: &String) -> Result<()> {
 
+        self.ws_id_to_stdin.remove(workspace_id);
+
         let monitor = match self.ws_id_to_monitor.remove(workspace_id) {
             Some(monitor) => monitor,
             None => {
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use log::{debug, warn};
+use wsync_config::{config, ConfigKey};
+use crate::monitor_manager::{write_command, MonitorCommand, MonitorStdin};
+
+type Result<T> = std::result::Result<T, Error>;
+
+pub(crate) struct Error {
+    pub(crate) msg: String
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl Error {
+    pub(crate) fn new(msg: String) -> Self {
+        Error { msg }
+    }
+}
+
+/// Priority of workspaces that are not listed in the config
+const DEFAULT_PRIORITY: u32 = 1;
+
+/// Permit to run a transfer, requested by a monitor before it syncs with a remote workspace
+struct Permit {
+    workspace_id: String,
+    /// Identifies the permit in the monitor that requested it
+    id: String,
+    remote_workspace: String,
+    priority: u32,
+    /// Bandwidth limit in KiB per second the permit was granted with
+    bwlimit_kbps: Option<u32>,
+    /// Used to deliver the grant, and to tell which monitor holds the permit
+    monitor: MonitorStdin
+}
+
+/// Hands out permits to run transfers to the monitors, so that the number of transfers running at
+/// once and the bandwidth they use are limited across all workspaces. Permits are granted to the
+/// workspace with the highest priority first and in the order they were requested otherwise.
+///
+/// Each transfer is granted a share of the aggregate bandwidth that is still available, so that
+/// the bandwidth limits of the running transfers never add up to more than the aggregate
+/// bandwidth. Transfers wait for a permit while the aggregate bandwidth is used up.
+pub(crate) struct SyncPermits {
+    max_transfers: Option<usize>,
+    /// Aggregate bandwidth in KiB per second shared by all running transfers
+    total_bwlimit_kbps: Option<u32>,
+    priorities: HashMap<String, u32>,
+    waiting: Vec<Permit>,
+    granted: Vec<Permit>
+}
+
+pub(crate) type SharedSyncPermits = Arc<Mutex<SyncPermits>>;
+
+impl SyncPermits {
+
+    /// Create the coordinator from the config, which is `None` if the config doesn't limit the
+    /// transfers, in which case the monitors sync without asking for permits
+    pub(crate) fn init() -> Result<Option<SharedSyncPermits>> {
+        let max_transfers = config()
+            .get_parsed::<usize>(ConfigKey::MaxConcurrentTransfers)
+            .map_err(|e| Error::new(format!("{e}")))?;
+        let total_bwlimit_kbps = config()
+            .get_parsed::<u32>(ConfigKey::TotalBandwidthKbps)
+            .map_err(|e| Error::new(format!("{e}")))?;
+
+        if max_transfers == Some(0) || total_bwlimit_kbps == Some(0) {
+            return Err(Error::new("The transfer limits in the config must be greater than 0".to_string()));
+        }
+
+        let priorities = match config().get_string(ConfigKey::WorkspacePriorities) {
+            Some(priorities) => parse_priorities(priorities)?,
+            None => HashMap::new()
+        };
+
+        if max_transfers.is_none() && total_bwlimit_kbps.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Arc::new(Mutex::new(
+            SyncPermits { max_transfers, total_bwlimit_kbps, priorities, waiting: vec![], granted: vec![] }
+        ))))
+    }
+
+    /// Queue a permit requested by the monitor of the given workspace, which is granted right away
+    /// if the limits allow it
+    pub(crate) fn request(&mut self, workspace_id: &str, id: String, remote_workspace: String, monitor: MonitorStdin) {
+        let priority = self.priorities.get(workspace_id).copied().unwrap_or(DEFAULT_PRIORITY);
+
+        debug!("Monitor of '{workspace_id}' requested permit '{id}' to sync with '{remote_workspace}'");
+        self.waiting.push(Permit {
+            workspace_id: workspace_id.to_string(),
+            id,
+            remote_workspace,
+            priority,
+            bwlimit_kbps: None,
+            monitor
+        });
+        self.grant_waiting();
+    }
+
+    /// Return a permit once the monitor's transfer finished, handing it to the next one waiting
+    pub(crate) fn release(&mut self, monitor: &MonitorStdin, id: &str) {
+        self.granted.retain(|permit| !(Arc::ptr_eq(&permit.monitor, monitor) && permit.id == id));
+        self.grant_waiting();
+    }
+
+    /// Drop the permits of a monitor that stopped, as it will never release them
+    pub(crate) fn monitor_stopped(&mut self, monitor: &MonitorStdin) {
+        self.waiting.retain(|permit| !Arc::ptr_eq(&permit.monitor, monitor));
+        self.granted.retain(|permit| !Arc::ptr_eq(&permit.monitor, monitor));
+        self.grant_waiting();
+    }
+
+    fn grant_waiting(&mut self) {
+        while self.max_transfers.is_none_or(|max_transfers| self.granted.len() < max_transfers) {
+            // Ties are resolved in favor of the earliest request, as `max_by_key` returns the last maximum
+            let next = self.waiting
+                .iter()
+                .enumerate()
+                .rev()
+                .max_by_key(|(_, permit)| permit.priority)
+                .map(|(idx, _)| idx);
+
+            let Some(next) = next else {
+                break;
+            };
+
+            let mut permit = self.waiting.remove(next);
+
+            if let Some(total_bwlimit_kbps) = self.total_bwlimit_kbps {
+                match self.bwlimit_share(permit.priority, total_bwlimit_kbps) {
+                    Some(bwlimit_kbps) => permit.bwlimit_kbps = Some(bwlimit_kbps),
+                    None => {
+                        debug!("Aggregate bandwidth is used up, permit '{}' has to wait", permit.id);
+                        self.waiting.insert(next, permit);
+                        break;
+                    }
+                }
+            }
+
+            let command = MonitorCommand::PermitGranted { id: permit.id.clone(), bwlimit_kbps: permit.bwlimit_kbps };
+
+            match write_command(&permit.monitor, &command) {
+                Ok(()) => {
+                    debug!(
+                        "Granted permit '{}' to sync '{}' with '{}': {:?}",
+                        permit.id, permit.workspace_id, permit.remote_workspace, command
+                    );
+                    self.granted.push(permit);
+                },
+                Err(e) => warn!("Unable to grant permit to the monitor of '{}': {e}", permit.workspace_id)
+            }
+        }
+    }
+
+    /// Share of the available bandwidth of a transfer with the given priority that is granted next,
+    /// or `None` if the running transfers use up the aggregate bandwidth. The available bandwidth
+    /// is shared with the waiting transfers that can be granted along with it and, if the number
+    /// of transfers is limited, held back for the remaining ones at the default priority. Shares
+    /// are not adjusted once the transfers are running.
+    fn bwlimit_share(&self, priority: u32, total_bwlimit_kbps: u32) -> Option<u32> {
+        let granted_kbps: u64 = self.granted
+            .iter()
+            .filter_map(|permit| permit.bwlimit_kbps)
+            .map(u64::from)
+            .sum();
+        let available_kbps = u64::from(total_bwlimit_kbps).saturating_sub(granted_kbps);
+
+        // Transfers that may start next to this one
+        let other_transfers = self.max_transfers
+            .map(|max_transfers| max_transfers.saturating_sub(self.granted.len() + 1))
+            .unwrap_or(self.waiting.len());
+
+        let mut waiting_priorities: Vec<u32> = self.waiting.iter().map(|permit| permit.priority).collect();
+        waiting_priorities.sort_unstable_by(|a, b| b.cmp(a));
+        waiting_priorities.resize(other_transfers, DEFAULT_PRIORITY);
+
+        share_bandwidth(available_kbps, priority, &waiting_priorities)
+    }
+}
+
+/// Share of the available bandwidth of a transfer with the given priority, which is shared with
+/// transfers of the other priorities in proportion to their priority. `None` if no bandwidth is
+/// available.
+fn share_bandwidth(available_kbps: u64, priority: u32, other_priorities: &[u32]) -> Option<u32> {
+    if available_kbps == 0 {
+        return None;
+    }
+
+    let total_priority = other_priorities.iter().copied().map(u64::from).sum::<u64>() + u64::from(priority);
+    let share = available_kbps * u64::from(priority) / total_priority;
+
+    Some(share.max(1) as u32)
+}
+
+/// Parse priorities given as `WORKSPACE:PRIORITY` pairs separated by commas, e.g. `docs:1,code:4`
+fn parse_priorities(priorities: &str) -> Result<HashMap<String, u32>> {
+    priorities
+        .split(',')
+        .map(|entry| {
+            let parsed = entry
+                .split_once(':')
+                .and_then(|(workspace, priority)| Some((workspace.trim(), priority.trim().parse::<u32>().ok()?)))
+                .filter(|(workspace, priority)| !workspace.is_empty() && *priority > 0);
+
+            match parsed {
+                Some((workspace, priority)) => Ok((workspace.to_string(), priority)),
+                None => Err(Error::new(format!(
+                    "Workspace priority '{entry}' does not conform to the format 'WORKSPACE:PRIORITY' with a \
+                    priority greater than 0"
+                )))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_are_parsed() {
+        let priorities = parse_priorities("docs:1, code : 4").ok();
+        let expected = HashMap::from([("docs".to_string(), 1), ("code".to_string(), 4)]);
+
+        assert_eq!(priorities, Some(expected));
+    }
+
+    #[test]
+    fn malformed_priorities_are_rejected() {
+        for priorities in ["docs", "docs:", ":1", "docs:0", "docs:-1", "docs:high", "docs:1,", "docs:1;code:2"] {
+            assert!(parse_priorities(priorities).is_err(), "{priorities}");
+        }
+    }
+
+    #[test]
+    fn bandwidth_is_shared_by_priority() {
+        assert_eq!(share_bandwidth(1000, 1, &[]), Some(1000));
+        assert_eq!(share_bandwidth(1000, 1, &[1]), Some(500));
+        assert_eq!(share_bandwidth(1000, 3, &[1]), Some(750));
+        assert_eq!(share_bandwidth(1000, 1, &[2, 1]), Some(250));
+    }
+
+    #[test]
+    fn used_up_bandwidth_is_not_shared() {
+        assert_eq!(share_bandwidth(0, 1, &[]), None);
+        assert_eq!(share_bandwidth(1, 1, &[4, 4]), Some(1));
+    }
+
+    #[test]
+    fn shares_never_exceed_the_available_bandwidth() {
+        let mut available_kbps = 1000;
+        let mut granted_kbps = 0;
+
+        while let Some(share) = share_bandwidth(available_kbps, 1, &[1, 1]) {
+            available_kbps -= u64::from(share);
+            granted_kbps += u64::from(share);
+        }
+
+        assert_eq!(granted_kbps, 1000);
+    }
+}
@@ -111,6 +111,62 @@ impl WorkspaceConfiguration {
         Ok(())
     }
 
+    /// Replace the attached remote workspace of the same name
+    pub(crate) fn update_remote_workspace(
+        &mut self,
+        workspace_id: String,
+        remote_workspace: RemoteWorkspace
+    ) -> Result<()> {
+        let entry = self.find_by_name_mut(&workspace_id).ok_or(
+            Error::Message(format!("No local workspace named '{}' exists", workspace_id))
+        )?;
+
+        let Some(attached) = entry.remote_workspaces.iter_mut().find(|rw| rw.name == remote_workspace.name) else {
+            return Err(Error::Message(
+                format!(
+                    "No remote workspace named '{}' is attached to the local workspace '{}'",
+                    remote_workspace.name,
+                    workspace_id
+                )
+            ));
+        };
+
+        *attached = remote_workspace;
+        self.write_file()?;
+        Ok(())
+    }
+
+    /// Pause or resume the syncs of the workspace, or only those with the given remote workspace
+    pub(crate) fn set_paused(
+        &mut self,
+        workspace_id: String,
+        remote_workspace_id: Option<String>,
+        paused: bool
+    ) -> Result<()> {
+        let entry = self.find_by_name_mut(&workspace_id).ok_or(
+            Error::Message(format!("No local workspace named '{}' exists", workspace_id))
+        )?;
+
+        match remote_workspace_id {
+            Some(remote_workspace_id) => {
+                let Some(rw) = entry.remote_workspaces.iter_mut().find(|rw| rw.name == remote_workspace_id) else {
+                    return Err(Error::Message(
+                        format!(
+                            "No remote workspace named '{}' is attached to the local workspace '{}'",
+                            remote_workspace_id,
+                            workspace_id
+                        )
+                    ));
+                };
+                rw.paused = paused;
+            },
+            None => entry.paused = paused
+        }
+
+        self.write_file()?;
+        Ok(())
+    }
+
     pub(crate) fn detach_remote_workspace(
         &mut self,
         workspace_id: String,
@@ -0,0 +1,77 @@
+use std::io::{BufReader, Stdin};
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::thread;
+use log::{debug, warn};
+use serde::Deserialize;
+use serde_json::Deserializer;
+use serde_json::de::IoRead;
+use crate::permits;
+use crate::sync_loop::WatcherEvent;
+
+/// Commands sent by the daemon to a running monitor, each as a JSON value on stdin following the
+/// workspace information
+#[derive(Deserialize, Debug)]
+pub(crate) enum ControlCommand {
+    /// Approve or reject the held deletions of the given remote workspace, or of all remote
+    /// workspaces if none is given
+    ResolveDeletions {
+        remote_workspace: Option<String>,
+        approve: bool
+    },
+    /// Pause or resume the syncs with the given remote workspace, or those of the whole workspace
+    /// if none is given
+    SetPaused {
+        remote_workspace: Option<String>,
+        paused: bool
+    },
+    /// Sync a resource with remote workspaces right away, regardless of whether it changed
+    TriggerSync(SyncTrigger),
+    /// Permit to run the transfer that requested it, limited to the given bandwidth
+    PermitGranted {
+        id: String,
+        bwlimit_kbps: Option<u32>
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct SyncTrigger {
+    /// Identifies the sync in the event reporting its outcome
+    pub(crate) id: String,
+    /// Remote workspace to sync with, all remote workspaces being synced if none is given
+    pub(crate) remote_workspace: Option<String>,
+    /// Workspace-relative path of the resource, `None` referring to the workspace root
+    pub(crate) path: Option<PathBuf>
+}
+
+pub(crate) type CommandReader = Deserializer<IoRead<BufReader<Stdin>>>;
+
+/// Forward the commands received from the daemon to the sync loop, until stdin is closed. Permits
+/// are handed to the waiting transfers directly, as the sync loop waits for the transfers to finish.
+pub(crate) fn spawn_command_forwarder(reader: CommandReader, tx: Sender<WatcherEvent>) {
+    thread::spawn(move || {
+        for command in reader.into_iter::<ControlCommand>() {
+            let command = match command {
+                Ok(command) => command,
+                Err(e) => {
+                    warn!("Unable to read command from the daemon: {e}");
+                    break;
+                }
+            };
+
+            debug!("Received command from the daemon: {:?}", command);
+            if let ControlCommand::PermitGranted { id, bwlimit_kbps } = command {
+                permits::grant(&id, bwlimit_kbps);
+                continue;
+            }
+
+            if tx.send(WatcherEvent::Command(command)).is_err() {
+                break;
+            }
+        }
+
+        // No permits will be granted anymore
+        permits::disable();
+        debug!("Stopped reading commands from the daemon");
+    });
+}
@@ -0,0 +1,360 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::models::DebounceSettings;
+use crate::sync::{PathMove, SyncTargets};
+
+/// Set of workspace-relative paths that need to be synced, where a path implicitly covers its
+/// entire subtree. Adding a path whose ancestor is already contained is a no-op, while adding a
+/// path removes all of its contained descendants.
+#[derive(Default)]
+pub(crate) struct DirtySet {
+    pub(self) root_dirty: bool,
+    pub(self) paths: BTreeSet<PathBuf>
+}
+
+impl DirtySet {
+
+    pub(crate) fn insert(&mut self, relative_path: Option<PathBuf>) {
+        if self.root_dirty {
+            return;
+        }
+
+        let path = match relative_path {
+            Some(path) => path,
+            None => {
+                self.root_dirty = true;
+                self.paths.clear();
+                return;
+            }
+        };
+
+        if path.ancestors().any(|ancestor| self.paths.contains(ancestor)) {
+            return;
+        }
+
+        // Paths are ordered component-wise, so all descendants of 'path' directly follow it.
+        let descendants: Vec<PathBuf> = self.paths
+            .range(path.clone()..)
+            .take_while(|entry| entry.starts_with(&path))
+            .cloned()
+            .collect();
+
+        for descendant in descendants.iter() {
+            self.paths.remove(descendant);
+        }
+
+        self.paths.insert(path);
+    }
+
+    /// Whether the given path is contained, either itself or by one of its ancestors
+    pub(crate) fn covers(&self, path: &Path) -> bool {
+        self.root_dirty || path.ancestors().any(|ancestor| self.paths.contains(ancestor))
+    }
+
+    /// Replace the contained paths within the moved resource by their paths after the move
+    pub(crate) fn apply_move(&mut self, path_move: &PathMove) {
+        if self.root_dirty {
+            return;
+        }
+
+        let moved: Vec<PathBuf> = self.paths
+            .range(path_move.from.clone()..)
+            .take_while(|entry| entry.starts_with(&path_move.from))
+            .cloned()
+            .collect();
+
+        for path in moved {
+            self.paths.remove(&path);
+            self.insert(path_move.moved_path(&path));
+        }
+    }
+
+    /// Return all contained paths. `None` represents the workspace root.
+    pub(crate) fn entries(&self) -> Vec<Option<PathBuf>> {
+        if self.root_dirty {
+            return vec![None];
+        }
+
+        self.paths.iter().cloned().map(Some).collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        if self.root_dirty { 1 } else { self.paths.len() }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove and return all contained paths. `None` represents the workspace root.
+    pub(crate) fn take(&mut self) -> Vec<Option<PathBuf>> {
+        let entries = self.entries();
+        self.root_dirty = false;
+        self.paths.clear();
+        entries
+    }
+}
+
+/// Changes that are yet to be synced with a remote workspace. Moves are synced before all other
+/// changes, whose paths therefore refer to the resources after the moves.
+#[derive(Default)]
+pub(crate) struct DirtyTargets {
+    pub(self) dirty: DirtySet,
+    pub(self) dirty_files: BTreeSet<PathBuf>,
+    pub(self) moves: Vec<PathMove>
+}
+
+impl DirtyTargets {
+
+    /// Record a change of the directory tree at the given path
+    pub(crate) fn record(&mut self, relative_path: Option<PathBuf>) {
+        self.dirty.insert(relative_path);
+    }
+
+    /// Record a change of the file at the given path
+    pub(crate) fn record_file(&mut self, relative_path: PathBuf) {
+        self.dirty_files.insert(relative_path);
+    }
+
+    /// Record that a resource was moved, which is synced by moving it in the remote workspaces.
+    /// Changes already collected within the moved resource are synced at its new path. A resource
+    /// that changed itself (or whose ancestor did) since the changes were last taken might differ
+    /// from the one in the remote workspaces or not exist there at all, so it is synced instead.
+    pub(crate) fn record_move(&mut self, path_move: PathMove) {
+        if self.dirty_files.contains(&path_move.from) || self.dirty.covers(&path_move.from) {
+            let fallback = path_move.fallback();
+
+            for directory in fallback.directories {
+                self.dirty.insert(directory);
+            }
+            self.dirty_files.extend(fallback.files);
+        } else {
+            self.dirty.apply_move(&path_move);
+            self.dirty_files = std::mem::take(&mut self.dirty_files)
+                .into_iter()
+                .map(|file| path_move.moved_path(&file).unwrap_or(file))
+                .collect();
+            self.moves.push(path_move);
+        }
+    }
+
+    /// Record targets that were collected after the contained changes. Their moves are recorded
+    /// first, since the paths of their other changes already refer to the moved resources.
+    pub(crate) fn add(&mut self, targets: SyncTargets) {
+        for path_move in targets.moves {
+            self.record_move(path_move);
+        }
+
+        for directory in targets.directories {
+            self.record(directory);
+        }
+
+        self.dirty_files.extend(targets.files);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.dirty.is_empty() && self.dirty_files.is_empty() && self.moves.is_empty()
+    }
+
+    /// Remove and return the collected changes. Files within a changed directory tree are not
+    /// returned separately, as they are synced along with it. Moves are returned in the order they
+    /// were made.
+    pub(crate) fn take(&mut self) -> SyncTargets {
+        let directories = self.dirty.take();
+        let files = std::mem::take(&mut self.dirty_files)
+            .into_iter()
+            .filter(|file| !directories.iter().any(|directory| match directory {
+                Some(directory) => file.starts_with(directory),
+                None => true
+            }))
+            .collect();
+
+        SyncTargets { directories, files, moves: std::mem::take(&mut self.moves) }
+    }
+}
+
+/// Collects the paths affected by filesystem events and decides when they should be flushed,
+/// i.e. synced with the remote workspaces.
+pub(crate) struct Debouncer {
+    pub(self) quiet_window: Duration,
+    pub(self) max_latency: Duration,
+    pub(self) targets: DirtyTargets,
+    pub(self) first_change: Option<Instant>,
+    pub(self) last_change: Option<Instant>
+}
+
+impl Debouncer {
+
+    pub(crate) fn new(settings: &DebounceSettings) -> Self {
+        Debouncer {
+            quiet_window: Duration::from_millis(settings.quiet_window_ms),
+            max_latency: Duration::from_millis(settings.max_latency_ms),
+            targets: DirtyTargets::default(),
+            first_change: None,
+            last_change: None
+        }
+    }
+
+    /// Record a change of the directory tree at the given path
+    pub(crate) fn record(&mut self, relative_path: Option<PathBuf>, now: Instant) {
+        self.targets.record(relative_path);
+        self.record_change_time(now);
+    }
+
+    /// Record a change of the file at the given path
+    pub(crate) fn record_file(&mut self, relative_path: PathBuf, now: Instant) {
+        self.targets.record_file(relative_path);
+        self.record_change_time(now);
+    }
+
+    /// Record that a resource was moved, see [DirtyTargets::record_move]
+    pub(crate) fn record_move(&mut self, path_move: PathMove, now: Instant) {
+        self.targets.record_move(path_move);
+        self.record_change_time(now);
+    }
+
+    fn record_change_time(&mut self, now: Instant) {
+        self.first_change.get_or_insert(now);
+        self.last_change = Some(now);
+    }
+
+    /// Point in time at which the collected changes must be flushed, or `None` if there is nothing
+    /// to flush.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        match (self.first_change, self.last_change) {
+            (Some(first), Some(last)) => {
+                Some((last + self.quiet_window).min(first + self.max_latency))
+            },
+            _ => None
+        }
+    }
+
+    pub(crate) fn is_due(&self, now: Instant) -> bool {
+        self.deadline().is_some_and(|deadline| deadline <= now)
+    }
+
+    /// Remove and return the collected changes, see [DirtyTargets::take]
+    pub(crate) fn take(&mut self) -> SyncTargets {
+        self.first_change = None;
+        self.last_change = None;
+
+        self.targets.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> Option<PathBuf> {
+        Some(PathBuf::from(path))
+    }
+
+    fn path_move(from: &str, to: &str, is_dir: bool) -> PathMove {
+        PathMove { from: PathBuf::from(from), to: PathBuf::from(to), is_dir }
+    }
+
+    #[test]
+    fn dirty_set_keeps_topmost_paths() {
+        let mut dirty = DirtySet::default();
+        dirty.insert(path("a/b/c"));
+        dirty.insert(path("a/d"));
+        dirty.insert(path("a/b"));
+        dirty.insert(path("a/b/e"));
+        dirty.insert(path("ab"));
+
+        assert_eq!(dirty.entries(), [path("a/b"), path("a/d"), path("ab")]);
+        assert!(dirty.covers(Path::new("a/b/c/d")));
+        assert!(!dirty.covers(Path::new("a/c")));
+
+        dirty.insert(None);
+        dirty.insert(path("a"));
+        assert_eq!(dirty.take(), [None]);
+        assert!(dirty.is_empty());
+    }
+
+    #[test]
+    fn dirty_set_follows_moves() {
+        let mut dirty = DirtySet::default();
+        dirty.insert(path("a/b"));
+        dirty.insert(path("ab"));
+        dirty.insert(path("c/d"));
+
+        dirty.apply_move(&path_move("a", "c", true));
+
+        assert_eq!(dirty.entries(), [path("ab"), path("c/b"), path("c/d")]);
+    }
+
+    #[test]
+    fn files_within_dirty_directories_are_not_taken() {
+        let mut targets = DirtyTargets::default();
+        targets.record(path("a"));
+        targets.record_file(PathBuf::from("a/file"));
+        targets.record_file(PathBuf::from("b/file"));
+
+        let taken = targets.take();
+        assert_eq!(taken.directories, [path("a")]);
+        assert_eq!(taken.files, [PathBuf::from("b/file")]);
+        assert!(targets.is_empty());
+    }
+
+    #[test]
+    fn changes_are_taken_at_their_moved_paths() {
+        let mut targets = DirtyTargets::default();
+        targets.record(path("old/dir"));
+        targets.record_file(PathBuf::from("old/file"));
+        targets.record_file(PathBuf::from("other"));
+        targets.record_move(path_move("old", "new", true));
+
+        let taken = targets.take();
+        assert_eq!(taken.directories, [path("new/dir")]);
+        assert_eq!(taken.files, [PathBuf::from("new/file"), PathBuf::from("other")]);
+        assert_eq!(taken.moves.len(), 1);
+        assert_eq!((taken.moves[0].from.as_path(), taken.moves[0].to.as_path()), (Path::new("old"), Path::new("new")));
+    }
+
+    #[test]
+    fn changed_resources_are_synced_instead_of_moved() {
+        let mut targets = DirtyTargets::default();
+        targets.record_file(PathBuf::from("file"));
+        targets.record_move(path_move("file", "dir/file", false));
+
+        targets.record(path("changed"));
+        targets.record_move(path_move("changed/sub", "moved/sub", true));
+
+        let taken = targets.take();
+        assert!(taken.moves.is_empty());
+        assert_eq!(taken.directories, [path("changed"), path("moved")]);
+        assert_eq!(taken.files, [PathBuf::from("dir/file"), PathBuf::from("file")]);
+    }
+
+    #[test]
+    fn debouncer_flushes_after_quiet_window() {
+        let mut debouncer = Debouncer::new(&DebounceSettings { quiet_window_ms: 100, max_latency_ms: 1000 });
+        let start = Instant::now();
+        assert_eq!(debouncer.deadline(), None);
+
+        debouncer.record_file(PathBuf::from("file"), start);
+        debouncer.record_file(PathBuf::from("file"), start + Duration::from_millis(50));
+
+        assert_eq!(debouncer.deadline(), Some(start + Duration::from_millis(150)));
+        assert!(!debouncer.is_due(start + Duration::from_millis(149)));
+        assert!(debouncer.is_due(start + Duration::from_millis(150)));
+
+        assert_eq!(debouncer.take().files, [PathBuf::from("file")]);
+        assert_eq!(debouncer.deadline(), None);
+    }
+
+    #[test]
+    fn debouncer_flushes_continuous_changes_after_max_latency() {
+        let mut debouncer = Debouncer::new(&DebounceSettings { quiet_window_ms: 100, max_latency_ms: 250 });
+        let start = Instant::now();
+
+        for offset_ms in (0..300).step_by(50) {
+            debouncer.record(None, start + Duration::from_millis(offset_ms));
+        }
+
+        assert_eq!(debouncer.deadline(), Some(start + Duration::from_millis(250)));
+    }
+}
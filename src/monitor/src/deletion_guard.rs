@@ -0,0 +1,530 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use crate::debounce::DirtySet;
+use crate::events::{emit, unix_timestamp, MonitorEvent};
+use crate::ignore_rules::IgnoreRules;
+use crate::models::{DeletionGuardSettings, WorkspaceInfo};
+use crate::sync::SyncTargets;
+use crate::util::fs::escape_file_name;
+use crate::util::state_directory;
+
+/// Targets that are held back from a remote workspace until the deletions are resolved
+struct HeldSync {
+    directories: DirtySet,
+    files: BTreeSet<PathBuf>,
+    nr_of_deletions: usize,
+    /// Seconds since the unix epoch
+    held_since: u64
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedHeldSync {
+    directories: Vec<Option<PathBuf>>,
+    files: Vec<PathBuf>,
+    nr_of_deletions: usize,
+    held_since: u64
+}
+
+impl HeldSync {
+
+    /// Moves are held as the targets syncing them instead, as held targets are collected regardless
+    /// of their order
+    fn add(&mut self, targets: &SyncTargets) {
+        let targets = targets.without_moves();
+
+        for directory in targets.directories.iter() {
+            self.directories.insert(directory.clone());
+        }
+        self.files.extend(targets.files.iter().cloned());
+    }
+
+    /// Files within a held directory tree are not returned separately, as they are synced along
+    /// with it
+    fn to_targets(&self) -> SyncTargets {
+        let directories = self.directories.entries();
+        let files = self.files
+            .iter()
+            .filter(|file| !directories.iter().any(|directory| match directory {
+                Some(directory) => file.starts_with(directory),
+                None => true
+            }))
+            .cloned()
+            .collect();
+
+        SyncTargets { directories, files, moves: vec![] }
+    }
+}
+
+/// Holds syncs that would delete more files than allowed by the workspace's deletion guard, e.g.
+/// after a mistaken `rm -rf`, until the deletions are approved or rejected via the daemon. Once a
+/// sync is held for a remote workspace, all further targets are held as well, as syncing them
+/// might propagate the deletions. Only remote workspaces that delete resources are held.
+///
+/// Deletions are detected by comparing the targets against the files the workspace contained as of
+/// the previous sync. Held syncs and the known files are persisted in the state directory, so that
+/// held syncs, as well as deletions made while the monitor was not running, are not performed by
+/// the initial sync once the monitor is restarted.
+pub(crate) struct DeletionGuard {
+    settings: Option<DeletionGuardSettings>,
+    ws_root_path: PathBuf,
+    /// Workspace-relative paths of the non-directory resources that are not ignored
+    known_files: BTreeSet<PathBuf>,
+    held: BTreeMap<String, HeldSync>,
+    state_path: Option<PathBuf>,
+    known_files_path: Option<PathBuf>
+}
+
+impl DeletionGuard {
+
+    pub(crate) fn open(ws_info: &WorkspaceInfo, ignore_rules: &IgnoreRules) -> Self {
+        let mut guard = DeletionGuard {
+            settings: ws_info.deletion_guard.clone(),
+            ws_root_path: ws_info.local_path.clone(),
+            known_files: BTreeSet::new(),
+            held: BTreeMap::new(),
+            state_path: None,
+            known_files_path: None
+        };
+
+        if guard.settings.is_none() {
+            return guard;
+        }
+
+        if let Err(e) = record_files(&guard.ws_root_path, Path::new(""), ignore_rules, &mut guard.known_files) {
+            warn!("Unable to determine the files of workspace '{}': {e}", ws_info.name);
+        }
+
+        match state_directory() {
+            Ok(state_directory) => {
+                let directory = state_directory.join("deletion-guard");
+                let file_name = escape_file_name(&ws_info.name);
+
+                guard.state_path = Some(directory.join(&file_name).with_extension("json"));
+                guard.known_files_path = Some(directory.join("known-files").join(&file_name).with_extension("json"));
+            },
+            Err(e) => warn!("Held syncs of workspace '{}' will not be persisted: {e}", ws_info.name)
+        }
+
+        // Changes made while the monitor was not running are only known to be somewhere in the
+        // workspace
+        for (remote_workspace, held_sync) in guard.load() {
+            let is_deleting = ws_info.remote_workspaces
+                .iter()
+                .any(|remote| remote.name == remote_workspace && remote.sync_options.delete);
+
+            if is_deleting {
+                let mut directories = DirtySet::default();
+                directories.insert(None);
+
+                info!("Syncs with '{remote_workspace}' are still held until the deletions are resolved");
+                emit(MonitorEvent::DeletionsHeld {
+                    remote_workspace: remote_workspace.clone(),
+                    nr_of_deletions: held_sync.nr_of_deletions,
+                    held_since: held_sync.held_since
+                });
+
+                guard.held.insert(remote_workspace, HeldSync {
+                    directories,
+                    files: BTreeSet::new(),
+                    nr_of_deletions: held_sync.nr_of_deletions,
+                    held_since: held_sync.held_since
+                });
+            }
+        }
+
+        guard.hold_offline_deletions(ws_info);
+
+        guard.persist();
+        guard.persist_known_files();
+        guard
+    }
+
+    pub(crate) fn is_held(&self, remote_workspace: &str) -> bool {
+        self.held.contains_key(remote_workspace)
+    }
+
+    /// Check whether the given targets, which are about to be synced, delete more files than
+    /// allowed, in which case the sync is held for all remote workspaces that delete resources.
+    /// The targets are added to the held syncs.
+    pub(crate) fn check(&mut self, targets: &SyncTargets, ws_info: &WorkspaceInfo, ignore_rules: &IgnoreRules) {
+        let Some(settings) = self.settings.clone() else {
+            return;
+        };
+
+        if targets.is_empty() {
+            return;
+        }
+
+        let nr_of_files = self.known_files.len();
+        let nr_of_deletions = self.detect_deletions(targets, ignore_rules);
+
+        self.persist_known_files();
+
+        if exceeds_thresholds(&settings, nr_of_deletions, nr_of_files) {
+            warn!(
+                "Holding sync, as it would delete #{nr_of_deletions} of #{nr_of_files} file(s) of workspace '{}'",
+                ws_info.name
+            );
+
+            let now = unix_timestamp();
+            for remote_workspace in ws_info.remote_workspaces.iter().filter(|remote| remote.sync_options.delete) {
+                self.held.entry(remote_workspace.name.clone()).or_insert_with(|| HeldSync {
+                    directories: DirtySet::default(),
+                    files: BTreeSet::new(),
+                    nr_of_deletions: 0,
+                    held_since: now
+                });
+            }
+        }
+
+        if self.held.is_empty() {
+            return;
+        }
+
+        for (remote_workspace, held_sync) in self.held.iter_mut() {
+            held_sync.add(targets);
+
+            if nr_of_deletions > 0 {
+                held_sync.nr_of_deletions += nr_of_deletions;
+                emit(MonitorEvent::DeletionsHeld {
+                    remote_workspace: remote_workspace.clone(),
+                    nr_of_deletions: held_sync.nr_of_deletions,
+                    held_since: held_sync.held_since
+                });
+            }
+        }
+
+        self.persist();
+    }
+
+    /// Hold the syncs with all remote workspaces that delete resources if more files were deleted
+    /// while the monitor was not running than allowed, as the initial sync would propagate the
+    /// deletions. The known files of the previous run are compared against the workspace's current
+    /// files, so files moved in the meantime count as deleted as well.
+    fn hold_offline_deletions(&mut self, ws_info: &WorkspaceInfo) {
+        let Some(settings) = &self.settings else {
+            return;
+        };
+
+        let Some(previous_files) = self.load_known_files() else {
+            return;
+        };
+
+        let nr_of_files = previous_files.len();
+        let nr_of_deletions = count_deleted_files(&self.ws_root_path, &previous_files);
+
+        if !exceeds_thresholds(settings, nr_of_deletions, nr_of_files) {
+            return;
+        }
+
+        warn!(
+            "Holding initial sync, as #{nr_of_deletions} of #{nr_of_files} file(s) of workspace '{}' were deleted \
+            while it was not monitored",
+            ws_info.name
+        );
+
+        let now = unix_timestamp();
+        for remote_workspace in ws_info.remote_workspaces.iter().filter(|remote| remote.sync_options.delete) {
+            let held_sync = self.held.entry(remote_workspace.name.clone()).or_insert_with(|| {
+                let mut directories = DirtySet::default();
+                directories.insert(None);
+
+                HeldSync { directories, files: BTreeSet::new(), nr_of_deletions: 0, held_since: now }
+            });
+
+            held_sync.nr_of_deletions += nr_of_deletions;
+            emit(MonitorEvent::DeletionsHeld {
+                remote_workspace: remote_workspace.name.clone(),
+                nr_of_deletions: held_sync.nr_of_deletions,
+                held_since: held_sync.held_since
+            });
+        }
+    }
+
+    /// Targets held for the given remote workspace, or for all remote workspaces if none is given
+    pub(crate) fn held_targets(&self, remote_workspace: Option<&str>) -> Vec<(String, SyncTargets)> {
+        self.held
+            .iter()
+            .filter(|(name, _)| remote_workspace.is_none_or(|remote_workspace| *name == remote_workspace))
+            .map(|(name, held_sync)| (name.clone(), held_sync.to_targets()))
+            .collect()
+    }
+
+    /// Stop holding syncs with a remote workspace, once its deletions were approved or its deleted
+    /// files were restored
+    pub(crate) fn release(&mut self, remote_workspace: &str, approved: bool) {
+        if self.held.remove(remote_workspace).is_none() {
+            return;
+        }
+
+        info!(
+            "Deletions for '{remote_workspace}' were {}, resuming syncs",
+            if approved { "approved" } else { "rejected" }
+        );
+        emit(MonitorEvent::DeletionsReleased { remote_workspace: remote_workspace.to_string(), approved });
+
+        self.persist();
+    }
+
+    /// Update the known files based on the given targets, returning the number of known files that
+    /// no longer exist. Moved files are known at their new path, rather than being deleted.
+    fn detect_deletions(&mut self, targets: &SyncTargets, ignore_rules: &IgnoreRules) -> usize {
+        let mut nr_of_deletions = 0;
+
+        for path_move in targets.moves.iter() {
+            let moved: Vec<PathBuf> = self.known_files
+                .range(path_move.from.clone()..)
+                .take_while(|path| path.starts_with(&path_move.from))
+                .cloned()
+                .collect();
+
+            for path in moved {
+                self.known_files.remove(&path);
+                self.known_files.extend(path_move.moved_path(&path));
+            }
+        }
+
+        for directory in targets.directories.iter() {
+            let directory = directory.clone().unwrap_or_default();
+
+            // Paths are ordered component-wise, so all descendants of 'directory' directly follow it
+            let contained: Vec<PathBuf> = self.known_files
+                .range(directory.clone()..)
+                .take_while(|path| path.starts_with(&directory))
+                .cloned()
+                .collect();
+
+            for path in contained {
+                if fs::symlink_metadata(self.ws_root_path.join(&path)).is_err() {
+                    self.known_files.remove(&path);
+                    nr_of_deletions += 1;
+                }
+            }
+
+            if let Err(e) = record_files(&self.ws_root_path, &directory, ignore_rules, &mut self.known_files) {
+                debug!("Unable to determine the files in '{:?}': {e}", directory);
+            }
+        }
+
+        for file in targets.files.iter() {
+            match fs::symlink_metadata(self.ws_root_path.join(file)) {
+                Ok(metadata) => {
+                    if !metadata.is_dir() && !ignore_rules.is_ignored(file, false) {
+                        self.known_files.insert(file.clone());
+                    }
+                },
+                Err(_) => {
+                    if self.known_files.remove(file) {
+                        nr_of_deletions += 1;
+                    }
+                }
+            }
+        }
+
+        nr_of_deletions
+    }
+
+    fn load(&self) -> HashMap<String, PersistedHeldSync> {
+        let Some(path) = &self.state_path else {
+            return HashMap::new();
+        };
+
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != ErrorKind::NotFound {
+                    warn!("Unable to open held syncs '{:?}': {e}", path);
+                }
+                return HashMap::new();
+            }
+        };
+
+        serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|e| {
+            warn!("Ignoring malformed held syncs '{:?}': {e}", path);
+            HashMap::new()
+        })
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.state_path else {
+            return;
+        };
+
+        let result = if self.held.is_empty() {
+            fs::remove_file(path).or_else(|e| if e.kind() == ErrorKind::NotFound { Ok(()) } else { Err(e) })
+        } else {
+            let held: BTreeMap<&String, PersistedHeldSync> = self.held
+                .iter()
+                .map(|(remote_workspace, held_sync)| (remote_workspace, PersistedHeldSync {
+                    directories: held_sync.directories.entries(),
+                    files: held_sync.files.iter().cloned().collect(),
+                    nr_of_deletions: held_sync.nr_of_deletions,
+                    held_since: held_sync.held_since
+                }))
+                .collect();
+
+            write_json(path, &held)
+        };
+
+        if let Err(e) = result {
+            warn!("Unable to persist held syncs '{:?}': {e}", path);
+        }
+    }
+
+    /// Known files as of the previous run, or `None` if they were not persisted
+    fn load_known_files(&self) -> Option<BTreeSet<PathBuf>> {
+        let path = self.known_files_path.as_ref()?;
+
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != ErrorKind::NotFound {
+                    warn!("Unable to open known files '{:?}': {e}", path);
+                }
+                return None;
+            }
+        };
+
+        serde_json::from_reader(BufReader::new(file))
+            .inspect_err(|e| warn!("Ignoring malformed known files '{:?}': {e}", path))
+            .ok()
+    }
+
+    fn persist_known_files(&self) {
+        let Some(path) = &self.known_files_path else {
+            return;
+        };
+
+        if let Err(e) = write_json(path, &self.known_files) {
+            warn!("Unable to persist known files '{:?}': {e}", path);
+        }
+    }
+}
+
+fn exceeds_thresholds(settings: &DeletionGuardSettings, nr_of_deletions: usize, nr_of_files: usize) -> bool {
+    let exceeds_max_deletions = settings.max_deletions.is_some_and(|max| nr_of_deletions > max);
+    let exceeds_max_percent = settings.max_deletion_percent
+        .is_some_and(|max| nr_of_deletions * 100 > usize::from(max) * nr_of_files);
+
+    exceeds_max_deletions || exceeds_max_percent
+}
+
+/// Number of the given workspace-relative files that no longer exist
+fn count_deleted_files(ws_root_path: &Path, files: &BTreeSet<PathBuf>) -> usize {
+    files
+        .iter()
+        .filter(|file| fs::symlink_metadata(ws_root_path.join(file)).is_err())
+        .count()
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp_path = path.with_extension("json.tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+
+    serde_json::to_writer(&mut writer, value)?;
+    writer.flush()?;
+
+    fs::rename(&tmp_path, path)
+}
+
+/// Add the non-directory resources at or below the given path that are not ignored to `files`
+fn record_files(
+    ws_root_path: &Path,
+    relative_path: &Path,
+    ignore_rules: &IgnoreRules,
+    files: &mut BTreeSet<PathBuf>
+) -> std::io::Result<()> {
+    let path = ws_root_path.join(relative_path);
+
+    let metadata = match fs::symlink_metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e)
+    };
+
+    let is_root = relative_path.parent().is_none();
+    if !is_root && ignore_rules.is_ignored(relative_path, metadata.is_dir()) {
+        return Ok(());
+    }
+
+    if !metadata.is_dir() {
+        if !is_root {
+            files.insert(relative_path.to_path_buf());
+        }
+        return Ok(());
+    }
+
+    let entries = match fs::read_dir(&path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e)
+    };
+
+    for entry in entries {
+        record_files(ws_root_path, &relative_path.join(entry?.file_name()), ignore_rules, files)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(max_deletions: Option<usize>, max_deletion_percent: Option<u8>) -> DeletionGuardSettings {
+        DeletionGuardSettings { max_deletions, max_deletion_percent }
+    }
+
+    #[test]
+    fn thresholds_are_exceeded_strictly() {
+        assert!(!exceeds_thresholds(&settings(Some(2), None), 2, 10));
+        assert!(exceeds_thresholds(&settings(Some(2), None), 3, 10));
+
+        assert!(!exceeds_thresholds(&settings(None, Some(50)), 5, 10));
+        assert!(exceeds_thresholds(&settings(None, Some(50)), 6, 10));
+
+        assert!(exceeds_thresholds(&settings(Some(100), Some(10)), 2, 10));
+        assert!(!exceeds_thresholds(&settings(None, None), 10, 10));
+    }
+
+    #[test]
+    fn deleted_files_are_counted_against_the_persisted_listing() {
+        let dir = std::env::temp_dir().join(format!("wsync-deletion-guard-test-{}", std::process::id()));
+        let ws_root_path = dir.join("ws");
+        fs::create_dir_all(ws_root_path.join("sub")).unwrap();
+        fs::write(ws_root_path.join("kept"), b"").unwrap();
+        fs::write(ws_root_path.join("sub/kept"), b"").unwrap();
+
+        let known_files: BTreeSet<PathBuf> = ["kept", "deleted", "sub/kept", "sub/deleted", "gone/deleted"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+
+        let known_files_path = dir.join("known-files").join("ws.json");
+        write_json(&known_files_path, &known_files).unwrap();
+
+        let guard = DeletionGuard {
+            settings: Some(settings(Some(1), None)),
+            ws_root_path: ws_root_path.clone(),
+            known_files: BTreeSet::new(),
+            held: BTreeMap::new(),
+            state_path: None,
+            known_files_path: Some(known_files_path)
+        };
+        let previous_files = guard.load_known_files().unwrap();
+
+        assert_eq!(previous_files, known_files);
+        assert_eq!(count_deleted_files(&ws_root_path, &previous_files), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
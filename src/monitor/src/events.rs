@@ -0,0 +1,95 @@
+use std::io;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::warn;
+use serde::Serialize;
+use crate::models::{ConflictReport, FailureCause, TransferStats, TriggeredSyncOutcome};
+
+/// Events reported to the daemon, each being written to stdout as a single line of JSON. Logs are
+/// written to stderr, so that they don't interfere with the events.
+#[derive(Serialize, Debug)]
+pub(crate) enum MonitorEvent {
+    Conflict(ConflictReport),
+    SyncStarted {
+        remote_workspace: String,
+        started_at: u64
+    },
+    SyncSucceeded {
+        remote_workspace: String,
+        finished_at: u64,
+        duration_ms: u64
+    },
+    SyncFailed {
+        remote_workspace: String,
+        finished_at: u64,
+        duration_ms: u64,
+        error: String,
+        /// Cause of the failure, if it could be determined
+        cause: Option<FailureCause>
+    },
+    /// Statistics of what a sync transferred, along with the totals since the monitor started
+    Transferred {
+        remote_workspace: String,
+        last_sync: TransferStats,
+        remote_total: TransferStats,
+        workspace_total: TransferStats
+    },
+    /// Syncs with the remote workspace are held, as they would delete more resources than allowed
+    DeletionsHeld {
+        remote_workspace: String,
+        nr_of_deletions: usize,
+        held_since: u64
+    },
+    DeletionsReleased {
+        remote_workspace: String,
+        approved: bool
+    },
+    /// The post-sync hook of the remote workspace finished
+    HookFinished {
+        remote_workspace: String,
+        finished_at: u64,
+        duration_ms: u64,
+        /// Exit code of the hook, `None` if it was terminated by a signal or could not be run
+        exit_code: Option<i32>,
+        timed_out: bool,
+        /// Reason why the hook could not be run
+        error: Option<String>
+    },
+    /// A sync triggered by the daemon finished, `id` being the one given by the daemon
+    TriggeredSyncFinished {
+        id: String,
+        outcomes: Vec<TriggeredSyncOutcome>
+    },
+    /// A transfer with the remote workspace waits for the daemon to grant it the permit with the
+    /// given id
+    PermitRequested {
+        id: String,
+        remote_workspace: String
+    },
+    /// The transfer holding the permit with the given id finished
+    PermitReleased {
+        id: String
+    }
+}
+
+/// Seconds since the unix epoch, which is how points in time are reported to the daemon
+pub(crate) fn unix_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs())
+}
+
+pub(crate) fn emit(event: MonitorEvent) {
+    let mut line = match serde_json::to_string(&event) {
+        Ok(line) => line,
+        Err(e) => {
+            warn!("Unable to serialize event {:?}: {e}", event);
+            return;
+        }
+    };
+    line.push('\n');
+
+    // Lock stdout, so that events emitted by concurrent syncs are not interleaved
+    let mut stdout = io::stdout().lock();
+    if let Err(e) = stdout.write_all(line.as_bytes()).and_then(|_| stdout.flush()) {
+        warn!("Unable to report event {:?} to the daemon: {e}", event);
+    }
+}
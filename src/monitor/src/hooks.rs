@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::path::{Component, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{LazyLock, Mutex};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{debug, info, warn};
+use crate::events::{emit, unix_timestamp, MonitorEvent};
+use crate::models::{ConnectionInfo, PostSyncHook, RemoteWorkspace, WorkspaceInfo};
+use crate::util::{shell_quote, ssh_command};
+
+/// Only the end of what a hook writes to stdout or stderr is logged, since that is where errors
+/// usually show up
+const MAX_HOOK_OUTPUT: usize = 4096;
+
+/// Interval in which a running hook is checked for having finished or timed out
+const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Point in time at which the post-sync hook of each remote workspace is due. Syncs are performed
+/// by the sync workers, which schedule the hooks of the remote workspaces they synced.
+static DUE_HOOKS: LazyLock<Mutex<HashMap<String, Instant>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Schedule the post-sync hook of a remote workspace that was just synced successfully. A hook
+/// that is already scheduled is postponed, so that it runs once after a burst of syncs.
+pub(crate) fn record_sync(remote_workspace: &RemoteWorkspace, now: Instant) {
+    if let Some(hook) = &remote_workspace.post_sync_hook {
+        let due = now + Duration::from_millis(hook.debounce_ms);
+        DUE_HOOKS.lock().unwrap().insert(remote_workspace.name.clone(), due);
+    }
+}
+
+/// Runs the post-sync hooks of the remote workspaces once they are due. Each remote workspace's
+/// hook is run by a worker thread of its own, so that neither the syncs nor the hooks of other
+/// remote workspaces wait for it. A hook that becomes due while it is running is run once more
+/// afterwards.
+pub(crate) struct HookRunner {
+    workers: HashMap<String, Sender<()>>
+}
+
+impl HookRunner {
+
+    pub(crate) fn new() -> Self {
+        HookRunner { workers: HashMap::new() }
+    }
+
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        DUE_HOOKS.lock().unwrap().values().min().copied()
+    }
+
+    pub(crate) fn is_due(&self, now: Instant) -> bool {
+        self.next_deadline().is_some_and(|deadline| deadline <= now)
+    }
+
+    pub(crate) fn run_due(&mut self, ws_info: &WorkspaceInfo, now: Instant) {
+        let mut due_hooks = DUE_HOOKS.lock().unwrap();
+        let due: Vec<String> = due_hooks.iter().filter(|(_, due)| **due <= now).map(|(name, _)| name.clone()).collect();
+
+        for name in due {
+            due_hooks.remove(&name);
+
+            let Some(remote_workspace) = ws_info.remote_workspaces.iter().find(|remote| remote.name == name) else {
+                continue;
+            };
+
+            let worker = self.workers
+                .entry(name)
+                .or_insert_with(|| spawn_worker(remote_workspace.clone()));
+
+            if worker.send(()).is_err() {
+                warn!("Unable to run the post-sync hook of '{}', as its worker stopped", remote_workspace.name);
+            }
+        }
+    }
+}
+
+fn spawn_worker(remote_workspace: RemoteWorkspace) -> Sender<()> {
+    let (tx, rx) = channel::<()>();
+
+    thread::spawn(move || {
+        while rx.recv().is_ok() {
+            // Runs requested while the hook was running are covered by a single run
+            while rx.try_recv().is_ok() {}
+
+            if let Some(hook) = &remote_workspace.post_sync_hook {
+                run_hook(&remote_workspace, hook);
+            }
+        }
+    });
+
+    tx
+}
+
+/// Outcome of a hook that could be started
+struct HookExecution {
+    /// `None` if the hook was killed because it timed out
+    status: Option<ExitStatus>,
+    stdout: String,
+    stderr: String
+}
+
+/// Run the hook of the remote workspace, log its outcome and report it to the daemon
+fn run_hook(remote_workspace: &RemoteWorkspace, hook: &PostSyncHook) {
+    debug!("Running the post-sync hook of '{}': {}", remote_workspace.name, hook.command);
+
+    let started = Instant::now();
+    let result = hook_command(remote_workspace, hook)
+        .and_then(|command| execute(command, Duration::from_secs(hook.timeout_secs)));
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    let (exit_code, timed_out, error) = match result {
+        Ok(execution) => {
+            let output = format_output(&execution);
+
+            match execution.status {
+                Some(status) if status.success() => {
+                    info!("Post-sync hook of '{}' succeeded after {duration_ms}ms{output}", remote_workspace.name);
+                },
+                Some(status) => {
+                    warn!("Post-sync hook of '{}' failed with {status} after {duration_ms}ms{output}", remote_workspace.name);
+                },
+                None => {
+                    warn!("Post-sync hook of '{}' timed out after {duration_ms}ms{output}", remote_workspace.name);
+                }
+            }
+
+            (execution.status.and_then(|status| status.code()), execution.status.is_none(), None)
+        },
+        Err(e) => {
+            warn!("Unable to run the post-sync hook of '{}': {e}", remote_workspace.name);
+            (None, false, Some(e))
+        }
+    };
+
+    emit(MonitorEvent::HookFinished {
+        remote_workspace: remote_workspace.name.clone(),
+        finished_at: unix_timestamp(),
+        duration_ms,
+        exit_code,
+        timed_out,
+        error
+    });
+}
+
+/// Command running the hook in the remote workspace, via SSH unless the remote workspace is
+/// located on this system
+fn hook_command(remote_workspace: &RemoteWorkspace, hook: &PostSyncHook) -> Result<Command, String> {
+    let working_dir = hook_working_dir(remote_workspace, hook)?;
+
+    let Some(mut command) = ssh_command(&remote_workspace.connection_info, &[]) else {
+        if let ConnectionInfo::RsyncDaemon { .. } = &remote_workspace.connection_info {
+            return Err(String::from("Post-sync hooks can't be run on remote systems that are connected via the rsync daemon"));
+        }
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&hook.command).current_dir(working_dir);
+        return Ok(command);
+    };
+
+    let working_dir = working_dir
+        .to_str()
+        .ok_or_else(|| format!("Working directory '{:?}' is not valid UTF-8", working_dir))?;
+
+    // Killing ssh doesn't stop the remote command, which is thus run via 'timeout' to be killed
+    // on the remote system as well. The remote shell joins all arguments into a single command line.
+    command.arg("--").arg(format!(
+        "cd -- {} && exec timeout -k 5 {} sh -c {}",
+        shell_quote(working_dir),
+        hook.timeout_secs,
+        shell_quote(&hook.command)
+    ));
+
+    Ok(command)
+}
+
+/// Directory the hook is run in, which must be located within the remote workspace
+fn hook_working_dir(remote_workspace: &RemoteWorkspace, hook: &PostSyncHook) -> Result<PathBuf, String> {
+    let Some(working_dir) = &hook.working_dir else {
+        return Ok(remote_workspace.remote_path.clone());
+    };
+
+    if !working_dir.components().all(|component| matches!(component, Component::Normal(_))) {
+        return Err(format!("Working directory '{:?}' is not a path within the remote workspace", working_dir));
+    }
+
+    Ok(remote_workspace.remote_path.join(working_dir))
+}
+
+/// Run the command, killing it and everything it started if it doesn't finish in time
+fn execute(mut command: Command, timeout: Duration) -> Result<HookExecution, String> {
+    let program = command.get_program().to_string_lossy().to_string();
+
+    // Run the command in a process group of its own, so that it can be killed along with its
+    // children, which would otherwise keep its output open
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("Unable to run '{program}': {e}"))?;
+
+    let stdout = child.stdout.take().map(|stdout| thread::spawn(move || read_tail(stdout)));
+    let stderr = child.stderr.take().map(|stderr| thread::spawn(move || read_tail(stderr)));
+
+    let deadline = Instant::now() + timeout;
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if Instant::now() >= deadline => {
+                unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) };
+                let _ = child.wait();
+                break None;
+            },
+            Ok(None) => thread::sleep(HOOK_POLL_INTERVAL),
+            Err(e) => return Err(format!("Unable to wait for '{program}' to finish: {e}"))
+        }
+    };
+
+    let join = |reader: Option<thread::JoinHandle<String>>| {
+        reader.and_then(|reader| reader.join().ok()).unwrap_or_default()
+    };
+
+    Ok(HookExecution { status, stdout: join(stdout), stderr: join(stderr) })
+}
+
+/// Read until the end of the given output, keeping only its last [MAX_HOOK_OUTPUT] bytes
+fn read_tail(mut reader: impl Read) -> String {
+    let mut tail: Vec<u8> = vec![];
+    let mut buffer = [0u8; 8192];
+    let mut truncated = false;
+
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => tail.extend_from_slice(&buffer[..n])
+        }
+
+        if tail.len() > 2 * MAX_HOOK_OUTPUT {
+            tail.drain(..tail.len() - MAX_HOOK_OUTPUT);
+            truncated = true;
+        }
+    }
+
+    if tail.len() > MAX_HOOK_OUTPUT {
+        tail.drain(..tail.len() - MAX_HOOK_OUTPUT);
+        truncated = true;
+    }
+
+    let output = String::from_utf8_lossy(&tail).trim().to_string();
+
+    if truncated && !output.is_empty() {
+        format!("[...] {output}")
+    } else {
+        output
+    }
+}
+
+fn format_output(execution: &HookExecution) -> String {
+    let mut output = String::new();
+
+    for (name, content) in [("stdout", &execution.stdout), ("stderr", &execution.stderr)] {
+        if !content.is_empty() {
+            output.push_str(&format!("\n{name}: {content}"));
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote_workspace(connection_info: ConnectionInfo, working_dir: Option<&str>) -> RemoteWorkspace {
+        RemoteWorkspace {
+            name: String::from("remote"),
+            remote_path: PathBuf::from("/srv/ws"),
+            connection_info,
+            sync_options: Default::default(),
+            two_way: None,
+            versioning: None,
+            schedule: vec![],
+            post_sync_hook: Some(PostSyncHook {
+                command: String::from("make 'all'"),
+                working_dir: working_dir.map(PathBuf::from),
+                timeout_secs: 30,
+                debounce_ms: 0
+            }),
+            paused: false
+        }
+    }
+
+    fn command_for(remote_workspace: &RemoteWorkspace) -> Result<Command, String> {
+        hook_command(remote_workspace, remote_workspace.post_sync_hook.as_ref().unwrap())
+    }
+
+    #[test]
+    fn remote_hook_is_killed_by_timeout() {
+        let remote_workspace = remote_workspace(ConnectionInfo::HostAlias { host_alias: String::from("alias") }, Some("app"));
+        let command = command_for(&remote_workspace).unwrap();
+
+        assert_eq!(command.get_args().last().unwrap(), r"cd -- '/srv/ws/app' && exec timeout -k 5 30 sh -c 'make '\''all'\'''");
+    }
+
+    #[test]
+    fn working_dir_must_be_within_remote_workspace() {
+        let connection_info = ConnectionInfo::Local { path: PathBuf::from("/srv") };
+
+        for working_dir in ["/etc", "../other", "app/../../other", "./app"] {
+            assert!(command_for(&remote_workspace(connection_info.clone(), Some(working_dir))).is_err(), "{working_dir}");
+        }
+
+        let command = command_for(&remote_workspace(connection_info, Some("app/sub"))).unwrap();
+        assert_eq!(command.get_current_dir(), Some(PathBuf::from("/srv/ws/app/sub").as_path()));
+    }
+}
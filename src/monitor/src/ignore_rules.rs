@@ -0,0 +1,196 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use globset::Glob;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use log::{debug, warn};
+use crate::models::{Error, WorkspaceInfo};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Name of the file in the workspace root that holds additional ignore patterns
+pub(crate) const IGNORE_FILE_NAME: &str = ".wsyncignore";
+
+/// Ignore rules shared between the filesystem watcher and the sync loop
+pub(crate) type SharedIgnoreRules = Arc<RwLock<IgnoreRules>>;
+
+#[derive(Clone)]
+struct IgnorePattern {
+    negated: bool,
+    dir_only: bool,
+    /// Whether the pattern is relative to the workspace root, as opposed to matching at any depth
+    anchored: bool,
+    /// The pattern without the negation prefix, the anchoring slash and the trailing slash
+    glob: String
+}
+
+/// Ignore patterns (gitignore syntax) of a workspace, made up of the patterns stored with the
+/// workspace information followed by the patterns in the workspace's `.wsyncignore` file. As with
+/// gitignore, later patterns take precedence over earlier ones.
+#[derive(Clone)]
+pub(crate) struct IgnoreRules {
+    matcher: Gitignore,
+    patterns: Vec<IgnorePattern>
+}
+
+impl IgnoreRules {
+
+    pub(crate) fn load(ws_info: &WorkspaceInfo) -> Result<Self> {
+        let mut lines: Vec<String> = ws_info.ignore_patterns.clone();
+
+        let ignore_file_path = ws_info.local_path.join(IGNORE_FILE_NAME);
+        match fs::read_to_string(&ignore_file_path) {
+            Ok(content) => lines.extend(content.lines().map(|line| line.to_string())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {},
+            Err(e) => {
+                return Err(Error::new(format!("Unable to read '{:?}': {e}", ignore_file_path)));
+            }
+        }
+
+        let mut builder = GitignoreBuilder::new(&ws_info.local_path);
+        let mut patterns = vec![];
+
+        for line in lines.iter() {
+            if let Err(e) = builder.add_line(None, line) {
+                warn!("Skipping invalid ignore pattern '{line}': {e}");
+                continue;
+            }
+
+            if let Some(pattern) = IgnorePattern::parse(line) {
+                patterns.push(pattern);
+            }
+        }
+
+        let matcher = builder.build().map_err(|e| {
+            Error::new(format!("Unable to build ignore rules: {e}"))
+        })?;
+
+        debug!("Loaded #{} ignore pattern(s) for workspace '{}'", patterns.len(), ws_info.name);
+        Ok(IgnoreRules { matcher, patterns })
+    }
+
+    pub(crate) fn load_shared(ws_info: &WorkspaceInfo) -> Result<SharedIgnoreRules> {
+        Ok(Arc::new(RwLock::new(Self::load(ws_info)?)))
+    }
+
+    /// Reload the rules in place, e.g. after the `.wsyncignore` file changed. If the new rules
+    /// cannot be loaded, the previous ones are kept.
+    pub(crate) fn reload_shared(rules: &SharedIgnoreRules, ws_info: &WorkspaceInfo) {
+        match Self::load(ws_info) {
+            Ok(reloaded) => *rules.write().unwrap() = reloaded,
+            Err(e) => warn!("Keeping previous ignore rules, as reloading them failed: {e}")
+        }
+    }
+
+    /// Checks whether the resource at the given workspace-relative path, or any of its parent
+    /// directories, is ignored.
+    pub(crate) fn is_ignored(&self, relative_path: &Path, is_dir: bool) -> bool {
+        self.matcher.matched_path_or_any_parents(relative_path, is_dir).is_ignore()
+    }
+
+    /// Translate the ignore patterns into rsync filter rules for a transfer rooted at the given
+    /// workspace-relative directory. Since rsync applies the first matching rule whereas the last
+    /// matching gitignore pattern wins, the rules are returned in reverse order.
+    pub(crate) fn rsync_filter_args(&self, transfer_root: Option<&PathBuf>) -> Vec<String> {
+        let transfer_root_components: Vec<String> = transfer_root
+            .map(|path| {
+                path.components()
+                    .filter_map(|component| match component {
+                        Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                        _ => None
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        self.patterns
+            .iter()
+            .rev()
+            .filter_map(|pattern| pattern.to_rsync_rule(&transfer_root_components))
+            .map(|rule| format!("--filter={rule}"))
+            .collect()
+    }
+}
+
+impl IgnorePattern {
+
+    fn parse(line: &str) -> Option<Self> {
+        if line.starts_with('#') {
+            return None;
+        }
+
+        let mut pattern = line.trim_end();
+        if pattern.is_empty() {
+            return None;
+        }
+
+        // A leading backslash escapes a literal '!' or '#'
+        let negated = pattern.starts_with('!');
+        if negated || pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+            pattern = &pattern[1..];
+        }
+
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+
+        // Like with gitignore, a slash at the beginning or in the middle anchors the pattern, unless
+        // it is preceded by a leading '**', which matches in all directories.
+        let (anchored, glob) = match pattern.strip_prefix("**/") {
+            Some(remainder) => (false, remainder.trim_start_matches("**/").to_string()),
+            None => (pattern.contains('/'), pattern.trim_start_matches('/').to_string())
+        };
+
+        if glob.is_empty() {
+            return None;
+        }
+
+        Some(IgnorePattern { negated, dir_only, anchored, glob })
+    }
+
+    fn to_rsync_rule(&self, transfer_root_components: &[String]) -> Option<String> {
+        let rule_prefix = if self.negated { "+" } else { "-" };
+        let dir_suffix = if self.dir_only { "/" } else { "" };
+
+        if !self.anchored {
+            return Some(format!("{rule_prefix} {}{dir_suffix}", self.glob));
+        }
+
+        let glob_components: Vec<&str> = self.glob.split('/').collect();
+
+        for (idx, transfer_root_component) in transfer_root_components.iter().enumerate() {
+            let glob_component = match glob_components.get(idx) {
+                Some(glob_component) => *glob_component,
+                // The pattern refers to the transfer root or one of its parents, which would not be
+                // synced in the first place if they were ignored.
+                None => return None
+            };
+
+            if glob_component == "**" {
+                // Matches at any depth below this point, which is what an unanchored rsync
+                // pattern does.
+                let remainder = glob_components[idx + 1..].join("/");
+                if remainder.is_empty() {
+                    return None;
+                }
+
+                return Some(format!("{rule_prefix} {remainder}{dir_suffix}"));
+            }
+
+            let matches = Glob::new(glob_component)
+                .map(|glob| glob.compile_matcher().is_match(transfer_root_component))
+                .unwrap_or(false);
+
+            if !matches {
+                return None;
+            }
+        }
+
+        let remainder = glob_components[transfer_root_components.len()..].join("/");
+        if remainder.is_empty() {
+            return None;
+        }
+
+        Some(format!("{rule_prefix} /{remainder}{dir_suffix}"))
+    }
+}
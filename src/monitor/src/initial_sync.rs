@@ -0,0 +1,293 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use crate::events::unix_timestamp;
+use crate::ignore_rules::IgnoreRules;
+use crate::models::{InitialSync, RemoteWorkspace, WorkspaceInfo};
+use crate::sync::{verify_remote_workspace, SyncTargets};
+use crate::util::fs::escape_file_name;
+use crate::util::state_directory;
+
+/// Minimum interval in which the checkpoint is persisted while the monitor is running
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Changes made shortly before the checkpoint might not have been received from the watcher yet,
+/// so they are considered to be made after it
+const CHECKPOINT_MARGIN_SECS: u64 = 10;
+
+#[derive(Serialize, Deserialize)]
+struct PersistedCheckpoint {
+    /// Seconds since the unix epoch before which all changes were synced or journaled
+    synced_until: u64,
+    /// Remote workspaces the checkpoint applies to, remote workspaces attached afterwards have never
+    /// been synced by the monitor
+    remote_workspaces: Vec<String>
+}
+
+/// Point in time up to which the monitor synced or journaled all changes of the workspace, which is
+/// persisted in the state directory while the monitor is running. Changes made after the last
+/// checkpoint were made while the monitor was not running (or shortly before it stopped), so that
+/// only they need to be synced by the initial sync once it is restarted.
+pub(crate) struct SyncCheckpoint {
+    path: Option<PathBuf>,
+    remote_workspaces: Vec<String>,
+    last_persisted: Option<Instant>,
+    /// Whether changes were received from the watcher since the checkpoint was last persisted
+    outdated: bool
+}
+
+impl SyncCheckpoint {
+
+    pub(crate) fn open(ws_info: &WorkspaceInfo) -> Self {
+        let path = match state_directory() {
+            Ok(state_directory) => Some(state_directory
+                .join("checkpoint")
+                .join(escape_file_name(&ws_info.name))
+                .with_extension("json")),
+            Err(e) => {
+                warn!("The sync checkpoint of workspace '{}' will not be persisted: {e}", ws_info.name);
+                None
+            }
+        };
+
+        let remote_workspaces = ws_info.remote_workspaces.iter().map(|remote| remote.name.clone()).collect();
+
+        SyncCheckpoint { path, remote_workspaces, last_persisted: None, outdated: true }
+    }
+
+    fn load(&self) -> Option<PersistedCheckpoint> {
+        let path = self.path.as_ref()?;
+
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                if e.kind() != ErrorKind::NotFound {
+                    warn!("Unable to open sync checkpoint '{:?}': {e}", path);
+                }
+                return None;
+            }
+        };
+
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+            warn!("Ignoring malformed sync checkpoint '{:?}': {e}", path);
+        }).ok()
+    }
+
+    pub(crate) fn record_change(&mut self) {
+        self.outdated = true;
+    }
+
+    /// Point in time at which the checkpoint should be advanced, or `None` if it is up to date or
+    /// isn't persisted at all
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        if !self.outdated || self.path.is_none() {
+            return None;
+        }
+
+        Some(self.last_persisted.map_or_else(Instant::now, |last| last + CHECKPOINT_INTERVAL))
+    }
+
+    /// Persist that all changes made until now were synced or journaled, unless that was done
+    /// recently. Must only be called once all changes received from the watcher were handled.
+    pub(crate) fn advance(&mut self, now: Instant) {
+        if !self.outdated || self.last_persisted.is_some_and(|last| now.duration_since(last) < CHECKPOINT_INTERVAL) {
+            return;
+        }
+
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let checkpoint = PersistedCheckpoint {
+            synced_until: unix_timestamp().saturating_sub(CHECKPOINT_MARGIN_SECS),
+            remote_workspaces: self.remote_workspaces.clone()
+        };
+
+        match write_checkpoint(path, &checkpoint) {
+            Ok(()) => debug!("Advanced sync checkpoint to {}", checkpoint.synced_until),
+            Err(e) => warn!("Unable to persist sync checkpoint '{:?}': {e}", path)
+        }
+
+        self.last_persisted = Some(now);
+        self.outdated = false;
+    }
+}
+
+fn write_checkpoint(path: &Path, checkpoint: &PersistedCheckpoint) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp_path = path.with_extension("json.tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+
+    serde_json::to_writer(&mut writer, checkpoint)?;
+    writer.flush()?;
+
+    fs::rename(&tmp_path, path)
+}
+
+/// Determines what the initial sync of each remote workspace covers, according to the workspace's
+/// initial sync strategy. Remote workspaces whose targets can't be narrowed down, e.g. because they
+/// were never synced by the monitor, are synced entirely.
+pub(crate) struct InitialTargets<'a> {
+    ws_info: &'a WorkspaceInfo,
+    ignore_rules: &'a IgnoreRules,
+    /// Targets modified since the checkpoint, along with the remote workspaces it applies to
+    modified: Option<(SyncTargets, Vec<String>)>
+}
+
+impl<'a> InitialTargets<'a> {
+
+    pub(crate) fn new(ws_info: &'a WorkspaceInfo, ignore_rules: &'a IgnoreRules, checkpoint: &SyncCheckpoint) -> Self {
+        let mut modified = None;
+
+        if let InitialSync::Modified = ws_info.initial_sync {
+            match checkpoint.load() {
+                Some(persisted) => match modified_targets(&ws_info.local_path, persisted.synced_until, ignore_rules) {
+                    Ok(targets) => {
+                        info!(
+                            "#{} resource(s) of workspace '{}' were modified since {}",
+                            targets.len(),
+                            ws_info.name,
+                            persisted.synced_until
+                        );
+                        modified = Some((targets, persisted.remote_workspaces));
+                    },
+                    Err(e) => warn!("Unable to determine the resources modified since the monitor stopped: {e}")
+                },
+                None => info!("No sync checkpoint of workspace '{}' found, syncing it entirely", ws_info.name)
+            }
+        }
+
+        InitialTargets { ws_info, ignore_rules, modified }
+    }
+
+    /// Targets of the initial sync of the given remote workspace
+    pub(crate) fn targets(&self, remote_workspace: &RemoteWorkspace) -> SyncTargets {
+        let full = SyncTargets::from_directories(vec![None]);
+
+        match self.ws_info.initial_sync {
+            InitialSync::Full => full,
+            InitialSync::Skip => SyncTargets::default(),
+            InitialSync::Modified => match &self.modified {
+                Some((targets, remote_workspaces)) if remote_workspaces.contains(&remote_workspace.name) => {
+                    targets.clone()
+                },
+                _ => full
+            },
+            InitialSync::Verify => {
+                match verify_remote_workspace(self.ws_info, remote_workspace, self.ignore_rules) {
+                    Ok(targets) => {
+                        info!("#{} resource(s) differ from '{}'", targets.len(), remote_workspace.name);
+                        targets
+                    },
+                    Err(e) => {
+                        warn!("Unable to verify '{}', syncing it entirely: {e}", remote_workspace.name);
+                        full
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Resources that were changed at or after the given point in time. Directories whose entries
+/// changed are synced as a whole, so that deleted entries are picked up. The change time is used
+/// instead of the modification time, since the latter can be set arbitrarily, e.g. when extracting
+/// an archive.
+fn modified_targets(ws_root_path: &Path, since: u64, ignore_rules: &IgnoreRules) -> std::io::Result<SyncTargets> {
+    let mut targets = SyncTargets::default();
+    collect_modified(ws_root_path, Path::new(""), since, ignore_rules, &mut targets)?;
+
+    Ok(targets)
+}
+
+fn collect_modified(
+    ws_root_path: &Path,
+    relative_path: &Path,
+    since: u64,
+    ignore_rules: &IgnoreRules,
+    targets: &mut SyncTargets
+) -> std::io::Result<()> {
+    let path = ws_root_path.join(relative_path);
+
+    let metadata = match fs::symlink_metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e)
+    };
+
+    let is_root = relative_path.parent().is_none();
+    if !is_root && ignore_rules.is_ignored(relative_path, metadata.is_dir()) {
+        return Ok(());
+    }
+
+    let is_modified = u64::try_from(metadata.ctime()).is_ok_and(|ctime| ctime >= since);
+
+    if !metadata.is_dir() {
+        if is_modified {
+            targets.files.push(relative_path.to_path_buf());
+        }
+        return Ok(());
+    }
+
+    if is_modified {
+        targets.directories.push((!is_root).then(|| relative_path.to_path_buf()));
+        return Ok(());
+    }
+
+    let entries = match fs::read_dir(&path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e)
+    };
+
+    for entry in entries {
+        collect_modified(ws_root_path, &relative_path.join(entry?.file_name()), since, ignore_rules, targets)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint(path: Option<PathBuf>) -> SyncCheckpoint {
+        SyncCheckpoint { path, remote_workspaces: vec![], last_persisted: None, outdated: true }
+    }
+
+    #[test]
+    fn checkpoint_without_state_directory_has_no_deadline() {
+        let mut checkpoint = checkpoint(None);
+        assert_eq!(checkpoint.next_deadline(), None);
+
+        checkpoint.record_change();
+        checkpoint.advance(Instant::now());
+        assert_eq!(checkpoint.next_deadline(), None);
+    }
+
+    #[test]
+    fn checkpoint_is_due_once_per_interval() {
+        let dir = std::env::temp_dir().join(format!("wsync-checkpoint-test-{}", std::process::id()));
+        let mut checkpoint = checkpoint(Some(dir.join("ws.json")));
+
+        let now = Instant::now();
+        assert!(checkpoint.next_deadline().is_some_and(|deadline| deadline <= Instant::now()));
+
+        checkpoint.advance(now);
+        assert_eq!(checkpoint.next_deadline(), None);
+        assert!(dir.join("ws.json").exists());
+
+        checkpoint.record_change();
+        assert_eq!(checkpoint.next_deadline(), Some(now + CHECKPOINT_INTERVAL));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
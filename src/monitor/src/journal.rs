@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use log::{debug, warn};
+use crate::debounce::DirtySet;
+use crate::models::{Error, WorkspaceInfo};
+use crate::util::fs::escape_file_name;
+use crate::util::state_directory;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const JOURNAL_FILE_EXTENSION: &str = "journal";
+
+/// Number of lines appended to a journal file after which it is compacted
+const COMPACTION_THRESHOLD: usize = 1024;
+
+/// Number of distinct dirty paths above which a journal is collapsed into the workspace root
+const MAX_JOURNAL_ENTRIES: usize = 10_000;
+
+/// On-disk journal of the dirty paths of a single remote workspace
+struct RemoteJournal {
+    path: PathBuf,
+    file: File,
+    dirty: DirtySet,
+    nr_of_lines: usize
+}
+
+/// Per remote workspace journals of workspace-relative paths that still need to be synced. Paths
+/// are journaled before a sync is attempted and the journal of a remote workspace is cleared once
+/// it is in sync again, so that changes are not lost if the remote workspace is unreachable for a
+/// longer time or the monitor is restarted in the meantime.
+///
+/// The journals are stored in the state directory specified in the wsync config. If no state
+/// directory is configured or it cannot be used, changes are not journaled.
+pub(crate) struct Journal {
+    remote_journals: HashMap<String, RemoteJournal>
+}
+
+impl Journal {
+
+    pub(crate) fn disabled() -> Self {
+        Journal { remote_journals: HashMap::new() }
+    }
+
+    pub(crate) fn open(ws_info: &WorkspaceInfo) -> Self {
+        match Self::try_open(ws_info) {
+            Ok(journal) => journal,
+            Err(e) => {
+                warn!("Changes of workspace '{}' will not be journaled: {e}", ws_info.name);
+                Self::disabled()
+            }
+        }
+    }
+
+    fn try_open(ws_info: &WorkspaceInfo) -> Result<Self> {
+        let state_directory = state_directory()?;
+        let journal_directory = state_directory.join("journal").join(escape_file_name(&ws_info.name));
+        let remote_workspaces: Vec<&str> = ws_info.remote_workspaces
+            .iter()
+            .map(|remote_workspace| remote_workspace.name.as_str())
+            .collect();
+
+        Self::open_directory(&journal_directory, &remote_workspaces)
+    }
+
+    /// Open the journals of the given remote workspaces in the given directory
+    pub(crate) fn open_directory(journal_directory: &Path, remote_workspaces: &[&str]) -> Result<Self> {
+        fs::create_dir_all(journal_directory).map_err(|e| {
+            Error::new(format!("Unable to create journal directory '{:?}': {e}", journal_directory))
+        })?;
+
+        let mut remote_journals = HashMap::new();
+
+        for remote_workspace in remote_workspaces.iter() {
+            let path = journal_directory
+                .join(escape_file_name(remote_workspace))
+                .with_extension(JOURNAL_FILE_EXTENSION);
+
+            remote_journals.insert(remote_workspace.to_string(), RemoteJournal::open(path)?);
+        }
+
+        remove_stale_journals(journal_directory, &remote_journals);
+
+        Ok(Journal { remote_journals })
+    }
+
+    /// The journaled paths per remote workspace, i.e. the paths that were not synced before the
+    /// monitor was last stopped.
+    pub(crate) fn pending(&self) -> Vec<(String, Vec<Option<PathBuf>>)> {
+        self.remote_journals
+            .iter()
+            .filter(|(_, remote_journal)| !remote_journal.dirty.is_empty())
+            .map(|(remote_workspace, remote_journal)| (remote_workspace.clone(), remote_journal.dirty.entries()))
+            .collect()
+    }
+
+    pub(crate) fn append(&mut self, remote_workspace: &str, targets: &[Option<PathBuf>]) {
+        if let Some(remote_journal) = self.remote_journals.get_mut(remote_workspace)
+            && let Err(e) = remote_journal.append(targets) {
+            warn!("Unable to journal changes for '{remote_workspace}': {e}");
+        }
+    }
+
+    /// Clear the journal of a remote workspace after it was synced successfully
+    pub(crate) fn clear(&mut self, remote_workspace: &str) {
+        if let Some(remote_journal) = self.remote_journals.get_mut(remote_workspace)
+            && let Err(e) = remote_journal.clear() {
+            warn!("Unable to clear the journal of '{remote_workspace}': {e}");
+        }
+    }
+}
+
+impl RemoteJournal {
+
+    fn open(path: PathBuf) -> Result<Self> {
+        let mut dirty = DirtySet::default();
+
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line.map_err(|e| Error::new(format!("Unable to read '{:?}': {e}", path)))?;
+
+                    // A partially written line (e.g. due to a crash) is skipped, which is safe since
+                    // the remaining entries are still synced.
+                    match serde_json::from_str::<Option<PathBuf>>(&line) {
+                        Ok(target) => dirty.insert(target),
+                        Err(e) => warn!("Skipping malformed journal entry '{line}' in '{:?}': {e}", path)
+                    }
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {},
+            Err(e) => return Err(Error::new(format!("Unable to open '{:?}': {e}", path)))
+        }
+
+        let file = Self::rewrite(&path, &mut dirty)?;
+        let nr_of_lines = dirty.len();
+
+        if !dirty.is_empty() {
+            debug!("Loaded #{} journaled path(s) from '{:?}'", dirty.len(), path);
+        }
+
+        Ok(RemoteJournal { path, file, dirty, nr_of_lines })
+    }
+
+    fn append(&mut self, targets: &[Option<PathBuf>]) -> Result<()> {
+        let mut lines = String::new();
+
+        for target in targets.iter() {
+            let line = serde_json::to_string(target).map_err(|e| {
+                Error::new(format!("Unable to serialize journal entry '{:?}': {e}", target))
+            })?;
+
+            lines.push_str(&line);
+            lines.push('\n');
+            self.dirty.insert(target.clone());
+        }
+
+        self.file.write_all(lines.as_bytes()).map_err(|e| {
+            Error::new(format!("Unable to write to '{:?}': {e}", self.path))
+        })?;
+
+        self.nr_of_lines += targets.len();
+
+        if self.nr_of_lines > COMPACTION_THRESHOLD {
+            self.compact()?;
+        }
+
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<()> {
+        if self.nr_of_lines == 0 {
+            return Ok(());
+        }
+
+        self.file.set_len(0).map_err(|e| Error::new(format!("Unable to truncate '{:?}': {e}", self.path)))?;
+        self.dirty = DirtySet::default();
+        self.nr_of_lines = 0;
+
+        Ok(())
+    }
+
+    fn compact(&mut self) -> Result<()> {
+        debug!("Compacting journal '{:?}' with #{} line(s)", self.path, self.nr_of_lines);
+
+        self.file = Self::rewrite(&self.path, &mut self.dirty)?;
+        self.nr_of_lines = self.dirty.len();
+
+        Ok(())
+    }
+
+    /// Atomically replace the journal file with one only holding the given paths, which are
+    /// collapsed into the workspace root if there are too many of them. Returns the new journal
+    /// file opened for appending.
+    fn rewrite(path: &Path, dirty: &mut DirtySet) -> Result<File> {
+        if dirty.len() > MAX_JOURNAL_ENTRIES {
+            dirty.insert(None);
+        }
+
+        let tmp_path = path.with_extension(format!("{JOURNAL_FILE_EXTENSION}.tmp"));
+
+        let tmp_file = File::create(&tmp_path).map_err(|e| {
+            Error::new(format!("Unable to create '{:?}': {e}", tmp_path))
+        })?;
+
+        let mut writer = BufWriter::new(tmp_file);
+        for target in dirty.entries() {
+            let line = serde_json::to_string(&target).map_err(|e| {
+                Error::new(format!("Unable to serialize journal entry '{:?}': {e}", target))
+            })?;
+
+            writeln!(writer, "{line}").map_err(|e| Error::new(format!("Unable to write to '{:?}': {e}", tmp_path)))?;
+        }
+
+        let tmp_file = writer.into_inner().map_err(|e| {
+            Error::new(format!("Unable to write to '{:?}': {e}", tmp_path))
+        })?;
+
+        tmp_file.sync_all().map_err(|e| Error::new(format!("Unable to sync '{:?}': {e}", tmp_path)))?;
+
+        fs::rename(&tmp_path, path).map_err(|e| {
+            Error::new(format!("Unable to move '{:?}' to '{:?}': {e}", tmp_path, path))
+        })?;
+
+        OpenOptions::new().append(true).open(path).map_err(|e| {
+            Error::new(format!("Unable to open '{:?}': {e}", path))
+        })
+    }
+}
+
+/// Remove the journals of remote workspaces that are no longer attached to the workspace
+fn remove_stale_journals(journal_directory: &Path, remote_journals: &HashMap<String, RemoteJournal>) {
+    let entries = match fs::read_dir(journal_directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Unable to look for stale journals in '{:?}': {e}", journal_directory);
+            return;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if remote_journals.values().any(|remote_journal| remote_journal.path == path) {
+            continue;
+        }
+
+        debug!("Removing stale journal '{:?}'", path);
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Unable to remove stale journal '{:?}': {e}", path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journaled_paths_are_replayed_until_cleared() {
+        let dir = std::env::temp_dir().join(format!("wsync-journal-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let Ok(mut journal) = Journal::open_directory(&dir, &["r1", "r2"]) else {
+            panic!("Failed to open journal");
+        };
+        journal.append("r1", &[Some(PathBuf::from("a/b")), Some(PathBuf::from("c"))]);
+        journal.append("r1", &[Some(PathBuf::from("a"))]);
+        journal.append("r2", &[None]);
+        journal.clear("r2");
+        drop(journal);
+
+        // A line that was only partially written before a crash
+        let mut file = OpenOptions::new().append(true).open(dir.join("r1.journal")).unwrap();
+        file.write_all(b"\"d/e").unwrap();
+
+        let Ok(journal) = Journal::open_directory(&dir, &["r1", "r2"]) else {
+            panic!("Failed to reopen journal");
+        };
+        assert_eq!(journal.pending(), [(String::from("r1"), vec![Some(PathBuf::from("a")), Some(PathBuf::from("c"))])]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,600 @@
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::io;
+use std::io::ErrorKind;
+use std::mem::size_of;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use log::{debug, error, warn};
+use crate::ignore_rules::{IgnoreRules, SharedIgnoreRules, IGNORE_FILE_NAME};
+use crate::linux::{notify_change, notify_file_change, notify_move};
+use crate::models::{Error, WorkspaceInfo};
+use crate::sync::PathMove;
+use crate::sync_loop::WatcherEvent;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Size of the buffer events are read into, which must be able to hold at least one event along
+/// with the file handles and names it reports
+const EVENT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Size of the header of each information record following the metadata of an event
+const INFO_HEADER_SIZE: usize = 4;
+
+/// Size of the filesystem id preceding the file handle in an information record
+const FSID_SIZE: usize = 8;
+
+/// Size of the 'struct file_handle' without the handle itself
+const FILE_HANDLE_HEADER_SIZE: usize = 8;
+
+fn get_event_mask() -> u64 {
+    libc::FAN_CREATE |
+    libc::FAN_DELETE |
+    // Reports both the old and the new name of a moved resource in a single event
+    libc::FAN_RENAME |
+    // Rather than 'FAN_MODIFY', so that files being written are not synced half-written
+    libc::FAN_CLOSE_WRITE |
+    // Changes of permissions, ownership, timestamps or extended attributes
+    libc::FAN_ATTRIB |
+    // Report the above events for directories as well
+    libc::FAN_ONDIR
+}
+
+/// fanotify group watching the filesystem the workspace is located on. Since the mark covers the
+/// entire filesystem, no directory needs to be watched individually, but the events of resources
+/// outside the workspace must be filtered out.
+pub(super) struct Fanotify {
+    fd: OwnedFd,
+    /// Workspace root, which is used to open the directories reported by their file handle
+    mount_fd: OwnedFd,
+    /// Canonical path of the workspace root, as the paths of opened file handles are canonical
+    ws_root_path: PathBuf
+}
+
+/// Event reported by fanotify, with the paths of the affected resource being resolved
+struct FanotifyEvent {
+    mask: u64,
+    /// Path of the resource, or its previous path if it was moved. `None` if it can't be resolved,
+    /// e.g. because its directory was deleted in the meantime.
+    path: Option<PathBuf>,
+    /// Path the resource was moved to
+    new_path: Option<PathBuf>
+}
+
+fn last_os_error(msg: &str) -> Error {
+    Error::new(format!("{msg}: {}", io::Error::last_os_error()))
+}
+
+/// Set up a fanotify group reporting the changes on the workspace's filesystem by the file handle
+/// of the affected directory and the name of the resource within it. Fails if the kernel doesn't
+/// support this (Linux 5.17 or later is required) or the monitor lacks the privileges to mark the
+/// filesystem and open file handles.
+pub(super) fn init_fanotify_instance(ws_info: &WorkspaceInfo) -> Result<Fanotify> {
+    let canonical_ws_root_path = fs::canonicalize(&ws_info.local_path).map_err(|e| {
+        Error::new(format!("Unable to resolve workspace path '{:?}': {e}", ws_info.local_path))
+    })?;
+    let ws_root_path = CString::new(canonical_ws_root_path.as_os_str().as_bytes()).map_err(|e| {
+        Error::new(format!("Invalid workspace path '{:?}': {e}", canonical_ws_root_path))
+    })?;
+
+    let fd = unsafe {
+        libc::fanotify_init(
+            libc::FAN_CLASS_NOTIF | libc::FAN_CLOEXEC | libc::FAN_REPORT_DFID_NAME,
+            (libc::O_RDONLY | libc::O_LARGEFILE) as libc::c_uint
+        )
+    };
+    if fd == -1 {
+        return Err(last_os_error("Failed to create fanotify group"));
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mount_fd = unsafe { libc::open(ws_root_path.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC) };
+    if mount_fd == -1 {
+        return Err(last_os_error("Failed to open workspace root"));
+    }
+    let mount_fd = unsafe { OwnedFd::from_raw_fd(mount_fd) };
+
+    let res = unsafe {
+        libc::fanotify_mark(
+            fd.as_raw_fd(),
+            libc::FAN_MARK_ADD | libc::FAN_MARK_FILESYSTEM,
+            get_event_mask(),
+            libc::AT_FDCWD,
+            ws_root_path.as_ptr()
+        )
+    };
+    if res == -1 {
+        return Err(last_os_error("Failed to mark the workspace's filesystem"));
+    }
+
+    let fanotify = Fanotify { fd, mount_fd, ws_root_path: canonical_ws_root_path };
+
+    // Opening file handles requires further privileges, which are checked right away instead of
+    // failing once the first event is received
+    let handle = name_to_handle(&ws_root_path)?;
+    if open_handle(&fanotify, &handle)?.is_none() {
+        return Err(Error::new("Failed to open the file handle of the workspace root"));
+    }
+
+    debug!("Watching filesystem of '{:?}' via fanotify", ws_info.local_path);
+    Ok(fanotify)
+}
+
+/// File handle of the resource at the given path, as 'struct file_handle'
+fn name_to_handle(path: &CString) -> Result<Vec<u8>> {
+    // Large enough for the handles of all filesystems ('MAX_HANDLE_SZ')
+    let mut handle = vec![0u8; FILE_HANDLE_HEADER_SIZE + 128];
+    handle[..4].copy_from_slice(&128u32.to_ne_bytes());
+    let mut mount_id: libc::c_int = 0;
+
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_name_to_handle_at,
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            handle.as_mut_ptr(),
+            &mut mount_id as *mut libc::c_int,
+            0
+        )
+    };
+    if res == -1 {
+        return Err(last_os_error("Failed to get the file handle of the workspace root"));
+    }
+
+    let handle_bytes = u32::from_ne_bytes(handle[..4].try_into().unwrap()) as usize;
+    handle.truncate(FILE_HANDLE_HEADER_SIZE + handle_bytes);
+    Ok(handle)
+}
+
+/// Path of the directory with the given handle, given as 'struct file_handle'. `None` if the
+/// directory no longer exists.
+fn open_handle(fanotify: &Fanotify, handle: &[u8]) -> Result<Option<PathBuf>> {
+    // 'struct file_handle' must be aligned to its 32-bit fields
+    let mut aligned = vec![0u32; handle.len().div_ceil(4)];
+    unsafe { std::ptr::copy_nonoverlapping(handle.as_ptr(), aligned.as_mut_ptr() as *mut u8, handle.len()) };
+
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_open_by_handle_at,
+            fanotify.mount_fd.as_raw_fd(),
+            aligned.as_mut_ptr(),
+            libc::O_PATH | libc::O_CLOEXEC
+        )
+    };
+    if fd == -1 {
+        let error = io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(libc::ESTALE) | Some(libc::ENOENT) => Ok(None),
+            _ => Err(Error::new(format!("Failed to open file handle: {error}")))
+        };
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) };
+
+    // Deleted directories can still be opened as long as they are referenced
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } == -1 {
+        return Err(last_os_error("Failed to stat opened file handle"));
+    }
+    if stat.st_nlink == 0 {
+        return Ok(None);
+    }
+
+    fs::read_link(format!("/proc/self/fd/{}", fd.as_raw_fd())).map(Some).map_err(|e| {
+        Error::new(format!("Unable to resolve path of opened file handle: {e}"))
+    })
+}
+
+/// Resolve the path of the resource reported by an information record, consisting of the handle
+/// of its directory and its name. Directories are opened via `open_dir` once per read, as events
+/// tend to affect the same ones.
+fn resolve_record(
+    record: &[u8],
+    resolved_dirs: &mut HashMap<Vec<u8>, Option<PathBuf>>,
+    open_dir: &mut impl FnMut(&[u8]) -> Result<Option<PathBuf>>
+) -> Result<Option<PathBuf>> {
+    let handle_start = INFO_HEADER_SIZE + FSID_SIZE;
+    let handle_bytes = record
+        .get(handle_start..handle_start + 4)
+        .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()) as usize)
+        .ok_or(Error::new("Truncated fanotify information record"))?;
+
+    let name_start = handle_start + FILE_HANDLE_HEADER_SIZE + handle_bytes;
+    let handle = record
+        .get(handle_start..name_start)
+        .ok_or(Error::new("Truncated file handle in fanotify information record"))?;
+
+    let name = &record[name_start..];
+    let name = &name[..name.iter().position(|c| *c == 0).unwrap_or(name.len())];
+
+    let dir_path = match resolved_dirs.get(handle) {
+        Some(dir_path) => dir_path.clone(),
+        None => {
+            let dir_path = open_dir(handle)?;
+            resolved_dirs.insert(handle.to_vec(), dir_path.clone());
+            dir_path
+        }
+    };
+
+    // Events of the directory itself are reported with '.' as the name
+    Ok(dir_path.map(|dir_path| match name {
+        b"" | b"." => dir_path,
+        name => dir_path.join(OsStr::from_bytes(name))
+    }))
+}
+
+/// Split the events read from the fanotify group and resolve the paths they report, opening the
+/// reported directories via `open_dir`
+fn parse_events(buffer: &[u8], mut open_dir: impl FnMut(&[u8]) -> Result<Option<PathBuf>>) -> Result<Vec<FanotifyEvent>> {
+    let mut events = vec![];
+    let mut resolved_dirs = HashMap::new();
+    let mut offset = 0;
+
+    while offset + size_of::<libc::fanotify_event_metadata>() <= buffer.len() {
+        let metadata = unsafe {
+            std::ptr::read_unaligned(buffer[offset..].as_ptr() as *const libc::fanotify_event_metadata)
+        };
+
+        if metadata.vers != libc::FANOTIFY_METADATA_VERSION {
+            return Err(Error::new(format!("Unsupported fanotify metadata version {}", metadata.vers)));
+        }
+
+        // A zero length would never advance to the next event
+        if (metadata.event_len as usize) < size_of::<libc::fanotify_event_metadata>()
+            || metadata.metadata_len as u32 > metadata.event_len {
+            return Err(Error::new("Malformed fanotify event"));
+        }
+
+        let event = buffer
+            .get(offset..offset + metadata.event_len as usize)
+            .ok_or(Error::new("Truncated fanotify event"))?;
+        offset += metadata.event_len as usize;
+
+        let mut path = None;
+        let mut new_path = None;
+        let mut records = &event[metadata.metadata_len as usize..];
+
+        while records.len() >= INFO_HEADER_SIZE {
+            let info_type = records[0];
+            let len = u16::from_ne_bytes([records[2], records[3]]) as usize;
+            let record = records
+                .get(..len)
+                .filter(|_| len >= INFO_HEADER_SIZE)
+                .ok_or(Error::new("Malformed fanotify information record"))?;
+            records = &records[len..];
+
+            match info_type {
+                libc::FAN_EVENT_INFO_TYPE_DFID_NAME | libc::FAN_EVENT_INFO_TYPE_OLD_DFID_NAME => {
+                    path = resolve_record(record, &mut resolved_dirs, &mut open_dir)?;
+                },
+                libc::FAN_EVENT_INFO_TYPE_NEW_DFID_NAME => {
+                    new_path = resolve_record(record, &mut resolved_dirs, &mut open_dir)?;
+                },
+                _ => {}
+            }
+        }
+
+        events.push(FanotifyEvent { mask: metadata.mask, path, new_path });
+    }
+
+    Ok(events)
+}
+
+/// Workspace-relative path of a resource, `None` if it is not located within the workspace or is
+/// the workspace root
+fn workspace_relative_path(ws_root_path: &Path, path: Option<&PathBuf>) -> Option<PathBuf> {
+    path?
+        .strip_prefix(ws_root_path)
+        .ok()
+        .filter(|relative_path| !relative_path.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+}
+
+fn parent_path(relative_path: &Path) -> Option<PathBuf> {
+    relative_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+}
+
+/// Handle a resource that was created in, or deleted from, the workspace. Directories are synced
+/// by their parent directory, which includes all of their content.
+fn handle_created_or_deleted(relative_path: PathBuf, is_dir: bool, tx: &Sender<WatcherEvent>) -> Result<()> {
+    if is_dir {
+        notify_change(tx, parent_path(&relative_path))
+    } else {
+        notify_file_change(tx, relative_path)
+    }
+}
+
+fn handle_ignore_file_change(ws_info: &WorkspaceInfo, ignore_rules: &SharedIgnoreRules, tx: &Sender<WatcherEvent>) -> Result<()> {
+    debug!("'{IGNORE_FILE_NAME}' changed. Reloading ignore rules...");
+
+    // No watches depend on the rules, but (un)ignored resources might have to be synced
+    IgnoreRules::reload_shared(ignore_rules, ws_info);
+    notify_change(tx, None)
+}
+
+fn handle_fanotify_event(
+    event: FanotifyEvent,
+    ws_root_path: &Path,
+    ws_info: &WorkspaceInfo,
+    ignore_rules: &SharedIgnoreRules,
+    tx: &Sender<WatcherEvent>
+) -> Result<()> {
+    let is_dir = event.mask & libc::FAN_ONDIR != 0;
+
+    if event.mask & (libc::FAN_DELETE | libc::FAN_RENAME) != 0
+        && event.path.as_deref() == Some(ws_root_path) {
+        return Err(Error::new("Workspace was moved or deleted"));
+    }
+
+    let relative_path = workspace_relative_path(ws_root_path, event.path.as_ref());
+    let new_relative_path = workspace_relative_path(ws_root_path, event.new_path.as_ref());
+
+    let ignore_file = Path::new(IGNORE_FILE_NAME);
+    if relative_path.as_deref() == Some(ignore_file) || new_relative_path.as_deref() == Some(ignore_file) {
+        if event.mask & (libc::FAN_CLOSE_WRITE | libc::FAN_RENAME | libc::FAN_DELETE) != 0 {
+            return handle_ignore_file_change(ws_info, ignore_rules, tx);
+        }
+        return Ok(());
+    }
+
+    // Resources that are ignored, or moved into or out of an ignored directory, are handled as if
+    // they were located outside the workspace
+    let (relative_path, new_relative_path) = {
+        let ignore_rules = ignore_rules.read().unwrap();
+        let is_ignored = |path: &PathBuf| {
+            let ignored = ignore_rules.is_ignored(path, is_dir);
+            if ignored {
+                debug!("Dropping event for ignored resource '{:?}'", path);
+            }
+            ignored
+        };
+
+        (relative_path.filter(|path| !is_ignored(path)), new_relative_path.filter(|path| !is_ignored(path)))
+    };
+
+    if event.mask & libc::FAN_RENAME != 0 {
+        return match (relative_path, new_relative_path) {
+            (Some(from), Some(to)) => {
+                debug!("'{:?}' was moved to '{:?}'", from, to);
+                notify_move(tx, PathMove { from, to, is_dir })
+            },
+            // Resources moved into or out of the workspace are handled like created or deleted ones
+            (None, Some(relative_path)) | (Some(relative_path), None) => {
+                handle_created_or_deleted(relative_path, is_dir, tx)
+            },
+            (None, None) => Ok(())
+        };
+    }
+
+    let Some(relative_path) = relative_path else {
+        return Ok(());
+    };
+
+    if is_dir && event.mask & (libc::FAN_CREATE | libc::FAN_DELETE) != 0 {
+        return handle_created_or_deleted(relative_path, is_dir, tx);
+    }
+
+    // Changes of files, and changes of the attributes of directories, are synced individually
+    notify_file_change(tx, relative_path)
+}
+
+fn read_events(fanotify: &Fanotify, buffer: &mut [u8]) -> io::Result<usize> {
+    let res = unsafe { libc::read(fanotify.fd.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) };
+
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(res as usize)
+}
+
+pub(super) fn listen_for_events(
+    fanotify: &Fanotify,
+    ws_info: &WorkspaceInfo,
+    ignore_rules: &SharedIgnoreRules,
+    tx: &Sender<WatcherEvent>
+) {
+    let mut buffer = vec![0; EVENT_BUFFER_SIZE];
+
+    'event_reader: loop {
+        let len = match read_events(fanotify, &mut buffer) {
+            Ok(len) => len,
+            Err(error) if error.kind() == ErrorKind::Interrupted => {
+                warn!("Monitor loop reading fanotify events was interrupted: {error}");
+                warn!("Terminating fanotify-event reader loop...");
+                break;
+            },
+            Err(error) => {
+                error!("An error occurred while reading fs events: {error}");
+                error!("Terminating fanotify-event reader loop...");
+                break;
+            }
+        };
+
+        let events = match parse_events(&buffer[..len], |handle| open_handle(fanotify, handle)) {
+            Ok(events) => events,
+            Err(error) => {
+                error!("Error parsing fanotify events: {error}");
+                let _ = tx.send(WatcherEvent::Failed(format!("{error}")));
+                break;
+            }
+        };
+
+        for event in events {
+            let res = if event.mask & libc::FAN_Q_OVERFLOW != 0 {
+                // Since we don't know which changes were missed, the entire workspace is synced.
+                // Unlike with inotify, there are no watches that need to be rebuilt.
+                debug!("fanotify event queue overflowed!");
+                notify_change(tx, None)
+            } else {
+                handle_fanotify_event(event, &fanotify.ws_root_path, ws_info, ignore_rules, tx)
+            };
+
+            if let Err(error) = res {
+                error!("Error handling fanotify event: {error}");
+                let _ = tx.send(WatcherEvent::Failed(format!("{error}")));
+                break 'event_reader;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Information record reporting the resource with the given name in the directory with the
+    /// given handle
+    fn record(info_type: u8, handle: &[u8], name: &[u8]) -> Vec<u8> {
+        let mut record = vec![info_type, 0, 0, 0];
+        record.extend_from_slice(&[0; FSID_SIZE]);
+        record.extend_from_slice(&(handle.len() as u32).to_ne_bytes());
+        record.extend_from_slice(&1i32.to_ne_bytes());
+        record.extend_from_slice(handle);
+        record.extend_from_slice(name);
+        record.push(0);
+        // Records are padded to the alignment of the event metadata
+        record.resize(record.len().next_multiple_of(8), 0);
+
+        let len = (record.len() as u16).to_ne_bytes();
+        record[2..4].copy_from_slice(&len);
+        record
+    }
+
+    fn event(mask: u64, records: &[Vec<u8>]) -> Vec<u8> {
+        let metadata_len = size_of::<libc::fanotify_event_metadata>();
+        let metadata = libc::fanotify_event_metadata {
+            event_len: (metadata_len + records.iter().map(Vec::len).sum::<usize>()) as u32,
+            vers: libc::FANOTIFY_METADATA_VERSION,
+            reserved: 0,
+            metadata_len: metadata_len as u16,
+            mask,
+            fd: libc::FAN_NOFD,
+            pid: 0
+        };
+
+        let mut event = unsafe {
+            std::slice::from_raw_parts(&metadata as *const _ as *const u8, metadata_len)
+        }.to_vec();
+        for record in records {
+            event.extend_from_slice(record);
+        }
+        event
+    }
+
+    /// Opens the handles `[1]` and `[2]` as '/ws' and '/ws/dir', whereas other handles no longer exist
+    fn open_dir(handle: &[u8]) -> Result<Option<PathBuf>> {
+        Ok(match &handle[FILE_HANDLE_HEADER_SIZE..] {
+            [1] => Some(PathBuf::from("/ws")),
+            [2] => Some(PathBuf::from("/ws/dir")),
+            _ => None
+        })
+    }
+
+    #[test]
+    fn record_is_resolved_by_directory_and_name() {
+        let mut resolved_dirs = HashMap::new();
+        let mut open_dir = open_dir;
+
+        let path = resolve_record(&record(libc::FAN_EVENT_INFO_TYPE_DFID_NAME, &[2], b"file"), &mut resolved_dirs, &mut open_dir);
+        assert_eq!(path.ok(), Some(Some(PathBuf::from("/ws/dir/file"))));
+
+        let path = resolve_record(&record(libc::FAN_EVENT_INFO_TYPE_DFID_NAME, &[2], b"."), &mut resolved_dirs, &mut open_dir);
+        assert_eq!(path.ok(), Some(Some(PathBuf::from("/ws/dir"))));
+
+        let path = resolve_record(&record(libc::FAN_EVENT_INFO_TYPE_DFID_NAME, &[3], b"file"), &mut resolved_dirs, &mut open_dir);
+        assert_eq!(path.ok(), Some(None));
+    }
+
+    #[test]
+    fn directories_are_opened_once_per_read() {
+        let mut resolved_dirs = HashMap::new();
+        let mut nr_of_opened_dirs = 0;
+        let mut open_dir = |handle: &[u8]| {
+            nr_of_opened_dirs += 1;
+            open_dir(handle)
+        };
+
+        for name in [b"a", b"b"] {
+            let path = resolve_record(&record(libc::FAN_EVENT_INFO_TYPE_DFID_NAME, &[1], name), &mut resolved_dirs, &mut open_dir);
+            assert!(path.is_ok_and(|path| path.is_some()));
+        }
+        assert_eq!(nr_of_opened_dirs, 1);
+    }
+
+    #[test]
+    fn truncated_record_is_rejected() {
+        let record = record(libc::FAN_EVENT_INFO_TYPE_DFID_NAME, &[1, 2, 3, 4], b"");
+        let truncated = &record[..INFO_HEADER_SIZE + FSID_SIZE + FILE_HANDLE_HEADER_SIZE + 2];
+
+        assert!(resolve_record(truncated, &mut HashMap::new(), &mut open_dir).is_err());
+    }
+
+    #[test]
+    fn events_are_split_and_resolved() {
+        let mut buffer = event(libc::FAN_CREATE, &[record(libc::FAN_EVENT_INFO_TYPE_DFID_NAME, &[1], b"file")]);
+        buffer.extend(event(libc::FAN_RENAME | libc::FAN_ONDIR, &[
+            record(libc::FAN_EVENT_INFO_TYPE_OLD_DFID_NAME, &[1], b"old"),
+            record(libc::FAN_EVENT_INFO_TYPE_NEW_DFID_NAME, &[2], b"new")
+        ]));
+        buffer.extend(event(libc::FAN_DELETE, &[record(libc::FAN_EVENT_INFO_TYPE_DFID_NAME, &[3], b"file")]));
+
+        let Ok(events) = parse_events(&buffer, open_dir) else {
+            panic!("Failed to parse events");
+        };
+        assert_eq!(events.len(), 3);
+
+        assert_eq!(events[0].mask, libc::FAN_CREATE);
+        assert_eq!(events[0].path, Some(PathBuf::from("/ws/file")));
+        assert_eq!(events[0].new_path, None);
+
+        assert_eq!(events[1].mask, libc::FAN_RENAME | libc::FAN_ONDIR);
+        assert_eq!(events[1].path, Some(PathBuf::from("/ws/old")));
+        assert_eq!(events[1].new_path, Some(PathBuf::from("/ws/dir/new")));
+
+        assert_eq!(events[2].path, None);
+    }
+
+    #[test]
+    fn malformed_events_are_rejected() {
+        let buffer = event(libc::FAN_CREATE, &[record(libc::FAN_EVENT_INFO_TYPE_DFID_NAME, &[1], b"file")]);
+
+        assert!(parse_events(&buffer[..buffer.len() - 1], open_dir).is_err());
+
+        let mut unsupported_version = buffer.clone();
+        unsupported_version[4] = libc::FANOTIFY_METADATA_VERSION + 1;
+        assert!(parse_events(&unsupported_version, open_dir).is_err());
+
+        let mut zero_length_record = buffer;
+        let record_start = size_of::<libc::fanotify_event_metadata>();
+        zero_length_record[record_start + 2..record_start + 4].copy_from_slice(&0u16.to_ne_bytes());
+        assert!(parse_events(&zero_length_record, open_dir).is_err());
+    }
+
+    #[test]
+    fn events_with_inconsistent_lengths_are_rejected() {
+        let buffer = event(libc::FAN_CREATE, &[record(libc::FAN_EVENT_INFO_TYPE_DFID_NAME, &[1], b"file")]);
+
+        let mut zero_length_event = buffer.clone();
+        zero_length_event[0..4].copy_from_slice(&0u32.to_ne_bytes());
+        assert!(parse_events(&zero_length_event, open_dir).is_err());
+
+        let mut overlong_metadata = buffer.clone();
+        overlong_metadata[6..8].copy_from_slice(&(buffer.len() as u16 + 1).to_ne_bytes());
+        assert!(parse_events(&overlong_metadata, open_dir).is_err());
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_workspace_root() {
+        let ws_root_path = Path::new("/ws");
+
+        assert_eq!(workspace_relative_path(ws_root_path, Some(&PathBuf::from("/ws/dir/file"))), Some(PathBuf::from("dir/file")));
+        assert_eq!(workspace_relative_path(ws_root_path, Some(&PathBuf::from("/ws"))), None);
+        assert_eq!(workspace_relative_path(ws_root_path, Some(&PathBuf::from("/wsx/file"))), None);
+        assert_eq!(workspace_relative_path(ws_root_path, None), None);
+    }
+}
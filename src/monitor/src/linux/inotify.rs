@@ -1,25 +1,40 @@
 use std::cmp::Ordering;
 use std::ffi::OsStr;
+use std::io;
 use std::io::ErrorKind;
-use std::path::PathBuf;
-use inotify::{Event, EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+use inotify::{Event, EventMask, Events, Inotify, WatchDescriptor, WatchMask};
 use log::{debug, error, warn};
+use crate::ignore_rules::{IgnoreRules, IGNORE_FILE_NAME};
 use crate::linux::monitor_state;
-use crate::linux::monitor_state::{MonitorState, WatchMetadata};
+use crate::linux::{notify_change, notify_file_change, notify_move};
+use crate::linux::monitor_state::{MonitorState, PendingMove, WatchMetadata};
 use crate::models::{Error, WorkspaceInfo};
-use crate::sync::synchronize_workspace;
+use crate::sync::PathMove;
+use crate::sync_loop::WatcherEvent;
 use crate::util::fs::{concat_paths, get_subdir_names};
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// Time to wait for the 'IN_MOVED_TO' event of a resource reported by an 'IN_MOVED_FROM' event,
+/// after which the resource is considered to be moved out of the workspace. Both events are emitted
+/// by the same rename, so they are usually read at once.
+const MOVE_PAIRING_TIMEOUT: Duration = Duration::from_millis(500);
+
 fn get_watch_mask() -> WatchMask {
-    WatchMask::MODIFY |
+    // Files are synced once they are closed after writing rather than on every write, so that files
+    // being written are not synced half-written
     WatchMask::CLOSE_WRITE |
     WatchMask::CREATE |
     WatchMask::DELETE |
     WatchMask::DELETE_SELF |
     WatchMask::MOVE |
     WatchMask::MOVE_SELF |
+    // Changes of permissions, ownership, timestamps or extended attributes
+    WatchMask::ATTRIB |
     WatchMask::EXCL_UNLINK |
     // Since we only add watches for directories, we will not be notified if a file contained in the
     // ws tree is modified via a symlink outside the workspace. But this is outside wsync's scope anyway.
@@ -148,6 +163,12 @@ fn add_watches_recursively(
 
         for subdir_name in get_subdir_names(&full_path)? {
             let path = concat_paths(relative_subdir_path.as_ref(), Some(&subdir_name))?;
+
+            if state.ignore_rules.read().unwrap().is_ignored(&path, true) {
+                debug!("Not watching ignored directory '{:?}'", path);
+                continue;
+            }
+
             relative_subdir_paths.push(path);
         }
 
@@ -171,29 +192,90 @@ pub(super) fn init_inotify_instance(ws_info: &WorkspaceInfo, state: &mut Monitor
     Ok(inotify)
 }
 
-fn handle_queue_overflow(inotify: &mut Inotify, state: &mut MonitorState) -> Result<()> {
-    debug!("Inotify instance's event queue overflowed!");
+/// Handle a resource that was moved within the workspace, whose watches are kept with their paths
+/// being updated
+fn handle_move(state: &mut MonitorState, pending_move: PendingMove, relative_path: PathBuf, path: PathBuf, tx: &Sender<WatcherEvent>) -> Result<()> {
+    debug!("'{:?}' was moved to '{:?}'", pending_move.relative_path, relative_path);
+
+    if pending_move.is_dir {
+        state.move_watches(&pending_move.path, &path).map_err(|e| {
+            Error::new(format!("Unable to update the watches of moved directory '{:?}': {e}", pending_move.path))
+        })?;
+    }
+
+    notify_move(tx, PathMove { from: pending_move.relative_path, to: relative_path, is_dir: pending_move.is_dir })
+}
+
+/// Handle the resources whose 'IN_MOVED_TO' event was not received in time like deleted ones, as
+/// they were moved out of the workspace (or into an ignored directory)
+fn expire_pending_moves(inotify: &mut Inotify, state: &mut MonitorState, tx: &Sender<WatcherEvent>, now: Instant) -> Result<()> {
+    let expired: Vec<u32> = state.pending_moves
+        .iter()
+        .filter(|(_, pending_move)| now.duration_since(pending_move.received) >= MOVE_PAIRING_TIMEOUT)
+        .map(|(cookie, _)| *cookie)
+        .collect();
+
+    for cookie in expired {
+        let pending_move = state.pending_moves.remove(&cookie).unwrap();
+        debug!("'{:?}' was moved out of the workspace", pending_move.relative_path);
+
+        if !pending_move.is_dir {
+            notify_file_change(tx, pending_move.relative_path)?;
+            continue;
+        }
+
+        if let Some(dir_wd) = state.get_wd(&pending_move.path).cloned() {
+            let _ = remove_watches_recursively(inotify, state, &dir_wd);
+        }
+
+        notify_change(tx, pending_move.parent_relative_path)?;
+    }
+
+    Ok(())
+}
+
+fn rebuild_watches(inotify: &mut Inotify, state: &mut MonitorState, tx: &Sender<WatcherEvent>) -> Result<()> {
+    for wd in state.all_wds() {
+        let _ = inotify.watches().remove(wd);
+    }
 
     state.reset_state();
     add_watches_recursively(inotify, state, state.workspace_info.local_path.clone(), None).map_err(|e| {
         Error::new("Failed to rebuild state after inotify event queue overflow: {e}")
     })?;
 
-    synchronize_workspace(state.workspace_info, None).map_err(|e|
-        Error::new(format!("{e}"))
-    )?;
+    notify_change(tx, None)?;
 
     Ok(())
 }
 
-fn handle_inotify_event(event: Event<&OsStr>, inotify: &mut Inotify, state: &mut MonitorState) -> Result<()> {
+fn handle_queue_overflow(inotify: &mut Inotify, state: &mut MonitorState, tx: &Sender<WatcherEvent>) -> Result<()> {
+    debug!("Inotify instance's event queue overflowed!");
+    rebuild_watches(inotify, state, tx)
+}
+
+fn handle_ignore_file_change(inotify: &mut Inotify, state: &mut MonitorState, tx: &Sender<WatcherEvent>) -> Result<()> {
+    debug!("'{IGNORE_FILE_NAME}' changed. Reloading ignore rules...");
+
+    // Directories might have been (un)ignored, so rebuild all watches according to the new rules and
+    // sync the entire workspace.
+    IgnoreRules::reload_shared(&state.ignore_rules, state.workspace_info);
+    rebuild_watches(inotify, state, tx)
+}
+
+fn handle_inotify_event(
+    event: Event<&OsStr>,
+    inotify: &mut Inotify,
+    state: &mut MonitorState,
+    tx: &Sender<WatcherEvent>
+) -> Result<()> {
     debug!("{:?}", event);
 
     if event.mask.contains(EventMask::Q_OVERFLOW) {
         // Since we don't know what events, and therewith what workspace changes, we missed due to
         // the overflow, we sync the ws with all remote workspaces starting from the ws root and
         // also rebuild the internal state.
-        return handle_queue_overflow(inotify, state);
+        return handle_queue_overflow(inotify, state, tx);
     }
 
     match state.contains_wd(&event.wd) {
@@ -231,6 +313,22 @@ fn handle_inotify_event(event: Event<&OsStr>, inotify: &mut Inotify, state: &mut
         Some(concat_paths(metadata.relative_path.as_ref(), event_name_path.as_ref())?)
     };
 
+    if let Some(relative_resource_path) = relative_resource_path.as_ref() {
+        let is_ignore_file = relative_resource_path == Path::new(IGNORE_FILE_NAME);
+
+        if is_ignore_file && event.mask.intersects(
+            EventMask::CLOSE_WRITE | EventMask::MOVED_TO | EventMask::MOVED_FROM | EventMask::DELETE
+        ) {
+            return handle_ignore_file_change(inotify, state, tx);
+        }
+
+        let is_dir = event.mask.contains(EventMask::ISDIR);
+        if state.ignore_rules.read().unwrap().is_ignored(relative_resource_path, is_dir) {
+            debug!("Dropping event for ignored resource '{:?}'", relative_resource_path);
+            return Ok(());
+        }
+    }
+
     if event.mask.contains(EventMask::DELETE_SELF) || event.mask.contains(EventMask::MOVE_SELF) {
 
         if metadata.relative_path.is_some() {
@@ -243,21 +341,60 @@ fn handle_inotify_event(event: Event<&OsStr>, inotify: &mut Inotify, state: &mut
         return Err(Error::new("Workspace was moved or deleted"));
     }
 
-    if event.mask.contains(EventMask::CREATE) || event.mask.contains(EventMask::MOVED_TO) {
+    if event.mask.contains(EventMask::ATTRIB) {
+        // Only the attributes of the resource changed, which is synced like a file change, without
+        // syncing the content of directories. Each watched directory also reports changes of its
+        // own attributes, which are either reported by its parent as well or, for the workspace
+        // root, picked up by the next full sync.
+        if let Some(relative_resource_path) = relative_resource_path
+            && event_name_path.is_some() {
+            notify_file_change(tx, relative_resource_path)?;
+        }
 
-        if !event.mask.contains(EventMask::ISDIR) {
-            // File creation causes both an 'IN_CREATE' and an 'IN_CLOSE_WRITE' event to be emitted.
-            // To prevent unnecessary duplicate syncs with the remote system(s), we ignore create
-            // events for files.
-            return Ok(());
+        return Ok(());
+    }
+
+    let is_dir = event.mask.contains(EventMask::ISDIR);
+
+    if event.mask.contains(EventMask::MOVED_FROM)
+        && let Some(relative_resource_path) = relative_resource_path.clone() {
+        // The resource is either moved within the workspace, in which case the 'IN_MOVED_TO' event
+        // with the same cookie follows, or out of it, which is handled once the pairing timed out
+        state.pending_moves.insert(event.cookie, PendingMove {
+            relative_path: relative_resource_path,
+            path: resource_path,
+            parent_relative_path: metadata.relative_path.clone(),
+            is_dir,
+            received: Instant::now()
+        });
+
+        return Ok(());
+    }
+
+    // Resources moved into the workspace from outside of it are handled like created ones
+    if event.mask.contains(EventMask::MOVED_TO)
+        && let Some(relative_resource_path) = relative_resource_path.clone()
+        && let Some(pending_move) = state.pending_moves.remove(&event.cookie) {
+        return handle_move(state, pending_move, relative_resource_path, resource_path, tx);
+    }
+
+    if !is_dir {
+        // Changes of files are synced individually. Duplicate notifications, e.g. due to a file
+        // creation causing both an 'IN_CREATE' and an 'IN_CLOSE_WRITE' event, are coalesced by the
+        // sync loop.
+        if let Some(relative_resource_path) = relative_resource_path {
+            notify_file_change(tx, relative_resource_path)?;
         }
 
+        return Ok(());
+    }
+
+    if event.mask.contains(EventMask::CREATE) || event.mask.contains(EventMask::MOVED_TO) {
         debug!("Registering watches for subdirectory (tree) '{:?}'", relative_resource_path);
         add_watches_recursively(inotify, state, metadata.ws_root_path.clone(), relative_resource_path.clone())?;
     }
 
-    if event.mask.contains(EventMask::ISDIR)
-        && (event.mask.intersects(EventMask::DELETE | EventMask::MOVED_FROM | EventMask::UNMOUNT)) {
+    if event.mask.intersects(EventMask::DELETE | EventMask::UNMOUNT) {
 
         let dir_wd = state
             .get_wd(&resource_path)
@@ -266,20 +403,48 @@ fn handle_inotify_event(event: Event<&OsStr>, inotify: &mut Inotify, state: &mut
         let _ = remove_watches_recursively(inotify, state, &dir_wd);
     }
 
-    synchronize_workspace(state.workspace_info, metadata.relative_path.as_ref()).map_err(|e| {
-        Error::new(format!("{e:?}"))
-    })?;
+    notify_change(tx, metadata.relative_path.clone())?;
 
     Ok(())
 }
 
-pub(super) fn listen_for_events(inotify: &mut Inotify, state: &mut MonitorState) {
+/// Wait until events can be read, but only until the pairing of the pending moves times out.
+/// Returns `None` if no events were read.
+fn read_events<'a>(inotify: &mut Inotify, state: &MonitorState, buffer: &'a mut [u8]) -> io::Result<Option<Events<'a>>> {
+    let pairing_deadline = state.pending_moves
+        .values()
+        .map(|pending_move| pending_move.received + MOVE_PAIRING_TIMEOUT)
+        .min();
+
+    if let Some(deadline) = pairing_deadline {
+        // Rounded up, so that the deadline has passed once the timeout elapsed
+        let timeout = deadline.saturating_duration_since(Instant::now()) + Duration::from_micros(999);
+        let mut poll_fd = libc::pollfd { fd: inotify.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+
+        match unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis() as libc::c_int) } {
+            -1 => return Err(io::Error::last_os_error()),
+            0 => return Ok(None),
+            _ => {}
+        }
+    }
+
+    inotify.read_events_blocking(buffer).map(Some)
+}
+
+pub(super) fn listen_for_events(inotify: &mut Inotify, state: &mut MonitorState, tx: &Sender<WatcherEvent>) {
     let mut buffer = [0; 4096];
 
     'event_reader: loop {
 
-        let events = match inotify.read_events_blocking(&mut buffer) {
-            Ok(events) => events,
+        if let Err(error) = expire_pending_moves(inotify, state, tx, Instant::now()) {
+            error!("Error handling resources moved out of the workspace: {error}");
+            let _ = tx.send(WatcherEvent::Failed(format!("{error}")));
+            break;
+        }
+
+        let events = match read_events(inotify, state, &mut buffer) {
+            Ok(Some(events)) => events,
+            Ok(None) => continue,
             Err(error) if error.kind() == ErrorKind::Interrupted => {
                 warn!("Monitor loop reading inotify events was interrupted: {error}");
                 warn!("Terminating inotify-event reader loop...");
@@ -293,8 +458,9 @@ pub(super) fn listen_for_events(inotify: &mut Inotify, state: &mut MonitorState)
         };
 
         for event in events {
-            if let Err(error) = handle_inotify_event(event, inotify, state) {
+            if let Err(error) = handle_inotify_event(event, inotify, state, tx) {
                 error!("Error handling inotify event: {error}");
+                let _ = tx.send(WatcherEvent::Failed(format!("{error}")));
                 break 'event_reader;
             }
         }
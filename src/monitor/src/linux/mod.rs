@@ -1,28 +1,106 @@
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::thread;
+use log::warn;
+use crate::control::{spawn_command_forwarder, CommandReader};
+use crate::deletion_guard::DeletionGuard;
+use crate::ignore_rules::SharedIgnoreRules;
+use crate::linux::fanotify::init_fanotify_instance;
 use crate::linux::inotify::{init_inotify_instance, listen_for_events};
 use crate::linux::monitor_state::MonitorState;
-use crate::linux::signals::install_signal_handlers;
-use crate::models::WorkspaceInfo;
+use crate::linux::signals::{block_sigint, install_signal_handlers};
+use crate::models::{Error, Watcher, WorkspaceInfo};
+use crate::retry_queue::RetryQueue;
+use crate::sync::PathMove;
+use crate::sync_loop::{run_sync_loop, WatcherEvent};
 use crate::util::error_exit;
 
 pub(self) mod monitor_state;
 pub(self) mod inotify;
+mod fanotify;
 pub(self) mod signals;
 
-pub(crate) fn run_fs_listener(workspace_info: WorkspaceInfo) {
+fn notify_change(tx: &Sender<WatcherEvent>, relative_path: Option<PathBuf>) -> Result<(), Error> {
+    tx.send(WatcherEvent::Changed(relative_path)).map_err(|e| {
+        Error::new(format!("Unable to pass change to the sync loop: {e}"))
+    })
+}
+
+fn notify_file_change(tx: &Sender<WatcherEvent>, relative_path: PathBuf) -> Result<(), Error> {
+    tx.send(WatcherEvent::FileChanged(relative_path)).map_err(|e| {
+        Error::new(format!("Unable to pass change to the sync loop: {e}"))
+    })
+}
+
+fn notify_move(tx: &Sender<WatcherEvent>, path_move: PathMove) -> Result<(), Error> {
+    tx.send(WatcherEvent::Moved(path_move)).map_err(|e| {
+        Error::new(format!("Unable to pass change to the sync loop: {e}"))
+    })
+}
+
+pub(crate) fn run_fs_listener(
+    workspace_info: WorkspaceInfo,
+    ignore_rules: SharedIgnoreRules,
+    retry_queue: RetryQueue,
+    deletion_guard: DeletionGuard,
+    commands: CommandReader
+) {
 
     let _ = install_signal_handlers().unwrap_or_else(|e| {
         error_exit(Some(format!("Unable to install signal handler(s): {}", e.msg)))
     });
 
-    let mut state = MonitorState::default(&workspace_info);
+    let (tx, rx) = mpsc::channel::<WatcherEvent>();
+    let watcher_ws_info = workspace_info.clone();
+    let watcher_ignore_rules = ignore_rules.clone();
+    let command_tx = tx.clone();
+    let (ready_tx, ready_rx) = mpsc::channel::<()>();
 
-    let mut inotify = init_inotify_instance(&workspace_info, &mut state).unwrap_or_else(|e| {
-        error_exit(Some(format!("Unable to initialize inotify instance: {}", e.msg)))
-    });
+    // The watcher runs on its own thread and passes the detected changes to the sync loop, which
+    // debounces them before syncing with the remote workspaces.
+    thread::spawn(move || {
+        if watcher_ws_info.watcher == Watcher::Fanotify {
+            match init_fanotify_instance(&watcher_ws_info) {
+                Ok(fanotify) => {
+                    let _ = ready_tx.send(());
+
+                    fanotify::listen_for_events(&fanotify, &watcher_ws_info, &watcher_ignore_rules, &tx);
+                    let _ = tx.send(WatcherEvent::Stopped);
+                    return;
+                },
+                Err(e) => warn!("Watching the workspace via inotify, as fanotify is not available: {e}")
+            }
+        }
 
-    listen_for_events(&mut inotify, &mut state);
+        let mut state = MonitorState::default(&watcher_ws_info, watcher_ignore_rules);
 
-    let _ = inotify.close().unwrap_or_else(|e| {
-        error_exit(Some(format!("Failed to close inotify instance fd: {e}")))
+        let mut inotify = init_inotify_instance(&watcher_ws_info, &mut state).unwrap_or_else(|e| {
+            error_exit(Some(format!("Unable to initialize inotify instance: {}", e.msg)))
+        });
+        let _ = ready_tx.send(());
+
+        listen_for_events(&mut inotify, &mut state, &tx);
+        let _ = tx.send(WatcherEvent::Stopped);
+
+        let _ = inotify.close().unwrap_or_else(|e| {
+            error_exit(Some(format!("Failed to close inotify instance fd: {e}")))
+        });
+    });
+
+    // SIGINT must be delivered to the watcher thread, as only the interrupted blocking read lets it
+    // break out of its event-reader-loop.
+    block_sigint().unwrap_or_else(|e| {
+        error_exit(Some(format!("Unable to block SIGINT for the sync loop: {}", e.msg)))
     });
+
+    // Spawned after blocking SIGINT, so that the thread inherits the signal mask
+    spawn_command_forwarder(commands, command_tx);
+
+    // The initial sync must not start before the workspace is watched
+    if ready_rx.recv().is_err() {
+        error_exit(Some(String::from("The watcher stopped before watching the workspace")));
+    }
+
+    run_sync_loop(rx, &workspace_info, &ignore_rules, retry_queue, deletion_guard);
 }
@@ -1,7 +1,9 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use inotify::WatchDescriptor;
+use crate::ignore_rules::SharedIgnoreRules;
 use crate::models::WorkspaceInfo;
 
 pub(super) enum Error {
@@ -12,9 +14,23 @@ pub(super) enum Error {
 
 pub(super) struct MonitorState<'ws_info> {
     pub(super) workspace_info: &'ws_info WorkspaceInfo,
+    pub(super) ignore_rules: SharedIgnoreRules,
+    /// Resources reported by 'IN_MOVED_FROM' events whose 'IN_MOVED_TO' event is yet to be received,
+    /// by the cookie shared by both events
+    pub(super) pending_moves: HashMap<u32, PendingMove>,
     pub(self) inotify_watch_state: InotifyWatchState
 }
 
+pub(super) struct PendingMove {
+    pub(super) relative_path: PathBuf,
+    pub(super) path: PathBuf,
+    /// Workspace-relative path of the directory the resource was moved out of, `None` being the
+    /// workspace root
+    pub(super) parent_relative_path: Option<PathBuf>,
+    pub(super) is_dir: bool,
+    pub(super) received: Instant
+}
+
 pub(self) struct InotifyWatchState {
     pub(self) wd_to_metadata: HashMap<WatchDescriptor, WatchMetadata>,
     pub(self) path_to_wd: HashMap<PathBuf, WatchDescriptor>
@@ -30,14 +46,21 @@ pub(super) struct WatchMetadata {
 }
 
 impl<'a> MonitorState<'a> {
-    pub(super) fn default(ws_info: &'a WorkspaceInfo) -> Self {
+    pub(super) fn default(ws_info: &'a WorkspaceInfo, ignore_rules: SharedIgnoreRules) -> Self {
         MonitorState {
             workspace_info: ws_info,
+            ignore_rules,
+            pending_moves: HashMap::new(),
             inotify_watch_state: InotifyWatchState::default()
         }
     }
 
+    pub(super) fn all_wds(&self) -> Vec<WatchDescriptor> {
+        self.inotify_watch_state.wd_to_metadata.keys().cloned().collect()
+    }
+
     pub(super) fn reset_state(&mut self) {
+        self.pending_moves.clear();
         self.inotify_watch_state.clear_state();
     }
 
@@ -53,6 +76,37 @@ impl<'a> MonitorState<'a> {
         self.inotify_watch_state.rm_watch_metadata(&wd)
     }
 
+    /// Update the watches of the directory at `from` and of its descendants in place, after the
+    /// directory was moved to `to` within the workspace
+    pub(super) fn move_watches(&mut self, from: &Path, to: &Path) -> Result<(), Error> {
+        let wd = self.get_wd(&from.to_path_buf())
+            .ok_or(Error::NotFound(format!("No watch registered for '{:?}'", from)))?
+            .clone();
+
+        // Directories are only replaced by a move if they are empty, so the replaced directory has
+        // no descendant watches. Its watch is removed by the kernel.
+        if let Some(replaced_wd) = self.get_wd(&to.to_path_buf()).cloned() {
+            self.parent_metadata_mut(to)?.child_watches.retain(|child_wd| *child_wd != replaced_wd);
+            self.rm_watch_metadata(&replaced_wd)?;
+        }
+
+        // The directory is no longer a child of its previous parent directory, but of the new one
+        self.parent_metadata_mut(from)?.child_watches.retain(|child_wd| *child_wd != wd);
+        self.parent_metadata_mut(to)?.child_watches.push(wd);
+
+        self.inotify_watch_state.move_watches(from, to)
+    }
+
+    fn parent_metadata_mut(&mut self, path: &Path) -> Result<&mut WatchMetadata, Error> {
+        let parent_path = path
+            .parent()
+            .ok_or(Error::NotFound(format!("Unable to get parent of '{:?}'", path)))?
+            .to_path_buf();
+
+        self.get_metadata_mut_from_path(&parent_path)
+            .ok_or(Error::InconsistentState(format!("No metadata stored for '{:?}'", parent_path)))
+    }
+
     pub(super) fn get_wd(&self, path: &PathBuf) -> Option<&WatchDescriptor> {
         self.inotify_watch_state.path_to_wd.get(path)
     }
@@ -122,6 +176,30 @@ impl InotifyWatchState {
         self.path_to_wd.insert(path.clone(), wd.clone());
     }
 
+    pub(self) fn move_watches(&mut self, from: &Path, to: &Path) -> Result<(), Error> {
+        let moved_paths: Vec<PathBuf> = self.path_to_wd
+            .keys()
+            .filter(|path| path.starts_with(from))
+            .cloned()
+            .collect();
+
+        for path in moved_paths {
+            let wd = self.path_to_wd.remove(&path).unwrap();
+            let metadata = self.wd_to_metadata
+                .get_mut(&wd)
+                .ok_or(Error::InconsistentState("wd-to-metadata mapping missing".to_string()))?;
+
+            let suffix = path.strip_prefix(from).unwrap();
+            let new_path = if suffix.as_os_str().is_empty() { to.to_path_buf() } else { to.join(suffix) };
+
+            metadata.relative_path = new_path.strip_prefix(&metadata.ws_root_path).ok().map(Path::to_path_buf);
+            metadata.path = new_path.clone();
+            self.path_to_wd.insert(new_path, wd);
+        }
+
+        Ok(())
+    }
+
     pub(self) fn rm_watch_metadata(&mut self, wd: &WatchDescriptor) -> Result<(), Error> {
         let contained = self.contains_wd(wd)?;
 
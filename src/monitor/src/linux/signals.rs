@@ -21,3 +21,14 @@ pub(super) fn install_signal_handlers() -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Block SIGINT for the calling thread, so that the signal is delivered to a thread that did not
+/// block it.
+pub(super) fn block_sigint() -> Result<(), Error> {
+    let mut sigset = SigSet::empty();
+    sigset.add(Signal::SIGINT);
+
+    sigset.thread_block().map_err(|e| {
+        Error::new(format!("Unable to block SIGINT: {e}"))
+    })
+}
@@ -1,11 +1,18 @@
-use std::{path::PathBuf, sync::mpsc::Receiver};
-use log::{debug, error, info, warn};
-use notify::Event;
-use crate::{models::{Error, WorkspaceInfo}, sync::synchronize_workspace, util::fs::strip_ws_root_prefix};
+use std::{path::PathBuf, sync::mpsc::Sender};
+use log::{debug, error, warn};
+use notify::{Event, EventKind};
+use notify::event::{CreateKind, ModifyKind, RemoveKind};
+use crate::{models::{Error, WorkspaceInfo}, sync_loop::WatcherEvent, util::fs::strip_ws_root_prefix};
+use crate::ignore_rules::{IgnoreRules, SharedIgnoreRules, IGNORE_FILE_NAME};
 
 type Result<T> = std::result::Result<T, Error>;
 
-fn handle_event(event: Event, ws_info: &WorkspaceInfo) -> Result<()> {
+fn handle_event(
+    event: Event,
+    ws_info: &WorkspaceInfo,
+    ignore_rules: &SharedIgnoreRules,
+    tx: &Sender<WatcherEvent>
+) -> Result<()> {
     debug!("{:?}", event);
 
     for target_path in event.paths {
@@ -16,7 +23,37 @@ fn handle_event(event: Event, ws_info: &WorkspaceInfo) -> Result<()> {
             if event.kind.is_remove() {
                 return Err(Error::new("Workspace was removed"));
             }
+        } else if target_path == ws_info.local_path.join(IGNORE_FILE_NAME) {
+            debug!("'{IGNORE_FILE_NAME}' changed. Reloading ignore rules...");
+
+            // Since the set of ignored resources might have changed, sync the entire workspace
+            IgnoreRules::reload_shared(ignore_rules, ws_info);
         } else {
+            let is_dir = target_path.is_dir() || matches!(
+                event.kind,
+                EventKind::Create(CreateKind::Folder) | EventKind::Remove(RemoveKind::Folder)
+            );
+
+            let relative_target_path = strip_ws_root_prefix(&ws_info.local_path, &target_path);
+
+            if let Ok(Some(relative_target_path)) = &relative_target_path
+                && ignore_rules.read().unwrap().is_ignored(relative_target_path, is_dir) {
+                debug!("Dropping event for ignored resource '{:?}'", relative_target_path);
+                continue;
+            }
+
+            // Changes of files are synced individually. Since removed or moved away resources
+            // cannot be inspected anymore, they are treated as files too, unless the event states
+            // otherwise. Directories whose attributes changed are synced like files as well,
+            // without syncing their content.
+            let is_metadata_change = matches!(event.kind, EventKind::Modify(ModifyKind::Metadata(_)));
+            if (!is_dir || is_metadata_change) && let Ok(Some(relative_target_path)) = relative_target_path {
+                tx.send(WatcherEvent::FileChanged(relative_target_path)).map_err(|e| {
+                    Error::new(format!("Unable to pass change to the sync loop: {e}"))
+                })?;
+                continue;
+            }
+
             // Get the parent directory path, because the directory containing the resource in question should be synced.
             let parent_path = match target_path.parent() {
                 Some(parent_path) => parent_path.to_path_buf(),
@@ -32,30 +69,45 @@ fn handle_event(event: Event, ws_info: &WorkspaceInfo) -> Result<()> {
             };
         }
 
-        synchronize_workspace(ws_info, rel_path_to_sync.as_ref()).map_err(|e| {
-            Error::new(format!("{e:?}"))
+        tx.send(WatcherEvent::Changed(rel_path_to_sync)).map_err(|e| {
+            Error::new(format!("Unable to pass change to the sync loop: {e}"))
         })?;
     }
 
     Ok(())
 }
 
-pub(in crate::macos) fn listen_for_events(rx: Receiver<notify::Result<Event>>, ws_info: &WorkspaceInfo) {
+/// Returns the handler invoked by the `notify` watcher for every fs event, which forwards the
+/// detected changes to the sync loop.
+pub(in crate::macos) fn event_forwarder(
+    ws_info: WorkspaceInfo,
+    ignore_rules: SharedIgnoreRules,
+    tx: Sender<WatcherEvent>
+) -> impl FnMut(notify::Result<Event>) + Send + 'static {
 
-    for result in rx {
-        match result {
-            Ok(event) => {
-                if let Err(error) = handle_event(event, ws_info) {
+    let mut failed = false;
+
+    move |result| {
+        if failed {
+            return;
+        }
+
+        let error = match result {
+            Ok(event) => match handle_event(event, &ws_info, &ignore_rules, &tx) {
+                Ok(()) => return,
+                Err(error) => {
                     error!("Error handling fs event: {error}");
-                    break;
+                    format!("{error}")
                 }
             },
             Err(e) => {
                 error!("An error occurred while reading fs events: {:?}", e);
                 error!("Terminating fs-event-reader loop...");
-                break;
+                format!("{e:?}")
             }
-        }
-    }
+        };
 
+        failed = true;
+        let _ = tx.send(WatcherEvent::Failed(error));
+    }
 }
@@ -1,14 +1,29 @@
 use std::sync::mpsc;
-use notify::{Event, Result, Watcher};
-use crate::{macos::event_handler::listen_for_events, models::WorkspaceInfo, util::error_exit};
+use notify::Watcher;
+use crate::{macos::event_handler::event_forwarder, models::WorkspaceInfo, util::error_exit};
+use crate::control::{spawn_command_forwarder, CommandReader};
+use crate::deletion_guard::DeletionGuard;
+use crate::ignore_rules::SharedIgnoreRules;
+use crate::retry_queue::RetryQueue;
+use crate::sync_loop::{run_sync_loop, WatcherEvent};
 
 pub(self) mod event_handler;
 
-pub(crate) fn run_fs_listener(workspace_info: WorkspaceInfo) {
+pub(crate) fn run_fs_listener(
+    workspace_info: WorkspaceInfo,
+    ignore_rules: SharedIgnoreRules,
+    retry_queue: RetryQueue,
+    deletion_guard: DeletionGuard,
+    commands: CommandReader
+) {
 
-    let (tx, rx) = mpsc::channel::<Result<Event>>();
+    let (tx, rx) = mpsc::channel::<WatcherEvent>();
 
-    let mut watcher = notify::recommended_watcher(tx).unwrap_or_else(|e| {
+    spawn_command_forwarder(commands, tx.clone());
+
+    let forwarder = event_forwarder(workspace_info.clone(), ignore_rules.clone(), tx);
+
+    let mut watcher = notify::recommended_watcher(forwarder).unwrap_or_else(|e| {
         error_exit(Some(format!("Unable to create watcher: {:?}", e)))
     });
 
@@ -16,5 +31,5 @@ pub(crate) fn run_fs_listener(workspace_info: WorkspaceInfo) {
         error_exit(Some(format!("Unable to start watcher: {:?}", e)))
     });
 
-    listen_for_events(rx, &workspace_info);
+    run_sync_loop(rx, &workspace_info, &ignore_rules, retry_queue, deletion_guard);
 }
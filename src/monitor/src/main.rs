@@ -1,24 +1,37 @@
-use std::io;
-use std::io::{BufReader, Read, Stdin};
+use std::{env, io};
+use std::io::BufReader;
+use std::path::{Component, Path, PathBuf};
 use log::debug;
-use serde::de::DeserializeOwned;
-use serde_json::{Deserializer, StreamDeserializer};
-use serde_json::de::IoRead;
+use serde::Deserialize;
+use serde_json::Deserializer;
 use util::log::setup_logging;
+use crate::control::CommandReader;
+use crate::deletion_guard::DeletionGuard;
+use crate::ignore_rules::IgnoreRules;
+use crate::journal::Journal;
 use crate::models::{Error, WorkspaceInfo};
-use crate::sync::synchronize_workspace;
+use crate::retry_queue::RetryQueue;
+use crate::sync::{list_versions, preview_workspace, restore_version};
 use crate::util::error_exit;
 
 mod util;
 mod linux;
 mod macos;
 mod sync;
+mod sync_loop;
+mod debounce;
+mod ignore_rules;
+mod retry_queue;
+mod journal;
 mod models;
-
-fn get_json_deserializer<R: Read, T: DeserializeOwned>(reader: R) -> StreamDeserializer<'static, IoRead<BufReader<R>>, T> {
-    let r = BufReader::new(reader);
-    Deserializer::from_reader(r).into_iter::<T>()
-}
+mod events;
+mod poller;
+mod control;
+mod deletion_guard;
+mod schedule;
+mod initial_sync;
+mod hooks;
+mod permits;
 
 fn validate_workspace_info(workspace_info: &WorkspaceInfo) -> Result<(), Error> {
     if !workspace_info.local_path.exists() {
@@ -36,40 +49,148 @@ fn validate_workspace_info(workspace_info: &WorkspaceInfo) -> Result<(), Error>
     Ok(())
 }
 
-fn get_workspace_information() -> Result<WorkspaceInfo, Error> {
+/// Read the workspace information from stdin. The returned reader yields the commands subsequently
+/// sent by the daemon.
+fn get_workspace_information() -> Result<(WorkspaceInfo, CommandReader), Error> {
     debug!("Attempting to get and parse workspace information....");
 
-    let mut deserializer = get_json_deserializer::<Stdin, WorkspaceInfo>(io::stdin());
-    let data = deserializer.next();
+    let mut deserializer = Deserializer::from_reader(BufReader::new(io::stdin()));
 
-    let data = data
-        .ok_or_else(|| Error::new("No input workspace information found"))?
-        .map_err(|e| Error::new(format!("Unable to read input workspace information: {e}")))?
-        ;
+    let data = WorkspaceInfo::deserialize(&mut deserializer).map_err(|e| {
+        if e.is_eof() {
+            Error::new("No input workspace information found")
+        } else {
+            Error::new(format!("Unable to read input workspace information: {e}"))
+        }
+    })?;
 
     debug!("Successfully got workspace information!");
 
     validate_workspace_info(&data)?;
-    Ok(data)
+    Ok((data, deserializer))
+}
+
+/// Passed by the daemon to only determine the changes a sync would make, which are written to
+/// stdout instead of watching the workspace
+const PREVIEW_ARG: &str = "--preview";
+
+/// Passed by the daemon along with a workspace-relative path to list the versions of the file kept
+/// in the remote workspaces, which are written to stdout instead of watching the workspace
+const LIST_VERSIONS_ARG: &str = "--list-versions";
+
+/// Passed by the daemon along with a workspace-relative path and a version to restore that version
+/// from the only remote workspace given in the workspace information
+const RESTORE_VERSION_ARG: &str = "--restore-version";
+
+/// Passed by the daemon if each transfer must wait for a permit of the daemon, which coordinates
+/// the transfers of all monitors
+const SYNC_PERMITS_ARG: &str = "--sync-permits";
+
+/// Tasks the monitor performs instead of watching the workspace
+enum OneShotTask {
+    Preview,
+    ListVersions(PathBuf),
+    RestoreVersion(PathBuf, String)
+}
+
+fn get_one_shot_task(args: &[String]) -> Result<Option<OneShotTask>, Error> {
+    let task = match args.first().map(String::as_str) {
+        None => return Ok(None),
+        Some(PREVIEW_ARG) => OneShotTask::Preview,
+        Some(LIST_VERSIONS_ARG) => match &args[1..] {
+            [path] => OneShotTask::ListVersions(PathBuf::from(path)),
+            _ => return Err(Error::new(format!("Usage: {LIST_VERSIONS_ARG} <PATH>")))
+        },
+        Some(RESTORE_VERSION_ARG) => match &args[1..] {
+            [path, version] => OneShotTask::RestoreVersion(PathBuf::from(path), version.clone()),
+            _ => return Err(Error::new(format!("Usage: {RESTORE_VERSION_ARG} <PATH> <VERSION>")))
+        },
+        Some(arg) => return Err(Error::new(format!("Unknown argument '{arg}'")))
+    };
+
+    if let OneShotTask::ListVersions(path) | OneShotTask::RestoreVersion(path, _) = &task {
+        let is_workspace_relative = path.components().next().is_some()
+            && path.components().all(|component| matches!(component, Component::Normal(_)));
+
+        if !is_workspace_relative {
+            return Err(Error::new(format!("'{}' is not a path relative to the workspace root", path.display())));
+        }
+    }
+
+    if let OneShotTask::RestoreVersion(_, version) = &task
+        && (version.is_empty() || !version.bytes().all(|c| c.is_ascii_digit())) {
+        return Err(Error::new(format!("'{version}' is not a valid version")));
+    }
+
+    Ok(Some(task))
+}
+
+fn run_one_shot_task(workspace: &WorkspaceInfo, task: OneShotTask) {
+    let ignore_rules = IgnoreRules::load(workspace).unwrap_or_else(|e| {
+        error_exit(Some(format!("Unable to load ignore rules: {e}")))
+    });
+
+    let result = match task {
+        OneShotTask::Preview => serde_json::to_writer(io::stdout(), &preview_workspace(workspace, &ignore_rules)),
+        OneShotTask::ListVersions(path) => {
+            serde_json::to_writer(io::stdout(), &list_versions(workspace, &path, &ignore_rules))
+        },
+        OneShotTask::RestoreVersion(path, version) => {
+            let Some(remote_workspace) = workspace.remote_workspaces.first() else {
+                error_exit(Some(String::from("No remote workspace to restore the version from given")));
+            };
+
+            let restore = restore_version(workspace, remote_workspace, Path::new(&path), &version, &ignore_rules);
+            serde_json::to_writer(io::stdout(), &restore)
+        }
+    };
+
+    result.unwrap_or_else(|e| {
+        error_exit(Some(format!("Unable to write result: {e}")))
+    });
 }
 
 fn main() {
     setup_logging();
 
-    let workspace: WorkspaceInfo = get_workspace_information().unwrap_or_else(|e| {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let sync_permits = args.first().is_some_and(|arg| arg == SYNC_PERMITS_ARG);
+    if sync_permits {
+        args.remove(0);
+    }
+
+    let one_shot_task = get_one_shot_task(&args).unwrap_or_else(|e| {
         error_exit(Some(format!("{e}")));
     });
 
-    // To account for possible workspace changes that happened while the monitor was inactive, sync
-    // the entire ws with all remote workspaces.
-    let _ = synchronize_workspace(&workspace, None).unwrap_or_else(|e| {
-        error_exit(Some(format!("Failed initial sync of workspace with remote systems: {e:?}")))
+    let (workspace, commands) = get_workspace_information().unwrap_or_else(|e| {
+        error_exit(Some(format!("{e}")));
     });
 
+    if let Some(task) = one_shot_task {
+        run_one_shot_task(&workspace, task);
+        return;
+    }
+
+    if sync_permits {
+        permits::enable();
+    }
+
+    let ignore_rules = IgnoreRules::load_shared(&workspace).unwrap_or_else(|e| {
+        error_exit(Some(format!("Unable to load ignore rules: {e}")))
+    });
+
+    let retry_queue = RetryQueue::new(Journal::open(&workspace));
+
+    let initial_ignore_rules = ignore_rules.read().unwrap().clone();
+    let deletion_guard = DeletionGuard::open(&workspace, &initial_ignore_rules);
+
+    // The initial sync is performed by the sync loop once the watcher is watching the workspace, so
+    // that changes made during the initial sync are not missed
     if cfg!(target_os = "linux") {
-        linux::run_fs_listener(workspace);
+        linux::run_fs_listener(workspace, ignore_rules, retry_queue, deletion_guard, commands);
     } else if cfg!(target_os = "macos") {
-        macos::run_fs_listener(workspace);
+        macos::run_fs_listener(workspace, ignore_rules, retry_queue, deletion_guard, commands);
     } else {
         panic!("OS not supported (yet)");
     }
@@ -6,14 +6,225 @@ use serde::{Deserialize, Serialize};
 pub(crate) struct WorkspaceInfo {
     pub(crate) name: String,
     pub(crate) local_path: PathBuf,
-    pub(crate) remote_workspaces: Vec<RemoteWorkspace>
+    pub(crate) remote_workspaces: Vec<RemoteWorkspace>,
+    #[serde(default)]
+    pub(crate) debounce: DebounceSettings,
+    /// Maximum number of remote workspaces that are synced concurrently
+    #[serde(default = "default_max_parallel_syncs")]
+    pub(crate) max_parallel_syncs: usize,
+    /// Patterns (gitignore syntax) of resources that are neither watched nor synced
+    #[serde(default)]
+    pub(crate) ignore_patterns: Vec<String>,
+    /// Set if syncs deleting too many resources are held until they are approved
+    #[serde(default)]
+    pub(crate) deletion_guard: Option<DeletionGuardSettings>,
+    /// How the remote workspaces are caught up with changes made while the monitor was not running
+    #[serde(default)]
+    pub(crate) initial_sync: InitialSync,
+    /// How changes in the workspace are detected on Linux
+    #[serde(default)]
+    pub(crate) watcher: Watcher,
+    /// Set if the syncs with all remote workspaces are paused via the daemon
+    #[serde(default)]
+    pub(crate) paused: bool
+}
+
+fn default_max_parallel_syncs() -> usize {
+    4
+}
+
+impl WorkspaceInfo {
+
+    /// Whether the syncs with the remote workspace are paused via the daemon, either by themselves
+    /// or along with those of the whole workspace
+    pub(crate) fn is_paused(&self, remote_workspace: &RemoteWorkspace) -> bool {
+        self.paused || remote_workspace.paused
+    }
+}
+
+/// Controls how long detected changes are collected before they are synced with the remote
+/// workspaces.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct DebounceSettings {
+    /// Changes are synced once no further change was detected for this many milliseconds
+    pub(crate) quiet_window_ms: u64,
+    /// Upper bound on how long a detected change may be held back in case changes keep coming in
+    pub(crate) max_latency_ms: u64
+}
+
+/// Strategy of the sync performed when the monitor starts
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub(crate) enum InitialSync {
+    /// Sync the entire workspace
+    #[default]
+    Full,
+    /// Compare the workspace with the remote workspaces by checksum and only sync the resources
+    /// that differ
+    Verify,
+    /// Only sync the resources that changed since the monitor last stopped
+    Modified,
+    /// Only sync the changes that were left unsynced when the monitor last stopped
+    Skip
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Watcher {
+    /// Watch each directory of the workspace via inotify
+    #[default]
+    Inotify,
+    /// Watch the workspace's filesystem via fanotify, falling back to inotify if it is not available
+    Fanotify
+}
+
+/// Thresholds above which deletions are not propagated to the remote workspaces without approval.
+/// A sync is held if any of the configured thresholds is exceeded.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct DeletionGuardSettings {
+    /// Maximum number of files that may be deleted by a single sync
+    pub(crate) max_deletions: Option<usize>,
+    /// Maximum percentage of the workspace's files that may be deleted by a single sync
+    pub(crate) max_deletion_percent: Option<u8>
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub(crate) struct RemoteWorkspace {
     pub(crate) name: String,
     pub(crate) remote_path: PathBuf,
-    pub(crate) connection_info: ConnectionInfo
+    pub(crate) connection_info: ConnectionInfo,
+    #[serde(default)]
+    pub(crate) sync_options: SyncOptions,
+    /// Set if changes made in the remote workspace are synced back into the local workspace
+    #[serde(default)]
+    pub(crate) two_way: Option<TwoWaySettings>,
+    /// Set if the previous versions of files that syncs overwrite or delete are kept in the remote
+    /// workspace
+    #[serde(default)]
+    pub(crate) versioning: Option<VersioningSettings>,
+    /// Windows of the day in which syncs are paused or transferred at a different bandwidth
+    #[serde(default)]
+    pub(crate) schedule: Vec<ScheduleWindow>,
+    /// Set if a command is run in the remote workspace after changes were synced to it
+    #[serde(default)]
+    pub(crate) post_sync_hook: Option<PostSyncHook>,
+    /// Set if the syncs with the remote workspace are paused via the daemon
+    #[serde(default)]
+    pub(crate) paused: bool
+}
+
+/// Command that is run on the remote system after a successful sync, e.g. to restart a server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct PostSyncHook {
+    /// Shell command to run
+    pub(crate) command: String,
+    /// Directory the command is run in, relative to the remote workspace. Defaults to the remote
+    /// workspace.
+    pub(crate) working_dir: Option<PathBuf>,
+    /// The command is killed if it runs for longer than this
+    pub(crate) timeout_secs: u64,
+    /// The command is only run once no further sync succeeded for this many milliseconds
+    pub(crate) debounce_ms: u64
+}
+
+/// Window of the day, in local time, in which syncs with a remote workspace are treated
+/// differently. Times are given in minutes since midnight, windows ending before they start span
+/// midnight.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct ScheduleWindow {
+    pub(crate) start_minute: u16,
+    pub(crate) end_minute: u16,
+    pub(crate) action: ScheduleAction
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub(crate) enum ScheduleAction {
+    /// Don't sync, changes are deferred until the window closes
+    Pause,
+    /// Sync without limiting the bandwidth
+    FullSpeed,
+    /// Limit the bandwidth to the given number of KiB per second
+    Bwlimit(u32)
+}
+
+/// Retention of the versions kept in a remote workspace. Versions exceeding any of the limits are
+/// removed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct VersioningSettings {
+    /// Maximum number of versions kept per file
+    pub(crate) keep_versions: Option<usize>,
+    /// Maximum number of days a version is kept
+    pub(crate) keep_days: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct TwoWaySettings {
+    pub(crate) conflict_policy: ConflictPolicy,
+    /// Interval in which the remote workspace is checked for changes
+    pub(crate) poll_interval_secs: u64
+}
+
+/// Decides which version of a resource is kept if it was changed both locally and remotely
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub(crate) enum ConflictPolicy {
+    LocalWins,
+    RemoteWins,
+    /// Keep the remote version and move the local version to a copy with a conflict suffix
+    KeepBoth
+}
+
+/// Options controlling how rsync transfers changes to a remote workspace
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub(crate) struct SyncOptions {
+    /// Delete resources in the remote workspace that no longer exist locally
+    pub(crate) delete: bool,
+    pub(crate) compress: bool,
+    /// Compression algorithm to be used (`--compress-choice`)
+    pub(crate) compress_choice: Option<String>,
+    /// Decide whether files need to be transferred based on their checksum instead of their
+    /// size and modification time
+    pub(crate) checksum: bool,
+    /// Bandwidth limit in KiB per second
+    pub(crate) bwlimit_kbps: Option<u32>,
+    pub(crate) chmod: Option<String>,
+    pub(crate) chown: Option<String>,
+    /// Keep partially transferred files
+    pub(crate) partial: bool,
+    /// Update files in place instead of creating a new copy and moving it into place
+    pub(crate) inplace: bool,
+    pub(crate) hard_links: bool,
+    pub(crate) acls: bool,
+    pub(crate) xattrs: bool,
+    /// Preserve permissions
+    pub(crate) perms: bool,
+    /// Preserve the owner, if permitted on the remote system
+    pub(crate) owner: bool,
+    /// Preserve the group, if permitted on the remote system
+    pub(crate) group: bool,
+    /// Preserve modification times. Without them, files are compared by content instead.
+    pub(crate) times: bool
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            delete: true,
+            compress: true,
+            compress_choice: None,
+            checksum: false,
+            bwlimit_kbps: None,
+            chmod: None,
+            chown: None,
+            partial: false,
+            inplace: false,
+            hard_links: false,
+            acls: false,
+            xattrs: false,
+            perms: true,
+            owner: true,
+            group: true,
+            times: true
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -31,6 +242,176 @@ pub(crate) enum ConnectionInfo {
         host: String,
         port: Option<u16>,
         username: Option<String>
+    },
+    /// Directory on a locally mounted filesystem, with the remote path being located below the
+    /// given mount point
+    Local {
+        path: PathBuf
+    },
+    /// Remote system running `wsync-agent`, which is started via SSH. If no host is given, the
+    /// agent is started as a local child process instead.
+    Agent {
+        host: Option<String>,
+        port: Option<u16>,
+        username: Option<String>,
+        identity_file: Option<PathBuf>,
+        /// Path to the agent executable, `wsync-agent` being looked up in the PATH by default
+        agent_path: Option<PathBuf>
+    }
+}
+
+/// Changes that syncing the workspace would make to a remote workspace, as determined by a dry run
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub(crate) struct SyncPreview {
+    pub(crate) remote_workspace: String,
+    pub(crate) created: Vec<PathBuf>,
+    pub(crate) updated: Vec<PathBuf>,
+    pub(crate) deleted: Vec<PathBuf>,
+    /// Reason why the changes could not be determined
+    pub(crate) error: Option<String>
+}
+
+/// Previous versions of a file kept in a remote workspace, newest first
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub(crate) struct FileVersions {
+    pub(crate) remote_workspace: String,
+    pub(crate) versions: Vec<FileVersion>,
+    /// Reason why the versions could not be determined
+    pub(crate) error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct FileVersion {
+    /// Name of the directory holding the version, which is the time of the sync that replaced or
+    /// deleted it in milliseconds since the unix epoch
+    pub(crate) version: String,
+    /// Seconds since the unix epoch
+    pub(crate) created_at: u64,
+    pub(crate) size: u64
+}
+
+/// Outcome of restoring a version of a file into the local workspace
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct VersionRestore {
+    pub(crate) remote_workspace: String,
+    /// Reason why the version could not be restored
+    pub(crate) error: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) enum ConflictResolution {
+    LocalWon,
+    RemoteWon,
+    KeptBoth {
+        conflict_copy: PathBuf
+    }
+}
+
+/// Resource that was changed both locally and in a two-way remote workspace since they were last
+/// synced
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct ConflictReport {
+    pub(crate) remote_workspace: String,
+    pub(crate) path: PathBuf,
+    pub(crate) resolution: ConflictResolution,
+    /// Seconds since the unix epoch
+    pub(crate) detected_at: u64
+}
+
+/// Outcome of a sync with a remote workspace that was triggered on demand by the daemon
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct TriggeredSyncOutcome {
+    pub(crate) remote_workspace: String,
+    /// Reason why the remote workspace was not synced, e.g. because its syncs are paused
+    pub(crate) skipped: Option<String>,
+    pub(crate) error: Option<String>,
+    pub(crate) cause: Option<FailureCause>,
+    pub(crate) transferred: Option<TransferStats>
+}
+
+/// Statistics of the transfers to and from a remote workspace, as reported by rsync's `--stats`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct TransferStats {
+    /// Number of files, directories and other resources that were compared
+    pub(crate) files_considered: u64,
+    /// Number of regular files whose content was transferred
+    pub(crate) files_transferred: u64,
+    pub(crate) bytes_sent: u64,
+    pub(crate) bytes_received: u64,
+    /// Total size of the considered files, i.e. what would have been sent without the
+    /// delta-transfer algorithm
+    pub(crate) total_size: u64,
+    pub(crate) duration_ms: u64
+}
+
+impl TransferStats {
+
+    pub(crate) fn add(&mut self, other: &TransferStats) {
+        self.files_considered += other.files_considered;
+        self.files_transferred += other.files_transferred;
+        self.bytes_sent += other.bytes_sent;
+        self.bytes_received += other.bytes_received;
+        self.total_size += other.total_size;
+        self.duration_ms += other.duration_ms;
+    }
+}
+
+/// Cause of a failed sync, as far as it could be determined from the exit code of the transfer
+/// and what it reported
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FailureCause {
+    /// The remote system could not be connected to, e.g. because it is down or can't be resolved
+    Unreachable,
+    /// The connection to the remote system broke down during the transfer, e.g. due to a timeout
+    ConnectionLost,
+    AuthenticationFailed,
+    /// The remote workspace, or a directory within it, does not exist
+    RemotePathMissing,
+    PermissionDenied,
+    DiskFull,
+    /// Local files vanished while they were being transferred
+    VanishedFiles,
+    /// Some resources could not be transferred for another reason
+    PartialTransfer,
+    Other
+}
+
+impl FailureCause {
+
+    /// Whether the failure persists until someone intervenes, e.g. by fixing the credentials or
+    /// freeing up space, as opposed to failures that might go away by themselves
+    pub(crate) fn requires_intervention(self) -> bool {
+        matches!(
+            self,
+            FailureCause::AuthenticationFailed
+                | FailureCause::RemotePathMissing
+                | FailureCause::PermissionDenied
+                | FailureCause::DiskFull
+        )
+    }
+}
+
+impl Display for FailureCause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            FailureCause::Unreachable => "remote system unreachable",
+            FailureCause::ConnectionLost => "connection lost",
+            FailureCause::AuthenticationFailed => "authentication failed",
+            FailureCause::RemotePathMissing => "remote path missing",
+            FailureCause::PermissionDenied => "permission denied",
+            FailureCause::DiskFull => "disk full",
+            FailureCause::VanishedFiles => "files vanished",
+            FailureCause::PartialTransfer => "partial transfer",
+            FailureCause::Other => "unknown cause"
+        };
+
+        write!(f, "{description}")
+    }
+}
+
+impl Default for DebounceSettings {
+    fn default() -> Self {
+        DebounceSettings { quiet_window_ms: 250, max_latency_ms: 2000 }
     }
 }
 
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use log::debug;
+use crate::events::{emit, MonitorEvent};
+
+/// Workers waiting for the daemon to grant them a permit to run a transfer, by the id of the
+/// permit. `None` if transfers are not coordinated by the daemon, i.e. run without a permit.
+static WAITING: Mutex<Option<HashMap<String, Sender<Option<u32>>>>> = Mutex::new(None);
+
+/// Bandwidth limit in KiB per second the daemon granted along with the permit of the transfer
+/// that is currently running per remote workspace
+static GRANTED_BWLIMITS: LazyLock<Mutex<HashMap<String, u32>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Permit to run a transfer with a remote workspace, which is returned to the daemon once dropped
+pub(crate) struct Permit {
+    /// `None` if the transfer runs without a permit of the daemon
+    id: Option<String>,
+    remote_workspace: String
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            GRANTED_BWLIMITS.lock().unwrap().remove(&self.remote_workspace);
+            emit(MonitorEvent::PermitReleased { id });
+        }
+    }
+}
+
+/// Have transfers wait for a permit of the daemon, which coordinates the transfers of all monitors
+pub(crate) fn enable() {
+    WAITING.lock().unwrap().get_or_insert_with(HashMap::new);
+}
+
+/// Let transfers run without a permit once the daemon no longer sends commands, including those
+/// still waiting for one
+pub(crate) fn disable() {
+    WAITING.lock().unwrap().take();
+}
+
+/// Wait until the daemon grants a permit to run a transfer with the given remote workspace, if
+/// transfers are coordinated by the daemon
+pub(crate) fn acquire(remote_workspace: &str) -> Permit {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed).to_string();
+    let (tx, rx) = channel();
+
+    match WAITING.lock().unwrap().as_mut() {
+        Some(waiting) => waiting.insert(id.clone(), tx),
+        None => return Permit { id: None, remote_workspace: remote_workspace.to_string() }
+    };
+
+    debug!("Waiting for permit '{id}' to sync with '{remote_workspace}'");
+    emit(MonitorEvent::PermitRequested { id: id.clone(), remote_workspace: remote_workspace.to_string() });
+
+    match rx.recv() {
+        Ok(bwlimit_kbps) => {
+            debug!("Got permit '{id}' to sync with '{remote_workspace}', bandwidth limit: {:?}", bwlimit_kbps);
+
+            if let Some(bwlimit_kbps) = bwlimit_kbps {
+                GRANTED_BWLIMITS.lock().unwrap().insert(remote_workspace.to_string(), bwlimit_kbps);
+            }
+            Permit { id: Some(id), remote_workspace: remote_workspace.to_string() }
+        },
+        // Transfers are no longer coordinated
+        Err(_) => Permit { id: None, remote_workspace: remote_workspace.to_string() }
+    }
+}
+
+/// Hand the permit with the given id, granted by the daemon, to the worker waiting for it
+pub(crate) fn grant(id: &str, bwlimit_kbps: Option<u32>) {
+    let tx = WAITING.lock().unwrap().as_mut().and_then(|waiting| waiting.remove(id));
+
+    match tx {
+        Some(tx) => {
+            let _ = tx.send(bwlimit_kbps);
+        },
+        None => debug!("Ignoring permit '{id}', as no transfer is waiting for it")
+    }
+}
+
+/// Bandwidth limit in KiB per second the daemon granted to the running transfer with the remote
+/// workspace, if any
+pub(crate) fn bwlimit_kbps(remote_workspace: &str) -> Option<u32> {
+    GRANTED_BWLIMITS.lock().unwrap().get(remote_workspace).copied()
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use crate::models::WorkspaceInfo;
+
+/// Schedules the periodic syncs of two-way remote workspaces, which pick up changes made in the
+/// remote workspace. Any sync of a two-way remote workspace also picks up its changes, so its next
+/// poll is postponed whenever it is synced.
+pub(crate) struct Poller {
+    /// Poll interval and next poll per two-way remote workspace
+    schedule: HashMap<String, (Duration, Instant)>
+}
+
+impl Poller {
+
+    pub(crate) fn new(ws_info: &WorkspaceInfo) -> Self {
+        let now = Instant::now();
+
+        let schedule = ws_info.remote_workspaces
+            .iter()
+            .filter_map(|remote_workspace| {
+                let interval = Duration::from_secs(remote_workspace.two_way.as_ref()?.poll_interval_secs.max(1));
+                Some((remote_workspace.name.clone(), (interval, now + interval)))
+            })
+            .collect();
+
+        Poller { schedule }
+    }
+
+    pub(crate) fn is_poll_due(&self, remote_workspace: &str, now: Instant) -> bool {
+        self.schedule.get(remote_workspace).is_some_and(|(_, next_poll)| *next_poll <= now)
+    }
+
+    /// Point in time at which the next poll is due, or `None` if there are no two-way remote
+    /// workspaces
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.schedule.values().map(|(_, next_poll)| *next_poll).min()
+    }
+
+    pub(crate) fn is_due(&self, now: Instant) -> bool {
+        self.next_deadline().is_some_and(|deadline| deadline <= now)
+    }
+
+    /// Postpone the next poll of a remote workspace that was just synced
+    pub(crate) fn record_sync(&mut self, remote_workspace: &str, now: Instant) {
+        if let Some((interval, next_poll)) = self.schedule.get_mut(remote_workspace) {
+            *next_poll = now + *interval;
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use crate::debounce::DirtyTargets;
+use crate::journal::Journal;
+use crate::models::FailureCause;
+use crate::sync::{SyncReport, SyncTargets};
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// Target of a failed sync with a remote workspace that still has to be retried
+struct PendingRetry {
+    /// Common ancestor of all workspace-relative paths that failed to sync, `None` being the
+    /// workspace root
+    target: Option<PathBuf>,
+    /// Number of consecutive failed attempts
+    failed_attempts: u32,
+    next_attempt: Instant
+}
+
+/// Per remote workspace queue of sync targets that failed because of the remote system (e.g.
+/// because it was unreachable). Failed targets are retried with exponential backoff and jitter, and
+/// all targets queued for a remote workspace are collapsed into their common ancestor, so that a
+/// single sync catches the remote workspace up once it is reachable again.
+///
+/// Attempted, deferred and held targets are recorded in the [Journal], so that targets that were not
+/// synced before the monitor stopped are retried after a restart.
+pub(crate) struct RetryQueue {
+    pending: HashMap<String, PendingRetry>,
+    /// Changes held back from remote workspaces whose syncs are paused via the daemon
+    held: HashMap<String, DirtyTargets>,
+    journal: Journal
+}
+
+impl RetryQueue {
+
+    /// Create a queue that immediately retries the targets left in the journal
+    pub(crate) fn new(journal: Journal) -> Self {
+        let now = Instant::now();
+        let mut pending = HashMap::new();
+
+        for (remote_workspace, targets) in journal.pending() {
+            let target = collapse_targets(None, &targets);
+            pending.insert(remote_workspace, PendingRetry { target, failed_attempts: 0, next_attempt: now });
+        }
+
+        RetryQueue { pending, held: HashMap::new(), journal }
+    }
+
+    /// Journal the targets that are about to be synced with a remote workspace
+    pub(crate) fn record_attempt(&mut self, remote_workspace: &str, targets: &SyncTargets) {
+        self.journal.append(remote_workspace, &targets.to_directories());
+    }
+
+    pub(crate) fn is_pending(&self, remote_workspace: &str) -> bool {
+        self.pending.contains_key(remote_workspace)
+    }
+
+    /// Number of remote workspaces with a pending retry, i.e. that are lagging behind
+    pub(crate) fn nr_of_pending_retries(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Add targets to the pending retry of a remote workspace without affecting when it is retried.
+    /// Used for changes detected while a remote workspace is backing off, which would otherwise
+    /// most likely fail for the same reason.
+    pub(crate) fn defer(&mut self, remote_workspace: &str, targets: &SyncTargets) {
+        if targets.is_empty() {
+            return;
+        }
+
+        if let Some(pending_retry) = self.pending.get_mut(remote_workspace) {
+            let directories = targets.to_directories();
+            pending_retry.target = collapse_targets(Some(&pending_retry.target), &directories);
+            self.journal.append(remote_workspace, &directories);
+        }
+    }
+
+    /// Hold back the targets of a remote workspace whose syncs are paused by its schedule until the
+    /// given point in time, unless its retry is due even later
+    pub(crate) fn postpone(&mut self, remote_workspace: &str, targets: &SyncTargets, until: Instant) {
+        let directories = targets.to_directories();
+        if !directories.is_empty() {
+            self.journal.append(remote_workspace, &directories);
+        }
+
+        match self.pending.get_mut(remote_workspace) {
+            Some(pending_retry) => {
+                pending_retry.target = collapse_targets(Some(&pending_retry.target), &directories);
+                pending_retry.next_attempt = pending_retry.next_attempt.max(until);
+            },
+            None if !directories.is_empty() => {
+                let target = collapse_targets(None, &directories);
+                self.pending.insert(
+                    remote_workspace.to_string(),
+                    PendingRetry { target, failed_attempts: 0, next_attempt: until }
+                );
+            },
+            None => {}
+        }
+    }
+
+    /// Hold back the targets of a remote workspace whose syncs are paused via the daemon until they
+    /// are resumed. Unlike postponed targets, they are not collapsed, so that only what changed
+    /// meanwhile is synced once the syncs are resumed. A pending retry is held along with them, as
+    /// it must not be attempted either.
+    pub(crate) fn hold(&mut self, remote_workspace: &str, targets: &SyncTargets) {
+        let held = self.held.entry(remote_workspace.to_string()).or_default();
+
+        if let Some(pending_retry) = self.pending.remove(remote_workspace) {
+            held.record(pending_retry.target);
+        }
+
+        if !targets.is_empty() {
+            self.journal.append(remote_workspace, &targets.to_directories());
+            held.add(targets.clone());
+        }
+    }
+
+    /// The targets held back from a remote workspace whose syncs were resumed, followed by the
+    /// given ones
+    pub(crate) fn release(&mut self, remote_workspace: &str, targets: &SyncTargets) -> SyncTargets {
+        match self.held.remove(remote_workspace) {
+            Some(mut held) if !held.is_empty() => {
+                info!("Syncing the changes held while the syncs with '{remote_workspace}' were paused");
+                held.add(targets.clone());
+                held.take()
+            },
+            _ => targets.clone()
+        }
+    }
+
+    /// The target of the pending retry of the given remote workspace, regardless of when it is due
+    pub(crate) fn pending_target(&self, remote_workspace: &str) -> Option<Option<PathBuf>> {
+        self.pending.get(remote_workspace).map(|pending_retry| pending_retry.target.clone())
+    }
+
+    /// The target to be synced with the given remote workspace, if its retry is due
+    pub(crate) fn due_target(&self, remote_workspace: &str, now: Instant) -> Option<Option<PathBuf>> {
+        self.pending
+            .get(remote_workspace)
+            .filter(|pending_retry| pending_retry.next_attempt <= now)
+            .map(|pending_retry| pending_retry.target.clone())
+    }
+
+    /// Point in time at which the next retry is due, or `None` if there is nothing to retry
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|pending_retry| pending_retry.next_attempt).min()
+    }
+
+    pub(crate) fn is_due(&self, now: Instant) -> bool {
+        self.next_deadline().is_some_and(|deadline| deadline <= now)
+    }
+
+    /// Update the queue with the outcomes of a sync. Remote workspaces that were synced
+    /// successfully are removed from the queue, whereas the failed targets of remote workspaces
+    /// that failed are (re-)scheduled.
+    pub(crate) fn record_report(&mut self, report: &SyncReport) {
+        let now = Instant::now();
+
+        for outcome in report.outcomes.iter() {
+            if outcome.error.is_none() {
+                self.journal.clear(&outcome.remote_workspace);
+
+                if self.pending.remove(&outcome.remote_workspace).is_some_and(|pending_retry| pending_retry.failed_attempts > 0) {
+                    info!("Remote workspace '{}' caught up after failed sync attempts", outcome.remote_workspace);
+                }
+                continue;
+            }
+
+            let cause = outcome.error.as_ref().and_then(|e| e.cause());
+            self.record_failure(&outcome.remote_workspace, &outcome.failed_targets.to_directories(), cause, now);
+        }
+    }
+
+    /// Schedule the retry of the given targets. Failures that won't go away without intervention
+    /// (e.g. failed authentications) are retried at the maximum backoff right away, since retrying
+    /// them early is futile.
+    fn record_failure(
+        &mut self,
+        remote_workspace: &str,
+        targets: &[Option<PathBuf>],
+        cause: Option<FailureCause>,
+        now: Instant
+    ) {
+        let previous = self.pending.remove(remote_workspace);
+        let failed_attempts = previous.as_ref().map_or(0, |pending_retry| pending_retry.failed_attempts) + 1;
+        let target = collapse_targets(previous.as_ref().map(|pending_retry| &pending_retry.target), targets);
+        let backoff = if cause.is_some_and(FailureCause::requires_intervention) {
+            backoff_with_jitter(u32::MAX)
+        } else {
+            backoff_with_jitter(failed_attempts)
+        };
+
+        warn!(
+            "Remote workspace '{}' is lagging behind: retrying to sync '{}' in {:?} (failed attempts: {}, \
+            cause: {}, remote workspaces with pending retries: {})",
+            remote_workspace,
+            target.as_ref().map_or(String::from("/"), |path| path.display().to_string()),
+            backoff,
+            failed_attempts,
+            cause.unwrap_or(FailureCause::Other),
+            self.pending.len() + 1
+        );
+
+        self.pending.insert(
+            remote_workspace.to_string(),
+            PendingRetry { target, failed_attempts, next_attempt: now + backoff }
+        );
+    }
+}
+
+/// Exponential backoff starting at [INITIAL_BACKOFF] and capped at [MAX_BACKOFF], of which a random
+/// share of up to one half is subtracted, so that remote workspaces that failed at the same time
+/// are not all retried at once.
+fn backoff_with_jitter(failed_attempts: u32) -> Duration {
+    let exponent = failed_attempts.saturating_sub(1).min(16);
+    let backoff = INITIAL_BACKOFF.saturating_mul(1 << exponent).min(MAX_BACKOFF);
+
+    backoff.mul_f64(1.0 - fastrand::f64() / 2.0)
+}
+
+/// Collapse the given targets, and optionally an already queued one, into their common ancestor
+fn collapse_targets(queued: Option<&Option<PathBuf>>, targets: &[Option<PathBuf>]) -> Option<PathBuf> {
+    let mut targets = queued.into_iter().chain(targets.iter());
+
+    let mut ancestor = match targets.next() {
+        Some(target) => target.clone(),
+        None => return None
+    };
+
+    for target in targets {
+        ancestor = match (&ancestor, target) {
+            (Some(ancestor), Some(target)) => common_ancestor(ancestor, target),
+            _ => None
+        };
+
+        if ancestor.is_none() {
+            break;
+        }
+    }
+
+    ancestor
+}
+
+fn common_ancestor(a: &Path, b: &Path) -> Option<PathBuf> {
+    let ancestor: PathBuf = a.components()
+        .zip(b.components())
+        .take_while(|(a, b)| a == b)
+        .map(|(component, _)| component)
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+
+    if ancestor.as_os_str().is_empty() {
+        None
+    } else {
+        Some(ancestor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::sync::{Error, RemoteSyncOutcome};
+    use super::*;
+
+    fn path(path: &str) -> Option<PathBuf> {
+        Some(PathBuf::from(path))
+    }
+
+    fn report(remote_workspace: &str, failed_directory: Option<Option<PathBuf>>, cause: Option<FailureCause>) -> SyncReport {
+        let outcome = match failed_directory {
+            Some(directory) => RemoteSyncOutcome {
+                remote_workspace: remote_workspace.to_string(),
+                failed_targets: SyncTargets::from_directories(vec![directory]),
+                error: Some(Error::RemoteSystemError(String::from("failed"), cause)),
+                transferred: None
+            },
+            None => RemoteSyncOutcome {
+                remote_workspace: remote_workspace.to_string(),
+                failed_targets: SyncTargets::default(),
+                error: None,
+                transferred: None
+            }
+        };
+
+        SyncReport { outcomes: vec![outcome] }
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_maximum() {
+        for (failed_attempts, backoff) in [(1, INITIAL_BACKOFF), (3, INITIAL_BACKOFF * 4), (u32::MAX, MAX_BACKOFF)] {
+            for _ in 0..100 {
+                let jittered = backoff_with_jitter(failed_attempts);
+                assert!(jittered >= backoff / 2 && jittered <= backoff, "{failed_attempts}: {:?}", jittered);
+            }
+        }
+    }
+
+    #[test]
+    fn targets_are_collapsed_into_their_common_ancestor() {
+        assert_eq!(collapse_targets(None, &[]), None);
+        assert_eq!(collapse_targets(None, &[path("a/b/c"), path("a/b/d")]), path("a/b"));
+        assert_eq!(collapse_targets(Some(&path("a/b")), &[path("a/c")]), path("a"));
+        assert_eq!(collapse_targets(Some(&path("a")), &[path("b")]), None);
+        assert_eq!(collapse_targets(Some(&None), &[path("a")]), None);
+    }
+
+    #[test]
+    fn failed_targets_are_retried_until_synced() {
+        let mut retry_queue = RetryQueue::new(Journal::disabled());
+        let now = Instant::now();
+
+        retry_queue.record_report(&report("remote", Some(path("a/b")), None));
+        assert!(retry_queue.next_deadline().is_some_and(|deadline| deadline > now && deadline <= now + INITIAL_BACKOFF * 2));
+        assert_eq!(retry_queue.due_target("remote", now), None);
+        assert_eq!(retry_queue.due_target("remote", now + INITIAL_BACKOFF * 2), Some(path("a/b")));
+
+        retry_queue.defer("remote", &SyncTargets::from_directories(vec![path("a/c")]));
+        assert_eq!(retry_queue.pending_target("remote"), Some(path("a")));
+
+        retry_queue.record_report(&report("remote", None, None));
+        assert!(!retry_queue.is_pending("remote"));
+        assert_eq!(retry_queue.next_deadline(), None);
+    }
+
+    #[test]
+    fn failures_requiring_intervention_back_off_at_the_maximum() {
+        let mut retry_queue = RetryQueue::new(Journal::disabled());
+        let now = Instant::now();
+
+        retry_queue.record_report(&report("remote", Some(None), Some(FailureCause::AuthenticationFailed)));
+        assert!(retry_queue.next_deadline().is_some_and(|deadline| deadline >= now + MAX_BACKOFF / 2));
+    }
+
+    #[test]
+    fn journaled_targets_are_retried_right_away() {
+        let dir = std::env::temp_dir().join(format!("wsync-retry-queue-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let Ok(mut journal) = Journal::open_directory(&dir, &["remote"]) else {
+            panic!("Failed to open journal");
+        };
+        journal.append("remote", &[path("a/b"), path("a/c")]);
+        drop(journal);
+
+        let Ok(journal) = Journal::open_directory(&dir, &["remote"]) else {
+            panic!("Failed to reopen journal");
+        };
+        let retry_queue = RetryQueue::new(journal);
+
+        assert_eq!(retry_queue.due_target("remote", Instant::now()), Some(path("a")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
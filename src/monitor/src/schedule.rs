@@ -0,0 +1,129 @@
+use std::time::{Duration, Instant};
+use crate::models::{RemoteWorkspace, ScheduleAction, ScheduleWindow};
+use crate::permits;
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// Current time of day in local time, as minutes since midnight and seconds into the minute
+fn local_time_of_day() -> (u16, u64) {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut local: libc::tm = unsafe { std::mem::zeroed() };
+
+    // Fall back to UTC if the local time can't be determined
+    if unsafe { libc::localtime_r(&now, &mut local) }.is_null() {
+        let secs_of_day = now.rem_euclid(24 * 60 * 60) as u64;
+        return ((secs_of_day / 60) as u16, secs_of_day % 60);
+    }
+
+    ((local.tm_hour * 60 + local.tm_min) as u16, local.tm_sec.clamp(0, 59) as u64)
+}
+
+impl ScheduleWindow {
+
+    fn contains(&self, minute: u16) -> bool {
+        if self.start_minute <= self.end_minute {
+            (self.start_minute..self.end_minute).contains(&minute)
+        } else {
+            minute >= self.start_minute || minute < self.end_minute
+        }
+    }
+}
+
+/// The window of the remote workspace's schedule that applies at the given time of day. If
+/// windows overlap, the first one applies.
+fn active_window(remote_workspace: &RemoteWorkspace, minute: u16) -> Option<&ScheduleWindow> {
+    remote_workspace.schedule.iter().find(|window| window.contains(minute))
+}
+
+/// Point in time until which syncs with the remote workspace are paused by its schedule, or `None`
+/// if it may be synced now
+pub(crate) fn paused_until(remote_workspace: &RemoteWorkspace, now: Instant) -> Option<Instant> {
+    let (minute, second) = local_time_of_day();
+    let window = active_window(remote_workspace, minute)
+        .filter(|window| matches!(window.action, ScheduleAction::Pause))?;
+
+    let remaining_minutes = (window.end_minute + MINUTES_PER_DAY - minute) % MINUTES_PER_DAY;
+    let remaining = Duration::from_secs(u64::from(remaining_minutes) * 60 - second);
+
+    Some(now + remaining)
+}
+
+/// Bandwidth limit in KiB per second for a transfer to or from the remote workspace starting now,
+/// which is taken from the sync options unless the schedule says otherwise. The share of the
+/// aggregate bandwidth granted by the daemon caps it further.
+pub(crate) fn bwlimit_kbps(remote_workspace: &RemoteWorkspace) -> Option<u32> {
+    let (minute, _) = local_time_of_day();
+
+    let bwlimit = match active_window(remote_workspace, minute).map(|window| window.action) {
+        Some(ScheduleAction::FullSpeed) => None,
+        Some(ScheduleAction::Bwlimit(bwlimit)) => Some(bwlimit),
+        Some(ScheduleAction::Pause) | None => remote_workspace.sync_options.bwlimit_kbps
+    };
+
+    match (bwlimit, permits::bwlimit_kbps(&remote_workspace.name)) {
+        (Some(bwlimit), Some(granted)) => Some(bwlimit.min(granted)),
+        (bwlimit, granted) => bwlimit.or(granted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::models::ConnectionInfo;
+    use super::*;
+
+    fn remote_workspace(schedule: Vec<ScheduleWindow>) -> RemoteWorkspace {
+        RemoteWorkspace {
+            name: String::from("remote"),
+            remote_path: PathBuf::from("/srv/ws"),
+            connection_info: ConnectionInfo::Local { path: PathBuf::from("/mnt") },
+            sync_options: Default::default(),
+            two_way: None,
+            versioning: None,
+            schedule,
+            post_sync_hook: None,
+            paused: false
+        }
+    }
+
+    fn window(start_minute: u16, end_minute: u16, action: ScheduleAction) -> ScheduleWindow {
+        ScheduleWindow { start_minute, end_minute, action }
+    }
+
+    fn active_action(remote_workspace: &RemoteWorkspace, minute: u16) -> Option<String> {
+        active_window(remote_workspace, minute).map(|window| format!("{:?}", window.action))
+    }
+
+    #[test]
+    fn windows_include_their_start_but_not_their_end() {
+        let remote_workspace = remote_workspace(vec![window(9 * 60, 17 * 60, ScheduleAction::Pause)]);
+
+        assert_eq!(active_action(&remote_workspace, 9 * 60 - 1), None);
+        assert_eq!(active_action(&remote_workspace, 9 * 60).as_deref(), Some("Pause"));
+        assert_eq!(active_action(&remote_workspace, 17 * 60 - 1).as_deref(), Some("Pause"));
+        assert_eq!(active_action(&remote_workspace, 17 * 60), None);
+    }
+
+    #[test]
+    fn windows_ending_before_they_start_span_midnight() {
+        let remote_workspace = remote_workspace(vec![window(22 * 60, 6 * 60, ScheduleAction::FullSpeed)]);
+
+        assert_eq!(active_action(&remote_workspace, 21 * 60 + 59), None);
+        assert_eq!(active_action(&remote_workspace, 22 * 60).as_deref(), Some("FullSpeed"));
+        assert_eq!(active_action(&remote_workspace, MINUTES_PER_DAY - 1).as_deref(), Some("FullSpeed"));
+        assert_eq!(active_action(&remote_workspace, 0).as_deref(), Some("FullSpeed"));
+        assert_eq!(active_action(&remote_workspace, 6 * 60 - 1).as_deref(), Some("FullSpeed"));
+        assert_eq!(active_action(&remote_workspace, 6 * 60), None);
+    }
+
+    #[test]
+    fn first_of_overlapping_windows_applies() {
+        let remote_workspace = remote_workspace(vec![
+            window(23 * 60, 60, ScheduleAction::Bwlimit(100)),
+            window(0, 12 * 60, ScheduleAction::Pause)
+        ]);
+
+        assert_eq!(active_action(&remote_workspace, 30).as_deref(), Some("Bwlimit(100)"));
+        assert_eq!(active_action(&remote_workspace, 60).as_deref(), Some("Pause"));
+    }
+}
@@ -0,0 +1,613 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{File, Metadata};
+use std::io::{BufReader, BufWriter, ErrorKind, Read};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::UNIX_EPOCH;
+use log::{debug, warn};
+use agent_protocol::delta::{block_size_for, compute_delta, Digest, DeltaOp};
+use agent_protocol::frame::{read_frame, write_frame};
+use agent_protocol::{EntryInfo, EntryKind, Request, Response, Timestamp, PROTOCOL_VERSION};
+use crate::ignore_rules::IgnoreRules;
+use crate::models::{ConnectionInfo, RemoteWorkspace};
+use crate::sync::backend::SyncBackend;
+use crate::sync::Error;
+use crate::util::ssh_command;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const DEFAULT_AGENT_PATH: &str = "wsync-agent";
+
+/// Maximum amount of literal data sent in a single write chunk
+const MAX_CHUNK_LEN: usize = 1024 * 1024;
+
+/// Files up to this size are diffed against their remote counterpart, larger files are streamed
+/// as a whole to bound the monitor's memory usage
+const MAX_DELTA_FILE_LEN: u64 = 64 * 1024 * 1024;
+
+type SharedConnection = Arc<Mutex<Option<AgentConnection>>>;
+
+/// Connections to the agents of the remote workspaces, which are kept open between syncs
+static CONNECTIONS: LazyLock<Mutex<HashMap<String, SharedConnection>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Session with a `wsync-agent` process, which is either started via SSH or as a local child
+/// process. The connection is considered broken once sending or receiving a frame failed.
+struct AgentConnection {
+    child: Child,
+    reader: BufReader<ChildStdout>,
+    writer: BufWriter<ChildStdin>,
+    broken: bool
+}
+
+impl AgentConnection {
+
+    fn open(remote_workspace: &RemoteWorkspace) -> Result<Self> {
+        let mut command = get_agent_command(remote_workspace)?;
+        debug!("Starting agent for '{}': {:?}", remote_workspace.name, command);
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| Error::remote(format!("Unable to start agent: {e}")))?;
+
+        let (stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => return Err(Error::LocalError(String::from("Unable to access the agent's stdio")))
+        };
+
+        let mut connection = AgentConnection {
+            child,
+            reader: BufReader::new(stdout),
+            writer: BufWriter::new(stdin),
+            broken: false
+        };
+
+        let hello = Request::Hello { version: PROTOCOL_VERSION, root: remote_workspace.remote_path.clone() };
+        match connection.request(&hello)? {
+            Response::Ready { version } if version == PROTOCOL_VERSION => Ok(connection),
+            response => Err(Error::remote(format!("Unexpected response to hello request: {:?}", response)))
+        }
+    }
+
+    fn send(&mut self, request: &Request, payload: &[u8]) -> Result<()> {
+        write_frame(&mut self.writer, request, payload).map_err(|e| {
+            self.broken = true;
+            Error::remote(format!("Unable to send request to agent: {e}"))
+        })
+    }
+
+    fn receive(&mut self) -> Result<Response> {
+        match read_frame::<_, Response>(&mut self.reader) {
+            Ok((Response::Error { message }, _)) => Err(Error::remote(format!("Agent: {message}"))),
+            Ok((response, _)) => Ok(response),
+            Err(e) => {
+                self.broken = true;
+                Err(Error::remote(format!("Unable to receive response from agent: {e}")))
+            }
+        }
+    }
+
+    fn request(&mut self, request: &Request) -> Result<Response> {
+        self.send(request, &[])?;
+        self.receive()
+    }
+
+    fn expect_ok(&mut self, request: &Request) -> Result<()> {
+        match self.request(request)? {
+            Response::Ok => Ok(()),
+            response => Err(self.unexpected(response))
+        }
+    }
+
+    fn unexpected(&mut self, response: Response) -> Error {
+        // The connection is out of sync, so don't use it any further
+        self.broken = true;
+        Error::remote(format!("Unexpected response from agent: {:?}", response))
+    }
+}
+
+impl Drop for AgentConnection {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Command to start the agent, as a local child process if no host is specified
+fn get_agent_command(remote_workspace: &RemoteWorkspace) -> Result<Command> {
+    let agent_path = match &remote_workspace.connection_info {
+        ConnectionInfo::Agent { agent_path, .. } => agent_path,
+        _ => return Err(Error::LocalError(format!("'{}' is not synced via the agent", remote_workspace.name)))
+    };
+
+    let agent_path = agent_path.clone().unwrap_or(PathBuf::from(DEFAULT_AGENT_PATH));
+
+    // Detect dead connections, since the connection is kept open between syncs
+    let mut options = vec!["-o", "ServerAliveInterval=15", "-o", "ServerAliveCountMax=3"];
+    if remote_workspace.sync_options.compress {
+        options.push("-C");
+    }
+
+    let Some(mut command) = ssh_command(&remote_workspace.connection_info, &options) else {
+        return Ok(Command::new(agent_path));
+    };
+
+    command.arg("--").arg(agent_path);
+
+    Ok(command)
+}
+
+fn to_timestamp(metadata: &Metadata) -> Timestamp {
+    metadata.modified().unwrap_or(UNIX_EPOCH).into()
+}
+
+fn to_mode(metadata: &Metadata) -> u32 {
+    metadata.permissions().mode() & 0o7777
+}
+
+fn io_error(action: &str, path: &Path, error: std::io::Error) -> Error {
+    Error::remote(format!("Unable to {action} '{:?}': {error}", path))
+}
+
+/// Remote resources below a scanned directory, along with the names of each directory's children
+struct RemoteTree {
+    entries: HashMap<PathBuf, EntryInfo>,
+    children: HashMap<PathBuf, Vec<OsString>>
+}
+
+impl RemoteTree {
+
+    fn scan(connection: &mut AgentConnection, relative_path: &Path) -> Result<Self> {
+        let entries = match connection.request(&Request::Scan { path: relative_path.to_path_buf() })? {
+            Response::Entries { entries } => entries,
+            response => return Err(connection.unexpected(response))
+        };
+
+        let mut tree = RemoteTree { entries: HashMap::new(), children: HashMap::new() };
+
+        for entry in entries {
+            if let (Some(parent), Some(name)) = (entry.path.parent(), entry.path.file_name()) {
+                tree.children.entry(parent.to_path_buf()).or_default().push(name.to_os_string());
+            }
+            tree.entries.insert(entry.path, entry.info);
+        }
+
+        Ok(tree)
+    }
+}
+
+/// Syncs with remote workspaces by sending the changes to a `wsync-agent` process on the remote
+/// system. Only the differences between changed files and their remote counterparts are
+/// transferred, and the agent is kept running between syncs, so that syncing a change requires
+/// neither a new SSH connection nor a scan of the remote workspace.
+pub(super) struct AgentBackend<'a> {
+    ws_root_path: &'a PathBuf,
+    remote_workspace: &'a RemoteWorkspace,
+    ignore_rules: &'a IgnoreRules,
+    connection: SharedConnection
+}
+
+impl<'a> AgentBackend<'a> {
+
+    pub(super) fn new(
+        ws_root_path: &'a PathBuf,
+        remote_workspace: &'a RemoteWorkspace,
+        ignore_rules: &'a IgnoreRules
+    ) -> Self {
+        let connection = CONNECTIONS
+            .lock()
+            .unwrap()
+            .entry(remote_workspace.name.clone())
+            .or_default()
+            .clone();
+
+        AgentBackend { ws_root_path, remote_workspace, ignore_rules, connection }
+    }
+
+    /// Run the given function with the connection to the agent, which is (re-)established if
+    /// required. Broken connections are closed afterwards.
+    fn with_connection<T>(&self, f: impl FnOnce(&mut AgentConnection) -> Result<T>) -> Result<T> {
+        let mut guard = self.connection.lock().unwrap();
+
+        let connection = match guard.as_mut() {
+            Some(connection) => connection,
+            None => guard.insert(AgentConnection::open(self.remote_workspace)?)
+        };
+
+        let result = f(connection);
+
+        if connection.broken {
+            warn!("Closing broken connection to the agent of '{}'", self.remote_workspace.name);
+            *guard = None;
+        }
+
+        result
+    }
+
+    fn local_metadata(&self, relative_path: &Path) -> Result<Option<Metadata>> {
+        let path = self.ws_root_path.join(relative_path);
+
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) => Ok(Some(metadata)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(io_error("access", &path, e))
+        }
+    }
+
+    fn sync_tree(&self, connection: &mut AgentConnection, relative_path: &Path) -> Result<()> {
+        let src_metadata = match self.local_metadata(relative_path)? {
+            Some(metadata) => metadata,
+            None => {
+                if self.remote_workspace.sync_options.delete && relative_path.parent().is_some() {
+                    connection.expect_ok(&Request::Delete { path: relative_path.to_path_buf() })?;
+                }
+                return Ok(());
+            }
+        };
+
+        self.create_parent_dir(connection, relative_path)?;
+
+        let remote_tree = RemoteTree::scan(connection, relative_path)?;
+        self.mirror_entry(connection, relative_path, &src_metadata, &remote_tree)
+    }
+
+    /// Make sure that the parent directory of the given resource exists remotely
+    fn create_parent_dir(&self, connection: &mut AgentConnection, relative_path: &Path) -> Result<()> {
+        let parent = match relative_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => return Ok(())
+        };
+
+        let mode = match self.local_metadata(parent)? {
+            Some(metadata) => to_mode(&metadata),
+            None => 0o755
+        };
+
+        connection.expect_ok(&Request::Mkdir { path: parent.to_path_buf(), mode })
+    }
+
+    fn mirror_entry(
+        &self,
+        connection: &mut AgentConnection,
+        relative_path: &Path,
+        src_metadata: &Metadata,
+        remote_tree: &RemoteTree
+    ) -> Result<()> {
+        let remote_entry = remote_tree.entries.get(relative_path);
+        let file_type = src_metadata.file_type();
+
+        if file_type.is_dir() {
+            self.mirror_dir(connection, relative_path, src_metadata, remote_tree)
+        } else if file_type.is_symlink() {
+            self.mirror_symlink(connection, relative_path, remote_entry)
+        } else if file_type.is_file() {
+            self.mirror_file(connection, relative_path, src_metadata, remote_entry)
+        } else {
+            debug!("Skipping special file '{:?}'", relative_path);
+            Ok(())
+        }
+    }
+
+    fn mirror_dir(
+        &self,
+        connection: &mut AgentConnection,
+        relative_path: &Path,
+        src_metadata: &Metadata,
+        remote_tree: &RemoteTree
+    ) -> Result<()> {
+        let mode = to_mode(src_metadata);
+
+        match remote_tree.entries.get(relative_path) {
+            Some(entry) if entry.kind == EntryKind::Directory && entry.mode == mode => {},
+            _ => connection.expect_ok(&Request::Mkdir { path: relative_path.to_path_buf(), mode })?
+        }
+
+        let src_dir = self.ws_root_path.join(relative_path);
+        let dir_entries = match fs::read_dir(&src_dir) {
+            Ok(dir_entries) => dir_entries,
+            // The directory was removed in the meantime, which is synced once it is reported
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(io_error("read", &src_dir, e))
+        };
+
+        let mut src_names: HashSet<OsString> = HashSet::new();
+
+        for dir_entry in dir_entries {
+            let dir_entry = dir_entry.map_err(|e| io_error("read", &src_dir, e))?;
+            let child_path = relative_path.join(dir_entry.file_name());
+
+            let child_metadata = match dir_entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(io_error("access", &dir_entry.path(), e))
+            };
+
+            if self.ignore_rules.is_ignored(&child_path, child_metadata.is_dir()) {
+                continue;
+            }
+
+            src_names.insert(dir_entry.file_name());
+            self.mirror_entry(connection, &child_path, &child_metadata, remote_tree)?;
+        }
+
+        if !self.remote_workspace.sync_options.delete {
+            return Ok(());
+        }
+
+        // Delete remote resources that don't exist locally, leaving ignored resources untouched
+        for name in remote_tree.children.get(relative_path).into_iter().flatten() {
+            if src_names.contains(name) {
+                continue;
+            }
+
+            let child_path = relative_path.join(name);
+            let is_dir = remote_tree.entries.get(&child_path).is_some_and(|entry| entry.kind == EntryKind::Directory);
+
+            if !self.ignore_rules.is_ignored(&child_path, is_dir) {
+                connection.expect_ok(&Request::Delete { path: child_path })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn mirror_symlink(
+        &self,
+        connection: &mut AgentConnection,
+        relative_path: &Path,
+        remote_entry: Option<&EntryInfo>
+    ) -> Result<()> {
+        let src_path = self.ws_root_path.join(relative_path);
+
+        let target = match fs::read_link(&src_path) {
+            Ok(target) => target,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(io_error("read", &src_path, e))
+        };
+
+        if remote_entry.is_some_and(|entry| entry.link_target.as_ref() == Some(&target)) {
+            return Ok(());
+        }
+
+        connection.expect_ok(&Request::Symlink { path: relative_path.to_path_buf(), target })
+    }
+
+    fn mirror_file(
+        &self,
+        connection: &mut AgentConnection,
+        relative_path: &Path,
+        src_metadata: &Metadata,
+        remote_entry: Option<&EntryInfo>
+    ) -> Result<()> {
+        let mode = to_mode(src_metadata);
+        let remote_file = remote_entry.filter(|entry| entry.kind == EntryKind::File);
+
+        if let Some(remote_file) = remote_file
+            && !self.remote_workspace.sync_options.checksum
+            && remote_file.len == src_metadata.len()
+            && remote_file.mtime == to_timestamp(src_metadata) {
+            if remote_file.mode != mode {
+                connection.expect_ok(&Request::Chmod { path: relative_path.to_path_buf(), mode })?;
+            }
+            return Ok(());
+        }
+
+        if remote_file.is_some() && src_metadata.len() <= MAX_DELTA_FILE_LEN {
+            match self.send_file_delta(connection, relative_path, src_metadata) {
+                Err(Error::RemoteSystemError(msg, _)) if !connection.broken => {
+                    debug!("Sending delta of '{:?}' failed ({msg}), sending the whole file instead", relative_path);
+                },
+                result => return result
+            }
+        }
+
+        self.send_whole_file(connection, relative_path, src_metadata)
+    }
+
+    /// Send the differences between the local file and its remote counterpart
+    fn send_file_delta(&self, connection: &mut AgentConnection, relative_path: &Path, src_metadata: &Metadata) -> Result<()> {
+        let signature_request = Request::Signature {
+            path: relative_path.to_path_buf(),
+            block_size: block_size_for(src_metadata.len())
+        };
+
+        let signature = match connection.request(&signature_request)? {
+            Response::Signature(signature) => signature,
+            response => return Err(connection.unexpected(response))
+        };
+
+        let src_path = self.ws_root_path.join(relative_path);
+        let data = match fs::read(&src_path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(io_error("read", &src_path, e))
+        };
+
+        let delta = compute_delta(&signature, &data);
+        debug!("Sending delta of '{:?}' with {} bytes of literal data", relative_path, delta.literal_data.len());
+
+        let mut digest = Digest::new();
+        digest.update(&data);
+
+        connection.send(&Request::BeginWrite {
+            path: relative_path.to_path_buf(),
+            with_basis: true,
+            block_size: signature.block_size,
+            mode: to_mode(src_metadata),
+            mtime: to_timestamp(src_metadata)
+        }, &[])?;
+
+        let mut ops: Vec<DeltaOp> = vec![];
+        let mut literal_data: &[u8] = &delta.literal_data;
+        let mut chunk_start = literal_data;
+        let mut chunk_len = 0;
+
+        for op in delta.ops.iter() {
+            let mut remaining_len = match *op {
+                DeltaOp::Copy { .. } => {
+                    ops.push(*op);
+                    continue;
+                },
+                DeltaOp::Literal { len } => len as usize
+            };
+
+            // Split literal data across chunks, so that frames stay small
+            while remaining_len > 0 {
+                let len = remaining_len.min(MAX_CHUNK_LEN - chunk_len);
+                ops.push(DeltaOp::Literal { len: len as u32 });
+                chunk_len += len;
+                remaining_len -= len;
+                literal_data = &literal_data[len..];
+
+                if chunk_len == MAX_CHUNK_LEN {
+                    connection.send(&Request::WriteChunk { ops: std::mem::take(&mut ops) }, &chunk_start[..chunk_len])?;
+                    chunk_start = literal_data;
+                    chunk_len = 0;
+                }
+            }
+        }
+
+        if !ops.is_empty() {
+            connection.send(&Request::WriteChunk { ops }, &chunk_start[..chunk_len])?;
+        }
+
+        connection.expect_ok(&Request::EndWrite { digest: digest.finish() })
+    }
+
+    /// Stream the whole file to the agent
+    fn send_whole_file(&self, connection: &mut AgentConnection, relative_path: &Path, src_metadata: &Metadata) -> Result<()> {
+        let src_path = self.ws_root_path.join(relative_path);
+        let mut file = match File::open(&src_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(io_error("open", &src_path, e))
+        };
+
+        connection.send(&Request::BeginWrite {
+            path: relative_path.to_path_buf(),
+            with_basis: false,
+            block_size: 0,
+            mode: to_mode(src_metadata),
+            mtime: to_timestamp(src_metadata)
+        }, &[])?;
+
+        let mut digest = Digest::new();
+        let mut chunk = vec![0u8; MAX_CHUNK_LEN];
+
+        loop {
+            let len = match file.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    connection.send(&Request::AbortWrite, &[])?;
+                    return Err(io_error("read", &src_path, e));
+                }
+            };
+
+            digest.update(&chunk[..len]);
+            connection.send(&Request::WriteChunk { ops: vec![DeltaOp::Literal { len: len as u32 }] }, &chunk[..len])?;
+        }
+
+        connection.expect_ok(&Request::EndWrite { digest: digest.finish() })
+    }
+}
+
+impl SyncBackend for AgentBackend<'_> {
+
+    fn sync_subtree(&self, relative_path: Option<&PathBuf>) -> Result<()> {
+        let relative_path: &Path = relative_path.map(|path| path.as_path()).unwrap_or(Path::new(""));
+
+        if relative_path.parent().is_some() && self.ignore_rules.is_ignored(relative_path, true) {
+            return Ok(());
+        }
+
+        self.with_connection(|connection| self.sync_tree(connection, relative_path))
+    }
+
+    fn sync_files(&self, relative_paths: &[PathBuf]) -> Result<()> {
+        self.with_connection(|connection| {
+            let mut files: Vec<(&PathBuf, Metadata)> = vec![];
+
+            for relative_path in relative_paths.iter() {
+                match self.local_metadata(relative_path)? {
+                    // Directories are synced individually if only their attributes changed, so
+                    // their content is left to directory-level syncs
+                    Some(metadata) if metadata.is_dir() => {
+                        if !self.ignore_rules.is_ignored(relative_path, true) {
+                            let mode = to_mode(&metadata);
+                            connection.expect_ok(&Request::Mkdir { path: relative_path.clone(), mode })?;
+                        }
+                    },
+                    Some(metadata) => {
+                        if !self.ignore_rules.is_ignored(relative_path, false) {
+                            files.push((relative_path, metadata));
+                        }
+                    },
+                    None => {
+                        if self.remote_workspace.sync_options.delete && !self.ignore_rules.is_ignored(relative_path, false) {
+                            connection.expect_ok(&Request::Delete { path: relative_path.clone() })?;
+                        }
+                    }
+                }
+            }
+
+            if files.is_empty() {
+                return Ok(());
+            }
+
+            let paths: Vec<PathBuf> = files.iter().map(|(relative_path, _)| relative_path.to_path_buf()).collect();
+            let remote_entries = match connection.request(&Request::Stat { paths })? {
+                Response::Stats { entries } if entries.len() == files.len() => entries,
+                response => return Err(connection.unexpected(response))
+            };
+
+            let mut remote_tree = RemoteTree { entries: HashMap::new(), children: HashMap::new() };
+            let mut created_parents: HashSet<&Path> = HashSet::new();
+
+            for ((relative_path, _), remote_entry) in files.iter().zip(remote_entries) {
+                match remote_entry {
+                    Some(remote_entry) => {
+                        remote_tree.entries.insert(relative_path.to_path_buf(), remote_entry);
+                    },
+                    None => {
+                        if let Some(parent) = relative_path.parent()
+                            && created_parents.insert(parent) {
+                            self.create_parent_dir(connection, relative_path)?;
+                        }
+                    }
+                }
+            }
+
+            for (relative_path, metadata) in files.iter() {
+                self.mirror_entry(connection, relative_path, metadata, &remote_tree)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn delete_path(&self, relative_path: &Path) -> Result<()> {
+        self.with_connection(|connection| {
+            connection.expect_ok(&Request::Delete { path: relative_path.to_path_buf() })
+        })
+    }
+
+    fn move_path(&self, from: &Path, to: &Path) -> Result<()> {
+        self.with_connection(|connection| {
+            connection.expect_ok(&Request::Rename { from: from.to_path_buf(), to: to.to_path_buf() })
+        })
+    }
+
+    /// Connects to the agent if no connection is open yet and checks that it responds
+    fn health_check(&self) -> Result<()> {
+        self.with_connection(|connection| connection.expect_ok(&Request::Ping))
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+use crate::ignore_rules::IgnoreRules;
+use crate::models::{ConnectionInfo, RemoteWorkspace, SyncPreview, TransferStats};
+use crate::sync::Error;
+use crate::sync::agent::AgentBackend;
+use crate::sync::local::LocalBackend;
+use crate::sync::rsync::RsyncBackend;
+use crate::sync::two_way::TreeState;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const VERSIONING_UNSUPPORTED: &str = "Keeping versions is only supported for remote workspaces that are synced via rsync";
+
+/// Transfers workspace resources to a single remote workspace. All paths are relative to the
+/// workspace root. Failures caused by the remote side (e.g. an unreachable remote system) are
+/// reported as [Error::RemoteSystemError], so that they are retried later on.
+pub(super) trait SyncBackend {
+
+    /// Mirror the directory tree at the given path (`None` being the workspace root) to the remote
+    /// workspace.
+    fn sync_subtree(&self, relative_path: Option<&PathBuf>) -> Result<()>;
+
+    /// Mirror the given files (or any other non-directory resources). Files that no longer exist
+    /// locally are deleted remotely, unless the remote workspace is configured not to delete.
+    fn sync_files(&self, relative_paths: &[PathBuf]) -> Result<()>;
+
+    /// Delete the resource at the given path from the remote workspace
+    fn delete_path(&self, relative_path: &Path) -> Result<()>;
+
+    /// Move the resource at the first path to the second one within the remote workspace, creating
+    /// the destination's parent directories if required. Fails if the resource doesn't exist in the
+    /// remote workspace, in which case it is synced instead.
+    fn move_path(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Err(Error::LocalError(String::from("Moving resources is not supported by this remote workspace")))
+    }
+
+    fn full_sync(&self) -> Result<()> {
+        self.sync_subtree(None)
+    }
+
+    /// Check whether the remote workspace is reachable, without transferring anything
+    fn health_check(&self) -> Result<()>;
+
+    /// Determine the changes a full sync would make to the remote workspace, without making them
+    fn preview(&self) -> Result<SyncPreview> {
+        Err(Error::LocalError(String::from(
+            "Previewing a sync is only supported for remote workspaces that are synced via rsync"
+        )))
+    }
+
+    /// Determine the resources whose content or attributes differ from the remote workspace,
+    /// comparing the content of files by checksum, without changing anything
+    fn verify(&self) -> Result<SyncPreview> {
+        Err(Error::LocalError(String::from(
+            "Verifying a remote workspace is only supported for remote workspaces that are synced via rsync"
+        )))
+    }
+
+    /// List the resources in the remote workspace that are not ignored, which is used to detect
+    /// changes made in two-way remote workspaces and to restore deleted resources
+    fn scan_remote(&self) -> Result<TreeState> {
+        Err(Error::LocalError(String::from("Copying resources from this remote workspace is not supported")))
+    }
+
+    /// Copy the given resources from the remote workspace into the local workspace. Directories are
+    /// created without their content.
+    fn pull_files(&self, _relative_paths: &[PathBuf]) -> Result<()> {
+        Err(Error::LocalError(String::from("Copying resources from this remote workspace is not supported")))
+    }
+
+    /// List the versions kept in the remote workspace, by their path relative to the versions
+    /// directory
+    fn scan_versions(&self) -> Result<TreeState> {
+        Err(Error::LocalError(String::from(VERSIONING_UNSUPPORTED)))
+    }
+
+    /// Remove the given versions, given by their path relative to the versions directory, from the
+    /// remote workspace
+    fn delete_versions(&self, _paths: &[PathBuf]) -> Result<()> {
+        Err(Error::LocalError(String::from(VERSIONING_UNSUPPORTED)))
+    }
+
+    /// Copy the given version of a file from the remote workspace into the local workspace,
+    /// replacing the file if it exists
+    fn restore_version(&self, _relative_path: &Path, _version: &str) -> Result<()> {
+        Err(Error::LocalError(String::from(VERSIONING_UNSUPPORTED)))
+    }
+
+    /// Statistics of the transfers made by this backend so far, `None` if it doesn't keep any
+    fn transfer_stats(&self) -> Option<TransferStats> {
+        None
+    }
+}
+
+pub(super) fn backend_for<'a>(
+    ws_root_path: &'a PathBuf,
+    remote_workspace: &'a RemoteWorkspace,
+    ignore_rules: &'a IgnoreRules
+) -> Box<dyn SyncBackend + 'a> {
+
+    match &remote_workspace.connection_info {
+        ConnectionInfo::Local { path } => {
+            Box::new(LocalBackend::new(ws_root_path, path, remote_workspace, ignore_rules))
+        },
+        ConnectionInfo::Agent { .. } => Box::new(AgentBackend::new(ws_root_path, remote_workspace, ignore_rules)),
+        _ => Box::new(RsyncBackend::new(ws_root_path, remote_workspace, ignore_rules))
+    }
+}
@@ -0,0 +1,706 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{File, FileTimes, Metadata, OpenOptions};
+use std::io;
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use log::{debug, warn};
+use crate::ignore_rules::IgnoreRules;
+use crate::models::{FailureCause, RemoteWorkspace, SyncOptions};
+use crate::sync::backend::SyncBackend;
+use crate::sync::Error;
+use crate::sync::two_way::{EntryState, TreeState};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Suffix of the temporary files that are renamed to their final name once completely written
+const TMP_FILE_SUFFIX: &str = ".wsync-tmp";
+
+/// Resources pulled into the local workspace keep all of their attributes, regardless of the
+/// options for syncing the remote workspace
+static PULL_OPTIONS: LazyLock<SyncOptions> = LazyLock::new(SyncOptions::default);
+
+/// Mirrors the workspace to a directory on a locally mounted filesystem (e.g. an external drive or
+/// an NFS mount) without spawning any external process. File contents are reflinked if the target
+/// filesystem supports it and copied with `copy_file_range` otherwise.
+///
+/// Neither the mount point nor the remote workspace directory are created, so that an unmounted
+/// volume is detected instead of being filled up with a copy of the workspace.
+pub(super) struct LocalBackend<'a> {
+    ws_root_path: &'a PathBuf,
+    mount_point: &'a PathBuf,
+    remote_workspace: &'a RemoteWorkspace,
+    ignore_rules: &'a IgnoreRules
+}
+
+impl<'a> LocalBackend<'a> {
+
+    pub(super) fn new(
+        ws_root_path: &'a PathBuf,
+        mount_point: &'a PathBuf,
+        remote_workspace: &'a RemoteWorkspace,
+        ignore_rules: &'a IgnoreRules
+    ) -> Self {
+        LocalBackend { ws_root_path, mount_point, remote_workspace, ignore_rules }
+    }
+
+    fn target_root(&self) -> &Path {
+        &self.remote_workspace.remote_path
+    }
+
+    fn check_target_root(&self) -> Result<()> {
+        for dir in [self.mount_point.as_path(), self.target_root()] {
+            match fs::metadata(dir) {
+                Ok(metadata) if metadata.is_dir() => {},
+                Ok(_) => {
+                    return Err(Error::remote(format!("'{:?}' is not a directory", dir)));
+                },
+                Err(e) => return Err(io_error("access", dir, e))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mirror the resource at the given path, whose local metadata is passed along
+    fn mirror_entry(&self, relative_path: &Path, src_metadata: &Metadata) -> Result<()> {
+        let file_type = src_metadata.file_type();
+
+        if file_type.is_dir() {
+            self.mirror_dir(relative_path, src_metadata)
+        } else if file_type.is_symlink() {
+            self.mirror_symlink(relative_path)
+        } else if file_type.is_file() {
+            self.mirror_file(relative_path, src_metadata)
+        } else {
+            debug!("Skipping special file '{:?}'", relative_path);
+            Ok(())
+        }
+    }
+
+    /// Make sure that the given directory exists in the remote workspace, replacing any other kind
+    /// of resource at its path
+    fn create_dir(&self, relative_path: &Path) -> Result<()> {
+        let dst_dir = self.target_root().join(relative_path);
+
+        match fs::symlink_metadata(&dst_dir) {
+            Ok(dst_metadata) if dst_metadata.is_dir() => Ok(()),
+            Ok(_) => {
+                remove_resource(&dst_dir)?;
+                fs::create_dir(&dst_dir).map_err(|e| io_error("create", &dst_dir, e))
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                fs::create_dir(&dst_dir).map_err(|e| io_error("create", &dst_dir, e))
+            },
+            Err(e) => Err(io_error("access", &dst_dir, e))
+        }
+    }
+
+    fn mirror_dir(&self, relative_path: &Path, src_metadata: &Metadata) -> Result<()> {
+        let src_dir = self.ws_root_path.join(relative_path);
+        let dst_dir = self.target_root().join(relative_path);
+
+        self.create_dir(relative_path)?;
+
+        let entries = match fs::read_dir(&src_dir) {
+            Ok(entries) => entries,
+            // The directory was removed in the meantime, which is synced once it is reported
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(source_io_error("read", &src_dir, e))
+        };
+
+        let mut src_names: HashSet<OsString> = HashSet::new();
+        // Entries that could not be read locally are skipped, like rsync does, and reported once
+        // the rest of the directory is mirrored
+        let mut partial_error = None;
+        let mut is_listed = true;
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    partial_error.get_or_insert(source_io_error("read", &src_dir, e));
+                    is_listed = false;
+                    break;
+                }
+            };
+            let child_path = relative_path.join(entry.file_name());
+
+            let child_metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => {
+                    // Keep the resource in the remote workspace, as it still exists locally
+                    src_names.insert(entry.file_name());
+                    partial_error.get_or_insert(source_io_error("access", &entry.path(), e));
+                    continue;
+                }
+            };
+
+            if self.ignore_rules.is_ignored(&child_path, child_metadata.is_dir()) {
+                continue;
+            }
+
+            src_names.insert(entry.file_name());
+            match self.mirror_entry(&child_path, &child_metadata) {
+                Err(e) if e.cause() == Some(FailureCause::PartialTransfer) => {
+                    partial_error.get_or_insert(e);
+                },
+                result => result?
+            }
+        }
+
+        // Resources that were not listed must not be mistaken for deleted ones
+        if self.remote_workspace.sync_options.delete && is_listed {
+            self.delete_extraneous(relative_path, &src_names)?;
+        }
+
+        // Set the attributes last, since mirroring the entries modifies the directory and might
+        // require write permissions
+        copy_attributes(&dst_dir, src_metadata, &self.remote_workspace.sync_options)?;
+
+        match partial_error {
+            Some(e) => Err(e),
+            None => Ok(())
+        }
+    }
+
+    /// Delete the resources in the given directory that don't exist locally. Ignored resources are
+    /// left untouched.
+    fn delete_extraneous(&self, relative_path: &Path, src_names: &HashSet<OsString>) -> Result<()> {
+        let dst_dir = self.target_root().join(relative_path);
+        let entries = fs::read_dir(&dst_dir).map_err(|e| io_error("read", &dst_dir, e))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|e| io_error("read", &dst_dir, e))?;
+            if src_names.contains(&entry.file_name()) {
+                continue;
+            }
+
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            if self.ignore_rules.is_ignored(&relative_path.join(entry.file_name()), is_dir) {
+                continue;
+            }
+
+            remove_resource(&entry.path())?;
+        }
+
+        Ok(())
+    }
+
+    fn mirror_symlink(&self, relative_path: &Path) -> Result<()> {
+        let src_path = self.ws_root_path.join(relative_path);
+        let dst_path = self.target_root().join(relative_path);
+
+        let link_target = match fs::read_link(&src_path) {
+            Ok(link_target) => link_target,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(source_io_error("read", &src_path, e))
+        };
+
+        if fs::read_link(&dst_path).is_ok_and(|dst_link_target| dst_link_target == link_target) {
+            return Ok(());
+        }
+
+        remove_resource(&dst_path)?;
+        std::os::unix::fs::symlink(&link_target, &dst_path).map_err(|e| io_error("create", &dst_path, e))
+    }
+
+    fn mirror_file(&self, relative_path: &Path, src_metadata: &Metadata) -> Result<()> {
+        let src_path = self.ws_root_path.join(relative_path);
+        let dst_path = self.target_root().join(relative_path);
+
+        let src_file = match File::open(&src_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(source_io_error("open", &src_path, e))
+        };
+
+        let dst_metadata = match fs::symlink_metadata(&dst_path) {
+            Ok(dst_metadata) => Some(dst_metadata),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(io_error("access", &dst_path, e))
+        };
+
+        if let Some(dst_metadata) = &dst_metadata {
+            if self.is_up_to_date(&src_path, src_metadata, &dst_path, dst_metadata)? {
+                // Only the attributes might have changed, e.g. by a 'chmod'
+                return copy_attributes(&dst_path, src_metadata, &self.remote_workspace.sync_options);
+            }
+
+            if !dst_metadata.is_file() {
+                remove_resource(&dst_path)?;
+            }
+        }
+
+        if self.remote_workspace.sync_options.inplace && dst_metadata.is_some_and(|metadata| metadata.is_file()) {
+            let dst_file = OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .open(&dst_path)
+                .map_err(|e| io_error("open", &dst_path, e))?;
+
+            copy_contents(&src_file, &dst_file).map_err(|e| io_error("write", &dst_path, e))?;
+            return copy_file_attributes(&dst_file, &dst_path, src_metadata, &self.remote_workspace.sync_options);
+        }
+
+        // Write to a temporary file first, so that the remote workspace never holds a partially
+        // written file
+        let mut tmp_file_name = OsString::from(".");
+        tmp_file_name.push(dst_path.file_name().unwrap_or_default());
+        tmp_file_name.push(TMP_FILE_SUFFIX);
+        let tmp_path = dst_path.with_file_name(tmp_file_name);
+
+        let tmp_file = File::create(&tmp_path).map_err(|e| io_error("create", &tmp_path, e))?;
+
+        let copy_result = copy_contents(&src_file, &tmp_file)
+            .map_err(|e| io_error("write", &tmp_path, e))
+            .and_then(|_| copy_file_attributes(&tmp_file, &tmp_path, src_metadata, &self.remote_workspace.sync_options))
+            .and_then(|_| fs::rename(&tmp_path, &dst_path).map_err(|e| io_error("move", &tmp_path, e)));
+
+        if copy_result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+
+        copy_result
+    }
+
+    /// A file is up-to-date if it has the same size and modification time as the local one, or
+    /// the same content if checksums are used or modification times are not preserved.
+    fn is_up_to_date(
+        &self,
+        src_path: &Path,
+        src_metadata: &Metadata,
+        dst_path: &Path,
+        dst_metadata: &Metadata
+    ) -> Result<bool> {
+        if !dst_metadata.is_file() || dst_metadata.len() != src_metadata.len() {
+            return Ok(false);
+        }
+
+        let sync_options = &self.remote_workspace.sync_options;
+        if sync_options.checksum || !sync_options.times {
+            return have_same_content(src_path, dst_path);
+        }
+
+        match (src_metadata.modified(), dst_metadata.modified()) {
+            (Ok(src_modified), Ok(dst_modified)) => Ok(src_modified == dst_modified),
+            _ => Ok(false)
+        }
+    }
+
+    /// Make sure that the parent directories of the given resource exist in the remote workspace
+    fn create_parent_dirs(&self, relative_path: &Path) -> Result<()> {
+        if let Some(parent) = relative_path.parent() {
+            let dst_parent = self.target_root().join(parent);
+            fs::create_dir_all(&dst_parent).map_err(|e| io_error("create", &dst_parent, e))?;
+        }
+
+        Ok(())
+    }
+
+    fn scan_remote_dir(&self, relative_path: &Path, tree: &mut TreeState) -> Result<()> {
+        let dir = self.target_root().join(relative_path);
+        let entries = fs::read_dir(&dir).map_err(|e| io_error("read", &dir, e))?;
+
+        for entry in entries {
+            let entry = entry.map_err(|e| io_error("read", &dir, e))?;
+            let child_path = relative_path.join(entry.file_name());
+
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(io_error("access", &entry.path(), e))
+            };
+
+            // Files that are currently being written by another sync
+            if !metadata.is_dir() && entry.file_name().to_string_lossy().ends_with(TMP_FILE_SUFFIX) {
+                continue;
+            }
+
+            if self.ignore_rules.is_ignored(&child_path, metadata.is_dir()) {
+                continue;
+            }
+
+            let Some(state) = EntryState::from_metadata(&metadata) else {
+                continue;
+            };
+
+            tree.insert(child_path.clone(), state);
+
+            if metadata.is_dir() {
+                self.scan_remote_dir(&child_path, tree)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copy a single resource from the remote workspace into the local workspace
+    fn pull_entry(&self, relative_path: &Path) -> Result<()> {
+        let src_path = self.target_root().join(relative_path);
+        let dst_path = self.ws_root_path.join(relative_path);
+
+        let src_metadata = match fs::symlink_metadata(&src_path) {
+            Ok(metadata) => metadata,
+            // Removed in the meantime, which is picked up by the next sync
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(io_error("access", &src_path, e))
+        };
+
+        if let Some(parent) = dst_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| local_io_error("create", parent, e))?;
+        }
+
+        let dst_metadata = fs::symlink_metadata(&dst_path).ok();
+        let file_type = src_metadata.file_type();
+
+        if file_type.is_dir() {
+            if !dst_metadata.as_ref().is_some_and(|metadata| metadata.is_dir()) {
+                remove_resource(&dst_path).map_err(into_local)?;
+                fs::create_dir(&dst_path).map_err(|e| local_io_error("create", &dst_path, e))?;
+            }
+            return copy_attributes(&dst_path, &src_metadata, &PULL_OPTIONS).map_err(into_local);
+        }
+
+        if file_type.is_symlink() {
+            let link_target = fs::read_link(&src_path).map_err(|e| io_error("read", &src_path, e))?;
+            remove_resource(&dst_path).map_err(into_local)?;
+            return std::os::unix::fs::symlink(&link_target, &dst_path).map_err(|e| local_io_error("create", &dst_path, e));
+        }
+
+        if !file_type.is_file() {
+            debug!("Skipping special file '{:?}'", relative_path);
+            return Ok(());
+        }
+
+        let src_file = File::open(&src_path).map_err(|e| io_error("open", &src_path, e))?;
+
+        let mut tmp_file_name = OsString::from(".");
+        tmp_file_name.push(dst_path.file_name().unwrap_or_default());
+        tmp_file_name.push(TMP_FILE_SUFFIX);
+        let tmp_path = dst_path.with_file_name(tmp_file_name);
+
+        let tmp_file = File::create(&tmp_path).map_err(|e| local_io_error("create", &tmp_path, e))?;
+
+        let copy_result = copy_contents(&src_file, &tmp_file)
+            .map_err(|e| local_io_error("write", &tmp_path, e))
+            .and_then(|_| copy_file_attributes(&tmp_file, &tmp_path, &src_metadata, &PULL_OPTIONS).map_err(into_local))
+            .and_then(|_| {
+                if dst_metadata.is_some_and(|metadata| metadata.is_dir()) {
+                    remove_resource(&dst_path).map_err(into_local)?;
+                }
+                fs::rename(&tmp_path, &dst_path).map_err(|e| local_io_error("move", &tmp_path, e))
+            });
+
+        if copy_result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+
+        copy_result
+    }
+}
+
+impl SyncBackend for LocalBackend<'_> {
+
+    fn sync_subtree(&self, relative_path: Option<&PathBuf>) -> Result<()> {
+        self.check_target_root()?;
+
+        let relative_path: &Path = relative_path.map(|path| path.as_path()).unwrap_or(Path::new(""));
+
+        if relative_path.parent().is_some() && self.ignore_rules.is_ignored(relative_path, true) {
+            return Ok(());
+        }
+
+        let src_metadata = match fs::symlink_metadata(self.ws_root_path.join(relative_path)) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                if self.remote_workspace.sync_options.delete {
+                    return self.delete_path(relative_path);
+                }
+                return Ok(());
+            },
+            Err(e) if relative_path.parent().is_some() => {
+                return Err(source_io_error("access", relative_path, e));
+            },
+            Err(e) => return Err(local_io_error("access", relative_path, e))
+        };
+
+        let sync_result = self
+            .create_parent_dirs(relative_path)
+            .and_then(|_| self.mirror_entry(relative_path, &src_metadata));
+
+        match sync_result {
+            Err(Error::RemoteSystemError(msg, cause))
+                if relative_path.parent().is_some() && cause != Some(FailureCause::PartialTransfer) => {
+                // As with rsync, attempt to sync from the ws root, in case (parts) of the remote
+                // workspace's dir tree were deleted/moved/etc.
+                warn!(
+                    "Syncing with '{}' failed ({msg}). Attempting to sync from ws root to re-build the dir tree...",
+                    self.remote_workspace.name
+                );
+                self.full_sync()
+            },
+            result => result
+        }
+    }
+
+    fn sync_files(&self, relative_paths: &[PathBuf]) -> Result<()> {
+        self.check_target_root()?;
+
+        let mut dirs: Vec<(&PathBuf, Metadata)> = vec![];
+        let mut partial_error = None;
+
+        for relative_path in relative_paths.iter() {
+            let src_path = self.ws_root_path.join(relative_path);
+
+            match fs::symlink_metadata(&src_path) {
+                Ok(metadata) => {
+                    if self.ignore_rules.is_ignored(relative_path, metadata.is_dir()) {
+                        continue;
+                    }
+
+                    self.create_parent_dirs(relative_path)?;
+
+                    if metadata.is_dir() {
+                        dirs.push((relative_path, metadata));
+                        continue;
+                    }
+
+                    match self.mirror_entry(relative_path, &metadata) {
+                        Err(e) if e.cause() == Some(FailureCause::PartialTransfer) => {
+                            partial_error.get_or_insert(e);
+                        },
+                        result => result?
+                    }
+                },
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    if self.remote_workspace.sync_options.delete
+                        && !self.ignore_rules.is_ignored(relative_path, false) {
+                        self.delete_path(relative_path)?;
+                    }
+                },
+                Err(e) => {
+                    partial_error.get_or_insert(source_io_error("access", &src_path, e));
+                }
+            }
+        }
+
+        // Directories are synced individually if only their attributes changed, so their content
+        // is left to directory-level syncs. Their attributes are set last, as mirroring the files
+        // within them modifies them, subdirectories first.
+        dirs.sort_by(|(path, _), (other_path, _)| other_path.cmp(path));
+        for (relative_path, metadata) in dirs.iter() {
+            self.create_dir(relative_path)?;
+            copy_attributes(&self.target_root().join(relative_path), metadata, &self.remote_workspace.sync_options)?;
+        }
+
+        match partial_error {
+            Some(e) => Err(e),
+            None => Ok(())
+        }
+    }
+
+    fn delete_path(&self, relative_path: &Path) -> Result<()> {
+        self.check_target_root()?;
+
+        if relative_path.parent().is_none() {
+            return Err(Error::LocalError(String::from("Refusing to delete the remote workspace root")));
+        }
+
+        remove_resource(&self.target_root().join(relative_path))
+    }
+
+    fn move_path(&self, from: &Path, to: &Path) -> Result<()> {
+        self.check_target_root()?;
+
+        if from.parent().is_none() || to.parent().is_none() {
+            return Err(Error::LocalError(String::from("Refusing to move the remote workspace root")));
+        }
+
+        self.create_parent_dirs(to)?;
+
+        let src_path = self.target_root().join(from);
+        fs::rename(&src_path, self.target_root().join(to)).map_err(|e| io_error("move", &src_path, e))
+    }
+
+    fn health_check(&self) -> Result<()> {
+        self.check_target_root()?;
+
+        let target_root = self.target_root();
+        fs::read_dir(target_root).map(|_| ()).map_err(|e| io_error("read", target_root, e))
+    }
+
+    fn scan_remote(&self) -> Result<TreeState> {
+        self.check_target_root()?;
+
+        let mut tree = TreeState::new();
+        self.scan_remote_dir(Path::new(""), &mut tree)?;
+        Ok(tree)
+    }
+
+    fn pull_files(&self, relative_paths: &[PathBuf]) -> Result<()> {
+        self.check_target_root()?;
+
+        for relative_path in relative_paths.iter() {
+            self.pull_entry(relative_path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Error accessing the remote workspace
+fn io_error(action: &str, path: &Path, error: io::Error) -> Error {
+    Error::remote(format!("Unable to {action} '{:?}': {error}", path))
+}
+
+/// Error reading a resource of the local workspace that is synced. It is skipped, and the sync is
+/// retried as a partial transfer, as rsync would.
+fn source_io_error(action: &str, path: &Path, error: io::Error) -> Error {
+    warn!("Skipping '{:?}', as it can't be synced: Unable to {action} it: {error}", path);
+    Error::RemoteSystemError(format!("Unable to {action} '{:?}': {error}", path), Some(FailureCause::PartialTransfer))
+}
+
+/// Error accessing the local workspace as the destination of a pull
+fn local_io_error(action: &str, path: &Path, error: io::Error) -> Error {
+    Error::LocalError(format!("Unable to {action} '{:?}': {error}", path))
+}
+
+/// Attribute an error of the helpers, which access either workspace, to the local workspace
+fn into_local(error: Error) -> Error {
+    match error {
+        Error::RemoteSystemError(msg, _) => Error::LocalError(msg),
+        error => error
+    }
+}
+
+/// Remove the resource at the given path, whatever its type. Succeeds if it does not exist.
+fn remove_resource(path: &Path) -> Result<()> {
+    let remove_result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) => Err(e)
+    };
+
+    match remove_result {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(io_error("remove", path, e))
+    }
+}
+
+/// Copy the attributes preserved according to the sync options to an existing resource, leaving
+/// those that are already equal untouched
+fn copy_attributes(path: &Path, src_metadata: &Metadata, sync_options: &SyncOptions) -> Result<()> {
+    let dst_metadata = fs::symlink_metadata(path).map_err(|e| io_error("access", path, e))?;
+
+    let (uid, gid) = owner_to_copy(src_metadata, Some(&dst_metadata), sync_options);
+    if uid.is_some() || gid.is_some() {
+        ignore_permission_denied(std::os::unix::fs::lchown(path, uid, gid), path)?;
+    }
+
+    if sync_options.perms && dst_metadata.mode() != src_metadata.mode() {
+        fs::set_permissions(path, src_metadata.permissions()).map_err(|e| io_error("set permissions of", path, e))?;
+    }
+
+    if let Ok(modified) = src_metadata.modified()
+        && sync_options.times
+        && dst_metadata.modified().ok() != Some(modified) {
+        let file = File::open(path).map_err(|e| io_error("open", path, e))?;
+        file.set_times(FileTimes::new().set_modified(modified)).map_err(|e| {
+            io_error("set modification time of", path, e)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Copy the attributes preserved according to the sync options to a newly written file
+fn copy_file_attributes(file: &File, path: &Path, src_metadata: &Metadata, sync_options: &SyncOptions) -> Result<()> {
+    let (uid, gid) = owner_to_copy(src_metadata, None, sync_options);
+    if uid.is_some() || gid.is_some() {
+        ignore_permission_denied(std::os::unix::fs::fchown(file, uid, gid), path)?;
+    }
+
+    if sync_options.perms {
+        file.set_permissions(src_metadata.permissions()).map_err(|e| io_error("set permissions of", path, e))?;
+    }
+
+    if let Ok(modified) = src_metadata.modified()
+        && sync_options.times {
+        file.set_times(FileTimes::new().set_modified(modified)).map_err(|e| {
+            io_error("set modification time of", path, e)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// The owner and group to be applied to a resource, if preserved and different from the ones of
+/// the existing resource
+fn owner_to_copy(src_metadata: &Metadata, dst_metadata: Option<&Metadata>, sync_options: &SyncOptions) -> (Option<u32>, Option<u32>) {
+    let uid = Some(src_metadata.uid())
+        .filter(|uid| sync_options.owner && dst_metadata.is_none_or(|metadata| metadata.uid() != *uid));
+    let gid = Some(src_metadata.gid())
+        .filter(|gid| sync_options.group && dst_metadata.is_none_or(|metadata| metadata.gid() != *gid));
+
+    (uid, gid)
+}
+
+/// As with rsync, ownership is only preserved where permitted, i.e. the owner only if the monitor
+/// runs as root and the group only if the monitor's user is a member of it
+fn ignore_permission_denied(result: io::Result<()>, path: &Path) -> Result<()> {
+    match result {
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            debug!("Not permitted to change the ownership of '{:?}': {e}", path);
+            Ok(())
+        },
+        result => result.map_err(|e| io_error("change the ownership of", path, e))
+    }
+}
+
+/// Copy the content of the source file to the (empty) destination file. The content is shared
+/// with the source file (reflinked) if the filesystem supports it. Otherwise, the content is
+/// copied by [io::copy], which uses `copy_file_range` where available and thereby avoids copying
+/// the data through user space.
+fn copy_contents(src_file: &File, dst_file: &File) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+
+        // SAFETY: Both file descriptors are valid for the duration of the call
+        let result = unsafe { libc::ioctl(dst_file.as_raw_fd(), libc::FICLONE, src_file.as_raw_fd()) };
+        if result == 0 {
+            return Ok(());
+        }
+    }
+
+    let mut src_file = src_file;
+    let mut dst_file = dst_file;
+    io::copy(&mut src_file, &mut dst_file).map(|_| ())
+}
+
+fn have_same_content(src_path: &Path, dst_path: &Path) -> Result<bool> {
+    let mut src_file = File::open(src_path).map_err(|e| source_io_error("open", src_path, e))?;
+    let mut dst_file = File::open(dst_path).map_err(|e| io_error("open", dst_path, e))?;
+
+    let mut src_buf = vec![0u8; 64 * 1024];
+    let mut dst_buf = vec![0u8; 64 * 1024];
+
+    loop {
+        let nr_of_bytes = src_file.read(&mut src_buf).map_err(|e| source_io_error("read", src_path, e))?;
+        if nr_of_bytes == 0 {
+            // Both files have the same size
+            return Ok(true);
+        }
+
+        dst_file.read_exact(&mut dst_buf[..nr_of_bytes]).map_err(|e| io_error("read", dst_path, e))?;
+        if src_buf[..nr_of_bytes] != dst_buf[..nr_of_bytes] {
+            return Ok(false);
+        }
+    }
+}
@@ -0,0 +1,577 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use log::{debug, warn};
+use std::fmt::{Display, Formatter};
+use std::sync::{LazyLock, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
+use crate::events::{emit, unix_timestamp, MonitorEvent};
+use crate::hooks;
+use crate::permits;
+use crate::ignore_rules::IgnoreRules;
+use crate::models::{
+    FailureCause,
+    FileVersions,
+    RemoteWorkspace,
+    SyncPreview,
+    TransferStats,
+    VersionRestore,
+    WorkspaceInfo
+};
+use crate::sync::backend::{backend_for, SyncBackend};
+
+mod backend;
+mod rsync;
+mod rsync_error;
+mod local;
+mod agent;
+mod two_way;
+mod versioning;
+
+#[derive(Debug)]
+pub(super) enum Error {
+    /// Failure caused by the remote side, along with its cause if it could be determined (e.g. from
+    /// rsync's exit code and stderr)
+    RemoteSystemError(String, Option<FailureCause>),
+    LocalError(String)
+}
+
+impl Error {
+
+    /// Remote failure whose cause is unknown
+    pub(super) fn remote(msg: String) -> Self {
+        Error::RemoteSystemError(msg, None)
+    }
+
+    /// Cause of the failure, if it is known
+    pub(crate) fn cause(&self) -> Option<FailureCause> {
+        match self {
+            Error::RemoteSystemError(_, cause) => *cause,
+            Error::LocalError(_) => None
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::LocalError(msg) => write!(f, "[Local_Error] {msg}"),
+            Error::RemoteSystemError(msg, _) => write!(f, "[Remote_System_Error] {msg}")
+        }
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Maximum number of files passed to a backend at once, e.g. to a single rsync invocation
+const MAX_FILES_PER_BATCH: usize = 5000;
+
+/// Statistics of all transfers to and from each remote workspace since the monitor started
+static TRANSFER_TOTALS: LazyLock<Mutex<HashMap<String, TransferStats>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Resource that was moved (or renamed) within the workspace, given by its workspace-relative paths
+#[derive(Debug, Clone)]
+pub(crate) struct PathMove {
+    pub(crate) from: PathBuf,
+    pub(crate) to: PathBuf,
+    pub(crate) is_dir: bool
+}
+
+impl PathMove {
+
+    /// Path of the given resource after the move, if it is the moved resource or contained in it
+    pub(crate) fn moved_path(&self, relative_path: &Path) -> Option<PathBuf> {
+        let suffix = relative_path.strip_prefix(&self.from).ok()?;
+
+        if suffix.as_os_str().is_empty() {
+            return Some(self.to.clone());
+        }
+
+        Some(self.to.join(suffix))
+    }
+
+    /// Targets that sync the move by transferring the moved resource again, which is how moves
+    /// are synced if they can't be performed in the remote workspace
+    pub(crate) fn fallback(&self) -> SyncTargets {
+        if !self.is_dir {
+            return SyncTargets { files: vec![self.from.clone(), self.to.clone()], ..Default::default() };
+        }
+
+        let mut directories = vec![parent_directory(&self.from)];
+        let to_parent = parent_directory(&self.to);
+
+        if !directories.contains(&to_parent) {
+            directories.push(to_parent);
+        }
+
+        SyncTargets::from_directories(directories)
+    }
+}
+
+/// Workspace-relative resources to be synced
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SyncTargets {
+    /// Directory trees that are synced as a whole, `None` being the workspace root
+    pub(crate) directories: Vec<Option<PathBuf>>,
+    /// Individual files (or any other non-directory resources), which are synced in batches
+    pub(crate) files: Vec<PathBuf>,
+    /// Resources that are moved in the remote workspace in the given order, before the other
+    /// targets are synced
+    pub(crate) moves: Vec<PathMove>
+}
+
+impl SyncTargets {
+
+    pub(crate) fn from_directories(directories: Vec<Option<PathBuf>>) -> Self {
+        SyncTargets { directories, ..Default::default() }
+    }
+
+    /// Targets syncing the resource at the given workspace-relative path, `None` being the workspace
+    /// root. A resource that doesn't exist (anymore) is synced via its parent directory, so that it
+    /// is deleted in the remote workspaces regardless of what it was.
+    pub(crate) fn from_resource(ws_root_path: &Path, relative_path: Option<PathBuf>) -> Self {
+        let Some(relative_path) = relative_path else {
+            return SyncTargets::from_directories(vec![None]);
+        };
+
+        match fs::symlink_metadata(ws_root_path.join(&relative_path)) {
+            Ok(metadata) if metadata.is_dir() => SyncTargets::from_directories(vec![Some(relative_path)]),
+            Ok(_) => SyncTargets { files: vec![relative_path], ..Default::default() },
+            Err(_) => SyncTargets::from_directories(vec![parent_directory(&relative_path)])
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.directories.is_empty() && self.files.is_empty() && self.moves.is_empty()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.directories.len() + self.files.len() + self.moves.len()
+    }
+
+    /// Add the given targets, with their moves being performed after the contained ones
+    pub(crate) fn add(&mut self, targets: SyncTargets) {
+        self.directories.extend(targets.directories);
+        self.files.extend(targets.files);
+        self.moves.extend(targets.moves);
+    }
+
+    /// The targets with the moves being replaced by their fallback targets
+    pub(crate) fn without_moves(&self) -> SyncTargets {
+        let mut targets = SyncTargets { directories: self.directories.clone(), files: self.files.clone(), moves: vec![] };
+
+        for path_move in self.moves.iter() {
+            targets.add(path_move.fallback());
+        }
+
+        targets
+    }
+
+    /// The targets as directory trees, with files being represented by their parent directory and
+    /// moves by their fallback targets
+    pub(crate) fn to_directories(&self) -> Vec<Option<PathBuf>> {
+        let targets = self.without_moves();
+        let parent_directories = targets.files.iter().map(|file| parent_directory(file));
+
+        targets.directories.iter().cloned().chain(parent_directories).collect()
+    }
+}
+
+/// Parent directory of the workspace-relative path, `None` being the workspace root
+fn parent_directory(relative_path: &Path) -> Option<PathBuf> {
+    relative_path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map(|parent| parent.to_path_buf())
+}
+
+/// Outcome of syncing a set of targets with a single remote workspace
+#[derive(Debug)]
+pub(crate) struct RemoteSyncOutcome {
+    pub(crate) remote_workspace: String,
+    /// Targets that could not be synced
+    pub(crate) failed_targets: SyncTargets,
+    pub(crate) error: Option<Error>,
+    /// Statistics of what was transferred, if the remote workspace's backend keeps them
+    pub(crate) transferred: Option<TransferStats>
+}
+
+/// Targets to be synced with a remote workspace
+pub(crate) struct SyncJob<'a> {
+    pub(crate) remote_workspace: &'a RemoteWorkspace,
+    pub(crate) targets: SyncTargets,
+    /// Whether to check that the remote workspace is reachable before syncing, e.g. when retrying
+    /// to sync a remote workspace that was unreachable before
+    pub(crate) check_health: bool,
+    /// Whether the job only checks a two-way remote workspace for remote changes, in which case
+    /// its post-sync hook is not run
+    pub(crate) poll: bool
+}
+
+/// Aggregated outcomes of syncing a workspace with its remote workspaces
+#[derive(Debug)]
+pub(crate) struct SyncReport {
+    pub(crate) outcomes: Vec<RemoteSyncOutcome>
+}
+
+impl SyncReport {
+    pub(crate) fn failed_outcomes(&self) -> impl Iterator<Item = &RemoteSyncOutcome> {
+        self.outcomes.iter().filter(|outcome| outcome.error.is_some())
+    }
+}
+
+fn synchronize_remote_workspace_targets(
+    workspace_info: &WorkspaceInfo,
+    job: &SyncJob,
+    ignore_rules: &IgnoreRules
+) -> RemoteSyncOutcome {
+    let backend = backend_for(&workspace_info.local_path, job.remote_workspace, ignore_rules);
+
+    let mut outcome = sync_targets(workspace_info, job, backend.as_ref(), ignore_rules);
+    outcome.transferred = backend.transfer_stats();
+
+    outcome
+}
+
+fn sync_targets(
+    workspace_info: &WorkspaceInfo,
+    job: &SyncJob,
+    backend: &dyn SyncBackend,
+    ignore_rules: &IgnoreRules
+) -> RemoteSyncOutcome {
+    let targets = &job.targets;
+    let mut outcome = RemoteSyncOutcome {
+        remote_workspace: job.remote_workspace.name.clone(),
+        failed_targets: SyncTargets::default(),
+        error: None,
+        transferred: None
+    };
+
+    if job.check_health
+        && let Err(e) = backend.health_check() {
+        debug!("Health check of '{}' failed: {e}", job.remote_workspace.name);
+        outcome.failed_targets = targets.clone();
+        outcome.error = Some(e);
+        return outcome;
+    }
+
+    // Two-way remote workspaces are always reconciled as a whole, since changes made in the remote
+    // workspace are not tied to the targets
+    if let Some(two_way) = &job.remote_workspace.two_way {
+        let policy = two_way.conflict_policy;
+        match two_way::reconcile(workspace_info, job.remote_workspace, policy, backend, ignore_rules) {
+            Ok(_) => versioning::enforce_retention(job.remote_workspace, backend),
+            Err(e) => {
+                outcome.failed_targets = SyncTargets::from_directories(vec![None]);
+                outcome.error = Some(e);
+            }
+        }
+        return outcome;
+    }
+
+    // Moved resources are moved in the remote workspace first, so that they are not transferred
+    // again. Resources that could not be moved are synced along with the other targets instead.
+    let targets = &move_resources(job, backend);
+
+    // Chances are that the remaining targets would fail for the same reason as a failed one (e.g.
+    // the remote system being unreachable), so don't bother attempting them.
+    for (idx, relative_path) in targets.directories.iter().enumerate() {
+        let sync_result = match relative_path {
+            None => backend.full_sync(),
+            Some(relative_path) => backend.sync_subtree(Some(relative_path))
+        };
+
+        if let Err(e) = sync_result {
+            outcome.failed_targets = SyncTargets {
+                directories: targets.directories[idx..].to_vec(),
+                files: targets.files.clone(),
+                moves: vec![]
+            };
+            outcome.error = Some(e);
+            return outcome;
+        }
+    }
+
+    for (idx, batch) in targets.files.chunks(MAX_FILES_PER_BATCH).enumerate() {
+        if let Err(e) = backend.sync_files(batch) {
+            outcome.failed_targets = SyncTargets {
+                files: targets.files[idx * MAX_FILES_PER_BATCH..].to_vec(),
+                ..Default::default()
+            };
+            outcome.error = Some(e);
+            return outcome;
+        }
+    }
+
+    versioning::enforce_retention(job.remote_workspace, backend);
+
+    outcome
+}
+
+/// Perform the job's moves in its remote workspace and return the targets that remain to be synced,
+/// which include the attributes of the moved resources. If a move fails, e.g. because the backend
+/// doesn't support moving resources or the resource doesn't exist in the remote workspace, it and
+/// all subsequent moves, which might depend on it, are synced by their fallback targets instead.
+/// Remote workspaces that don't delete resources keep them at their previous path as well, so their
+/// moves are always synced by their fallback targets.
+fn move_resources(job: &SyncJob, backend: &dyn SyncBackend) -> SyncTargets {
+    let targets = &job.targets;
+
+    if !job.remote_workspace.sync_options.delete {
+        return targets.without_moves();
+    }
+
+    let mut remaining = SyncTargets { directories: targets.directories.clone(), files: targets.files.clone(), moves: vec![] };
+
+    for (idx, path_move) in targets.moves.iter().enumerate() {
+        match backend.move_path(&path_move.from, &path_move.to) {
+            Ok(_) => {
+                debug!("Moved '{:?}' to '{:?}' in '{}'", path_move.from, path_move.to, job.remote_workspace.name);
+                remaining.files.push(path_move.to.clone());
+            },
+            Err(e) => {
+                debug!(
+                    "Unable to move '{:?}' to '{:?}' in '{}', syncing it instead: {e}",
+                    path_move.from,
+                    path_move.to,
+                    job.remote_workspace.name
+                );
+
+                for path_move in targets.moves[idx..].iter() {
+                    remaining.add(path_move.fallback());
+                }
+                break;
+            }
+        }
+    }
+
+    remaining
+}
+
+/// Add the statistics of a sync to the totals of its remote workspace and of the workspace, and
+/// report them to the daemon
+fn record_transfer(remote_workspace: &str, transferred: &TransferStats) {
+    let mut totals = TRANSFER_TOTALS.lock().unwrap();
+    totals.entry(remote_workspace.to_string()).or_default().add(transferred);
+
+    let mut workspace_total = TransferStats::default();
+    for remote_total in totals.values() {
+        workspace_total.add(remote_total);
+    }
+
+    debug!("Transferred to/from '{remote_workspace}': {:?}", transferred);
+
+    emit(MonitorEvent::Transferred {
+        remote_workspace: remote_workspace.to_string(),
+        last_sync: transferred.clone(),
+        remote_total: totals[remote_workspace].clone(),
+        workspace_total
+    });
+}
+
+/// Sync each job's workspace-relative paths with its remote workspace. Remote workspaces are synced
+/// concurrently, with at most `max_parallel_syncs` syncs running at the same time, so that a slow
+/// or unreachable remote system does not delay the others. Failures of individual remote
+/// workspaces are reported in the returned [SyncReport], unless a local error occurred, in which
+/// case the first such error is returned.
+pub(crate) fn synchronize_jobs(
+    workspace_info: &WorkspaceInfo,
+    jobs: &[SyncJob],
+    ignore_rules: &IgnoreRules
+) -> Result<SyncReport> {
+    let nr_of_workers = workspace_info.max_parallel_syncs.clamp(1, jobs.len().max(1));
+
+    let next_job = AtomicUsize::new(0);
+    let outcomes: Mutex<Vec<(usize, RemoteSyncOutcome)>> = Mutex::new(vec![]);
+
+    let worker = || loop {
+        let idx = next_job.fetch_add(1, Ordering::Relaxed);
+        let job = match jobs.get(idx) {
+            Some(job) => job,
+            None => break
+        };
+
+        let remote_workspace = job.remote_workspace.name.clone();
+
+        // Held until the outcome is reported, so that the next transfer may start afterwards
+        let _permit = permits::acquire(&remote_workspace);
+        emit(MonitorEvent::SyncStarted { remote_workspace: remote_workspace.clone(), started_at: unix_timestamp() });
+
+        let started = Instant::now();
+        let outcome = synchronize_remote_workspace_targets(workspace_info, job, ignore_rules);
+        let duration_ms = started.elapsed().as_millis() as u64;
+
+        if let Some(transferred) = &outcome.transferred {
+            record_transfer(&remote_workspace, transferred);
+        }
+
+        if outcome.error.is_none() && !job.poll {
+            hooks::record_sync(job.remote_workspace, Instant::now());
+        }
+
+        emit(match &outcome.error {
+            None => MonitorEvent::SyncSucceeded { remote_workspace, finished_at: unix_timestamp(), duration_ms },
+            Some(e) => MonitorEvent::SyncFailed {
+                remote_workspace,
+                finished_at: unix_timestamp(),
+                duration_ms,
+                error: format!("{e}"),
+                cause: e.cause()
+            }
+        });
+
+        outcomes.lock().unwrap().push((idx, outcome));
+    };
+
+    if nr_of_workers == 1 {
+        worker();
+    } else {
+        thread::scope(|scope| {
+            for _ in 0..nr_of_workers {
+                scope.spawn(worker);
+            }
+        });
+    }
+
+    let mut outcomes = outcomes.into_inner().unwrap();
+    outcomes.sort_by_key(|(idx, _)| *idx);
+
+    let mut report = SyncReport { outcomes: outcomes.into_iter().map(|(_, outcome)| outcome).collect() };
+
+    for outcome in report.failed_outcomes() {
+        warn!(
+            "Failed to sync #{} target(s) with '{}': {}",
+            outcome.failed_targets.len(),
+            outcome.remote_workspace,
+            outcome.error.as_ref().unwrap()
+        );
+    }
+
+    let local_error = report.outcomes
+        .iter_mut()
+        .find(|outcome| matches!(outcome.error, Some(Error::LocalError(_))))
+        .and_then(|outcome| outcome.error.take());
+
+    match local_error {
+        Some(e) => Err(e),
+        None => Ok(report)
+    }
+}
+
+/// Determine the changes a full sync would make to each remote workspace. Remote workspaces whose
+/// changes could not be determined are reported along with the error.
+pub(crate) fn preview_workspace(workspace_info: &WorkspaceInfo, ignore_rules: &IgnoreRules) -> Vec<SyncPreview> {
+    workspace_info.remote_workspaces
+        .iter()
+        .map(|remote_workspace| {
+            let backend = backend_for(&workspace_info.local_path, remote_workspace, ignore_rules);
+
+            backend.preview().unwrap_or_else(|e| {
+                warn!("Unable to preview sync with '{}': {e}", remote_workspace.name);
+                SyncPreview { remote_workspace: remote_workspace.name.clone(), error: Some(format!("{e}")), ..Default::default() }
+            })
+        })
+        .collect()
+}
+
+/// Compare the workspace with the remote workspace by checksum and return the resources that differ,
+/// which are synced as individual files
+pub(crate) fn verify_remote_workspace(
+    workspace_info: &WorkspaceInfo,
+    remote_workspace: &RemoteWorkspace,
+    ignore_rules: &IgnoreRules
+) -> Result<SyncTargets> {
+    let backend = backend_for(&workspace_info.local_path, remote_workspace, ignore_rules);
+    let verified = backend.verify()?;
+
+    let files: Vec<PathBuf> = verified.created
+        .into_iter()
+        .chain(verified.updated)
+        .chain(verified.deleted)
+        .collect();
+
+    Ok(SyncTargets { files, ..Default::default() })
+}
+
+/// Copy the resources covered by the given targets that exist in the remote workspace, but no longer
+/// in the local workspace, back into the local workspace, e.g. to undo rejected deletions. Returns
+/// the number of restored resources.
+pub(crate) fn restore_from_remote(
+    workspace_info: &WorkspaceInfo,
+    remote_workspace: &RemoteWorkspace,
+    targets: &SyncTargets,
+    ignore_rules: &IgnoreRules
+) -> Result<usize> {
+    let backend = backend_for(&workspace_info.local_path, remote_workspace, ignore_rules);
+
+    let is_covered = |path: &PathBuf| {
+        targets.files.contains(path) || targets.directories.iter().any(|directory| match directory {
+            Some(directory) => path.starts_with(directory),
+            None => true
+        })
+    };
+
+    let missing: Vec<PathBuf> = backend.scan_remote()?
+        .into_keys()
+        .filter(|path| is_covered(path))
+        .filter(|path| {
+            fs::symlink_metadata(workspace_info.local_path.join(path)).is_err_and(|e| e.kind() == ErrorKind::NotFound)
+        })
+        .collect();
+
+    for batch in missing.chunks(MAX_FILES_PER_BATCH) {
+        backend.pull_files(batch)?;
+    }
+
+    Ok(missing.len())
+}
+
+/// List the versions of the file at the given workspace-relative path kept in each remote
+/// workspace. Remote workspaces whose versions could not be determined are reported along with the
+/// error.
+pub(crate) fn list_versions(
+    workspace_info: &WorkspaceInfo,
+    relative_path: &Path,
+    ignore_rules: &IgnoreRules
+) -> Vec<FileVersions> {
+    workspace_info.remote_workspaces
+        .iter()
+        .map(|remote_workspace| {
+            let backend = backend_for(&workspace_info.local_path, remote_workspace, ignore_rules);
+
+            match backend.scan_versions() {
+                Ok(versions) => FileVersions {
+                    remote_workspace: remote_workspace.name.clone(),
+                    versions: versioning::file_versions(&versions, relative_path),
+                    error: None
+                },
+                Err(e) => {
+                    warn!("Unable to list versions kept in '{}': {e}", remote_workspace.name);
+                    FileVersions { remote_workspace: remote_workspace.name.clone(), error: Some(format!("{e}")), ..Default::default() }
+                }
+            }
+        })
+        .collect()
+}
+
+/// Copy a version of the file at the given workspace-relative path from a remote workspace back
+/// into the local workspace. The restored file is synced like any other change once the monitor of
+/// the workspace detects it.
+pub(crate) fn restore_version(
+    workspace_info: &WorkspaceInfo,
+    remote_workspace: &RemoteWorkspace,
+    relative_path: &Path,
+    version: &str,
+    ignore_rules: &IgnoreRules
+) -> VersionRestore {
+    let backend = backend_for(&workspace_info.local_path, remote_workspace, ignore_rules);
+
+    let error = backend.restore_version(relative_path, version).err().map(|e| {
+        warn!("Unable to restore version '{version}' of '{:?}' from '{}': {e}", relative_path, remote_workspace.name);
+        format!("{e}")
+    });
+
+    VersionRestore { remote_workspace: remote_workspace.name.clone(), error }
+}
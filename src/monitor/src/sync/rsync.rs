@@ -0,0 +1,1022 @@
+use std::{fs, process};
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::io::Write as IoWrite;
+use std::os::unix::ffi::OsStrExt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Instant;
+use log::{debug, error, warn};
+use crate::ignore_rules::IgnoreRules;
+use crate::models::{ConnectionInfo, FailureCause, RemoteWorkspace, SyncPreview, TransferStats};
+use crate::schedule;
+use crate::sync::backend::SyncBackend;
+use crate::sync::{rsync_error, Error};
+use crate::sync::two_way::{EntryKind, EntryState, TreeState};
+use crate::sync::versioning::{new_version, relative_version_dir, VERSIONS_DIR};
+use crate::util::{error_exit, shell_quote, ssh_command};
+use crate::util::fs::concat_paths;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Distinguishes the empty directories that versions are deleted with, as the workers of different
+/// remote workspaces may delete versions at the same time
+static NEXT_EMPTY_DIR_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Syncs with remote workspaces by spawning 'rsync', which connects to the remote system via SSH or
+/// the rsync daemon.
+pub(super) struct RsyncBackend<'a> {
+    ws_root_path: &'a PathBuf,
+    remote_workspace: &'a RemoteWorkspace,
+    ignore_rules: &'a IgnoreRules,
+    /// Directory within the versions directory that holds the versions replaced by this sync
+    version: String,
+    /// Statistics of all transfers made so far
+    stats: RefCell<Option<TransferStats>>
+}
+
+impl<'a> RsyncBackend<'a> {
+    pub(super) fn new(
+        ws_root_path: &'a PathBuf,
+        remote_workspace: &'a RemoteWorkspace,
+        ignore_rules: &'a IgnoreRules
+    ) -> Self {
+        RsyncBackend { ws_root_path, remote_workspace, ignore_rules, version: new_version(), stats: RefCell::new(None) }
+    }
+
+    /// Determine the changes a full sync would make, optionally comparing files by checksum
+    fn dry_run(&self, checksum: bool) -> Result<SyncPreview> {
+        let mut args = get_rsync_arguments(
+            self.ws_root_path,
+            None,
+            self.remote_workspace,
+            self.ignore_rules,
+            &self.version
+        )?;
+
+        args.retain(|arg| arg != "--stats");
+        args.insert(0, String::from("--dry-run"));
+        args.insert(1, String::from("--itemize-changes"));
+
+        if checksum && !self.remote_workspace.sync_options.checksum {
+            args.insert(2, String::from("--checksum"));
+        }
+
+        debug!("Performing dry run with args: '{:?}'", args);
+        let stdout = run_rsync_command(args, None)?;
+
+        let mut preview = parse_itemized_changes(&stdout);
+        preview.remote_workspace = self.remote_workspace.name.clone();
+
+        Ok(preview)
+    }
+
+    /// Run rsync to transfer resources to or from the remote workspace and add the statistics it
+    /// reports to the ones of the previous transfers
+    fn transfer(&self, args: Vec<String>, input: Option<Vec<u8>>) -> Result<()> {
+        let started = Instant::now();
+
+        if let Some(mut stats) = execute_rsync_command_with_input(args, input)? {
+            stats.duration_ms = started.elapsed().as_millis() as u64;
+            self.stats.borrow_mut().get_or_insert_default().add(&stats);
+        }
+
+        Ok(())
+    }
+}
+
+impl SyncBackend for RsyncBackend<'_> {
+
+    fn sync_subtree(&self, relative_path: Option<&PathBuf>) -> Result<()> {
+        let args = get_rsync_arguments(
+            self.ws_root_path,
+            relative_path,
+            self.remote_workspace,
+            self.ignore_rules,
+            &self.version
+        )?;
+
+        match self.transfer(args, None) {
+            Ok(_) => {},
+            Err(Error::RemoteSystemError(
+                _,
+                Some(FailureCause::RemotePathMissing | FailureCause::PartialTransfer | FailureCause::Other)
+            )) if relative_path.is_some() => {
+                // Attempt to sync the workspace starting from the ws root, since its possible that
+                //  (parts) of the remote ws were deleted/moved/etc., causing rsync to fail. Other
+                // failures, e.g. an unreachable remote system, would only recur.
+                warn!(
+                    "Syncing with '{}' failed. Attempting to sync from ws root to re-build the dir tree...",
+                    self.remote_workspace.name
+                );
+
+                return self.full_sync();
+            },
+            Err(e) => return Err(e)
+        }
+
+        Ok(())
+    }
+
+    fn sync_files(&self, relative_paths: &[PathBuf]) -> Result<()> {
+        let mut input: Vec<u8> = vec![];
+
+        for relative_path in relative_paths.iter() {
+            input.extend_from_slice(relative_path.as_os_str().as_bytes());
+            input.push(0);
+        }
+
+        let args = get_rsync_file_batch_arguments(self.ws_root_path, self.remote_workspace, self.ignore_rules, &self.version)?;
+        self.transfer(args, Some(input))
+    }
+
+    /// Only resources that no longer exist locally can be deleted, as rsync deletes them by
+    /// syncing their absence.
+    fn delete_path(&self, relative_path: &Path) -> Result<()> {
+        if self.ws_root_path.join(relative_path).symlink_metadata().is_ok() {
+            return Err(Error::LocalError(format!(
+                "Refusing to delete '{:?}' in '{}', as it still exists locally",
+                relative_path,
+                self.remote_workspace.name
+            )));
+        }
+
+        let mut input = relative_path.as_os_str().as_bytes().to_vec();
+        input.push(0);
+
+        let mut args = get_rsync_file_batch_arguments(self.ws_root_path, self.remote_workspace, self.ignore_rules, &self.version)?;
+        if !self.remote_workspace.sync_options.delete {
+            // Deletion was requested explicitly, so don't let the sync options suppress it
+            args.retain(|arg| arg != "--ignore-missing-args");
+            args.insert(1, String::from("--delete-missing-args"));
+            args.insert(2, String::from("--force"));
+        }
+
+        self.transfer(args, Some(input))
+    }
+
+    /// Runs 'mv' on the remote system via SSH, which is not possible for remote workspaces that are
+    /// connected via the rsync daemon. Existing resources are never replaced, as 'mv' would move
+    /// the resource into an existing directory and versions of replaced files are kept by syncing
+    /// the move instead.
+    fn move_path(&self, from: &Path, to: &Path) -> Result<()> {
+        if from.parent().is_none() || to.parent().is_none() {
+            return Err(Error::LocalError(String::from("Refusing to move the remote workspace root")));
+        }
+
+        let remote_path = |path: PathBuf| pathbuf_to_string(path).map(|path| shell_quote(&path));
+
+        let dst = self.remote_workspace.remote_path.join(to);
+        let dst_parent = remote_path(dst.parent().unwrap().to_path_buf())?;
+        let dst_path = remote_path(dst)?;
+        let src_path = remote_path(self.remote_workspace.remote_path.join(from))?;
+
+        let mut command = get_ssh_command(self.remote_workspace)?;
+        command.arg("--").arg(format!(
+            "mkdir -p -- {dst_parent} && ! test -e {dst_path} && ! test -L {dst_path} && mv -- {src_path} {dst_path}"
+        ));
+
+        debug!("Moving '{:?}' to '{:?}' in '{}'", from, to, self.remote_workspace.name);
+        run_ssh_command(command)
+    }
+
+    /// Lists the remote workspace's root directory, which requires the remote system to be
+    /// reachable and the remote workspace to exist.
+    fn health_check(&self) -> Result<()> {
+        let mut args: Vec<String> = vec![String::from("--list-only"), String::from("--dirs")];
+
+        if let ConnectionInfo::Ssh { .. } = self.remote_workspace.connection_info {
+            args.push(get_remote_shell_args_ssh(self.remote_workspace)?);
+        }
+
+        args.push(get_target_dir_arg(None, self.remote_workspace)?);
+
+        run_rsync_command(args, None).map(|_| ())
+    }
+
+    fn preview(&self) -> Result<SyncPreview> {
+        self.dry_run(false)
+    }
+
+    fn verify(&self) -> Result<SyncPreview> {
+        self.dry_run(true)
+    }
+
+    fn scan_remote(&self) -> Result<TreeState> {
+        let mut args: Vec<String> = vec![String::from("--list-only"), String::from("--recursive")];
+        args.extend(get_versions_filter_args(None, self.remote_workspace));
+        args.extend(self.ignore_rules.rsync_filter_args(None));
+
+        if let ConnectionInfo::Ssh { .. } = self.remote_workspace.connection_info {
+            args.push(get_remote_shell_args_ssh(self.remote_workspace)?);
+        }
+
+        args.push(get_remote_source_dir_arg(self.remote_workspace)?);
+
+        debug!("Scanning remote workspace with args: '{:?}'", args);
+        let stdout = run_rsync_command(args, None)?;
+
+        Ok(parse_listing(&stdout))
+    }
+
+    fn pull_files(&self, relative_paths: &[PathBuf]) -> Result<()> {
+        let mut input: Vec<u8> = vec![];
+
+        for relative_path in relative_paths.iter() {
+            input.extend_from_slice(relative_path.as_os_str().as_bytes());
+            input.push(0);
+        }
+
+        let args = get_rsync_pull_arguments(self.ws_root_path, self.remote_workspace, self.ignore_rules)?;
+        self.transfer(args, Some(input))
+    }
+
+    fn scan_versions(&self) -> Result<TreeState> {
+        let mut args: Vec<String> = vec![
+            String::from("--list-only"),
+            String::from("--recursive"),
+            format!("--filter=+ /{VERSIONS_DIR}/***"),
+            String::from("--filter=- *")
+        ];
+
+        if let ConnectionInfo::Ssh { .. } = self.remote_workspace.connection_info {
+            args.push(get_remote_shell_args_ssh(self.remote_workspace)?);
+        }
+
+        // Listing the versions directory itself would fail as long as no version was kept
+        args.push(get_remote_source_dir_arg(self.remote_workspace)?);
+
+        debug!("Scanning versions with args: '{:?}'", args);
+        let stdout = run_rsync_command(args, None)?;
+
+        Ok(parse_listing(&stdout)
+            .into_iter()
+            .filter_map(|(path, state)| {
+                let path = path.strip_prefix(VERSIONS_DIR).ok()?;
+                (!path.as_os_str().is_empty()).then(|| (path.to_path_buf(), state))
+            })
+            .collect())
+    }
+
+    /// Deletes the versions by syncing their absence from an empty directory
+    fn delete_versions(&self, paths: &[PathBuf]) -> Result<()> {
+        let mut input: Vec<u8> = vec![];
+
+        for path in paths.iter() {
+            input.extend_from_slice(path.as_os_str().as_bytes());
+            input.push(0);
+        }
+
+        let empty_dir = create_empty_dir()?;
+
+        let mut args: Vec<String> = vec![
+            String::from("-rq"),
+            String::from("--files-from=-"),
+            String::from("--from0"),
+            String::from("--no-implied-dirs"),
+            String::from("--delete-missing-args"),
+            String::from("--force")
+        ];
+
+        if let ConnectionInfo::Ssh { .. } = self.remote_workspace.connection_info {
+            args.push(get_remote_shell_args_ssh(self.remote_workspace)?);
+        }
+
+        args.push(get_source_dir_arg(&empty_dir, None)?);
+        args.push(get_target_dir_arg(Some(&PathBuf::from(VERSIONS_DIR)), self.remote_workspace)?);
+
+        let result = execute_rsync_command_with_input(args, Some(input)).map(|_| ());
+        let _ = fs::remove_dir(&empty_dir);
+
+        result
+    }
+
+    fn restore_version(&self, relative_path: &Path, version: &str) -> Result<()> {
+        let local_path = self.ws_root_path.join(relative_path);
+
+        if local_path.is_dir() {
+            return Err(Error::LocalError(format!("Refusing to replace directory '{:?}' by a file", local_path)));
+        }
+
+        if let Some(parent) = local_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                Error::LocalError(format!("Unable to create directory '{:?}': {e}", parent))
+            })?;
+        }
+
+        let mut args = get_pull_option_args(self.remote_workspace);
+
+        if let ConnectionInfo::Ssh { .. } = self.remote_workspace.connection_info {
+            args.push(get_remote_shell_args_ssh(self.remote_workspace)?);
+        }
+
+        let version_path = Path::new(VERSIONS_DIR).join(version).join(relative_path);
+        args.push(get_target_dir_arg(Some(&version_path), self.remote_workspace)?);
+        args.push(pathbuf_to_string(local_path)?);
+
+        self.transfer(args, None)
+    }
+
+    fn transfer_stats(&self) -> Option<TransferStats> {
+        self.stats.borrow().clone()
+    }
+}
+
+/// Parse the output of rsync's `--list-only`, where each line consists of the permissions, the size
+/// (with digit grouping), the modification date and time and the path, e.g.
+/// `-rw-r--r--          1,024 2024/01/31 12:00:00 dir/file`. Times are expected to be in UTC.
+fn parse_listing(output: &[u8]) -> TreeState {
+    let mut tree = TreeState::new();
+
+    for line in String::from_utf8_lossy(output).lines() {
+        let mut rest = line;
+        let mut fields: Vec<&str> = vec![];
+
+        for _ in 0..4 {
+            let trimmed = rest.trim_start();
+            let end = trimmed.find(' ').unwrap_or(trimmed.len());
+            fields.push(&trimmed[..end]);
+            rest = trimmed.get(end + 1..).unwrap_or("");
+        }
+
+        let (Some(kind), Ok(len), Some(mtime)) = (
+            fields[0].chars().next(),
+            fields[1].replace([',', '.'], "").parse::<u64>(),
+            parse_listing_time(fields[2], fields[3])
+        ) else {
+            continue;
+        };
+
+        let kind = match kind {
+            '-' => EntryKind::File,
+            'd' => EntryKind::Directory,
+            'l' => EntryKind::Symlink,
+            _ => continue
+        };
+
+        let name = match kind {
+            EntryKind::Symlink => rest.split_once(" -> ").map(|(name, _)| name).unwrap_or(rest),
+            _ => rest
+        };
+
+        if name.is_empty() || name == "." {
+            continue;
+        }
+
+        tree.insert(PathBuf::from(name), EntryState::new(kind, len, mtime));
+    }
+
+    tree
+}
+
+/// Seconds since the unix epoch of a UTC date and time formatted as `YYYY/MM/DD` and `HH:MM:SS`
+fn parse_listing_time(date: &str, time: &str) -> Option<i64> {
+    let date: Vec<i64> = date.split('/').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    let time: Vec<i64> = time.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+
+    let (&[year, month, day], &[hours, minutes, seconds]) = (&date[..], &time[..]) else {
+        return None;
+    };
+
+    // Days since the unix epoch of the given date in the proleptic Gregorian calendar
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Some(days * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Parse rsync's `--itemize-changes` output, where each line consists of an 11 character long
+/// update string (e.g. `>f.st......`, `cd+++++++++` or `*deleting`), a space and the path.
+fn parse_itemized_changes(output: &[u8]) -> SyncPreview {
+    let mut preview = SyncPreview::default();
+
+    for line in String::from_utf8_lossy(output).lines() {
+        let (Some(item), Some(name)) = (line.get(..11), line.get(12..)) else {
+            continue;
+        };
+
+        // Symlinks and hard links are followed by their target
+        let name = match item.as_bytes()[1] {
+            b'L' => name.split_once(" -> ").map(|(name, _)| name).unwrap_or(name),
+            _ if item.starts_with('h') => name.split_once(" => ").map(|(name, _)| name).unwrap_or(name),
+            _ => name
+        };
+
+        let name = name.trim_end_matches('/');
+        if name.is_empty() || name == "." {
+            continue;
+        }
+
+        let path = PathBuf::from(name);
+
+        if item.starts_with("*deleting") {
+            preview.deleted.push(path);
+        } else if !matches!(item.as_bytes()[0], b'<' | b'>' | b'c' | b'h' | b'.') {
+            // Messages, e.g. '*skipping'
+            continue;
+        } else if item[2..].bytes().all(|c| c == b'+') {
+            preview.created.push(path);
+        } else {
+            preview.updated.push(path);
+        }
+    }
+
+    preview
+}
+
+/// Create a directory in the temp directory that is used by no one else
+fn create_empty_dir() -> Result<PathBuf> {
+    loop {
+        let id = NEXT_EMPTY_DIR_ID.fetch_add(1, Ordering::Relaxed);
+        let empty_dir = std::env::temp_dir().join(format!("wsync-empty-{}-{id}", process::id()));
+
+        match fs::create_dir(&empty_dir) {
+            Ok(()) => return Ok(empty_dir),
+            // Left behind by an earlier process with the same pid
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                return Err(Error::LocalError(format!("Unable to create empty directory '{:?}': {e}", empty_dir)));
+            }
+        }
+    }
+}
+
+fn pathbuf_to_string(path: PathBuf) -> Result<String> {
+    path.to_str()
+        .ok_or(Error::LocalError(format!("Error stringifying '{:?}'", path)))
+        .map(|val| val.to_string())
+}
+
+fn to_dir_arg_path(base: &PathBuf, relative_path: Option<&PathBuf>) -> Result<String> {
+    let dir_path = concat_paths(Some(base), relative_path).map_err(|e| {
+        Error::LocalError(format!("Unable to concat '{:?}' and '{:?}': {e}", base, relative_path))
+    })?;
+
+    pathbuf_to_string(dir_path)
+}
+
+fn get_remote_dir_arg_ssh(
+    relative_path: Option<&PathBuf>,
+    remote_workspace: &RemoteWorkspace
+) -> Result<String> {
+    let connection_info = &remote_workspace.connection_info;
+    let mut arg = String::new();
+
+    if let ConnectionInfo::Ssh { username: Some(username), .. } = connection_info {
+        write!(&mut arg, "{}@", username).map_err(|e| {
+            Error::LocalError(format!("Error appending username to arg string: {e}"))
+        })?;
+    }
+
+    if let ConnectionInfo::Ssh { host, .. } = connection_info {
+        write!(&mut arg, "{}", host).map_err(|e| {
+            Error::LocalError(format!("Error appending host to arg string: {e}"))
+        })?;
+    }
+
+    let dir_path = to_dir_arg_path(&remote_workspace.remote_path, relative_path)?;
+    write!(&mut arg, ":{}", dir_path).map_err(|e| {
+        Error::LocalError(format!("Unable to append target path to arg string: {e}"))
+    })?;
+
+    Ok(arg)
+}
+
+fn get_remote_dir_arg_host_alias(
+    relative_path: Option<&PathBuf>,
+    remote_workspace: &RemoteWorkspace
+) -> Result<String> {
+    let dir_path = to_dir_arg_path(&remote_workspace.remote_path, relative_path)?;
+
+    let alias = match &remote_workspace.connection_info {
+        ConnectionInfo::HostAlias { host_alias } => host_alias,
+        _ => error_exit(None) // Unreachable
+    };
+
+    Ok(format!("{}:{}", alias, dir_path))
+}
+
+fn get_remote_dir_arg_rsync_daemon(
+    relative_path: Option<&PathBuf>,
+    remote_workspace: &RemoteWorkspace
+) -> Result<String> {
+    let connection_info = &remote_workspace.connection_info;
+    let mut arg = String::from("rsync://");
+
+    if let ConnectionInfo::RsyncDaemon { username: Some(username), .. } = connection_info {
+        write!(&mut arg, "{}@", username).map_err(|e| {
+            Error::LocalError(format!("Error appending username to arg string: {e}"))
+        })?;
+    }
+
+    if let ConnectionInfo::RsyncDaemon { host, .. } = connection_info {
+        write!(&mut arg, "{}", host).map_err(|e| {
+            Error::LocalError(format!("Error appending host to arg string: {e}"))
+        })?;
+    }
+
+    if let ConnectionInfo::RsyncDaemon { port: Some(port), .. } = connection_info {
+        write!(&mut arg, ":{}", port).map_err(|e| {
+            Error::LocalError(format!("Error appending port to arg string: {e}"))
+        })?;
+    }
+
+    let dir_path = to_dir_arg_path(&remote_workspace.remote_path, relative_path)?;
+    write!(&mut arg, "{}", dir_path).map_err(|e| {
+        Error::LocalError(format!("Unable to append target path to arg string: {e}"))
+    })?;
+
+    Ok(arg)
+}
+
+fn get_target_dir_arg(relative_path: Option<&PathBuf>, remote_workspace: &RemoteWorkspace) -> Result<String> {
+
+    match remote_workspace.connection_info {
+        ConnectionInfo::HostAlias { .. } => {
+            get_remote_dir_arg_host_alias(relative_path, remote_workspace)
+        },
+        ConnectionInfo::Ssh { .. } => {
+            get_remote_dir_arg_ssh(relative_path, remote_workspace)
+        },
+        ConnectionInfo::RsyncDaemon { .. } => {
+            get_remote_dir_arg_rsync_daemon(relative_path, remote_workspace)
+        },
+        ConnectionInfo::Local { .. } | ConnectionInfo::Agent { .. } => {
+            Err(Error::LocalError(format!("'{}' is not synced via rsync", remote_workspace.name)))
+        }
+    }
+}
+
+fn get_source_dir_arg(ws_root_path: &PathBuf, relative_path: Option<&PathBuf>) -> Result<String> {
+    let mut src_dir_arg = to_dir_arg_path(ws_root_path, relative_path)?;
+
+    // The src dir arg must end with a trailing '/' symbol to avoid that rsync creates an additional
+    // directory level at the remote workspace.
+    if !src_dir_arg.ends_with("/") {
+        src_dir_arg.push('/');
+     }
+
+    Ok(src_dir_arg)
+}
+
+/// The remote workspace as the source of a transfer, which must end with a trailing '/' to transfer
+/// its content rather than the directory itself
+fn get_remote_source_dir_arg(remote_workspace: &RemoteWorkspace) -> Result<String> {
+    let mut src_dir_arg = get_target_dir_arg(None, remote_workspace)?;
+
+    if !src_dir_arg.ends_with("/") {
+        src_dir_arg.push('/');
+    }
+
+    Ok(src_dir_arg)
+}
+
+fn get_remote_shell_args_ssh(remote_workspace: &RemoteWorkspace) -> Result<String> {
+
+    let mut arg = String::from("-e \"ssh ");
+
+    if let ConnectionInfo::Ssh { port: Some(port), .. } = &remote_workspace.connection_info {
+        write!(&mut arg, "-p {}", port).map_err(|e| {
+            Error::LocalError(format!("Error appending port to remote shell arg: {e}"))
+        })?;
+    }
+
+    if let ConnectionInfo::Ssh { identity_file: Some(identity_file), .. } = &remote_workspace.connection_info {
+        let dir_path = pathbuf_to_string(identity_file.clone())?;
+        write!(&mut arg, " -i {}", dir_path).map_err(|e| {
+            Error::LocalError(format!("Error appending identity file to remote shell arg: {e}"))
+        })?;
+    }
+
+    arg.push('\"');
+
+    Ok(arg)
+}
+
+/// Command running a shell command, which is yet to be appended, on the remote system via SSH
+fn get_ssh_command(remote_workspace: &RemoteWorkspace) -> Result<Command> {
+    ssh_command(&remote_workspace.connection_info, &[]).ok_or_else(|| {
+        Error::LocalError(format!("'{}' is not connected via SSH", remote_workspace.name))
+    })
+}
+
+fn run_ssh_command(mut command: Command) -> Result<()> {
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| Error::LocalError(format!("Unable to run 'ssh': {error}")))?;
+
+    if !output.status.success() {
+        return Err(Error::remote(format!(
+            "'ssh' returned status code '{}': {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Arguments derived from the sync options, except for the ones controlling deletion, which depend
+/// on whether a directory tree or a batch of files is synced. The bandwidth limit depends on the
+/// remote workspace's schedule.
+fn get_sync_option_args(remote_workspace: &RemoteWorkspace) -> Vec<String> {
+    let sync_options = &remote_workspace.sync_options;
+    let mut args: Vec<String> = vec![];
+
+    // '-q' would suppress the statistics
+    args.push(String::from(if sync_options.compress { "-az" } else { "-a" }));
+    args.push(String::from("--stats"));
+
+    if let Some(compress_choice) = &sync_options.compress_choice {
+        args.push(format!("--compress-choice={compress_choice}"));
+    }
+
+    if sync_options.checksum {
+        args.push(String::from("--checksum"));
+    }
+
+    if let Some(bwlimit) = schedule::bwlimit_kbps(remote_workspace) {
+        args.push(format!("--bwlimit={bwlimit}"));
+    }
+
+    if let Some(chmod) = &sync_options.chmod {
+        args.push(format!("--chmod={chmod}"));
+    }
+
+    if let Some(chown) = &sync_options.chown {
+        args.push(format!("--chown={chown}"));
+    }
+
+    if sync_options.partial {
+        args.push(String::from("--partial"));
+    }
+
+    if sync_options.inplace {
+        args.push(String::from("--inplace"));
+    }
+
+    if sync_options.hard_links {
+        args.push(String::from("--hard-links"));
+    }
+
+    if sync_options.acls {
+        args.push(String::from("--acls"));
+    }
+
+    if sync_options.xattrs {
+        args.push(String::from("--xattrs"));
+    }
+
+    let preserved_attributes = [
+        ("--no-perms", sync_options.perms),
+        ("--no-owner", sync_options.owner),
+        ("--no-group", sync_options.group),
+        ("--no-times", sync_options.times)
+    ];
+
+    for (arg, preserved) in preserved_attributes {
+        if !preserved {
+            args.push(String::from(arg));
+        }
+    }
+
+    args
+}
+
+fn get_rsync_arguments(
+    ws_root_path: &PathBuf,
+    relative_path: Option<&PathBuf>,
+    remote_workspace: &RemoteWorkspace,
+    ignore_rules: &IgnoreRules,
+    version: &str
+) -> Result<Vec<String>> {
+
+    let mut args: Vec<String> = vec![];
+
+    args.extend(get_sync_option_args(remote_workspace));
+
+    if remote_workspace.sync_options.delete {
+        args.push(String::from("--delete"));
+    }
+
+    args.extend(get_backup_args(relative_path, remote_workspace, version)?);
+    args.extend(get_versions_filter_args(relative_path, remote_workspace));
+
+    // Ignored resources are excluded from the transfer. Since rsync does not delete excluded
+    // resources on the receiving side either, ignored resources are left untouched remotely.
+    args.extend(ignore_rules.rsync_filter_args(relative_path));
+
+    // Add extra remote shell arguments
+    match remote_workspace.connection_info {
+        ConnectionInfo::Ssh { .. } => {
+            let remote_shell_arg = get_remote_shell_args_ssh(remote_workspace)?;
+            args.push(remote_shell_arg);
+        },
+        _ => {}
+    }
+
+    let src = get_source_dir_arg(ws_root_path, relative_path)?;
+    args.push(src);
+
+    let dst = get_target_dir_arg(relative_path, remote_workspace)?;
+    args.push(dst);
+
+    Ok(args)
+}
+
+/// Arguments for syncing a batch of files, which are passed to rsync's stdin, with the remote
+/// workspace. Files that no longer exist locally are deleted in the remote workspace, unless
+/// deletion is disabled for it.
+fn get_rsync_file_batch_arguments(
+    ws_root_path: &PathBuf,
+    remote_workspace: &RemoteWorkspace,
+    ignore_rules: &IgnoreRules,
+    version: &str
+) -> Result<Vec<String>> {
+
+    let mut args: Vec<String> = vec![];
+
+    args.extend(get_sync_option_args(remote_workspace));
+
+    // The paths are relative to the source dir and are NUL-separated, as file names may contain
+    // newlines. Note that '--files-from' disables the recursion implied by '-a'.
+    args.push(String::from("--files-from=-"));
+    args.push(String::from("--from0"));
+
+    if remote_workspace.sync_options.delete {
+        // '--force' is required to delete directories, whose deletion was reported as the deletion
+        // of a file (e.g. on macOS, where removed resources cannot be inspected anymore)
+        args.push(String::from("--delete-missing-args"));
+        args.push(String::from("--force"));
+    } else {
+        args.push(String::from("--ignore-missing-args"));
+    }
+
+    args.extend(get_backup_args(None, remote_workspace, version)?);
+    args.extend(get_versions_filter_args(None, remote_workspace));
+    args.extend(ignore_rules.rsync_filter_args(None));
+
+    if let ConnectionInfo::Ssh { .. } = remote_workspace.connection_info {
+        args.push(get_remote_shell_args_ssh(remote_workspace)?);
+    }
+
+    args.push(get_source_dir_arg(ws_root_path, None)?);
+    args.push(get_target_dir_arg(None, remote_workspace)?);
+
+    Ok(args)
+}
+
+/// Arguments derived from the sync options that apply when copying resources from the remote
+/// workspace into the local workspace. Options that only apply to the remote workspace (e.g.
+/// `--chown`) are omitted.
+fn get_pull_option_args(remote_workspace: &RemoteWorkspace) -> Vec<String> {
+    let sync_options = &remote_workspace.sync_options;
+    let mut args: Vec<String> = vec![];
+
+    args.push(String::from(if sync_options.compress { "-az" } else { "-a" }));
+    args.push(String::from("--stats"));
+
+    if let Some(compress_choice) = &sync_options.compress_choice {
+        args.push(format!("--compress-choice={compress_choice}"));
+    }
+
+    if let Some(bwlimit) = schedule::bwlimit_kbps(remote_workspace) {
+        args.push(format!("--bwlimit={bwlimit}"));
+    }
+
+    for (is_set, arg) in [
+        (sync_options.partial, "--partial"),
+        (sync_options.hard_links, "--hard-links"),
+        (sync_options.acls, "--acls"),
+        (sync_options.xattrs, "--xattrs")
+    ] {
+        if is_set {
+            args.push(String::from(arg));
+        }
+    }
+
+    args
+}
+
+/// Excludes the versions directory from transfers of the workspace root, so that it is neither
+/// deleted nor listed as part of the remote workspace
+fn get_versions_filter_args(relative_path: Option<&PathBuf>, remote_workspace: &RemoteWorkspace) -> Vec<String> {
+    if remote_workspace.versioning.is_none() || relative_path.is_some() {
+        return vec![];
+    }
+
+    vec![format!("--filter=- /{VERSIONS_DIR}/")]
+}
+
+/// Arguments that move the files overwritten or deleted by a transfer of the given directory tree
+/// into the version directory of the sync, if versions are kept in the remote workspace
+fn get_backup_args(relative_path: Option<&PathBuf>, remote_workspace: &RemoteWorkspace, version: &str) -> Result<Vec<String>> {
+    if remote_workspace.versioning.is_none() {
+        return Ok(vec![]);
+    }
+
+    // A relative backup dir is relative to the destination directory of the transfer
+    let backup_dir = pathbuf_to_string(relative_version_dir(relative_path, version))?;
+
+    Ok(vec![String::from("--backup"), format!("--backup-dir={backup_dir}")])
+}
+
+/// Arguments for copying a batch of resources, which are passed to rsync's stdin, from the remote
+/// workspace into the local workspace
+fn get_rsync_pull_arguments(
+    ws_root_path: &PathBuf,
+    remote_workspace: &RemoteWorkspace,
+    ignore_rules: &IgnoreRules
+) -> Result<Vec<String>> {
+    let mut args = get_pull_option_args(remote_workspace);
+
+    // Resources removed in the meantime are picked up by the next sync. '--force' allows replacing
+    // a local directory by a file.
+    args.push(String::from("--files-from=-"));
+    args.push(String::from("--from0"));
+    args.push(String::from("--ignore-missing-args"));
+    args.push(String::from("--force"));
+
+    args.extend(get_versions_filter_args(None, remote_workspace));
+    args.extend(ignore_rules.rsync_filter_args(None));
+
+    if let ConnectionInfo::Ssh { .. } = remote_workspace.connection_info {
+        args.push(get_remote_shell_args_ssh(remote_workspace)?);
+    }
+
+    args.push(get_remote_source_dir_arg(remote_workspace)?);
+    args.push(get_source_dir_arg(ws_root_path, None)?);
+
+    Ok(args)
+}
+
+/// Run rsync and return the statistics it reported, if it was asked to (`--stats`)
+fn execute_rsync_command_with_input(args: Vec<String>, input: Option<Vec<u8>>) -> Result<Option<TransferStats>> {
+    debug!("Attempting to sync with args: '{:?}'", args);
+
+    let stdout = run_rsync_command(args, input)?;
+    let (messages, stats) = parse_stats(&stdout);
+
+    if !messages.is_empty() {
+        warn!("{}", messages);
+    }
+
+    Ok(stats)
+}
+
+/// Split the output of rsync into the messages preceding the statistics reported due to `--stats`
+/// and the parsed statistics, which look like `Number of files: 5 (reg: 3, dir: 2)` or
+/// `Total bytes sent: 1,234`. Statistics that are not reported are left at zero.
+fn parse_stats(output: &[u8]) -> (String, Option<TransferStats>) {
+    let output = String::from_utf8_lossy(output);
+    let lines: Vec<&str> = output.lines().collect();
+
+    let Some(start) = lines.iter().position(|line| line.starts_with("Number of files:")) else {
+        return (output.trim().to_string(), None);
+    };
+
+    let mut stats = TransferStats::default();
+
+    for line in lines[start..].iter() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        let field = match name {
+            "Number of files" => &mut stats.files_considered,
+            // Named 'Number of files transferred' before rsync 3.1
+            "Number of regular files transferred" | "Number of files transferred" => &mut stats.files_transferred,
+            "Total file size" => &mut stats.total_size,
+            "Total bytes sent" => &mut stats.bytes_sent,
+            "Total bytes received" => &mut stats.bytes_received,
+            _ => continue
+        };
+
+        // Digits are grouped with a separator that depends on the locale
+        *field = value
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| c.is_ascii_digit())
+            .collect::<String>()
+            .parse()
+            .unwrap_or_default();
+    }
+
+    (lines[..start].join("\n").trim().to_string(), Some(stats))
+}
+
+/// Run rsync with the given arguments, passing it the given input via stdin. Returns what rsync
+/// wrote to stdout.
+fn run_rsync_command(args: Vec<String>, input: Option<Vec<u8>>) -> Result<Vec<u8>> {
+    // Listed modification times are formatted in the local time zone
+    let mut child = Command::new("rsync")
+        .args(&args)
+        .env("TZ", "UTC")
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| Error::LocalError(format!("Unable to run 'rsync': {error}")))?;
+
+    // Feed the input from a separate thread, since rsync might not consume all of it before
+    // writing output, which would otherwise block both processes.
+    let input_writer = input.zip(child.stdin.take()).map(|(input, mut stdin)| {
+        thread::spawn(move || stdin.write_all(&input))
+    });
+
+    let rsync_output = child.wait_with_output().map_err(|error| {
+        Error::LocalError(format!("Unable to wait for 'rsync' to finish: {error}"))
+    })?;
+
+    if let Some(input_writer) = input_writer
+        && let Ok(Err(error)) = input_writer.join() {
+        // rsync closing its stdin early is reflected by its exit status
+        debug!("Unable to pass the complete input to 'rsync': {error}");
+    }
+
+    let stderr = String::from_utf8_lossy(&rsync_output.stderr);
+
+    if rsync_output.status.success() {
+        if !stderr.is_empty() {
+            warn!("{}", stderr);
+        }
+        return Ok(rsync_output.stdout);
+    }
+
+    let (cause, description) = rsync_error::classify(rsync_output.status.code(), &stderr);
+
+    if cause == FailureCause::VanishedFiles {
+        // Everything else was transferred, and the vanished files are reported by the watcher
+        warn!("Files vanished while 'rsync' was transferring them: {}", description.unwrap_or_default());
+        return Ok(rsync_output.stdout);
+    }
+
+    if !stderr.is_empty() {
+        error!("{}", stderr);
+    }
+
+    let msg = match description {
+        Some(description) => format!("'rsync' returned status code '{}': {description}", rsync_output.status),
+        None => format!("'rsync' returned status code '{}'", rsync_output.status)
+    };
+
+    Err(Error::RemoteSystemError(msg, Some(cause)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn itemized_changes_are_parsed() {
+        let output = b"\
+            cd+++++++++ ./\n\
+            cd+++++++++ dir/\n\
+            >f+++++++++ dir/new file.txt\n\
+            >f.st...... changed.txt\n\
+            .d..t...... unchanged-dir/\n\
+            cL+++++++++ link -> target\n\
+            hf+++++++++ hardlink => changed.txt\n\
+            *deleting   old/file.txt\n\
+            *deleting   old/\n\
+            skipping non-regular file \"fifo\"\n";
+
+        let preview = parse_itemized_changes(output);
+
+        assert_eq!(preview.created, [
+            PathBuf::from("dir"), PathBuf::from("dir/new file.txt"), PathBuf::from("link"), PathBuf::from("hardlink")
+        ]);
+        assert_eq!(preview.updated, [PathBuf::from("changed.txt"), PathBuf::from("unchanged-dir")]);
+        assert_eq!(preview.deleted, [PathBuf::from("old/file.txt"), PathBuf::from("old")]);
+    }
+
+    #[test]
+    fn empty_dirs_are_not_shared() {
+        let (Ok(empty_dir), Ok(other_empty_dir)) = (create_empty_dir(), create_empty_dir()) else {
+            panic!("Failed to create empty directories");
+        };
+
+        assert_ne!(empty_dir, other_empty_dir);
+
+        fs::remove_dir(&empty_dir).unwrap();
+        fs::remove_dir(&other_empty_dir).unwrap();
+    }
+
+    #[test]
+    fn empty_output_has_no_changes() {
+        let preview = parse_itemized_changes(b"");
+
+        assert!(preview.created.is_empty() && preview.updated.is_empty() && preview.deleted.is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+use crate::models::FailureCause;
+
+/// Messages written to stderr by rsync, SSH or the rsync daemon, along with the cause they
+/// indicate. A message matches if a line contains all of its fragments. Messages are checked in
+/// order, so that e.g. an SSH authentication failure is not mistaken for the connection loss rsync
+/// reports as a consequence.
+const KNOWN_MESSAGES: &[(&[&str], FailureCause)] = &[
+    (&["No space left on device"], FailureCause::DiskFull),
+    (&["Disk quota exceeded"], FailureCause::DiskFull),
+    (&["Host key verification failed"], FailureCause::AuthenticationFailed),
+    (&["Permission denied (publickey"], FailureCause::AuthenticationFailed),
+    (&["Permission denied, please try again"], FailureCause::AuthenticationFailed),
+    (&["Too many authentication failures"], FailureCause::AuthenticationFailed),
+    (&["auth failed on module"], FailureCause::AuthenticationFailed),
+    (&["Could not resolve hostname"], FailureCause::Unreachable),
+    (&["Name or service not known"], FailureCause::Unreachable),
+    (&["Connection refused"], FailureCause::Unreachable),
+    (&["Connection timed out"], FailureCause::Unreachable),
+    (&["No route to host"], FailureCause::Unreachable),
+    (&["Network is unreachable"], FailureCause::Unreachable),
+    (&["Unknown module"], FailureCause::RemotePathMissing),
+    (&["change_dir", "No such file or directory"], FailureCause::RemotePathMissing),
+    (&["mkdir", "No such file or directory"], FailureCause::RemotePathMissing),
+    (&["Permission denied (13)"], FailureCause::PermissionDenied),
+    (&["Operation not permitted (1)"], FailureCause::PermissionDenied),
+    (&["Read-only file system (30)"], FailureCause::PermissionDenied),
+    (&["connection unexpectedly closed"], FailureCause::ConnectionLost),
+    (&["Connection reset by peer"], FailureCause::ConnectionLost),
+    (&["Broken pipe"], FailureCause::ConnectionLost)
+];
+
+/// Exit code with which rsync reports that source files vanished during the transfer
+const VANISHED_FILES_EXIT_CODE: i32 = 24;
+
+/// Cause of rsync exiting with the given code (`None` if it was terminated by a signal), along
+/// with the line of its stderr that describes the failure best
+pub(super) fn classify(exit_code: Option<i32>, stderr: &str) -> (FailureCause, Option<String>) {
+    let lines: Vec<&str> = stderr.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+
+    if exit_code == Some(VANISHED_FILES_EXIT_CODE) {
+        let line = lines.iter().find(|line| line.contains("vanished"));
+        return (FailureCause::VanishedFiles, line.map(|line| line.to_string()));
+    }
+
+    for (fragments, cause) in KNOWN_MESSAGES {
+        if let Some(line) = lines.iter().find(|line| fragments.iter().all(|fragment| line.contains(fragment))) {
+            return (*cause, Some(line.to_string()));
+        }
+    }
+
+    // See the exit values in rsync's man page. SSH exits with 255 if it fails to connect.
+    let cause = match exit_code {
+        Some(5) => FailureCause::AuthenticationFailed,
+        Some(10) | Some(35) | Some(255) => FailureCause::Unreachable,
+        Some(12) | Some(30) => FailureCause::ConnectionLost,
+        Some(23) => FailureCause::PartialTransfer,
+        _ => FailureCause::Other
+    };
+
+    (cause, lines.first().map(|line| line.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_messages_are_classified() {
+        let stderr = "\
+            Permission denied (publickey,password).\n\
+            rsync: connection unexpectedly closed (0 bytes received so far) [sender]\n\
+            rsync error: unexplained error (code 255) at io.c(232) [sender=3.2.7]\n";
+
+        assert_eq!(
+            classify(Some(255), stderr),
+            (FailureCause::AuthenticationFailed, Some(String::from("Permission denied (publickey,password).")))
+        );
+    }
+
+    #[test]
+    fn all_fragments_of_a_message_have_to_match() {
+        let stderr = "rsync: [Receiver] mkdir \"/srv/ws/a\" failed: No such file or directory (2)";
+        assert_eq!(classify(Some(11), stderr).0, FailureCause::RemotePathMissing);
+
+        let stderr = "rsync: link_stat \"/tmp/ws/a\" failed: No such file or directory (2)";
+        assert_eq!(classify(Some(23), stderr).0, FailureCause::PartialTransfer);
+    }
+
+    #[test]
+    fn vanished_files_are_recognized_by_the_exit_code() {
+        let stderr = "file has vanished: \"/tmp/ws/a\"\nrsync warning: some files vanished before they could be transferred (code 24)";
+
+        assert_eq!(
+            classify(Some(24), stderr),
+            (FailureCause::VanishedFiles, Some(String::from("file has vanished: \"/tmp/ws/a\"")))
+        );
+    }
+
+    #[test]
+    fn unknown_messages_are_classified_by_the_exit_code() {
+        assert_eq!(classify(Some(5), "").0, FailureCause::AuthenticationFailed);
+        assert_eq!(classify(Some(255), "").0, FailureCause::Unreachable);
+        assert_eq!(classify(Some(12), "").0, FailureCause::ConnectionLost);
+        assert_eq!(classify(None, "").0, FailureCause::Other);
+        assert_eq!(classify(Some(1), "\n  first line  \nsecond line"), (FailureCause::Other, Some(String::from("first line"))));
+    }
+}
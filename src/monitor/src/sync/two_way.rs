@@ -0,0 +1,510 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{File, Metadata};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::UNIX_EPOCH;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use crate::events::{emit, unix_timestamp, MonitorEvent};
+use crate::ignore_rules::IgnoreRules;
+use crate::models::{ConflictPolicy, ConflictReport, ConflictResolution, RemoteWorkspace, WorkspaceInfo};
+use crate::sync::backend::SyncBackend;
+use crate::sync::{Error, MAX_FILES_PER_BATCH};
+use crate::util::fs::escape_file_name;
+use crate::util::state_directory;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Infix of the copies the local version of a conflicting resource is moved to
+const CONFLICT_COPY_INFIX: &str = ".wsync-conflict-";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum EntryKind {
+    File,
+    Directory,
+    Symlink
+}
+
+/// What is compared to detect changes of a resource. Directories only change by changing their
+/// kind and symlinks by changing their target, whose length is the best approximation that is
+/// available for all backends. Modification times have a precision of seconds, which is what rsync
+/// lists.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct EntryState {
+    kind: EntryKind,
+    len: u64,
+    mtime: i64
+}
+
+impl EntryState {
+
+    pub(super) fn new(kind: EntryKind, len: u64, mtime: i64) -> Self {
+        match kind {
+            EntryKind::Directory => EntryState { kind, len: 0, mtime: 0 },
+            EntryKind::Symlink => EntryState { kind, len, mtime: 0 },
+            EntryKind::File => EntryState { kind, len, mtime }
+        }
+    }
+
+    /// The state of a resource with the given metadata, `None` for special files, which are not
+    /// synced
+    pub(super) fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        let file_type = metadata.file_type();
+
+        let kind = if file_type.is_dir() {
+            EntryKind::Directory
+        } else if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else if file_type.is_file() {
+            EntryKind::File
+        } else {
+            return None;
+        };
+
+        let mtime = metadata.modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_secs() as i64);
+
+        Some(EntryState::new(kind, metadata.len(), mtime))
+    }
+
+    pub(super) fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+
+    pub(super) fn len(&self) -> u64 {
+        self.len
+    }
+}
+
+/// States of all resources in a workspace that are not ignored, by their workspace-relative path.
+/// Parents are ordered before their descendants.
+pub(super) type TreeState = BTreeMap<PathBuf, EntryState>;
+
+/// What is done to a single resource to bring both sides in sync
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    /// Both sides are in sync
+    None,
+    Push,
+    Pull,
+    DeleteRemote,
+    DeleteLocal,
+    /// Move the local version to the given conflict copy, which is pushed, and pull the remote one
+    KeepBoth(PathBuf)
+}
+
+struct Plan {
+    actions: BTreeMap<PathBuf, Action>,
+    conflicts: Vec<ConflictReport>,
+    /// State of both sides once all actions were performed
+    baseline: TreeState
+}
+
+/// Decide on the action for a single resource by comparing both sides with the state they had when
+/// they were last in sync. A resource that only changed on one side is synced to the other side,
+/// whereas a resource that changed differently on both sides is a conflict, which is resolved
+/// according to the conflict policy.
+fn decide(
+    base: Option<&EntryState>,
+    local: Option<&EntryState>,
+    remote: Option<&EntryState>,
+    policy: ConflictPolicy
+) -> (Action, Option<ConflictResolution>) {
+    let local_changed = local != base;
+    let remote_changed = remote != base;
+
+    let action = match (local_changed, remote_changed) {
+        (false, false) => Action::None,
+        (true, false) => if local.is_some() { Action::Push } else { Action::DeleteRemote },
+        (false, true) => if remote.is_some() { Action::Pull } else { Action::DeleteLocal },
+        (true, true) if local == remote => Action::None,
+        (true, true) => {
+            let resolution = match policy {
+                // A deleted resource never wins over a modified one, to not lose any data
+                ConflictPolicy::KeepBoth if local.is_none() => ConflictResolution::RemoteWon,
+                ConflictPolicy::KeepBoth if remote.is_none() => ConflictResolution::LocalWon,
+                ConflictPolicy::KeepBoth => ConflictResolution::KeptBoth { conflict_copy: PathBuf::new() },
+                ConflictPolicy::LocalWins => ConflictResolution::LocalWon,
+                ConflictPolicy::RemoteWins => ConflictResolution::RemoteWon
+            };
+
+            let action = match &resolution {
+                ConflictResolution::LocalWon => if local.is_some() { Action::Push } else { Action::DeleteRemote },
+                ConflictResolution::RemoteWon => if remote.is_some() { Action::Pull } else { Action::DeleteLocal },
+                ConflictResolution::KeptBoth { .. } => Action::KeepBoth(PathBuf::new())
+            };
+
+            return (action, Some(resolution));
+        }
+    };
+
+    (action, None)
+}
+
+fn plan(
+    ws_root_path: &Path,
+    remote_workspace: &str,
+    policy: ConflictPolicy,
+    base: &TreeState,
+    local: &TreeState,
+    remote: &TreeState
+) -> Plan {
+    let detected_at = unix_timestamp();
+    let paths: BTreeSet<&PathBuf> = base.keys().chain(local.keys()).chain(remote.keys()).collect();
+
+    let mut actions: BTreeMap<PathBuf, Action> = BTreeMap::new();
+    let mut conflicts: HashMap<PathBuf, ConflictResolution> = HashMap::new();
+
+    for path in paths {
+        let (action, resolution) = decide(base.get(path), local.get(path), remote.get(path), policy);
+
+        if let Some(resolution) = resolution {
+            conflicts.insert(path.clone(), resolution);
+        }
+        actions.insert(path.clone(), action);
+    }
+
+    // A directory that was deleted on one side is kept if resources below it are still synced,
+    // i.e. it is recreated on the side it was deleted on. Descendants are ordered after their
+    // ancestors, so walking backwards visits all descendants of a directory before the directory.
+    let mut required_dirs: BTreeSet<PathBuf> = BTreeSet::new();
+
+    for (path, action) in actions.iter_mut().rev() {
+        if required_dirs.contains(path) {
+            match action {
+                Action::DeleteLocal => *action = Action::Push,
+                Action::DeleteRemote => *action = Action::Pull,
+                _ => {}
+            }
+        }
+
+        if matches!(action, Action::Push | Action::Pull | Action::KeepBoth(_)) {
+            required_dirs.extend(path.ancestors().skip(1).map(|ancestor| ancestor.to_path_buf()));
+        }
+    }
+
+    // Resources below a resource that ends up being deleted or replaced by a non-directory are
+    // taken care of along with it
+    let mut covering: Option<&PathBuf> = None;
+    let mut covered: Vec<PathBuf> = vec![];
+
+    for (path, action) in actions.iter() {
+        if covering.is_some_and(|covering_path| path.starts_with(covering_path)) {
+            covered.push(path.clone());
+            continue;
+        }
+
+        let result = match action {
+            Action::None => continue,
+            Action::Push => local.get(path),
+            Action::Pull | Action::KeepBoth(_) => remote.get(path),
+            Action::DeleteRemote | Action::DeleteLocal => None
+        };
+
+        covering = if result.is_some_and(|state| state.is_dir()) { None } else { Some(path) };
+    }
+
+    for path in covered.iter() {
+        actions.remove(path);
+        conflicts.remove(path);
+    }
+
+    let mut baseline = TreeState::new();
+
+    for (path, action) in actions.iter_mut() {
+        let result = match action {
+            Action::None => match local.get(path) {
+                Some(state) if local.get(path) == remote.get(path) => Some(state),
+                _ => None
+            },
+            Action::Push => local.get(path),
+            Action::Pull => remote.get(path),
+            Action::DeleteRemote | Action::DeleteLocal => None,
+            Action::KeepBoth(conflict_copy) => {
+                *conflict_copy = conflict_copy_path(ws_root_path, path, detected_at);
+
+                // The local version is moved to the conflict copy along with its descendants
+                for (local_path, state) in local.range(path.clone()..).take_while(|(local_path, _)| local_path.starts_with(path)) {
+                    let relative = local_path.strip_prefix(path).unwrap_or(Path::new(""));
+                    let copy_path = if relative.as_os_str().is_empty() { conflict_copy.clone() } else { conflict_copy.join(relative) };
+                    baseline.insert(copy_path, *state);
+                }
+
+                if let Some(ConflictResolution::KeptBoth { conflict_copy: copy }) = conflicts.get_mut(path) {
+                    *copy = conflict_copy.clone();
+                }
+
+                remote.get(path)
+            }
+        };
+
+        if let Some(state) = result {
+            baseline.insert(path.clone(), *state);
+        }
+    }
+
+    let mut conflicts: Vec<ConflictReport> = conflicts
+        .into_iter()
+        .map(|(path, resolution)| ConflictReport {
+            remote_workspace: remote_workspace.to_string(),
+            path,
+            resolution,
+            detected_at
+        })
+        .collect();
+    conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Plan { actions, conflicts, baseline }
+}
+
+/// Path of the copy the local version of a conflicting resource is moved to, e.g.
+/// `notes.wsync-conflict-1700000000.txt` for `notes.txt`
+fn conflict_copy_path(ws_root_path: &Path, relative_path: &Path, detected_at: u64) -> PathBuf {
+    let stem = relative_path.file_stem().unwrap_or_default();
+    let extension = relative_path.extension();
+
+    for attempt in 0.. {
+        let mut file_name = OsString::from(stem);
+        file_name.push(format!("{CONFLICT_COPY_INFIX}{detected_at}"));
+        if attempt > 0 {
+            file_name.push(format!("-{attempt}"));
+        }
+        if let Some(extension) = extension {
+            file_name.push(".");
+            file_name.push(extension);
+        }
+
+        let copy_path = relative_path.with_file_name(file_name);
+        if ws_root_path.join(&copy_path).symlink_metadata().is_err() {
+            return copy_path;
+        }
+    }
+
+    unreachable!()
+}
+
+/// Scan the local workspace, skipping ignored resources
+fn scan_local(ws_root_path: &Path, ignore_rules: &IgnoreRules) -> Result<TreeState> {
+    let mut tree = TreeState::new();
+    scan_local_dir(ws_root_path, Path::new(""), ignore_rules, &mut tree)?;
+    Ok(tree)
+}
+
+fn scan_local_dir(ws_root_path: &Path, relative_path: &Path, ignore_rules: &IgnoreRules, tree: &mut TreeState) -> Result<()> {
+    let dir = ws_root_path.join(relative_path);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound && relative_path.parent().is_some() => return Ok(()),
+        Err(e) => return Err(Error::LocalError(format!("Unable to read '{:?}': {e}", dir)))
+    };
+
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::LocalError(format!("Unable to read '{:?}': {e}", dir)))?;
+        let child_path = relative_path.join(entry.file_name());
+
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(Error::LocalError(format!("Unable to access '{:?}': {e}", entry.path())))
+        };
+
+        if ignore_rules.is_ignored(&child_path, metadata.is_dir()) {
+            continue;
+        }
+
+        let Some(state) = EntryState::from_metadata(&metadata) else {
+            continue;
+        };
+
+        tree.insert(child_path.clone(), state);
+
+        if state.is_dir() {
+            scan_local_dir(ws_root_path, &child_path, ignore_rules, tree)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Failures to modify the local workspace are retried like failed transfers, as they are most
+/// likely caused by the resource being modified concurrently
+fn local_io_error(action: &str, path: &Path, error: std::io::Error) -> Error {
+    Error::remote(format!("Unable to {action} '{:?}' in the local workspace: {error}", path))
+}
+
+fn remove_local(path: &Path) -> Result<()> {
+    let remove_result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) => Err(e)
+    };
+
+    match remove_result {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(local_io_error("remove", path, e))
+    }
+}
+
+/// Perform the planned actions. The local workspace is brought into its final state first, so that
+/// whatever is pushed afterward matches the final state as well.
+fn execute(ws_root_path: &Path, actions: &BTreeMap<PathBuf, Action>, backend: &dyn SyncBackend) -> Result<()> {
+    let mut pulls: Vec<PathBuf> = vec![];
+    let mut pushed_files: Vec<PathBuf> = vec![];
+    let mut pushed_dirs: Vec<PathBuf> = vec![];
+
+    for (path, action) in actions.iter() {
+        match action {
+            Action::None => {},
+            Action::Pull => pulls.push(path.clone()),
+            Action::DeleteLocal => remove_local(&ws_root_path.join(path))?,
+            Action::KeepBoth(conflict_copy) => {
+                let (from, to) = (ws_root_path.join(path), ws_root_path.join(conflict_copy));
+                fs::rename(&from, &to).map_err(|e| local_io_error("move", &from, e))?;
+
+                pulls.push(path.clone());
+                if to.symlink_metadata().is_ok_and(|metadata| metadata.is_dir()) {
+                    pushed_dirs.push(conflict_copy.clone());
+                } else {
+                    pushed_files.push(conflict_copy.clone());
+                }
+            },
+            Action::Push if ws_root_path.join(path).symlink_metadata().is_ok_and(|metadata| metadata.is_dir()) => {
+                pushed_dirs.push(path.clone());
+            },
+            // Files that no longer exist locally are deleted remotely when they are pushed
+            Action::Push | Action::DeleteRemote => pushed_files.push(path.clone())
+        }
+    }
+
+    for batch in pulls.chunks(MAX_FILES_PER_BATCH) {
+        backend.pull_files(batch)?;
+    }
+
+    // Directories are mirrored as a whole, which covers everything pushed below them
+    pushed_dirs.sort();
+    let mut mirrored_dirs: Vec<PathBuf> = vec![];
+    for dir in pushed_dirs {
+        if !mirrored_dirs.last().is_some_and(|mirrored_dir| dir.starts_with(mirrored_dir)) {
+            mirrored_dirs.push(dir);
+        }
+    }
+    pushed_files.retain(|file| !mirrored_dirs.iter().any(|dir| file.starts_with(dir)));
+
+    for dir in mirrored_dirs.iter() {
+        backend.sync_subtree(Some(dir))?;
+    }
+
+    for batch in pushed_files.chunks(MAX_FILES_PER_BATCH) {
+        backend.sync_files(batch)?;
+    }
+
+    Ok(())
+}
+
+/// Sync a two-way remote workspace by comparing both sides with the state they had when they were
+/// last in sync, which is updated once all changes were synced. Each detected conflict is reported
+/// to the daemon.
+pub(super) fn reconcile(
+    workspace_info: &WorkspaceInfo,
+    remote_workspace: &RemoteWorkspace,
+    policy: ConflictPolicy,
+    backend: &dyn SyncBackend,
+    ignore_rules: &IgnoreRules
+) -> Result<()> {
+    let ws_root_path = workspace_info.local_path.as_path();
+    let key = (workspace_info.name.clone(), remote_workspace.name.clone());
+
+    let base = load_baseline(&key);
+    let remote = backend.scan_remote()?;
+    let local = scan_local(ws_root_path, ignore_rules)?;
+
+    let plan = plan(ws_root_path, &remote_workspace.name, policy, &base, &local, &remote);
+
+    let actions: Vec<(&PathBuf, &Action)> = plan.actions.iter().filter(|(_, action)| **action != Action::None).collect();
+    if !actions.is_empty() {
+        debug!("Performing #{} action(s) to sync with '{}': {:?}", actions.len(), remote_workspace.name, actions);
+    }
+
+    execute(ws_root_path, &plan.actions, backend)?;
+
+    for conflict in plan.conflicts {
+        info!("Resolved conflict in '{:?}' with '{}': {:?}", conflict.path, conflict.remote_workspace, conflict.resolution);
+        emit(MonitorEvent::Conflict(conflict));
+    }
+
+    save_baseline(key, plan.baseline);
+    Ok(())
+}
+
+/// Baselines by workspace and remote workspace name. Baselines are kept in memory and persisted in
+/// the state directory, if one is configured, so that changes made while the monitor was not
+/// running are not mistaken for conflicts.
+static BASELINES: LazyLock<Mutex<HashMap<(String, String), TreeState>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn baseline_path(key: &(String, String)) -> Option<PathBuf> {
+    let state_directory = state_directory().ok()?;
+
+    Some(state_directory
+        .join("two-way")
+        .join(escape_file_name(&key.0))
+        .join(escape_file_name(&key.1))
+        .with_extension("json"))
+}
+
+fn load_baseline(key: &(String, String)) -> TreeState {
+    if let Some(baseline) = BASELINES.lock().unwrap().get(key) {
+        return baseline.clone();
+    }
+
+    let Some(path) = baseline_path(key) else {
+        return TreeState::new();
+    };
+
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            if e.kind() != ErrorKind::NotFound {
+                warn!("Unable to open baseline '{:?}': {e}", path);
+            }
+            return TreeState::new();
+        }
+    };
+
+    match serde_json::from_reader::<_, Vec<(PathBuf, EntryState)>>(BufReader::new(file)) {
+        Ok(entries) => entries.into_iter().collect(),
+        Err(e) => {
+            warn!("Ignoring malformed baseline '{:?}': {e}", path);
+            TreeState::new()
+        }
+    }
+}
+
+fn save_baseline(key: (String, String), baseline: TreeState) {
+    if let Some(path) = baseline_path(&key)
+        && let Err(e) = write_baseline(&path, &baseline) {
+        warn!("Unable to persist baseline '{:?}': {e}", path);
+    }
+
+    BASELINES.lock().unwrap().insert(key, baseline);
+}
+
+fn write_baseline(path: &Path, baseline: &TreeState) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp_path = path.with_extension("json.tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_path)?);
+
+    let entries: Vec<(&PathBuf, &EntryState)> = baseline.iter().collect();
+    serde_json::to_writer(&mut writer, &entries)?;
+    writer.flush()?;
+
+    fs::rename(&tmp_path, path)
+}
@@ -0,0 +1,236 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use log::{debug, info, warn};
+use crate::events::unix_timestamp;
+use crate::models::{FileVersion, RemoteWorkspace, VersioningSettings};
+use crate::sync::backend::SyncBackend;
+use crate::sync::two_way::TreeState;
+use crate::sync::MAX_FILES_PER_BATCH;
+
+/// Directory in the root of a remote workspace that holds the previous versions of the files
+/// overwritten or deleted by syncs, in a subdirectory per sync that mirrors the workspace
+pub(super) const VERSIONS_DIR: &str = ".wsync-versions";
+
+/// Enforcing the retention requires listing all versions kept in the remote workspace, so it is
+/// done at most once per interval and remote workspace
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+static LAST_RETENTION: LazyLock<Mutex<HashMap<String, Instant>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Name of the directory holding the versions replaced by a sync starting now, which is the time
+/// in milliseconds since the unix epoch
+pub(super) fn new_version() -> String {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_millis());
+    millis.to_string()
+}
+
+/// Directory holding the versions replaced by a sync of the given directory tree (`None` being the
+/// workspace root), relative to the tree in the remote workspace
+pub(super) fn relative_version_dir(relative_path: Option<&PathBuf>, version: &str) -> PathBuf {
+    let mut dir = PathBuf::new();
+
+    if let Some(relative_path) = relative_path {
+        for _ in relative_path.components() {
+            dir.push("..");
+        }
+    }
+
+    dir.push(VERSIONS_DIR);
+    dir.push(version);
+
+    if let Some(relative_path) = relative_path {
+        dir.push(relative_path);
+    }
+
+    dir
+}
+
+/// Seconds since the unix epoch of the sync that created the given version directory, `None` if it
+/// was not created by a sync
+fn version_time(version: &str) -> Option<u64> {
+    version.parse::<u64>().ok().map(|millis| millis / 1000)
+}
+
+/// Split a path relative to the versions directory into the version and the workspace-relative
+/// path of the resource
+fn split_version_path(path: &Path) -> Option<(&str, &Path)> {
+    let mut components = path.components();
+    let version = components.next()?.as_os_str().to_str()?;
+
+    version_time(version)?;
+    Some((version, components.as_path()))
+}
+
+/// The versions of the file at the given workspace-relative path, newest first
+pub(super) fn file_versions(versions: &TreeState, relative_path: &Path) -> Vec<FileVersion> {
+    let mut file_versions: Vec<FileVersion> = versions
+        .iter()
+        .filter(|(_, state)| !state.is_dir())
+        .filter_map(|(path, state)| {
+            let (version, path) = split_version_path(path)?;
+
+            (path == relative_path).then(|| FileVersion {
+                version: version.to_string(),
+                created_at: version_time(version).unwrap_or_default(),
+                size: state.len()
+            })
+        })
+        .collect();
+
+    file_versions.sort_by_key(|file_version| Reverse(file_version.version.parse::<u64>().unwrap_or_default()));
+    file_versions
+}
+
+/// Paths relative to the versions directory of the versions exceeding the retention limits.
+/// Version directories that only hold expired versions are returned as a whole.
+fn expired_versions(versions: &TreeState, settings: &VersioningSettings, now: u64) -> Vec<PathBuf> {
+    let mut versions_by_file: BTreeMap<&Path, Vec<&str>> = BTreeMap::new();
+    let mut nr_of_files_by_version: BTreeMap<&str, usize> = BTreeMap::new();
+
+    for (path, state) in versions.iter() {
+        let Some((version, file)) = split_version_path(path) else {
+            continue;
+        };
+
+        let nr_of_files = nr_of_files_by_version.entry(version).or_default();
+
+        if !state.is_dir() && !file.as_os_str().is_empty() {
+            *nr_of_files += 1;
+            versions_by_file.entry(file).or_default().push(version);
+        }
+    }
+
+    let max_age = settings.keep_days.map(|days| days * 24 * 60 * 60);
+    let mut expired_by_version: BTreeMap<&str, Vec<&Path>> = BTreeMap::new();
+
+    for (file, mut file_versions) in versions_by_file {
+        file_versions.sort_by_key(|version| Reverse(version.parse::<u64>().unwrap_or_default()));
+
+        for (idx, version) in file_versions.into_iter().enumerate() {
+            let exceeds_count = settings.keep_versions.is_some_and(|keep_versions| idx >= keep_versions);
+            let exceeds_age = max_age.is_some_and(|max_age| {
+                now.saturating_sub(version_time(version).unwrap_or_default()) > max_age
+            });
+
+            if exceeds_count || exceeds_age {
+                expired_by_version.entry(version).or_default().push(file);
+            }
+        }
+    }
+
+    let mut expired = vec![];
+
+    for (version, nr_of_files) in nr_of_files_by_version {
+        let files = expired_by_version.remove(version).unwrap_or_default();
+
+        if files.len() == nr_of_files {
+            expired.push(PathBuf::from(version));
+        } else {
+            expired.extend(files.into_iter().map(|file| Path::new(version).join(file)));
+        }
+    }
+
+    expired
+}
+
+/// Remove the versions kept in the remote workspace that exceed its retention limits, unless that
+/// was done recently. Failures are only logged, as they don't affect the sync.
+pub(super) fn enforce_retention(remote_workspace: &RemoteWorkspace, backend: &dyn SyncBackend) {
+    let Some(settings) = &remote_workspace.versioning else {
+        return;
+    };
+
+    if settings.keep_versions.is_none() && settings.keep_days.is_none() {
+        return;
+    }
+
+    let now = Instant::now();
+    {
+        let mut last_retention = LAST_RETENTION.lock().unwrap();
+
+        if last_retention
+            .get(&remote_workspace.name)
+            .is_some_and(|last| now.duration_since(*last) < RETENTION_INTERVAL) {
+            return;
+        }
+
+        last_retention.insert(remote_workspace.name.clone(), now);
+    }
+
+    let versions = match backend.scan_versions() {
+        Ok(versions) => versions,
+        Err(e) => {
+            warn!("Unable to list the versions kept in '{}': {e}", remote_workspace.name);
+            return;
+        }
+    };
+
+    let expired = expired_versions(&versions, settings, unix_timestamp());
+    if expired.is_empty() {
+        debug!("No versions kept in '{}' have expired", remote_workspace.name);
+        return;
+    }
+
+    for batch in expired.chunks(MAX_FILES_PER_BATCH) {
+        if let Err(e) = backend.delete_versions(batch) {
+            warn!("Unable to remove expired versions from '{}': {e}", remote_workspace.name);
+            return;
+        }
+    }
+
+    info!("Removed #{} expired version(s) from '{}'", expired.len(), remote_workspace.name);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::two_way::{EntryKind, EntryState};
+    use super::*;
+
+    fn versions() -> TreeState {
+        let mut versions = TreeState::new();
+
+        for dir in ["1000000", "2000000", "2000000/dir", "3000000", "unrelated"] {
+            versions.insert(PathBuf::from(dir), EntryState::new(EntryKind::Directory, 0, 0));
+        }
+        for file in ["1000000/a", "1000000/b", "2000000/a", "2000000/dir/c", "3000000/a", "unrelated/a"] {
+            versions.insert(PathBuf::from(file), EntryState::new(EntryKind::File, 1, 0));
+        }
+
+        versions
+    }
+
+    #[test]
+    fn versions_exceeding_the_count_are_expired() {
+        let settings = VersioningSettings { keep_versions: Some(2), keep_days: None };
+
+        assert_eq!(expired_versions(&versions(), &settings, 4000), [PathBuf::from("1000000/a")]);
+    }
+
+    #[test]
+    fn versions_exceeding_the_age_are_expired_as_a_whole() {
+        let settings = VersioningSettings { keep_versions: None, keep_days: Some(1) };
+        let now = 2000 + 24 * 60 * 60 + 1;
+
+        assert_eq!(expired_versions(&versions(), &settings, now), [PathBuf::from("1000000"), PathBuf::from("2000000")]);
+    }
+
+    #[test]
+    fn versions_within_the_limits_are_kept() {
+        let settings = VersioningSettings { keep_versions: Some(3), keep_days: Some(1) };
+
+        assert!(expired_versions(&versions(), &settings, 4000).is_empty());
+    }
+
+    #[test]
+    fn file_versions_are_listed_newest_first() {
+        let versions: Vec<String> = file_versions(&versions(), Path::new("a"))
+            .into_iter()
+            .map(|file_version| file_version.version)
+            .collect();
+
+        assert_eq!(versions, ["3000000", "2000000", "1000000"]);
+    }
+}
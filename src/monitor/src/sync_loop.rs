@@ -0,0 +1,527 @@
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Instant;
+use log::{debug, error, info, warn};
+use crate::control::{ControlCommand, SyncTrigger};
+use crate::debounce::{Debouncer, DirtyTargets};
+use crate::deletion_guard::DeletionGuard;
+use crate::events::{emit, unix_timestamp, MonitorEvent};
+use crate::hooks::HookRunner;
+use crate::ignore_rules::{IgnoreRules, SharedIgnoreRules};
+use crate::initial_sync::{InitialTargets, SyncCheckpoint};
+use crate::models::{TriggeredSyncOutcome, WorkspaceInfo};
+use crate::poller::Poller;
+use crate::retry_queue::RetryQueue;
+use crate::schedule;
+use crate::sync;
+use crate::sync::{restore_from_remote, synchronize_jobs, PathMove, SyncJob, SyncReport, SyncTargets};
+use crate::util::error_exit;
+
+/// Messages sent by the platform specific filesystem watchers and the daemon to the sync loop.
+pub(crate) enum WatcherEvent {
+    /// A resource in the directory (tree) at the given workspace-relative path changed. `None`
+    /// refers to the workspace root.
+    Changed(Option<PathBuf>),
+    /// The file (or any other non-directory resource) at the given workspace-relative path was
+    /// created, modified or removed
+    FileChanged(PathBuf),
+    /// A resource was moved within the workspace
+    Moved(PathMove),
+    /// The watcher stopped because of an unrecoverable error
+    Failed(String),
+    /// The watcher stopped regularly, e.g. because the monitor was interrupted
+    Stopped,
+    Command(ControlCommand)
+}
+
+/// Sync the given targets with all remote workspaces that are not backing off after a failed sync.
+/// The targets of backing off remote workspaces are added to their pending retry instead, which
+/// is performed along with the other syncs once it is due. Two-way remote workspaces whose poll is
+/// due are synced as well. Remote workspaces whose syncs are held by the deletion guard are
+/// skipped, their targets being held as well. The targets of remote workspaces whose syncs are
+/// paused via the daemon are held until they are resumed, and those of remote workspaces whose
+/// schedule pauses their syncs are postponed until the pause ends.
+fn sync_targets(
+    targets: SyncTargets,
+    retry_queue: &mut RetryQueue,
+    poller: &mut Poller,
+    deletion_guard: &mut DeletionGuard,
+    ws_info: &WorkspaceInfo,
+    ignore_rules: &SharedIgnoreRules
+) -> Result<(), sync::Error> {
+    let now = Instant::now();
+    let mut jobs: Vec<SyncJob> = vec![];
+
+    // Work on a snapshot, so that the watcher is not blocked from reloading the rules while syncing
+    let ignore_rules: IgnoreRules = ignore_rules.read().unwrap().clone();
+
+    deletion_guard.check(&targets, ws_info, &ignore_rules);
+
+    for remote_workspace in ws_info.remote_workspaces.iter() {
+        if deletion_guard.is_held(&remote_workspace.name) {
+            poller.record_sync(&remote_workspace.name, now);
+            continue;
+        }
+
+        if ws_info.is_paused(remote_workspace) {
+            // Remote changes are picked up once the syncs are resumed
+            poller.record_sync(&remote_workspace.name, now);
+            retry_queue.hold(&remote_workspace.name, &targets);
+            continue;
+        }
+
+        // Changes held while the syncs were paused are synced along with the new ones
+        let targets = retry_queue.release(&remote_workspace.name, &targets);
+
+        if let Some(until) = schedule::paused_until(remote_workspace, now) {
+            if !targets.is_empty() {
+                debug!("Syncs with '{}' are paused by its schedule, postponing them", remote_workspace.name);
+            }
+
+            // The postponed sync also picks up remote changes
+            poller.record_sync(&remote_workspace.name, now);
+            retry_queue.postpone(&remote_workspace.name, &targets, until);
+            continue;
+        }
+
+        if !retry_queue.is_pending(&remote_workspace.name) {
+            if !targets.is_empty() {
+                jobs.push(SyncJob { remote_workspace, targets, check_health: false, poll: false });
+            } else if poller.is_poll_due(&remote_workspace.name, now) {
+                debug!("Polling '{}' for changes", remote_workspace.name);
+                jobs.push(SyncJob {
+                    remote_workspace,
+                    targets: SyncTargets::from_directories(vec![None]),
+                    check_health: false,
+                    poll: true
+                });
+            }
+            continue;
+        }
+
+        // Remote changes are picked up by the retry
+        poller.record_sync(&remote_workspace.name, now);
+        retry_queue.defer(&remote_workspace.name, &targets);
+
+        if let Some(retry_target) = retry_queue.due_target(&remote_workspace.name, now) {
+            debug!("Retrying to sync '{:?}' with '{}'", retry_target, remote_workspace.name);
+            jobs.push(SyncJob {
+                remote_workspace,
+                targets: SyncTargets::from_directories(vec![retry_target]),
+                check_health: true,
+                poll: false
+            });
+        }
+    }
+
+    if jobs.is_empty() {
+        return Ok(());
+    }
+
+    debug!("Syncing #{} coalesced target(s) with #{} remote workspace(s): {:?}", targets.len(), jobs.len(), targets);
+
+    run_jobs(&jobs, retry_queue, poller, ws_info, &ignore_rules).map(|_| ())
+}
+
+/// Catch the remote workspaces up with the changes made while the monitor was not running, according
+/// to the workspace's initial sync strategy. The changes left in the journal are synced along with
+/// them. Remote workspaces whose syncs are held by the deletion guard are skipped, the initial syncs
+/// of remote workspaces whose syncs are paused via the daemon are held until they are resumed, and
+/// those of remote workspaces whose schedule pauses their syncs are postponed until the pause ends.
+fn initial_sync(
+    retry_queue: &mut RetryQueue,
+    poller: &mut Poller,
+    deletion_guard: &DeletionGuard,
+    checkpoint: &SyncCheckpoint,
+    ws_info: &WorkspaceInfo,
+    ignore_rules: &SharedIgnoreRules
+) -> Result<(), sync::Error> {
+    let now = Instant::now();
+    let ignore_rules: IgnoreRules = ignore_rules.read().unwrap().clone();
+    let initial_targets = InitialTargets::new(ws_info, &ignore_rules, checkpoint);
+    let mut jobs: Vec<SyncJob> = vec![];
+
+    for remote_workspace in ws_info.remote_workspaces.iter() {
+        if deletion_guard.is_held(&remote_workspace.name) {
+            continue;
+        }
+
+        let mut targets = initial_targets.targets(remote_workspace);
+
+        if ws_info.is_paused(remote_workspace) {
+            debug!("Syncs with '{}' are paused, holding its initial sync", remote_workspace.name);
+            retry_queue.hold(&remote_workspace.name, &targets);
+            continue;
+        }
+
+        if let Some(until) = schedule::paused_until(remote_workspace, now) {
+            retry_queue.postpone(&remote_workspace.name, &targets, until);
+            continue;
+        }
+
+        // Journaled changes are pending retries that are due right away
+        if let Some(journaled) = retry_queue.due_target(&remote_workspace.name, now)
+            && !targets.directories.contains(&None) {
+            targets.directories.push(journaled);
+        }
+
+        if !targets.is_empty() {
+            jobs.push(SyncJob { remote_workspace, targets, check_health: false, poll: false });
+        }
+    }
+
+    if jobs.is_empty() {
+        debug!("No initial sync required");
+        return Ok(());
+    }
+
+    run_jobs(&jobs, retry_queue, poller, ws_info, &ignore_rules).map(|_| ())
+}
+
+fn run_jobs(
+    jobs: &[SyncJob],
+    retry_queue: &mut RetryQueue,
+    poller: &mut Poller,
+    ws_info: &WorkspaceInfo,
+    ignore_rules: &IgnoreRules
+) -> Result<SyncReport, sync::Error> {
+    let now = Instant::now();
+
+    for job in jobs.iter() {
+        retry_queue.record_attempt(&job.remote_workspace.name, &job.targets);
+        poller.record_sync(&job.remote_workspace.name, now);
+    }
+
+    let report = synchronize_jobs(ws_info, jobs, ignore_rules)?;
+
+    retry_queue.record_report(&report);
+
+    Ok(report)
+}
+
+/// Sync the targets held for the given remote workspace (or for all remote workspaces if none is
+/// given). If the deletions were rejected, the deleted resources are restored from the remote
+/// workspace first. Remote workspaces whose resources could not be restored remain held, whereas
+/// the released targets of remote workspaces whose syncs are paused are held until they are resumed.
+fn resolve_deletions(
+    remote_workspace: Option<String>,
+    approve: bool,
+    retry_queue: &mut RetryQueue,
+    poller: &mut Poller,
+    deletion_guard: &mut DeletionGuard,
+    ws_info: &WorkspaceInfo,
+    ignore_rules: &SharedIgnoreRules
+) -> Result<(), sync::Error> {
+    let held_targets = deletion_guard.held_targets(remote_workspace.as_deref());
+    if held_targets.is_empty() {
+        warn!("No syncs are held for {}", remote_workspace.map_or(String::from("any remote workspace"), |name| format!("'{name}'")));
+        return Ok(());
+    }
+
+    let ignore_rules: IgnoreRules = ignore_rules.read().unwrap().clone();
+    let mut jobs: Vec<SyncJob> = vec![];
+
+    for (name, targets) in held_targets {
+        let Some(remote_workspace) = ws_info.remote_workspaces.iter().find(|remote| remote.name == name) else {
+            continue;
+        };
+
+        if !approve {
+            match restore_from_remote(ws_info, remote_workspace, &targets, &ignore_rules) {
+                Ok(nr_of_restored) => {
+                    info!("Restored #{nr_of_restored} resource(s) from '{name}'");
+                },
+                Err(e) => {
+                    warn!("Unable to restore the deleted resources from '{name}', syncs remain held: {e}");
+                    emit(MonitorEvent::SyncFailed {
+                        remote_workspace: name,
+                        finished_at: unix_timestamp(),
+                        duration_ms: 0,
+                        error: format!("Unable to restore the deleted resources: {e}"),
+                        cause: e.cause()
+                    });
+                    continue;
+                }
+            }
+        }
+
+        deletion_guard.release(&name, approve);
+
+        if ws_info.is_paused(remote_workspace) {
+            retry_queue.hold(&name, &targets);
+            continue;
+        }
+
+        let targets = retry_queue.release(&name, &targets);
+        jobs.push(SyncJob { remote_workspace, targets, check_health: false, poll: false });
+    }
+
+    if jobs.is_empty() {
+        return Ok(());
+    }
+
+    run_jobs(&jobs, retry_queue, poller, ws_info, &ignore_rules).map(|_| ())
+}
+
+/// Sync the resource given by the trigger with the given remote workspace (or all of them) right
+/// away, regardless of whether it changed, of the remote workspaces' schedules and of their pending
+/// retries, whose targets are synced along with it. Remote workspaces whose syncs are paused or held
+/// by the deletion guard are skipped. The outcome is reported to the daemon once the syncs finished.
+fn trigger_sync(
+    trigger: SyncTrigger,
+    retry_queue: &mut RetryQueue,
+    poller: &mut Poller,
+    deletion_guard: &DeletionGuard,
+    ws_info: &WorkspaceInfo,
+    ignore_rules: &SharedIgnoreRules
+) -> Result<(), sync::Error> {
+    let ignore_rules: IgnoreRules = ignore_rules.read().unwrap().clone();
+    let triggered = SyncTargets::from_resource(&ws_info.local_path, trigger.path);
+    let mut outcomes: Vec<TriggeredSyncOutcome> = vec![];
+    let mut jobs: Vec<SyncJob> = vec![];
+
+    let remote_workspaces = ws_info.remote_workspaces
+        .iter()
+        .filter(|remote| trigger.remote_workspace.as_ref().is_none_or(|name| remote.name == *name));
+
+    for remote_workspace in remote_workspaces {
+        let skipped = if deletion_guard.is_held(&remote_workspace.name) {
+            Some("its syncs are held until the pending deletions are approved or rejected")
+        } else if ws_info.is_paused(remote_workspace) {
+            Some("its syncs are paused")
+        } else {
+            None
+        };
+
+        if let Some(reason) = skipped {
+            outcomes.push(TriggeredSyncOutcome {
+                remote_workspace: remote_workspace.name.clone(),
+                skipped: Some(reason.to_string()),
+                error: None,
+                cause: None,
+                transferred: None
+            });
+            continue;
+        }
+
+        // A successful sync clears the pending retry, so its target must be synced as well
+        let mut targets = DirtyTargets::default();
+        if let Some(retry_target) = retry_queue.pending_target(&remote_workspace.name) {
+            targets.record(retry_target);
+        }
+        targets.add(triggered.clone());
+
+        jobs.push(SyncJob { remote_workspace, targets: targets.take(), check_health: false, poll: false });
+    }
+
+    debug!("Triggered sync of {:?} with #{} remote workspace(s)", triggered, jobs.len());
+
+    let result = if jobs.is_empty() {
+        Ok(None)
+    } else {
+        run_jobs(&jobs, retry_queue, poller, ws_info, &ignore_rules).map(Some)
+    };
+
+    match &result {
+        Ok(Some(report)) => {
+            outcomes.extend(report.outcomes.iter().map(|outcome| TriggeredSyncOutcome {
+                remote_workspace: outcome.remote_workspace.clone(),
+                skipped: None,
+                error: outcome.error.as_ref().map(|e| format!("{e}")),
+                cause: outcome.error.as_ref().and_then(|e| e.cause()),
+                transferred: outcome.transferred.clone()
+            }));
+        },
+        Ok(None) => {},
+        Err(e) => {
+            outcomes.extend(jobs.iter().map(|job| TriggeredSyncOutcome {
+                remote_workspace: job.remote_workspace.name.clone(),
+                skipped: None,
+                error: Some(format!("{e}")),
+                cause: e.cause(),
+                transferred: None
+            }));
+        }
+    }
+
+    emit(MonitorEvent::TriggeredSyncFinished { id: trigger.id, outcomes });
+
+    result.map(|_| ())
+}
+
+/// Pause or resume the syncs with the given remote workspace, or those of the whole workspace if
+/// none is given. Returns whether the remote workspace exists.
+fn set_paused(ws_info: &mut WorkspaceInfo, remote_workspace: Option<&str>, paused: bool) -> bool {
+    let Some(name) = remote_workspace else {
+        info!("{} the syncs of workspace '{}'", if paused { "Paused" } else { "Resumed" }, ws_info.name);
+        ws_info.paused = paused;
+        return true;
+    };
+
+    let Some(remote_workspace) = ws_info.remote_workspaces.iter_mut().find(|remote| remote.name == name) else {
+        return false;
+    };
+
+    info!("{} the syncs with '{name}'", if paused { "Paused" } else { "Resumed" });
+    remote_workspace.paused = paused;
+    true
+}
+
+/// Perform the initial sync and then collect the changes reported by a watcher, which must already
+/// be watching the workspace, and sync them with all remote workspaces once the configured quiet
+/// window elapsed or the max latency deadline is hit. Remote workspaces in the retry queue are
+/// synced once their retry is due, and the post-sync hooks of successfully synced remote workspaces
+/// are started once their debounce elapsed. Syncs can be paused and resumed by the daemon, the
+/// changes detected meanwhile being synced once they are resumed. Returns once the watcher stopped
+/// or a sync failed locally.
+pub(crate) fn run_sync_loop(
+    rx: Receiver<WatcherEvent>,
+    ws_info: &WorkspaceInfo,
+    ignore_rules: &SharedIgnoreRules,
+    mut retry_queue: RetryQueue,
+    mut deletion_guard: DeletionGuard
+) {
+    // Updated once syncs are paused or resumed
+    let mut ws_info = ws_info.clone();
+    let mut debouncer = Debouncer::new(&ws_info.debounce);
+    let mut poller = Poller::new(&ws_info);
+    let mut checkpoint = SyncCheckpoint::open(&ws_info);
+    let mut hooks = HookRunner::new();
+
+    // Changes detected meanwhile are received once the initial sync is done
+    if let Err(e) = initial_sync(&mut retry_queue, &mut poller, &deletion_guard, &checkpoint, &ws_info, ignore_rules) {
+        error_exit(Some(format!("Failed initial sync of workspace with remote systems: {e:?}")));
+    }
+
+    loop {
+        // The checkpoint is only advanced once no changes are being collected
+        let deadline = debouncer.deadline()
+            .or_else(|| checkpoint.next_deadline())
+            .into_iter()
+            .chain(retry_queue.next_deadline())
+            .chain(poller.next_deadline())
+            .chain(hooks.next_deadline())
+            .min();
+
+        let received = match deadline {
+            Some(deadline) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        };
+
+        // No change was reported while waiting
+        let drained = matches!(received, Err(RecvTimeoutError::Timeout));
+
+        match received {
+            Ok(WatcherEvent::Changed(relative_path)) => {
+                debouncer.record(relative_path, Instant::now());
+                checkpoint.record_change();
+            },
+            Ok(WatcherEvent::FileChanged(relative_path)) => {
+                debouncer.record_file(relative_path, Instant::now());
+                checkpoint.record_change();
+            },
+            Ok(WatcherEvent::Moved(path_move)) => {
+                debouncer.record_move(path_move, Instant::now());
+                checkpoint.record_change();
+            },
+            Ok(WatcherEvent::Failed(msg)) => {
+                error!("Filesystem watcher failed: {msg}");
+                break;
+            },
+            Ok(WatcherEvent::Command(ControlCommand::ResolveDeletions { remote_workspace, approve })) => {
+                // Changes detected in the meantime must be checked before the held targets are
+                // synced, as they might delete further files
+                let targets = debouncer.take();
+                let result = sync_targets(targets, &mut retry_queue, &mut poller, &mut deletion_guard, &ws_info, ignore_rules)
+                    .and_then(|_| resolve_deletions(
+                        remote_workspace,
+                        approve,
+                        &mut retry_queue,
+                        &mut poller,
+                        &mut deletion_guard,
+                        &ws_info,
+                        ignore_rules
+                    ));
+
+                if let Err(e) = result {
+                    error!("Failed to sync the held changes with the remote workspaces: {e}");
+                    break;
+                }
+            },
+            Ok(WatcherEvent::Command(ControlCommand::SetPaused { remote_workspace, paused })) => {
+                if !set_paused(&mut ws_info, remote_workspace.as_deref(), paused) {
+                    warn!("No remote workspace named '{}' is attached", remote_workspace.unwrap_or_default());
+                    continue;
+                }
+
+                if paused {
+                    continue;
+                }
+
+                // Changes collected in the meantime are synced along with the held ones right away
+                let targets = debouncer.take();
+                if let Err(e) = sync_targets(targets, &mut retry_queue, &mut poller, &mut deletion_guard, &ws_info, ignore_rules) {
+                    error!("Failed to sync the held changes with the remote workspaces: {e}");
+                    break;
+                }
+            },
+            Ok(WatcherEvent::Command(ControlCommand::TriggerSync(trigger))) => {
+                // Changes detected in the meantime are synced first, so that the triggered sync
+                // doesn't bypass the deletion guard
+                let targets = debouncer.take();
+                let result = sync_targets(targets, &mut retry_queue, &mut poller, &mut deletion_guard, &ws_info, ignore_rules)
+                    .and_then(|_| trigger_sync(trigger, &mut retry_queue, &mut poller, &deletion_guard, &ws_info, ignore_rules));
+
+                if let Err(e) = result {
+                    error!("Failed to perform the triggered sync with the remote workspaces: {e}");
+                    break;
+                }
+            },
+            // Handed to the waiting transfers by the command forwarder
+            Ok(WatcherEvent::Command(ControlCommand::PermitGranted { .. })) => {},
+            Err(RecvTimeoutError::Timeout) => {},
+            Ok(WatcherEvent::Stopped) | Err(RecvTimeoutError::Disconnected) => {
+                // The watcher terminated regularly, so make sure already detected changes still
+                // reach the remote workspaces.
+                let targets = debouncer.take();
+                if let Err(e) = sync_targets(targets, &mut retry_queue, &mut poller, &mut deletion_guard, &ws_info, ignore_rules) {
+                    error!("Failed to sync pending changes: {e}");
+                }
+                break;
+            }
+        }
+
+        let now = Instant::now();
+        if debouncer.is_due(now) || retry_queue.is_due(now) || poller.is_due(now) {
+            // Changes that are not due yet are held back, while due retries and polls are performed
+            // regardless
+            let targets = if debouncer.is_due(now) { debouncer.take() } else { SyncTargets::default() };
+
+            if let Err(e) = sync_targets(targets, &mut retry_queue, &mut poller, &mut deletion_guard, &ws_info, ignore_rules) {
+                error!("Failed to sync changes with the remote workspaces: {e}");
+                break;
+            }
+        }
+
+        // Hooks of remote workspaces that were just synced are due once their debounce elapsed
+        let now = Instant::now();
+        if hooks.is_due(now) {
+            hooks.run_due(&ws_info, now);
+        }
+
+        // All changes reported until now were synced, journaled or held by the deletion guard
+        if drained && debouncer.deadline().is_none() {
+            checkpoint.advance(now);
+        }
+    }
+
+    if retry_queue.nr_of_pending_retries() > 0 {
+        warn!(
+            "Terminating with pending retries for #{} remote workspace(s), which will be caught up by \
+            the initial sync once the monitor is restarted",
+            retry_queue.nr_of_pending_retries()
+        );
+    }
+
+    debug!("Terminated sync loop");
+}
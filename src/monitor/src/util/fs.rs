@@ -79,3 +79,18 @@ pub(crate) fn strip_ws_root_prefix(ws_root: &PathBuf, abs_path: &PathBuf) -> Res
         }
     }
 }
+
+/// Escape characters that must not or should not appear in file names
+pub(crate) fn escape_file_name(name: &str) -> String {
+    let mut escaped = String::new();
+
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    escaped
+}
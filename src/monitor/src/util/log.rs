@@ -5,17 +5,18 @@ use log4rs::config::{Appender, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use log::{info, LevelFilter};
 
+/// Logs are written to stderr, since stdout is reserved for what the monitor reports to the daemon
 pub(crate) fn setup_logging() {
-    let stdout = ConsoleAppender::builder()
-        .target(Target::Stdout)
+    let stderr = ConsoleAppender::builder()
+        .target(Target::Stderr)
         .encoder(Box::new(PatternEncoder::new("{h({d(%Y-%m-%d %H:%M:%S)} - [{l}]: {m}{n})}")))
         .build();
 
-    let appender = Appender::builder().build("stdout", Box::new(stdout));
+    let appender = Appender::builder().build("stderr", Box::new(stderr));
 
     let config = Config::builder()
         .appender(appender)
-        .build(Root::builder().appender("stdout").build(LevelFilter::Debug))
+        .build(Root::builder().appender("stderr").build(LevelFilter::Debug))
         .unwrap_or_else(|e| {
             eprintln!("An error occurred while initializing the logging infrastructure: {e:?}");
             process::exit(1);
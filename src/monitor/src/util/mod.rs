@@ -1,5 +1,10 @@
+use std::path::PathBuf;
 use std::process;
+use std::process::Command;
+use std::sync::OnceLock;
 use ::log::error;
+use wsync_config::{config, ConfigKey};
+use crate::models::{ConnectionInfo, Error};
 
 pub(crate) mod fs;
 pub(crate) mod log;
@@ -11,3 +16,100 @@ pub(crate) fn error_exit(msg: Option<String>) -> ! {
     error!("Terminating workspace monitor");
     process::exit(1);
 }
+
+/// Quote an argument for a POSIX shell, e.g. for commands run on remote systems via SSH
+pub(crate) fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// SSH command connecting to the remote system, with the given options preceding the destination
+/// and the command to be run remotely yet to be appended. `None` if the remote system isn't
+/// reached via SSH.
+pub(crate) fn ssh_command(connection_info: &ConnectionInfo, options: &[&str]) -> Option<Command> {
+    let (host, port, username, identity_file) = match connection_info {
+        ConnectionInfo::Ssh { host, port, username, identity_file } => (host, *port, username, identity_file),
+        ConnectionInfo::Agent { host: Some(host), port, username, identity_file, .. } => {
+            (host, *port, username, identity_file)
+        },
+        ConnectionInfo::HostAlias { host_alias } => (host_alias, None, &None, &None),
+        _ => return None
+    };
+
+    let mut command = Command::new("ssh");
+
+    // Fail instead of prompting for passwords, which nobody would answer
+    command.args(["-o", "BatchMode=yes"]).args(options);
+
+    if let Some(port) = port {
+        command.arg("-p").arg(port.to_string());
+    }
+
+    if let Some(identity_file) = identity_file {
+        command.arg("-i").arg(identity_file);
+    }
+
+    match username {
+        Some(username) => command.arg(format!("{username}@{host}")),
+        None => command.arg(host)
+    };
+
+    Some(command)
+}
+
+/// Directory in which the monitor keeps state across restarts, as specified in the wsync config
+pub(crate) fn state_directory() -> Result<PathBuf, Error> {
+    static CONFIG_INITIALIZED: OnceLock<Result<(), String>> = OnceLock::new();
+
+    CONFIG_INITIALIZED
+        .get_or_init(|| wsync_config::init_config().map_err(|e| format!("{e}")))
+        .clone()
+        .map_err(|e| Error::new(format!("Failed to initialize config: {e}")))?;
+
+    config().get_path(ConfigKey::StateDirectory).ok_or(Error::new("Config does not specify a state directory"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<&str> {
+        command.get_args().map(|arg| arg.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn ssh_command_ends_with_destination() {
+        let connection_info = ConnectionInfo::Ssh {
+            host: String::from("example.org"),
+            port: Some(2222),
+            username: Some(String::from("user")),
+            identity_file: Some(PathBuf::from("/keys/id"))
+        };
+        let command = ssh_command(&connection_info, &["-C"]).unwrap();
+
+        assert_eq!(command.get_program(), "ssh");
+        assert_eq!(args(&command), ["-o", "BatchMode=yes", "-C", "-p", "2222", "-i", "/keys/id", "user@example.org"]);
+
+        let connection_info = ConnectionInfo::HostAlias { host_alias: String::from("alias") };
+        assert_eq!(args(&ssh_command(&connection_info, &[]).unwrap()), ["-o", "BatchMode=yes", "alias"]);
+    }
+
+    #[test]
+    fn ssh_command_requires_ssh_connection() {
+        let local = ConnectionInfo::Local { path: PathBuf::from("/mnt") };
+        let local_agent = ConnectionInfo::Agent {
+            host: None,
+            port: None,
+            username: None,
+            identity_file: None,
+            agent_path: None
+        };
+
+        assert!(ssh_command(&local, &[]).is_none());
+        assert!(ssh_command(&local_agent, &[]).is_none());
+    }
+
+    #[test]
+    fn shell_quote_escapes_single_quotes() {
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}